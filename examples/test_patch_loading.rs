@@ -11,8 +11,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let patch_path = "examples/basic_synth_patch.json";
     println!("\n📂 Loading: {}", patch_path);
     
-    let json_content = std::fs::read_to_string(patch_path)?;
-    let patch: PatchFile = serde_json::from_str(&json_content)?;
+    let (patch, report) = PatchFile::load(patch_path)?;
+    if !report.is_empty() {
+        println!("🔄 {}", report);
+    }
     
     println!("📄 Patch: {:?}", patch.patch_name);
     println!("📊 Nodes: {}", patch.nodes.len());
@@ -6,14 +6,21 @@
 use std::sync::{Arc, Mutex};
 use tauri::Manager;
 
-use orbital_modulator::audio::AudioEngine;
+use orbital_modulator::audio::{AudioConfig, AudioEngine};
 use orbital_modulator::tauri_commands::*;
 
 fn main() {
     tauri::Builder::default()
         .setup(|app| {
-            // Initialize audio engine
-            let audio_engine = AudioEngine::new()
+            // Initialize audio engine on the device chosen last time
+            let config = AudioConfig::default_path()
+                .map(AudioConfig::load_or_default)
+                .unwrap_or_default();
+            let audio_engine = AudioEngine::from_config(&config)
+                .or_else(|e| {
+                    eprintln!("{}; falling back to the default device", e);
+                    AudioEngine::new().map_err(|e| e.to_string())
+                })
                 .map_err(|e| format!("Failed to create audio engine: {}", e))?;
             
             let engine_state = Arc::new(Mutex::new(audio_engine));
@@ -28,18 +35,39 @@ fn main() {
             connect_nodes,
             disconnect_nodes,
             set_node_parameter,
+            set_node_bypass,
+            set_output_route,
+            get_output_route,
+            set_output_channels,
+            schedule_node_event,
+            undo,
+            redo,
+            begin_transaction,
+            end_transaction,
+            abort_transaction,
+            get_history_state,
             get_node_parameter,
             list_nodes,
             get_connections,
+            get_patch_latency,
+            transport_command,
+            get_transport_state,
+            set_audio_input_file,
+            set_audio_input_capture,
             start_audio,
             stop_audio,
             is_audio_running,
+            list_audio_hosts,
+            list_audio_devices,
+            get_audio_config,
+            set_audio_config,
             save_project,
             load_project,
             get_oscilloscope_data,
             load_patch_file,
             save_patch_file,
-            trigger_gate
+            trigger_gate,
+            render_to_wav
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
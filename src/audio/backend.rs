@@ -0,0 +1,355 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Audio backends driving the `AudioEngine` render callback.
+//!
+//! `CpalBackend` plays through a sound card. `NullBackend` has no device at all;
+//! blocks are pulled on demand through its `ManualClock`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use cpal::{Device, Stream, StreamConfig};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use super::device::{find_input_device, find_output_device, AudioConfig};
+use super::input::CaptureBuffer;
+
+/// Render callback - fills an interleaved output buffer
+pub type RenderCallback = Box<dyn FnMut(&mut [f32]) + Send + 'static>;
+
+/// オーディオバックエンド - デバイスとクロックの抽象化
+pub trait AudioBackend: Send {
+    /// Human readable device name
+    fn device_name(&self) -> String;
+
+    /// Sample rate the backend runs at
+    fn sample_rate(&self) -> f32;
+
+    /// Number of interleaved output channels
+    fn channels(&self) -> u16;
+
+    /// Open `channels` output channels from the next `start` on
+    fn set_channels(&mut self, channels: u16) -> Result<(), String> {
+        if channels == self.channels() {
+            return Ok(());
+        }
+        Err(format!("{} has a fixed layout of {} output channels", self.device_name(), self.channels()))
+    }
+
+    /// Start calling `callback` for every output block
+    fn start(&mut self, buffer_size: usize, callback: RenderCallback) -> Result<(), String>;
+
+    /// Stop calling the render callback
+    fn stop(&mut self) -> Result<(), String>;
+
+    /// Write the input device's frames into `buffer` until `stop`
+    fn start_capture(&mut self, _buffer: Arc<CaptureBuffer>) -> Result<(), String> {
+        Err(format!("Audio input is not supported by {}", self.device_name()))
+    }
+}
+
+/// cpal based backend using a real output device
+pub struct CpalBackend {
+    device: Device,
+    sample_rate: f32,
+    channels: u16,
+    stream: Option<Stream>,
+    input_stream: Option<Stream>,
+    // Where `start_capture` opens its input device; `None` for the defaults
+    host: Option<String>,
+    input_device: Option<String>,
+}
+
+impl CpalBackend {
+    /// Open the default output device of the default host
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        let host = cpal::default_host();
+        let device = host.default_output_device()
+            .ok_or("No output device available")?;
+        Self::from_device(device)
+    }
+
+    /// Open the host, device, sample rate and channel count of `config`
+    pub fn open(config: &AudioConfig) -> Result<Self, String> {
+        let device = find_output_device(config.host.as_deref(), config.device.as_deref())?;
+        let mut backend = Self::from_device(device)
+            .map_err(|e| format!("Failed to open output device: {}", e))?;
+        backend.host = config.host.clone();
+        backend.input_device = config.input_device.clone();
+        if let Some(rate) = config.sample_rate {
+            backend.set_sample_rate(rate)?;
+        }
+        if let Some(channels) = config.output_channels {
+            backend.set_channels(channels)?;
+        }
+        Ok(backend)
+    }
+
+    /// Run at `rate` instead of the device's default rate from the next `start` on
+    pub fn set_sample_rate(&mut self, rate: u32) -> Result<(), String> {
+        let supported = self.device.supported_output_configs()
+            .map_err(|e| format!("Failed to query output device: {}", e))?
+            .any(|config| (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&rate));
+        if !supported {
+            return Err(format!("{} does not support {} Hz", self.device_name(), rate));
+        }
+        self.sample_rate = rate as f32;
+        Ok(())
+    }
+
+    /// Use a specific cpal device
+    pub fn from_device(device: Device) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = device.default_output_config()?;
+
+        Ok(Self {
+            device,
+            sample_rate: config.sample_rate().0 as f32,
+            channels: 2, // Stereo output
+            stream: None,
+            input_stream: None,
+            host: None,
+            input_device: None,
+        })
+    }
+}
+
+impl AudioBackend for CpalBackend {
+    fn device_name(&self) -> String {
+        self.device.name().unwrap_or("Unknown".to_string())
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn set_channels(&mut self, channels: u16) -> Result<(), String> {
+        let max_channels = self.device.supported_output_configs()
+            .map_err(|e| format!("Failed to query output device: {}", e))?
+            .map(|config| config.channels())
+            .max()
+            .unwrap_or(0);
+        if channels == 0 || channels > max_channels {
+            return Err(format!("{} supports 1 to {} output channels", self.device_name(), max_channels));
+        }
+        self.channels = channels;
+        Ok(())
+    }
+
+    fn start(&mut self, buffer_size: usize, mut callback: RenderCallback) -> Result<(), String> {
+        let config = StreamConfig {
+            channels: self.channels,
+            sample_rate: cpal::SampleRate(self.sample_rate as u32),
+            buffer_size: cpal::BufferSize::Fixed(buffer_size as u32),
+        };
+
+        let stream = self.device.build_output_stream(
+            &config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                callback(data);
+            },
+            |err| {
+                eprintln!("Audio stream error: {}", err);
+            },
+            None,
+        ).map_err(|e| format!("Failed to create audio stream: {}", e))?;
+
+        stream.play().map_err(|e| format!("Failed to start audio stream: {}", e))?;
+
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), String> {
+        if let Some(stream) = self.stream.take() {
+            stream.pause().map_err(|e| format!("Failed to stop audio stream: {}", e))?;
+        }
+        if let Some(stream) = self.input_stream.take() {
+            stream.pause().map_err(|e| format!("Failed to stop input stream: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn start_capture(&mut self, buffer: Arc<CaptureBuffer>) -> Result<(), String> {
+        let device = find_input_device(self.host.as_deref(), self.input_device.as_deref())?;
+        let channels = device.default_input_config()
+            .map_err(|e| format!("Failed to query input device: {}", e))?
+            .channels();
+        // Same rate as the output so the input node needs no resampling
+        let config = StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(self.sample_rate as u32),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let stream = device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                buffer.push_interleaved(data, channels as usize);
+            },
+            |err| {
+                eprintln!("Audio input stream error: {}", err);
+            },
+            None,
+        ).map_err(|e| format!("Failed to create input stream: {}", e))?;
+
+        stream.play().map_err(|e| format!("Failed to start input stream: {}", e))?;
+
+        self.input_stream = Some(stream);
+        Ok(())
+    }
+}
+
+/// Device-less backend. Nothing is rendered until the clock is ticked.
+pub struct NullBackend {
+    sample_rate: f32,
+    channels: u16,
+    clock: ManualClock,
+}
+
+impl NullBackend {
+    pub fn new(sample_rate: f32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            clock: ManualClock {
+                channels,
+                callback: Arc::new(Mutex::new(None)),
+                capture: Arc::new(Mutex::new(None)),
+                frames_elapsed: Arc::new(AtomicU64::new(0)),
+            },
+        }
+    }
+
+    /// Handle used to pull blocks from the running engine
+    pub fn clock(&self) -> ManualClock {
+        self.clock.clone()
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn device_name(&self) -> String {
+        "Null".to_string()
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn start(&mut self, _buffer_size: usize, callback: RenderCallback) -> Result<(), String> {
+        let mut slot = self.clock.callback.lock()
+            .map_err(|e| format!("Failed to lock clock: {}", e))?;
+        *slot = Some(callback);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), String> {
+        let mut slot = self.clock.callback.lock()
+            .map_err(|e| format!("Failed to lock clock: {}", e))?;
+        *slot = None;
+        if let Ok(mut capture) = self.clock.capture.lock() {
+            *capture = None;
+        }
+        Ok(())
+    }
+
+    fn start_capture(&mut self, buffer: Arc<CaptureBuffer>) -> Result<(), String> {
+        let mut slot = self.clock.capture.lock()
+            .map_err(|e| format!("Failed to lock clock: {}", e))?;
+        *slot = Some(buffer);
+        Ok(())
+    }
+}
+
+/// Manual clock of a `NullBackend`
+#[derive(Clone)]
+pub struct ManualClock {
+    channels: u16,
+    callback: Arc<Mutex<Option<RenderCallback>>>,
+    capture: Arc<Mutex<Option<Arc<CaptureBuffer>>>>,
+    frames_elapsed: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Render `frames` frames and return them interleaved.
+    /// Returns silence while the backend is stopped.
+    pub fn tick(&self, frames: usize) -> Vec<f32> {
+        let mut buffer = vec![0.0; frames * self.channels as usize];
+
+        if let Ok(mut slot) = self.callback.lock() {
+            if let Some(callback) = slot.as_mut() {
+                callback(&mut buffer);
+                self.frames_elapsed.fetch_add(frames as u64, Ordering::Relaxed);
+            }
+        }
+
+        buffer
+    }
+
+    /// Feed interleaved input frames (in the backend's channel count) as if
+    /// they came from a capture device. Dropped while capture is not started.
+    pub fn capture(&self, interleaved: &[f32]) {
+        if let Ok(slot) = self.capture.lock() {
+            if let Some(buffer) = slot.as_ref() {
+                buffer.push_interleaved(interleaved, self.channels as usize);
+            }
+        }
+    }
+
+    /// Whether the backend has been started
+    pub fn is_running(&self) -> bool {
+        self.callback.lock().map(|slot| slot.is_some()).unwrap_or(false)
+    }
+
+    /// Total frames rendered since creation
+    pub fn frames_elapsed(&self) -> u64 {
+        self.frames_elapsed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_null_backend_pulls_on_demand() {
+        let mut backend = NullBackend::new(48000.0, 2);
+        let clock = backend.clock();
+
+        // Stopped: silence and no callback
+        assert_eq!(clock.tick(4), vec![0.0; 8]);
+        assert_eq!(clock.frames_elapsed(), 0);
+
+        backend.start(4, Box::new(|data: &mut [f32]| data.fill(0.5))).unwrap();
+        assert!(clock.is_running());
+        assert_eq!(clock.tick(4), vec![0.5; 8]);
+        assert_eq!(clock.frames_elapsed(), 4);
+
+        backend.stop().unwrap();
+        assert!(!clock.is_running());
+        assert_eq!(clock.tick(2), vec![0.0; 4]);
+    }
+}
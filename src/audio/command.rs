@@ -0,0 +1,397 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Real-time safe control path from the UI thread to the audio callback.
+//!
+//! Commands travel through a bounded queue that the audio thread drains with
+//! `try_recv` at the start of every block. Anything the audio thread has to
+//! throw away (removed nodes, replaced topologies, parameter names, delivered
+//! events) is sent back and dropped on the control thread, so the callback
+//! never frees memory. Node latency changes travel back the same way.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::graph::{GraphTopology, OutputRoute, ProcessingGraph, ScheduledEvent, DEFAULT_EVENT_CAPACITY};
+use crate::nodes::AudioInputNode;
+use crate::parameters::ParameterError;
+use crate::processing::{AudioNode, EventKind};
+use crate::transport::TransportCommand;
+use super::input::AudioSource;
+
+/// Default number of commands that can be queued between two audio blocks
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+
+/// Most items a single command retires (`RemoveNode`: the topology and the node)
+const RETIRED_PER_COMMAND: usize = 2;
+
+/// オーディオスレッドへのコマンド
+pub enum EngineCommand {
+    /// Set a parameter on a node
+    SetParameter { node_id: Uuid, param: String, value: f32 },
+    /// Deliver an event to a node at a sample position
+    ScheduleEvent(ScheduledEvent),
+    /// Play, stop, tempo, loop or locate (validated on the control thread)
+    Transport(TransportCommand),
+    /// Bypass a node or bring it back (crossfaded by the plan)
+    SetBypass { node_id: Uuid, bypass: bool },
+    /// Send an output node to other device channels
+    SetOutputRoute { node_id: Uuid, route: OutputRoute },
+    /// Put a node built off-thread, e.g. with restored state, in place of the one with its ID
+    ReplaceNode(Box<dyn AudioNode>),
+    /// Give an audio input node another source (prepared on the control thread)
+    SetAudioSource { node_id: Uuid, source: Box<dyn AudioSource> },
+    /// Attach a node built off-thread together with the topology that includes it.
+    /// `spare` is an empty node map with room for the whole graph, taken over
+    /// if the live map is full (see `ProcessingGraph::attach_node_with`).
+    AddNode { node: Box<dyn AudioNode>, topology: Box<GraphTopology>, spare: HashMap<Uuid, Box<dyn AudioNode>> },
+    /// Detach a node and install the topology without it
+    RemoveNode { node_id: Uuid, topology: Box<GraphTopology> },
+    /// Replace connections and processing order
+    SwapTopology(Box<GraphTopology>),
+    /// Remove every node and install an (empty) topology
+    Clear { topology: Box<GraphTopology> },
+    /// Replace every node and the topology at once, e.g. with a loaded patch
+    ReplaceGraph { nodes: HashMap<Uuid, Box<dyn AudioNode>>, topology: Box<GraphTopology> },
+}
+
+/// Things the audio thread hands back to be dropped on the control thread
+#[allow(dead_code)] // Payloads are only held so they are freed off the audio thread
+enum Retired {
+    Node(Box<dyn AudioNode>),
+    Nodes(HashMap<Uuid, Box<dyn AudioNode>>),
+    Topology(Box<GraphTopology>),
+    Name(String),
+    Source(Box<dyn AudioSource>),
+    /// `param` is the command's own name; the error is only formatted on the control thread
+    Rejected { node_id: Uuid, param: String, error: ParameterError },
+    /// Not garbage: a node reported another latency (see `CommandSender::take_latency_changes`)
+    Latency { node_id: Uuid, samples: u32 },
+}
+
+/// Create a command queue holding up to `capacity` pending commands.
+///
+/// The way back has room for everything a full queue and a block of events
+/// retire; if the control thread falls behind, the rest waits on the audio thread.
+pub fn command_queue(capacity: usize) -> (CommandSender, CommandReceiver) {
+    let (command_tx, command_rx) = mpsc::sync_channel(capacity);
+    let (retired_tx, retired_rx) = mpsc::sync_channel(capacity * RETIRED_PER_COMMAND + DEFAULT_EVENT_CAPACITY);
+
+    (
+        CommandSender {
+            commands: command_tx,
+            retired: Mutex::new(retired_rx),
+            latencies: Mutex::new(HashMap::new()),
+        },
+        CommandReceiver {
+            commands: command_rx,
+            retired: retired_tx,
+            overflow: VecDeque::with_capacity(capacity * RETIRED_PER_COMMAND + DEFAULT_EVENT_CAPACITY),
+        },
+    )
+}
+
+/// Control-thread side of the queue
+pub struct CommandSender {
+    commands: SyncSender<EngineCommand>,
+    retired: Mutex<Receiver<Retired>>,
+    /// Latest latency of every node that reported a change, until taken
+    latencies: Mutex<HashMap<Uuid, u32>>,
+}
+
+impl CommandSender {
+    /// Queue a command without blocking
+    pub fn send(&self, command: EngineCommand) -> Result<(), String> {
+        self.collect_garbage();
+
+        self.commands.try_send(command).map_err(|e| match e {
+            TrySendError::Full(_) => "Command queue is full".to_string(),
+            TrySendError::Disconnected(_) => "Audio thread is not receiving commands".to_string(),
+        })
+    }
+
+    /// Drop everything the audio thread has retired and report rejected changes
+    pub fn collect_garbage(&self) {
+        let Ok(retired) = self.retired.lock() else {
+            return;
+        };
+
+        while let Ok(item) = retired.try_recv() {
+            match item {
+                Retired::Rejected { node_id, param, error } => {
+                    eprintln!("Failed to set {} on node {}: {}", param, node_id, error);
+                }
+                Retired::Latency { node_id, samples } => {
+                    if let Ok(mut latencies) = self.latencies.lock() {
+                        latencies.insert(node_id, samples);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Node latencies the audio thread saw change since the last call
+    pub fn take_latency_changes(&self) -> HashMap<Uuid, u32> {
+        self.collect_garbage();
+        self.latencies.lock().map(|mut latencies| std::mem::take(&mut *latencies)).unwrap_or_default()
+    }
+}
+
+/// Audio-thread side of the queue
+pub struct CommandReceiver {
+    commands: Receiver<EngineCommand>,
+    retired: SyncSender<Retired>,
+    /// Retired items the control thread has no room for yet, sent before anything else
+    overflow: VecDeque<Retired>,
+}
+
+impl CommandReceiver {
+    /// Apply every queued command to the graph. Never blocks.
+    /// Returns the number of commands applied.
+    ///
+    /// Commands stay queued while the retired items waiting on the audio
+    /// thread leave no room for theirs, so nothing is dropped here.
+    pub fn apply_pending(&mut self, graph: &mut ProcessingGraph) -> usize {
+        self.flush_overflow();
+
+        // Events of the last block are done with; only parameter names own memory
+        graph.drain_spent_events(|event| {
+            if let EventKind::Parameter { name, .. } = event.kind {
+                self.retire(Retired::Name(name));
+            }
+        });
+        // Reported back so the control thread can compensate the new latency
+        graph.drain_latency_changes(|node_id, samples| self.retire(Retired::Latency { node_id, samples }));
+
+        let mut applied = 0;
+        while self.overflow.capacity() - self.overflow.len() >= RETIRED_PER_COMMAND {
+            let Ok(command) = self.commands.try_recv() else {
+                break;
+            };
+            self.apply(graph, command);
+            applied += 1;
+        }
+        applied
+    }
+
+    fn apply(&mut self, graph: &mut ProcessingGraph, command: EngineCommand) {
+        match command {
+            EngineCommand::SetParameter { node_id, param, value } => {
+                let result = match graph.audio_nodes.get_mut(&node_id) {
+                    Some(node) => set_node_parameter(node.as_mut(), &param, value),
+                    None => Ok(()), // Removed before the change arrived
+                };
+                match result {
+                    Ok(()) => self.retire(Retired::Name(param)),
+                    Err(error) => self.retire(Retired::Rejected { node_id, param, error }),
+                }
+            }
+            EngineCommand::ScheduleEvent(event) => graph.schedule_event(event),
+            EngineCommand::Transport(command) => {
+                let _ = graph.transport_mut().apply(command);
+            }
+            EngineCommand::SetBypass { node_id, bypass } => {
+                // Validated on the control thread; only fails if the node was removed since
+                let _ = graph.set_bypass(node_id, bypass);
+            }
+            EngineCommand::SetOutputRoute { node_id, route } => {
+                // Validated on the control thread as well
+                let _ = graph.set_output_route(node_id, route);
+            }
+            EngineCommand::ReplaceNode(node) => {
+                // Removed since: the replacement is dropped off-thread as well
+                let retired = match graph.audio_nodes.get_mut(&node.node_info().id) {
+                    Some(slot) => std::mem::replace(slot, node),
+                    None => node,
+                };
+                self.retire(Retired::Node(retired));
+            }
+            EngineCommand::SetAudioSource { node_id, source } => {
+                let input = graph.audio_nodes.get_mut(&node_id)
+                    .and_then(|node| node.as_any_mut().downcast_mut::<AudioInputNode>());
+                // Removed since: the new source is dropped off-thread as well
+                let retired = match input {
+                    Some(input) => input.set_source(source),
+                    None => Some(source),
+                };
+                if let Some(source) = retired {
+                    self.retire(Retired::Source(source));
+                }
+            }
+            EngineCommand::AddNode { node, topology, spare } => {
+                let unused = graph.attach_node_with(node, spare);
+                self.retire(Retired::Nodes(unused));
+                let previous = graph.swap_topology(topology);
+                self.retire(Retired::Topology(previous));
+            }
+            EngineCommand::RemoveNode { node_id, topology } => {
+                let previous = graph.swap_topology(topology);
+                self.retire(Retired::Topology(previous));
+                if let Some(node) = graph.detach_node(node_id) {
+                    self.retire(Retired::Node(node));
+                }
+            }
+            EngineCommand::SwapTopology(topology) => {
+                let previous = graph.swap_topology(topology);
+                self.retire(Retired::Topology(previous));
+            }
+            EngineCommand::Clear { topology } => {
+                let previous = graph.swap_topology(topology);
+                self.retire(Retired::Topology(previous));
+                // All nodes go back as one item
+                self.retire(Retired::Nodes(std::mem::take(&mut graph.audio_nodes)));
+            }
+            EngineCommand::ReplaceGraph { mut nodes, topology } => {
+                // Swapping the maps moves no node and allocates nothing
+                std::mem::swap(&mut graph.audio_nodes, &mut nodes);
+                let previous = graph.swap_topology(topology);
+                self.retire(Retired::Topology(previous));
+                self.retire(Retired::Nodes(nodes));
+            }
+        }
+    }
+
+    /// Hand an item to the control thread, or keep it until there is room.
+    /// The overflow only grows past its capacity if events pile up while the
+    /// control thread is not collecting.
+    fn retire(&mut self, item: Retired) {
+        if !self.overflow.is_empty() {
+            self.overflow.push_back(item);
+            return;
+        }
+        if let Err(TrySendError::Full(item) | TrySendError::Disconnected(item)) = self.retired.try_send(item) {
+            self.overflow.push_back(item);
+        }
+    }
+
+    /// Send what earlier blocks had to keep, oldest first
+    fn flush_overflow(&mut self) {
+        while let Some(item) = self.overflow.pop_front() {
+            if let Err(TrySendError::Full(item) | TrySendError::Disconnected(item)) = self.retired.try_send(item) {
+                self.overflow.push_front(item);
+                break;
+            }
+        }
+    }
+}
+
+/// Set a parameter, honouring the UI aliases of the oscilloscope.
+/// Formats nothing, so a rejected value costs no allocation on the audio thread.
+pub(crate) fn set_node_parameter(node: &mut dyn AudioNode, param: &str, value: f32) -> Result<(), ParameterError> {
+    if node.as_any().is::<crate::nodes::OscilloscopeNode>() {
+        node.set_parameter(crate::nodes::OscilloscopeNode::parameter_name(param), value)
+    } else {
+        node.set_parameter(param, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{OscillatorNode, OutputNode};
+
+    #[test]
+    fn test_commands_apply_on_drain() {
+        let (sender, mut receiver) = command_queue(8);
+        let mut graph = ProcessingGraph::new();
+
+        let osc = Box::new(OscillatorNode::new(44100.0, "osc".to_string()));
+        let out = Box::new(OutputNode::new(44100.0, "out".to_string()));
+        let osc_id = osc.node_info().id;
+        let out_id = out.node_info().id;
+
+        // Topology prepared on the control thread
+        let mut topology = GraphTopology::new();
+        topology.add_node(osc.node_info().clone()).unwrap();
+        topology.add_node(out.node_info().clone()).unwrap();
+        let with_nodes = topology.clone();
+        topology.connect(&osc_id.to_string(), "audio_out", &out_id.to_string(), "audio_in_l").unwrap();
+
+        sender.send(EngineCommand::AddNode { node: osc, topology: Box::new(with_nodes.clone()), spare: HashMap::new() }).unwrap();
+        sender.send(EngineCommand::AddNode { node: out, topology: Box::new(with_nodes), spare: HashMap::new() }).unwrap();
+        sender.send(EngineCommand::SwapTopology(Box::new(topology))).unwrap();
+        sender.send(EngineCommand::SetParameter { node_id: osc_id, param: "frequency".to_string(), value: 220.0 }).unwrap();
+
+        // Nothing changes until the audio thread drains the queue
+        assert!(graph.connections().is_empty());
+        assert_eq!(receiver.apply_pending(&mut graph), 4);
+
+        assert_eq!(graph.connections().len(), 1);
+        assert_eq!(graph.topology().processing_order().len(), 2);
+        let osc = graph.get_node(&osc_id.to_string()).unwrap();
+        assert_eq!(osc.get_parameter("frequency").unwrap(), 220.0);
+
+        sender.send(EngineCommand::RemoveNode { node_id: osc_id, topology: Box::default() }).unwrap();
+        receiver.apply_pending(&mut graph);
+        assert!(graph.get_node(&osc_id.to_string()).is_none());
+        sender.collect_garbage();
+    }
+
+    #[test]
+    fn test_retired_items_wait_for_the_control_thread() {
+        let (sender, mut receiver) = command_queue(1);
+        let mut graph = ProcessingGraph::new();
+
+        // The control thread does not collect: retired topologies pile up on the
+        // audio thread until commands have to wait
+        let mut applied = 0;
+        loop {
+            sender.commands.try_send(EngineCommand::SwapTopology(Box::default())).unwrap();
+            if receiver.apply_pending(&mut graph) == 0 {
+                break;
+            }
+            applied += 1;
+        }
+        assert!(applied > RETIRED_PER_COMMAND + DEFAULT_EVENT_CAPACITY);
+        assert!(!receiver.overflow.is_empty());
+
+        // Nothing was dropped on the way; once collected, the waiting command is applied
+        let retired = sender.retired.lock().unwrap().try_iter().count();
+        assert_eq!(retired + receiver.overflow.len(), applied);
+        assert_eq!(receiver.apply_pending(&mut graph), 1);
+        assert!(receiver.overflow.is_empty());
+    }
+
+    #[test]
+    fn test_rejected_parameter_is_reported_back() {
+        let (sender, mut receiver) = command_queue(4);
+        let mut graph = ProcessingGraph::new();
+        let osc = Box::new(OscillatorNode::new(44100.0, "osc".to_string()));
+        let osc_id = osc.node_info().id;
+        graph.add_node_instance(osc).unwrap();
+
+        sender.send(EngineCommand::SetParameter { node_id: osc_id, param: "frequency".to_string(), value: -1.0 }).unwrap();
+        receiver.apply_pending(&mut graph);
+
+        let retired: Vec<Retired> = sender.retired.lock().unwrap().try_iter().collect();
+        assert!(matches!(retired.as_slice(), [Retired::Rejected { param, error: ParameterError::OutOfRange { .. }, .. }]
+                         if param == "frequency"));
+    }
+
+    #[test]
+    fn test_full_queue_does_not_block() {
+        let (sender, _receiver) = command_queue(1);
+        let node_id = Uuid::new_v4();
+
+        assert!(sender.send(EngineCommand::SetBypass { node_id, bypass: true }).is_ok());
+        assert!(sender.send(EngineCommand::SetBypass { node_id, bypass: true }).is_err());
+    }
+}
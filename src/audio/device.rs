@@ -0,0 +1,262 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Audio hosts and devices, and the device configuration saved between sessions.
+
+use std::path::{Path, PathBuf};
+
+use cpal::{Device, Host, SupportedBufferSize};
+use cpal::traits::{DeviceTrait, HostTrait};
+use serde::{Deserialize, Serialize};
+
+/// Buffer size used when nothing else is configured
+pub const DEFAULT_BUFFER_SIZE: usize = 512;
+
+/// Worker threads used when nothing else is configured: one per core besides the audio thread
+pub fn default_worker_threads() -> usize {
+    num_cpus::get().saturating_sub(1)
+}
+
+/// Rates offered for devices that report a continuous range
+pub const COMMON_SAMPLE_RATES: [u32; 8] = [22050, 32000, 44100, 48000, 88200, 96000, 176400, 192000];
+
+/// 出力デバイスの情報
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub host: String,
+    pub name: String,
+    /// The host's default output device
+    pub is_default: bool,
+    pub max_output_channels: u16,
+    pub default_sample_rate: Option<u32>,
+    /// `COMMON_SAMPLE_RATES` the device supports
+    pub sample_rates: Vec<u32>,
+    /// Smallest and largest buffer size, if the device reports them
+    pub buffer_size_range: Option<(u32, u32)>,
+}
+
+impl DeviceInfo {
+    fn from_device(host: &str, device: &Device, is_default: bool) -> Result<Self, String> {
+        let name = device.name().map_err(|e| format!("Failed to read device name: {}", e))?;
+        let configs: Vec<_> = device.supported_output_configs()
+            .map_err(|e| format!("Failed to query {}: {}", name, e))?
+            .collect();
+
+        let mut sample_rates: Vec<u32> = COMMON_SAMPLE_RATES.iter().copied()
+            .filter(|rate| configs.iter().any(|c| (c.min_sample_rate().0..=c.max_sample_rate().0).contains(rate)))
+            .collect();
+        let default_sample_rate = device.default_output_config().ok().map(|config| config.sample_rate().0);
+        if let Some(rate) = default_sample_rate.filter(|rate| !sample_rates.contains(rate)) {
+            sample_rates.push(rate);
+            sample_rates.sort_unstable();
+        }
+
+        let buffer_size_range = configs.iter()
+            .filter_map(|config| match config.buffer_size() {
+                SupportedBufferSize::Range { min, max } => Some((*min, *max)),
+                SupportedBufferSize::Unknown => None,
+            })
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)));
+
+        Ok(Self {
+            host: host.to_string(),
+            name,
+            is_default,
+            max_output_channels: configs.iter().map(|config| config.channels()).max().unwrap_or(0),
+            default_sample_rate,
+            sample_rates,
+            buffer_size_range,
+        })
+    }
+}
+
+/// Names of the audio hosts available on this platform (e.g. ALSA, JACK, CoreAudio, WASAPI)
+pub fn list_hosts() -> Vec<String> {
+    cpal::available_hosts().iter().map(|id| id.name().to_string()).collect()
+}
+
+/// Output devices of a host (`None`: the default host)
+pub fn list_output_devices(host: Option<&str>) -> Result<Vec<DeviceInfo>, String> {
+    let host = find_host(host)?;
+    let host_name = host.id().name();
+    let default_name = host.default_output_device().and_then(|device| device.name().ok());
+
+    let devices = host.output_devices()
+        .map_err(|e| format!("Failed to list devices of {}: {}", host_name, e))?;
+    Ok(devices
+        .filter_map(|device| {
+            let is_default = device.name().ok() == default_name;
+            // Devices that cannot be queried (busy, unplugged) are left out
+            DeviceInfo::from_device(host_name, &device, is_default).ok()
+        })
+        .collect())
+}
+
+fn find_host(name: Option<&str>) -> Result<Host, String> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+    let id = cpal::available_hosts().into_iter()
+        .find(|id| id.name() == name)
+        .ok_or_else(|| format!("Audio host not available: {}", name))?;
+    cpal::host_from_id(id).map_err(|e| format!("Failed to open audio host {}: {}", name, e))
+}
+
+/// Output device by host and device name; `None` picks the default
+pub fn find_output_device(host: Option<&str>, device: Option<&str>) -> Result<Device, String> {
+    let host = find_host(host)?;
+    match device {
+        None => host.default_output_device().ok_or_else(|| "No output device available".to_string()),
+        Some(name) => host.output_devices()
+            .map_err(|e| format!("Failed to list devices: {}", e))?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+            .ok_or_else(|| format!("Output device not found: {}", name)),
+    }
+}
+
+/// Input device by host and device name; `None` picks the default
+pub fn find_input_device(host: Option<&str>, device: Option<&str>) -> Result<Device, String> {
+    let host = find_host(host)?;
+    match device {
+        None => host.default_input_device().ok_or_else(|| "No input device available".to_string()),
+        Some(name) => host.input_devices()
+            .map_err(|e| format!("Failed to list input devices: {}", e))?
+            .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+            .ok_or_else(|| format!("Input device not found: {}", name)),
+    }
+}
+
+/// オーディオデバイス設定
+///
+/// Saved as TOML (see `default_path`). Unset fields use the defaults of the
+/// host and device, so a config keeps working when a device goes away.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Audio host; `None` for the platform default
+    pub host: Option<String>,
+    /// Output device; `None` for the host's default device
+    pub device: Option<String>,
+    /// Capture device of the same host; `None` for the host's default input
+    pub input_device: Option<String>,
+    /// `None` for the device's default rate
+    pub sample_rate: Option<u32>,
+    pub buffer_size: usize,
+    /// `None` for stereo
+    pub output_channels: Option<u16>,
+    /// Extra threads for independent graph branches; `None` for one per
+    /// core besides the audio thread, `Some(0)` to process on the audio thread only
+    pub worker_threads: Option<usize>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            host: None,
+            device: None,
+            input_device: None,
+            sample_rate: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            output_channels: None,
+            worker_threads: None,
+        }
+    }
+}
+
+impl AudioConfig {
+    /// `~/.orbital-modulator/audio.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".orbital-modulator").join("audio.toml"))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&content).map_err(|e| format!("Invalid audio config {}: {}", path.display(), e))
+    }
+
+    /// Load the config, or the defaults if there is none yet or it cannot be read
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::load(path).unwrap_or_else(|e| {
+            eprintln!("{}; using the default audio device", e);
+            Self::default()
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize audio config: {}", e))?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.buffer_size == 0 {
+            return Err("Buffer size must be at least 1 sample".to_string());
+        }
+        if self.output_channels == Some(0) {
+            return Err("At least one output channel is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip_and_defaults() {
+        let dir = std::env::temp_dir().join(format!("orbital_audio_config_{}", std::process::id()));
+        let path = dir.join("audio.toml");
+        assert_eq!(AudioConfig::load_or_default(&path), AudioConfig::default());
+
+        let config = AudioConfig {
+            host: Some("JACK".to_string()),
+            device: Some("Studio Interface".to_string()),
+            input_device: Some("Studio Interface In".to_string()),
+            sample_rate: Some(96000),
+            buffer_size: 128,
+            output_channels: Some(16),
+            worker_threads: Some(2),
+        };
+        config.save(&path).unwrap();
+        assert_eq!(AudioConfig::load(&path).unwrap(), config);
+
+        // Fields missing from older files fall back to the defaults
+        std::fs::write(&path, "device = \"Built-in Output\"\n").unwrap();
+        let partial = AudioConfig::load(&path).unwrap();
+        assert_eq!(partial.device.as_deref(), Some("Built-in Output"));
+        assert_eq!(partial.buffer_size, DEFAULT_BUFFER_SIZE);
+
+        std::fs::write(&path, "buffer_size = \"large\"\n").unwrap();
+        assert_eq!(AudioConfig::load_or_default(&path), AudioConfig::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(AudioConfig { buffer_size: 0, ..AudioConfig::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,294 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Undo/redo history of graph edits.
+//!
+//! The history stores the edits that take the graph back, not the ones that
+//! were made: creating a node records `RemoveNode`, removing it records
+//! `RestoreNode` with everything needed to bring it back. Applying an edit
+//! (`AudioEngine::apply_edit`) returns its own inverse, which goes on the
+//! other stack.
+
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::patch::{PatchConnection, PatchNode};
+
+/// Default number of undo steps kept
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Changes of one parameter less than this apart belong to the same gesture
+pub const MERGE_WINDOW: Duration = Duration::from_millis(500);
+
+/// 元に戻せるグラフ操作
+#[derive(Debug)]
+pub enum Edit {
+    /// Remove a node and its connections
+    RemoveNode { node_id: Uuid },
+    /// Recreate a removed node with its ID, parameters and state, then reconnect it
+    RestoreNode { node: Box<PatchNode>, connections: Vec<PatchConnection> },
+    Connect(PatchConnection),
+    Disconnect(PatchConnection),
+    SetParameter { node_id: String, param: String, value: f32 },
+}
+
+/// Edits undone or redone as one step
+#[derive(Debug)]
+pub struct Transaction {
+    pub label: String,
+    /// Inverse edits in the order they were recorded; applied back to front
+    pub edits: Vec<Edit>,
+}
+
+#[derive(Debug)]
+struct OpenTransaction {
+    transaction: Transaction,
+    depth: usize,
+}
+
+/// Undo and redo stacks plus the transaction being recorded
+#[derive(Debug)]
+pub struct EditHistory {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    open: Option<OpenTransaction>,
+    limit: usize,
+    /// Until when the last undo step may take in the next change of its parameter
+    merge_until: Option<Instant>,
+}
+
+impl EditHistory {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            open: None,
+            limit: limit.max(1),
+            merge_until: None,
+        }
+    }
+
+    /// Record the inverse of an edit that was just made. A new edit clears the redo stack.
+    ///
+    /// Back-to-back changes of the same parameter less than `MERGE_WINDOW`
+    /// apart (a knob being turned) are one edit: the inverse of the first,
+    /// which restores the value before all of them, is kept. Within a
+    /// transaction they are merged regardless of time.
+    pub fn record(&mut self, label: &str, inverse: Edit) {
+        self.record_at(label, inverse, Instant::now());
+    }
+
+    fn record_at(&mut self, label: &str, inverse: Edit, now: Instant) {
+        self.redo.clear();
+        let last = match &self.open {
+            Some(open) => open.transaction.edits.last(),
+            None if self.merge_until.is_some_and(|until| now < until) => {
+                self.undo.last().and_then(|transaction| transaction.edits.last())
+            }
+            None => None,
+        };
+        if last.is_some_and(|last| same_parameter(last, &inverse)) {
+            if self.open.is_none() {
+                self.merge_until = Some(now + MERGE_WINDOW);
+            }
+            return;
+        }
+
+        match &mut self.open {
+            Some(open) => open.transaction.edits.push(inverse),
+            None => {
+                self.push_undo(Transaction { label: label.to_string(), edits: vec![inverse] });
+                self.merge_until = Some(now + MERGE_WINDOW);
+            }
+        }
+    }
+
+    /// Group the following edits into one undo step until the matching `end_transaction`.
+    /// Nested transactions join the outermost one.
+    pub fn begin_transaction(&mut self, label: &str) {
+        self.merge_until = None;
+        match &mut self.open {
+            Some(open) => open.depth += 1,
+            None => {
+                self.open = Some(OpenTransaction {
+                    transaction: Transaction { label: label.to_string(), edits: Vec::new() },
+                    depth: 1,
+                });
+            }
+        }
+    }
+
+    /// Close the current transaction; the outermost one becomes an undo step if it recorded anything
+    pub fn end_transaction(&mut self) -> Result<(), String> {
+        let open = self.open.as_mut().ok_or("No transaction in progress")?;
+        open.depth -= 1;
+        if open.depth == 0 {
+            let transaction = self.open.take().unwrap().transaction;
+            if !transaction.edits.is_empty() {
+                self.push_undo(transaction);
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the open transaction out of the history (to roll it back), whatever its depth
+    pub fn abort_transaction(&mut self) -> Option<Transaction> {
+        self.open.take().map(|open| open.transaction)
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.open.is_some()
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Transaction> {
+        self.merge_until = None;
+        self.undo.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Transaction> {
+        self.redo.pop()
+    }
+
+    /// Store the inverse of an undone step
+    pub fn push_redo(&mut self, transaction: Transaction) {
+        self.redo.push(transaction);
+    }
+
+    /// Store the inverse of a redone step without touching the redo stack
+    pub fn push_undo(&mut self, transaction: Transaction) {
+        self.merge_until = None;
+        self.undo.push(transaction);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn undo_label(&self) -> Option<&str> {
+        self.undo.last().map(|transaction| transaction.label.as_str())
+    }
+
+    pub fn redo_label(&self) -> Option<&str> {
+        self.redo.last().map(|transaction| transaction.label.as_str())
+    }
+
+    /// Forget everything, e.g. after the graph was replaced
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = None;
+        self.merge_until = None;
+    }
+}
+
+fn same_parameter(a: &Edit, b: &Edit) -> bool {
+    matches!((a, b), (
+        Edit::SetParameter { node_id, param, .. },
+        Edit::SetParameter { node_id: other_node, param: other_param, .. },
+    ) if node_id == other_node && param == other_param)
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(value: f32) -> Edit {
+        Edit::SetParameter { node_id: "osc".to_string(), param: "frequency".to_string(), value }
+    }
+
+    #[test]
+    fn test_transactions_group_edits() {
+        let mut history = EditHistory::new(2);
+        history.record("Set frequency", set(1.0));
+
+        history.begin_transaction("Paste");
+        history.begin_transaction("Nested");
+        history.record("Set frequency", set(2.0));
+        history.end_transaction().unwrap();
+        history.record("Connect", Edit::Disconnect(PatchConnection {
+            source_node: "osc".to_string(),
+            source_port: "audio_out".to_string(),
+            target_node: "out".to_string(),
+            target_port: "audio_in_l".to_string(),
+        }));
+        history.end_transaction().unwrap();
+        assert!(history.end_transaction().is_err());
+
+        let paste = history.pop_undo().unwrap();
+        assert_eq!(paste.label, "Paste");
+        assert_eq!(paste.edits.len(), 2);
+        assert_eq!(history.undo_label(), Some("Set frequency"));
+
+        // Oldest steps fall off past the limit
+        history.push_undo(paste);
+        history.record("Set frequency", set(4.0));
+        history.pop_undo();
+        assert_eq!(history.undo_label(), Some("Paste"));
+        history.pop_undo();
+        assert!(history.pop_undo().is_none());
+    }
+
+    #[test]
+    fn test_parameter_changes_merge() {
+        let mut history = EditHistory::default();
+        history.record("Set frequency", set(1.0));
+        history.record("Set frequency", set(2.0));
+        history.record("Set frequency", set(3.0));
+        let step = history.pop_undo().unwrap();
+        assert!(matches!(step.edits[..], [Edit::SetParameter { value: 1.0, .. }]));
+        assert!(history.pop_undo().is_none());
+
+        // Not across an undo: the redone step stays separate
+        history.record("Set frequency", set(1.0));
+        let step = history.pop_undo().unwrap();
+        history.push_undo(step);
+        history.record("Set frequency", set(2.0));
+        assert!(history.pop_undo().is_some() && history.pop_undo().is_some());
+
+        // Nor across parameters
+        history.record("Set frequency", set(1.0));
+        history.record("Set level", Edit::SetParameter { node_id: "osc".to_string(), param: "level".to_string(), value: 0.5 });
+        history.record("Set frequency", set(2.0));
+        assert_eq!((0..4).filter_map(|_| history.pop_undo()).count(), 3);
+    }
+
+    #[test]
+    fn test_parameter_changes_merge_within_window() {
+        let mut history = EditHistory::default();
+        let start = Instant::now();
+        let step = MERGE_WINDOW / 2;
+
+        // A slow turn keeps merging as long as every change follows closely
+        for i in 0..4 {
+            history.record_at("Set frequency", set(i as f32), start + step * i);
+        }
+        // After a pause the same parameter starts a new step
+        history.record_at("Set frequency", set(10.0), start + step * 3 + MERGE_WINDOW);
+
+        let later = history.pop_undo().unwrap();
+        assert!(matches!(later.edits[..], [Edit::SetParameter { value: 10.0, .. }]));
+        let turn = history.pop_undo().unwrap();
+        assert!(matches!(turn.edits[..], [Edit::SetParameter { value: 0.0, .. }]));
+        assert!(history.pop_undo().is_none());
+    }
+}
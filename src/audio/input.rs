@@ -0,0 +1,306 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Sources of external audio for `AudioInputNode`.
+//!
+//! The engine's capture stream writes into a `CaptureBuffer`; every input
+//! node reads it through its own `CaptureSource`. Without a sound card the
+//! same nodes play a `BufferSource` (a WAV file or samples in memory).
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::processing::StereoBuffer;
+use super::wav::read_wav;
+
+/// Frames the capture ring holds (about 1.4 s at 48 kHz)
+pub const DEFAULT_CAPTURE_FRAMES: usize = 1 << 16;
+
+/// 入力ノードに音声を供給するソース
+pub trait AudioSource: Send + Sync {
+    /// Shown in the UI, e.g. the device or file name
+    fn name(&self) -> String;
+
+    /// Called with the node's `AudioNode::prepare`
+    fn prepare(&mut self, _sample_rate: f32, _max_block_size: usize) {}
+
+    /// Fill `left`/`right` with the next frames; silence where nothing is available.
+    /// Runs on the audio thread, so it must not block or allocate.
+    fn read(&mut self, left: &mut [f32], right: &mut [f32]);
+}
+
+/// キャプチャストリームのリングバッファ
+///
+/// One writer (the capture callback) and any number of `CaptureSource`
+/// readers, each with its own position. Samples are stored as atomic bits so
+/// neither side locks.
+#[derive(Debug)]
+pub struct CaptureBuffer {
+    /// Interleaved L/R
+    samples: Box<[AtomicU32]>,
+    /// Frames written since the start
+    written: AtomicU64,
+}
+
+impl CaptureBuffer {
+    pub fn new(capacity_frames: usize) -> Self {
+        Self {
+            samples: (0..capacity_frames.max(1) * 2).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len() / 2
+    }
+
+    /// Frames written since the start
+    pub fn written(&self) -> u64 {
+        self.written.load(Ordering::Acquire)
+    }
+
+    /// Append interleaved device frames. Mono is copied to both sides; channels
+    /// past the second are ignored.
+    pub fn push_interleaved(&self, data: &[f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        let capacity = self.capacity() as u64;
+        let start = self.written.load(Ordering::Relaxed);
+
+        let mut frames = 0;
+        for frame in data.chunks_exact(channels) {
+            let (left, right) = (frame[0], frame[channels.min(2) - 1]);
+            let index = ((start + frames) % capacity) as usize * 2;
+            self.samples[index].store(left.to_bits(), Ordering::Relaxed);
+            self.samples[index + 1].store(right.to_bits(), Ordering::Relaxed);
+            frames += 1;
+        }
+        self.written.store(start + frames, Ordering::Release);
+    }
+
+    fn frame(&self, position: u64) -> (f32, f32) {
+        let index = (position % self.capacity() as u64) as usize * 2;
+        (
+            f32::from_bits(self.samples[index].load(Ordering::Relaxed)),
+            f32::from_bits(self.samples[index + 1].load(Ordering::Relaxed)),
+        )
+    }
+}
+
+impl Default for CaptureBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPTURE_FRAMES)
+    }
+}
+
+/// オーディオデバイスからの入力
+#[derive(Debug)]
+pub struct CaptureSource {
+    buffer: Arc<CaptureBuffer>,
+    /// Next frame to read; `None` until the first block
+    position: Option<u64>,
+}
+
+impl CaptureSource {
+    pub fn new(buffer: Arc<CaptureBuffer>) -> Self {
+        Self { buffer, position: None }
+    }
+}
+
+impl AudioSource for CaptureSource {
+    fn name(&self) -> String {
+        "Audio input".to_string()
+    }
+
+    fn read(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len().min(right.len());
+        let written = self.buffer.written();
+
+        // Start one block behind the writer, and jump back there if the
+        // writer has lapped us (e.g. after the output stream stalled)
+        let lag_limit = (self.buffer.capacity() - frames.min(self.buffer.capacity())) as u64;
+        let position = match self.position {
+            Some(position) if written - position.min(written) <= lag_limit => position,
+            _ => written.saturating_sub(frames as u64),
+        };
+
+        // Underruns are filled with silence and not read again
+        let available = (written - position.min(written)).min(frames as u64) as usize;
+        for (i, (l, r)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            (*l, *r) = if i < available {
+                self.buffer.frame(position + i as u64)
+            } else {
+                (0.0, 0.0)
+            };
+        }
+        self.position = Some(position + available as u64);
+    }
+}
+
+/// メモリ上（またはWAVファイル）の音声を再生するソース
+///
+/// Played back at its own sample rate: `prepare` sets up linear
+/// interpolation to the engine's rate.
+#[derive(Debug, Clone)]
+pub struct BufferSource {
+    name: String,
+    buffer: StereoBuffer,
+    sample_rate: f32,
+    looping: bool,
+    /// Source frames per output frame
+    step: f64,
+    position: f64,
+}
+
+impl BufferSource {
+    pub fn new(name: &str, buffer: StereoBuffer, sample_rate: f32) -> Self {
+        Self {
+            name: name.to_string(),
+            buffer,
+            sample_rate,
+            looping: false,
+            step: 1.0,
+            position: 0.0,
+        }
+    }
+
+    /// Load a WAV file (mono files play on both sides)
+    pub fn from_wav_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let data = read_wav(&mut std::io::BufReader::new(file))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let channels = data.channels as usize;
+        let mut buffer = StereoBuffer::new(data.frames());
+        for (i, frame) in data.samples.chunks_exact(channels).enumerate() {
+            buffer.left[i] = frame[0];
+            buffer.right[i] = frame[channels.min(2) - 1];
+        }
+
+        let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+        Ok(Self::new(&name, buffer, data.sample_rate as f32))
+    }
+
+    /// Start over from the beginning when the end is reached
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Length in frames at the source's own rate
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Whether a non-looping source has played to the end
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.position >= self.buffer.len() as f64
+    }
+
+    pub fn rewind(&mut self) {
+        self.position = 0.0;
+    }
+
+    fn sample_at(&self, position: f64) -> (f32, f32) {
+        let len = self.buffer.len();
+        let index = position as usize;
+        let next = if index + 1 < len { index + 1 } else if self.looping { 0 } else { index };
+        let frac = (position - index as f64) as f32;
+
+        let lerp = |channel: &[f32]| channel[index] + (channel[next] - channel[index]) * frac;
+        (lerp(&self.buffer.left), lerp(&self.buffer.right))
+    }
+}
+
+impl AudioSource for BufferSource {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.step = self.sample_rate as f64 / sample_rate as f64;
+    }
+
+    fn read(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let len = self.buffer.len() as f64;
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            if self.looping && self.position >= len && len > 0.0 {
+                self.position %= len;
+            }
+            (*l, *r) = if self.position < len {
+                self.sample_at(self.position)
+            } else {
+                (0.0, 0.0)
+            };
+            self.position += self.step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_readers_follow_the_writer() {
+        let buffer = Arc::new(CaptureBuffer::new(8));
+        let mut source = CaptureSource::new(Arc::clone(&buffer));
+        let (mut left, mut right) = (vec![0.0; 2], vec![0.0; 2]);
+
+        // Mono device: both sides get the same frames
+        buffer.push_interleaved(&[0.1, 0.2, 0.3, 0.4], 1);
+        source.read(&mut left, &mut right);
+        assert_eq!((left.as_slice(), right.as_slice()), ([0.3, 0.4].as_slice(), [0.3, 0.4].as_slice()));
+
+        // Underrun: the missing frame is silent
+        buffer.push_interleaved(&[0.5, -0.5], 2);
+        source.read(&mut left, &mut right);
+        assert_eq!((left.as_slice(), right.as_slice()), ([0.5, 0.0].as_slice(), [-0.5, 0.0].as_slice()));
+
+        // Lapped by the writer: jump back to the newest block
+        buffer.push_interleaved(&[1.0; 20], 2);
+        buffer.push_interleaved(&[0.7, 0.7, 0.8, 0.8], 2);
+        source.read(&mut left, &mut right);
+        assert_eq!(left, vec![0.7, 0.8]);
+    }
+
+    #[test]
+    fn test_buffer_source_resamples_and_loops() {
+        let buffer = StereoBuffer { left: vec![0.0, 1.0, 2.0, 3.0], right: vec![0.0; 4] };
+        let mut source = BufferSource::new("ramp", buffer, 22050.0).with_looping(true);
+        source.prepare(44100.0, 8);
+
+        let (mut left, mut right) = (vec![0.0; 10], vec![0.0; 10]);
+        source.read(&mut left, &mut right);
+        // Half speed with interpolation, wrapping back to the start (interpolating 3 -> 0 on the way)
+        assert_eq!(left, vec![0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 1.5, 0.0, 0.5]);
+        assert!(!source.is_finished());
+
+        let mut once = BufferSource::new("once", StereoBuffer::new(4), 44100.0);
+        once.read(&mut left, &mut right);
+        assert!(once.is_finished());
+    }
+}
@@ -6,15 +6,48 @@
  */
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-use cpal::{Device, Stream, StreamConfig};
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-
-use crate::graph::ProcessingGraph;
-use crate::processing::{AudioNode, InputPorts, OutputPorts};
+use crate::graph::{patch_node_entry, restore_patch_node, topology_patch, GraphTopology, OutputRoute, ProcessingGraph, ScheduledEvent};
+use crate::nodes::{AudioInputNode, OscilloscopeNode};
+use crate::parameters::{ParameterDescriptor, ParameterError};
+use crate::processing::{AudioNode, EventKind, NodeState};
+use crate::patch::{PatchConnection, PatchFile, PatchNode, SubpatchDefinition};
+use crate::patch::migration::MigrationReport;
 use crate::plugin::PluginManager;
+use crate::transport::{Transport, TransportCommand, TransportInfo};
+
+pub mod backend;
+pub mod command;
+pub mod device;
+pub mod history;
+pub mod input;
+pub mod offline;
+pub mod wav;
+
+pub use backend::{AudioBackend, CpalBackend, ManualClock, NullBackend, RenderCallback};
+pub use command::{command_queue, CommandReceiver, CommandSender, EngineCommand};
+pub use device::{list_hosts, list_output_devices, AudioConfig, DeviceInfo};
+pub use history::{Edit, EditHistory, Transaction};
+pub use input::{AudioSource, BufferSource, CaptureBuffer, CaptureSource};
+pub use offline::{RenderSettings, RenderedAudio, render_graph, render_to_wav};
+pub use wav::WavFormat;
+
+/// How long `trigger_node_gate` holds the gate open
+pub const GATE_TRIGGER_SECONDS: f32 = 0.1;
+
+/// Counters the audio thread publishes for the control thread
+#[derive(Debug, Default)]
+struct EngineStatus {
+    /// Blocks skipped because the graph was busy
+    xruns: AtomicU64,
+    /// Graph position after the last processed block
+    position: AtomicU64,
+    /// Transport after the last processed block (skipped if the control thread holds it)
+    transport: Mutex<TransportInfo>,
+}
 
 /// Modern Audio Engine with plugin support
 pub struct AudioEngine {
@@ -22,38 +55,202 @@ pub struct AudioEngine {
     plugin_manager: Arc<Mutex<PluginManager>>,
     sample_rate: f32,
     buffer_size: usize,
-    device: Device,
-    stream: Option<Stream>,
+    backend: Box<dyn AudioBackend>,
     is_playing: bool,
+    /// Control-thread copy of the topology, edited while the audio thread runs
+    topology: Mutex<GraphTopology>,
+    /// Set when `topology` holds node latencies the audio thread's topology lacks
+    latency_unsent: AtomicBool,
+    /// Control-thread copy of every node's parameters and state while running,
+    /// updated as commands are sent, so reading them never locks the live graph
+    node_entries: Mutex<HashMap<Uuid, NodeEntry>>,
+    commands: CommandSender,
+    command_receiver: Arc<Mutex<CommandReceiver>>,
+    /// Counters published by the audio thread
+    status: Arc<EngineStatus>,
+    /// Undo/redo of node, connection and parameter edits
+    history: Mutex<EditHistory>,
+    /// Filled by the backend's input stream, read by every audio input node
+    capture: Arc<CaptureBuffer>,
+    /// Device settings to save, as last applied
+    audio_config: AudioConfig,
+    /// Extra threads the graph processes independent branches on, started with the stream
+    worker_threads: usize,
 }
 
 impl AudioEngine {
-    /// Create a new audio engine
+    /// Create a new audio engine on the default output device,
+    /// with a worker thread per core besides the audio thread
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let host = cpal::default_host();
-        let device = host.default_output_device()
-            .ok_or("No output device available")?;
+        let backend = CpalBackend::new()?;
+        let mut engine = Self::with_backend(Box::new(backend));
+        engine.worker_threads = device::default_worker_threads();
+        Ok(engine)
+    }
 
-        let config = device.default_output_config()?;
-        let sample_rate = config.sample_rate().0 as f32;
-        let buffer_size = 512; // Default buffer size
+    /// Create an audio engine without a sound card.
+    /// Returns the clock used to pull blocks once the engine is started.
+    pub fn new_headless(sample_rate: f32) -> (Self, ManualClock) {
+        let backend = NullBackend::new(sample_rate, 2);
+        let clock = backend.clock();
+        (Self::with_backend(Box::new(backend)), clock)
+    }
+
+    /// Create an audio engine on top of any backend.
+    /// Processes on the audio thread only until `set_worker_threads` is called.
+    pub fn with_backend(backend: Box<dyn AudioBackend>) -> Self {
+        let sample_rate = backend.sample_rate();
+        let buffer_size = device::DEFAULT_BUFFER_SIZE;
 
         println!("Audio Engine initialized:");
         println!("  Sample Rate: {} Hz", sample_rate);
         println!("  Buffer Size: {} samples", buffer_size);
-        println!("  Device: {}", device.name().unwrap_or("Unknown".to_string()));
+        println!("  Device: {}", backend.device_name());
 
         let plugin_manager = PluginManager::new("1.0.0".to_string());
+        let (commands, command_receiver) = command_queue(command::DEFAULT_QUEUE_CAPACITY);
 
-        Ok(Self {
+        Self {
             graph: Arc::new(Mutex::new(ProcessingGraph::new())),
             plugin_manager: Arc::new(Mutex::new(plugin_manager)),
             sample_rate,
             buffer_size,
-            device,
-            stream: None,
+            backend,
             is_playing: false,
-        })
+            topology: Mutex::new(GraphTopology::new()),
+            latency_unsent: AtomicBool::new(false),
+            node_entries: Mutex::new(HashMap::new()),
+            commands,
+            command_receiver: Arc::new(Mutex::new(command_receiver)),
+            status: Arc::new(EngineStatus::default()),
+            history: Mutex::new(EditHistory::default()),
+            capture: Arc::new(CaptureBuffer::default()),
+            audio_config: AudioConfig::default(),
+            worker_threads: 0,
+        }
+    }
+
+    /// Create an audio engine on the host, device and settings of `config`
+    pub fn from_config(config: &AudioConfig) -> Result<Self, String> {
+        config.validate()?;
+        let backend = CpalBackend::open(config)?;
+        let mut engine = Self::with_backend(Box::new(backend));
+        engine.buffer_size = config.buffer_size;
+        engine.worker_threads = config.worker_threads.unwrap_or_else(device::default_worker_threads);
+        engine.audio_config = config.clone();
+        Ok(engine)
+    }
+
+    /// Add a node to the graph - queued for the audio thread while running
+    fn insert_node(&self, mut node: Box<dyn AudioNode>) -> Result<(), String> {
+        self.attach_capture(node.as_mut());
+        node.prepare(self.sample_rate, self.buffer_size);
+        if !self.is_playing {
+            let mut graph = self.graph.lock()
+                .map_err(|e| format!("Failed to lock graph: {}", e))?;
+            return graph.add_node_instance(node);
+        }
+
+        let node_id = node.node_info().id;
+        let info = GraphTopology::node_entry(node.as_ref());
+        let entry = NodeEntry::new(node.as_ref());
+        self.edit_topology(
+            |topology| topology.add_node(info),
+            |topology| {
+                // Room to grow, so the audio thread never has to allocate for it
+                let spare = HashMap::with_capacity(2 * topology.processing_order().len());
+                EngineCommand::AddNode { node, topology, spare }
+            },
+        )?;
+        self.edit_entries(|entries| entries.insert(node_id, entry));
+        Ok(())
+    }
+
+    /// Audio input nodes without a source play the engine's capture stream
+    fn attach_capture(&self, node: &mut dyn AudioNode) {
+        if let Some(input) = node.as_any_mut().downcast_mut::<AudioInputNode>() {
+            if !input.has_source() {
+                input.set_source(Box::new(CaptureSource::new(Arc::clone(&self.capture))));
+            }
+        }
+    }
+
+    /// Add a newly created node and record it in the history
+    fn add_node(&self, node: Box<dyn AudioNode>) -> Result<String, String> {
+        let node_id = node.node_info().id;
+        let label = format!("Create {}", node.node_info().node_type);
+        self.insert_node(node)?;
+        self.record(&label, Edit::RemoveNode { node_id });
+        Ok(node_id.to_string())
+    }
+
+    /// Edit the control-thread topology and queue the result for the audio thread.
+    /// The copy is only committed once the command is queued.
+    fn edit_topology<E, C>(&self, edit: E, command: C) -> Result<(), String>
+    where
+        E: FnOnce(&mut GraphTopology) -> Result<(), String>,
+        C: FnOnce(Box<GraphTopology>) -> EngineCommand,
+    {
+        let mut topology = self.topology.lock()
+            .map_err(|e| format!("Failed to lock topology: {}", e))?;
+        // Every topology sent carries the latest node latencies
+        self.absorb_latency_changes(&mut topology);
+
+        let mut edited = topology.clone();
+        edit(&mut edited)?;
+
+        // Compile here so the audio thread only swaps pointers
+        let mut update = Box::new(edited.clone());
+        update.compile(self.buffer_size);
+        self.commands.send(command(update))?;
+        *topology = edited;
+        self.latency_unsent.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Copy the node latencies the audio thread reported into the control topology
+    fn absorb_latency_changes(&self, topology: &mut GraphTopology) {
+        for (node_id, samples) in self.commands.take_latency_changes() {
+            if topology.set_node_latency(node_id, samples) {
+                self.latency_unsent.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Compensate node latencies that changed while running (e.g. a plugin or
+    /// subpatch): the topology is recompiled and sent to the audio thread.
+    /// Done by `latency_samples`, `get_info` and every topology edit; hosts
+    /// that call none of them regularly can call it themselves.
+    pub fn update_latency_compensation(&self) -> Result<(), String> {
+        if !self.is_playing {
+            return Ok(());
+        }
+        if let Ok(mut topology) = self.topology.lock() {
+            self.absorb_latency_changes(&mut topology);
+        }
+        if !self.latency_unsent.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.edit_topology(|_| Ok(()), EngineCommand::SwapTopology)
+    }
+
+    /// Read node lists without touching the live graph while the audio thread runs
+    fn read_topology<R>(&self, read: impl FnOnce(&GraphTopology) -> R) -> Option<R> {
+        if self.is_playing {
+            self.topology.lock().ok().map(|topology| read(&topology))
+        } else {
+            self.graph.lock().ok().map(|graph| read(graph.topology()))
+        }
+    }
+
+    /// Entry of a node as last sent to the audio thread; only kept while running
+    fn read_entry<R>(&self, node_id: &str, read: impl FnOnce(&PatchNode) -> R) -> Option<R> {
+        let uuid = Uuid::parse_str(node_id).ok()?;
+        self.node_entries.lock().ok()?.get(&uuid).map(|entry| read(&entry.patch))
+    }
+
+    fn edit_entries<R>(&self, edit: impl FnOnce(&mut HashMap<Uuid, NodeEntry>) -> R) -> Option<R> {
+        self.node_entries.lock().ok().map(|mut entries| edit(&mut entries))
     }
 
     /// Add a plugin directory
@@ -87,55 +284,49 @@ impl AudioEngine {
 
     /// Create a built-in node
     pub fn create_builtin_node(&self, node_type: &str, name: String) -> Result<String, String> {
-        let mut graph = self.graph.lock()
-            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+        let node = crate::nodes::create_builtin_node(node_type, self.sample_rate, name.clone())?;
 
-        let node: Box<dyn AudioNode> = match node_type {
-            // Generator Nodes
-            "sine_oscillator" => Box::new(crate::nodes::SineOscillatorNode::new(self.sample_rate, name.clone())),
-            "oscillator" => Box::new(crate::nodes::OscillatorNode::new(self.sample_rate, name.clone())),
-            "noise" => Box::new(crate::nodes::NoiseNode::new(self.sample_rate, name.clone())),
-
-            // Processor Nodes
-            "vcf" => Box::new(crate::nodes::VCFNode::new(self.sample_rate, name.clone())),
-            "vca" => Box::new(crate::nodes::VCANode::new(self.sample_rate, name.clone())),
-            "delay" => Box::new(crate::nodes::DelayNode::new(self.sample_rate, name.clone())),
-            "compressor" => Box::new(crate::nodes::CompressorNode::new(self.sample_rate, name.clone())),
-            "waveshaper" => Box::new(crate::nodes::WaveshaperNode::new(self.sample_rate, name.clone())),
-            "ring_modulator" => Box::new(crate::nodes::RingModulatorNode::new(self.sample_rate, name.clone())),
-
-            // Controller Nodes
-            "adsr" => Box::new(crate::nodes::ADSRNode::new(self.sample_rate, name.clone())),
-            "lfo" => Box::new(crate::nodes::LFONode::new(self.sample_rate, name.clone())),
-            "sequencer" => Box::new(crate::nodes::SequencerNode::new(self.sample_rate, name.clone())),
-
-            // Utility Nodes
-            "sample_hold" => Box::new(crate::nodes::SampleHoldNode::new(self.sample_rate, name.clone())),
-            "quantizer" => Box::new(crate::nodes::QuantizerNode::new(self.sample_rate, name.clone())),
-            "attenuverter" => Box::new(crate::nodes::AttenuverterNode::new(self.sample_rate, name.clone())),
-            "multiple" => Box::new(crate::nodes::MultipleNode::new(self.sample_rate, name.clone(), 4)),
-            "clock_divider" => Box::new(crate::nodes::ClockDividerNode::new(self.sample_rate, name.clone())),
-
-            // Mixing/Routing Nodes
-            "mixer" => Box::new(crate::nodes::MixerNode::new(self.sample_rate, name.clone())),
-            "output" => Box::new(crate::nodes::OutputNode::new(self.sample_rate, name.clone())),
-
-            // Analysis Nodes
-            "oscilloscope" => Box::new(crate::nodes::OscilloscopeNode::new(self.sample_rate, name.clone())),
-            "spectrum_analyzer" => Box::new(crate::nodes::SpectrumAnalyzerNode::new(self.sample_rate, name.clone())),
-
-            _ => return Err(format!("Unknown built-in node type: {}", node_type)),
-        };
+        let node_id = self.add_node(node)?;
 
-        let node_id = node.node_info().id.to_string();
-        graph.add_node_instance(node)?;
-        
         println!("Created built-in node: {} ({})", name, node_type);
         Ok(node_id)
     }
 
+    /// Create a subpatch node from its definition
+    pub fn create_subpatch_node(&self, name: String, definition: &SubpatchDefinition) -> Result<String, String> {
+        let node = crate::nodes::SubpatchNode::from_definition(definition, self.sample_rate, name.clone())?;
+
+        let node_id = self.add_node(Box::new(node))?;
+
+        println!("Created subpatch node: {}", name);
+        Ok(node_id)
+    }
+
+    /// Definition of a subpatch node, for saving
+    pub fn subpatch_definition(&self, node_id: &str) -> Option<SubpatchDefinition> {
+        if self.is_playing {
+            return self.read_entry(node_id, |entry| entry.subpatch.as_deref().cloned()).flatten();
+        }
+        let graph = self.graph.lock().ok()?;
+        graph.get_node(node_id)?
+            .as_any()
+            .downcast_ref::<crate::nodes::SubpatchNode>()
+            .map(|subpatch| subpatch.definition())
+    }
+
     /// Create a plugin node
     pub fn create_plugin_node(&self, node_type: &str, name: String) -> Result<String, String> {
+        let plugin_node = self.instantiate_plugin_node(node_type, name.clone())?;
+
+        // Add to graph
+        let node_id = self.add_node(plugin_node)?;
+
+        println!("Created plugin node: {} ({})", name, node_type);
+        Ok(node_id)
+    }
+
+    /// Plugin node instance, not yet in the graph
+    fn instantiate_plugin_node(&self, node_type: &str, name: String) -> Result<Box<dyn AudioNode>, String> {
         let manager = self.plugin_manager.lock()
             .map_err(|e| format!("Failed to lock plugin manager: {}", e))?;
 
@@ -146,23 +337,34 @@ impl AudioEngine {
             .map(|(id, _)| id.clone())
             .ok_or_else(|| format!("No plugin found for node type: {}", node_type))?;
 
-        // Create the plugin node
-        let plugin_node = manager.create_node(&plugin_id, node_type, name.clone(), self.sample_rate)
-            .map_err(|e| format!("Failed to create plugin node: {}", e))?;
-
-        // Add to graph
-        let mut graph = self.graph.lock()
-            .map_err(|e| format!("Failed to lock graph: {}", e))?;
-
-        let node_id = plugin_node.node_info().id.to_string();
-        graph.add_node_instance(plugin_node)?;
+        manager.create_node(&plugin_id, node_type, name, self.sample_rate)
+            .map_err(|e| format!("Failed to create plugin node: {}", e))
+    }
 
-        println!("Created plugin node: {} ({}) from plugin: {}", name, node_type, plugin_id);
-        Ok(node_id)
+    /// Node instance for a patch entry - built-in, subpatch or plugin node
+    fn instantiate_patch_node(&self, patch_node: &PatchNode) -> Result<Box<dyn AudioNode>, String> {
+        crate::nodes::create_patch_node(patch_node, self.sample_rate).or_else(|builtin_error| {
+            self.instantiate_plugin_node(&patch_node.node_type, patch_node.name.clone())
+                .map_err(|_| builtin_error)
+        })
     }
 
-    /// Remove a node from the graph
+    /// Remove a node from the graph. Undo restores it with its connections.
     pub fn remove_node(&self, node_id: Uuid) -> Result<(), String> {
+        let label = format!("Remove {}", self.find_node_name_by_id(node_id).unwrap_or_default());
+        self.edit(&label, Edit::RemoveNode { node_id })
+    }
+
+    fn detach_node(&self, node_id: Uuid) -> Result<(), String> {
+        if self.is_playing {
+            self.edit_topology(
+                |topology| topology.remove_node(node_id),
+                |topology| EngineCommand::RemoveNode { node_id, topology },
+            ).map_err(|e| format!("Failed to remove node: {}", e))?;
+            self.edit_entries(|entries| entries.remove(&node_id));
+            return Ok(());
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
         
@@ -171,20 +373,51 @@ impl AudioEngine {
     }
 
     /// Set node parameter using the modern parameter system
+    ///
+    /// While running the change is checked against the node's parameter
+    /// descriptors, then queued and applied at the start of the next audio block.
     pub fn set_node_parameter(&self, node_id: &str, param_name: &str, value: f32) -> Result<(), String> {
+        self.edit(&format!("Set {}", param_name), Edit::SetParameter {
+            node_id: node_id.to_string(),
+            param: param_name.to_string(),
+            value,
+        })
+    }
+
+    fn write_parameter(&self, node_id: &str, param_name: &str, value: f32) -> Result<(), String> {
+        if self.is_playing {
+            // Checked here so the caller gets the same errors as when stopped
+            let (uuid, checked) = Uuid::parse_str(node_id).ok()
+                .and_then(|uuid| {
+                    self.edit_entries(|entries| entries.get(&uuid).map(|entry| entry.check_parameter(param_name, value)))
+                        .flatten()
+                        .map(|checked| (uuid, checked))
+                })
+                .ok_or_else(|| format!("Node not found: {}", node_id))?;
+            checked.map_err(|e| format!("Failed to set parameter: {}", e))?;
+
+            self.commands.send(EngineCommand::SetParameter {
+                node_id: uuid,
+                param: param_name.to_string(),
+                value,
+            })?;
+            self.edit_entries(|entries| {
+                let Some(entry) = entries.get_mut(&uuid) else { return };
+                let name = entry_parameter_name(&entry.patch, param_name);
+                if let Some(stored) = entry.patch.parameters.get_mut(name) {
+                    *stored = value;
+                }
+            });
+            return Ok(());
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
 
         if let Some(node) = graph.get_node_mut(node_id) {
             // Special handling for oscilloscope nodes with UI parameter aliases
-            if let Some(osc_node) = node.as_any_mut().downcast_mut::<crate::nodes::OscilloscopeNode>() {
-                osc_node.set_parameter_override(param_name, value)
-                    .map_err(|e| format!("Failed to set parameter: {}", e))?;
-            } else {
-                node.set_parameter(param_name, value)
-                    .map_err(|e| format!("Failed to set parameter: {}", e))?;
-            }
-            Ok(())
+            command::set_node_parameter(node, param_name, value)
+                .map_err(|e| format!("Failed to set parameter: {}", e))
         } else {
             Err(format!("Node not found: {}", node_id))
         }
@@ -192,6 +425,13 @@ impl AudioEngine {
 
     /// Get node parameter
     pub fn get_node_parameter(&self, node_id: &str, param_name: &str) -> Result<f32, String> {
+        if self.is_playing {
+            return self.read_entry(node_id, |entry| {
+                entry.parameters.get(entry_parameter_name(entry, param_name)).copied()
+                    .ok_or_else(|| format!("Failed to get parameter: unknown parameter {}", param_name))
+            }).unwrap_or_else(|| Err(format!("Node not found: {}", node_id)));
+        }
+
         let graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
 
@@ -211,6 +451,17 @@ impl AudioEngine {
 
     /// Connect two nodes
     pub fn connect_nodes(&self, source_id: &str, source_port: &str, target_id: &str, target_port: &str) -> Result<(), String> {
+        self.edit("Connect", Edit::Connect(patch_connection(source_id, source_port, target_id, target_port)))
+    }
+
+    fn insert_connection(&self, source_id: &str, source_port: &str, target_id: &str, target_port: &str) -> Result<(), String> {
+        if self.is_playing {
+            return self.edit_topology(
+                |topology| topology.connect(source_id, source_port, target_id, target_port),
+                EngineCommand::SwapTopology,
+            );
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
 
@@ -219,25 +470,362 @@ impl AudioEngine {
 
     /// Disconnect two nodes
     pub fn disconnect_nodes(&self, source_id: &str, source_port: &str, target_id: &str, target_port: &str) -> Result<(), String> {
+        self.edit("Disconnect", Edit::Disconnect(patch_connection(source_id, source_port, target_id, target_port)))
+    }
+
+    fn delete_connection(&self, source_id: &str, source_port: &str, target_id: &str, target_port: &str) -> Result<(), String> {
+        if self.is_playing {
+            return self.edit_topology(
+                |topology| topology.disconnect(source_id, source_port, target_id, target_port),
+                EngineCommand::SwapTopology,
+            );
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
 
         graph.disconnect_by_id(source_id, source_port, target_id, target_port)
     }
 
-    /// Trigger gate signal for ADSR nodes
+    /// Apply an edit and record its inverse in the history
+    fn edit(&self, label: &str, edit: Edit) -> Result<(), String> {
+        if let Some(inverse) = self.apply_edit(edit)? {
+            self.record(label, inverse);
+        }
+        Ok(())
+    }
+
+    fn record(&self, label: &str, inverse: Edit) {
+        if let Ok(mut history) = self.history.lock() {
+            history.record(label, inverse);
+        }
+    }
+
+    /// Apply an edit without recording it. Returns the edit that reverts it,
+    /// or `None` if the previous value is not known (a parameter the node does
+    /// not report). Previous values come from the control-thread copy while running.
+    pub fn apply_edit(&self, edit: Edit) -> Result<Option<Edit>, String> {
+        match edit {
+            Edit::RemoveNode { node_id } => {
+                let (node, connections) = self.snapshot_node(node_id)?;
+                self.detach_node(node_id)?;
+                Ok(Some(Edit::RestoreNode { node: Box::new(node), connections }))
+            }
+            Edit::RestoreNode { node: patch_node, connections } => {
+                let mut node = self.instantiate_patch_node(&patch_node)?;
+                restore_patch_node(node.as_mut(), &patch_node);
+                let node_id = node.node_info().id;
+                if node_id.to_string() != patch_node.id {
+                    return Err(format!("Node {} cannot be restored with its ID", patch_node.id));
+                }
+                self.insert_node(node)?;
+                if let Some(route) = patch_node.output_route {
+                    self.set_output_route(&patch_node.id, route)?;
+                }
+                if patch_node.bypassed {
+                    self.set_node_bypass(&patch_node.id, true)?;
+                }
+                for conn in &connections {
+                    if let Err(e) = self.insert_connection(&conn.source_node, &conn.source_port,
+                                                           &conn.target_node, &conn.target_port) {
+                        eprintln!("Failed to reconnect {}.{} -> {}.{}: {}",
+                                  conn.source_node, conn.source_port, conn.target_node, conn.target_port, e);
+                    }
+                }
+                Ok(Some(Edit::RemoveNode { node_id }))
+            }
+            Edit::Connect(conn) => {
+                self.insert_connection(&conn.source_node, &conn.source_port, &conn.target_node, &conn.target_port)?;
+                Ok(Some(Edit::Disconnect(conn)))
+            }
+            Edit::Disconnect(conn) => {
+                self.delete_connection(&conn.source_node, &conn.source_port, &conn.target_node, &conn.target_port)?;
+                Ok(Some(Edit::Connect(conn)))
+            }
+            Edit::SetParameter { node_id, param, value } => {
+                let previous = self.get_node_parameter(&node_id, &param).ok();
+                self.write_parameter(&node_id, &param, value)?;
+                Ok(previous.map(|value| Edit::SetParameter { node_id, param, value }))
+            }
+        }
+    }
+
+    /// Everything needed to bring a node back: patch entry and connections
+    fn snapshot_node(&self, node_id: Uuid) -> Result<(PatchNode, Vec<PatchConnection>), String> {
+        let connections = self.read_topology(|topology| {
+            topology.connections().iter()
+                .filter(|conn| conn.source_node == node_id || conn.target_node == node_id)
+                .map(|conn| PatchConnection {
+                    source_node: conn.source_node.to_string(),
+                    source_port: conn.source_port.clone(),
+                    target_node: conn.target_node.to_string(),
+                    target_port: conn.target_port.clone(),
+                })
+                .collect()
+        }).unwrap_or_default();
+
+        let node = if self.is_playing {
+            self.read_entry(&node_id.to_string(), PatchNode::clone)
+        } else {
+            self.graph.lock().ok().and_then(|graph| graph.node_to_patch(node_id))
+        };
+        let node = node.ok_or_else(|| format!("Node not found: {}", node_id))?;
+        // The graph may not have applied a queued route or bypass change yet
+        let node = PatchNode {
+            output_route: self.read_topology(|topology| topology.output_route(node_id)).flatten()
+                .filter(|route| *route != OutputRoute::default()),
+            bypassed: self.is_node_bypassed(&node_id.to_string()),
+            ..node
+        };
+
+        Ok((node, connections))
+    }
+
+    /// Revert the last edit or transaction. Returns its label, `None` if there is nothing to undo.
+    pub fn undo(&self) -> Result<Option<String>, String> {
+        let mut history = self.history.lock()
+            .map_err(|e| format!("Failed to lock history: {}", e))?;
+        if history.in_transaction() {
+            return Err("Cannot undo while a transaction is in progress".to_string());
+        }
+
+        let Some(transaction) = history.pop_undo() else {
+            return Ok(None);
+        };
+        let label = transaction.label.clone();
+        history.push_redo(self.replay(transaction)?);
+        Ok(Some(label))
+    }
+
+    /// Make the last undone edit or transaction again
+    pub fn redo(&self) -> Result<Option<String>, String> {
+        let mut history = self.history.lock()
+            .map_err(|e| format!("Failed to lock history: {}", e))?;
+        if history.in_transaction() {
+            return Err("Cannot redo while a transaction is in progress".to_string());
+        }
+
+        let Some(transaction) = history.pop_redo() else {
+            return Ok(None);
+        };
+        let label = transaction.label.clone();
+        history.push_undo(self.replay(transaction)?);
+        Ok(Some(label))
+    }
+
+    /// Apply the edits of a transaction back to front and collect their inverses.
+    /// On failure the part already applied is reverted and the transaction is dropped.
+    fn replay(&self, transaction: Transaction) -> Result<Transaction, String> {
+        let mut inverses = Vec::with_capacity(transaction.edits.len());
+        for edit in transaction.edits.into_iter().rev() {
+            match self.apply_edit(edit) {
+                Ok(inverse) => inverses.extend(inverse),
+                Err(e) => {
+                    self.revert(inverses);
+                    return Err(format!("Failed to apply {}: {}", transaction.label, e));
+                }
+            }
+        }
+        Ok(Transaction { label: transaction.label, edits: inverses })
+    }
+
+    fn revert(&self, edits: Vec<Edit>) {
+        for edit in edits.into_iter().rev() {
+            if let Err(e) = self.apply_edit(edit) {
+                eprintln!("Failed to revert edit: {}", e);
+            }
+        }
+    }
+
+    /// Group the following edits into one undo step (e.g. pasting a subgraph).
+    /// Transactions nest; only the outermost one becomes a step.
+    pub fn begin_transaction(&self, label: &str) -> Result<(), String> {
+        self.history.lock()
+            .map_err(|e| format!("Failed to lock history: {}", e))?
+            .begin_transaction(label);
+        Ok(())
+    }
+
+    /// Finish the transaction started with `begin_transaction`
+    pub fn end_transaction(&self) -> Result<(), String> {
+        self.history.lock()
+            .map_err(|e| format!("Failed to lock history: {}", e))?
+            .end_transaction()
+    }
+
+    /// Revert every edit of the open transaction (including nested ones) and discard it
+    pub fn abort_transaction(&self) -> Result<(), String> {
+        let transaction = self.history.lock()
+            .map_err(|e| format!("Failed to lock history: {}", e))?
+            .abort_transaction()
+            .ok_or("No transaction in progress")?;
+        self.revert(transaction.edits);
+        Ok(())
+    }
+
+    /// Labels of the next undo and redo steps
+    pub fn history_labels(&self) -> (Option<String>, Option<String>) {
+        match self.history.lock() {
+            Ok(history) => (history.undo_label().map(str::to_string), history.redo_label().map(str::to_string)),
+            Err(_) => (None, None),
+        }
+    }
+
+    /// Forget all undo and redo steps
+    pub fn clear_history(&self) {
+        if let Ok(mut history) = self.history.lock() {
+            history.clear();
+        }
+    }
+
+    /// Deliver an event to a node at sample `time` of the graph clock
+    /// (`sample_position`). Times already passed land on the next block.
+    pub fn schedule_event(&self, node_id: &str, time: u64, kind: EventKind) -> Result<(), String> {
+        let uuid = Uuid::parse_str(node_id)
+            .ok()
+            .filter(|uuid| self.read_topology(|t| t.node_info(*uuid).is_some()).unwrap_or(false))
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+        let event = ScheduledEvent { node_id: uuid, time, kind };
+
+        if self.is_playing {
+            return self.commands.send(EngineCommand::ScheduleEvent(event));
+        }
+
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+        graph.schedule_event(event);
+        Ok(())
+    }
+
+    /// Samples processed by the graph so far - the time base of `schedule_event`
+    pub fn sample_position(&self) -> u64 {
+        if self.is_playing {
+            return self.status.position.load(Ordering::Relaxed);
+        }
+        self.graph.lock().map(|graph| graph.position()).unwrap_or(0)
+    }
+
+    /// Play, stop, change tempo, time signature or loop, or locate the song position
+    pub fn transport(&self, command: TransportCommand) -> Result<(), String> {
+        if self.is_playing {
+            // Validate here; the audio thread applies it without reporting back
+            Transport::new().apply(command)?;
+            return self.commands.send(EngineCommand::Transport(command));
+        }
+
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+        graph.transport_mut().apply(command)
+    }
+
+    /// Transport state at the start of the next block
+    pub fn transport_info(&self) -> TransportInfo {
+        if self.is_playing {
+            return self.status.transport.lock().map(|info| *info).unwrap_or_default();
+        }
+        self.graph.lock().map(|graph| graph.transport().info()).unwrap_or_default()
+    }
+
+    /// Open the gate of a node (e.g. an ADSR) for `GATE_TRIGGER_SECONDS` from the next block on
     pub fn trigger_node_gate(&self, node_id: &str) -> Result<(), String> {
+        let now = self.sample_position();
+        let length = (GATE_TRIGGER_SECONDS * self.sample_rate) as u64;
+        self.schedule_event(node_id, now, EventKind::GateOn)?;
+        self.schedule_event(node_id, now + length, EventKind::GateOff)
+    }
+
+    /// Bypass a node: its primary audio input is crossfaded to its primary
+    /// output, delayed by the node's latency so the patch stays aligned
+    pub fn set_node_bypass(&self, node_id: &str, bypass: bool) -> Result<(), String> {
+        let uuid = Uuid::parse_str(node_id)
+            .map_err(|_| format!("Node not found: {}", node_id))?;
+
+        if self.is_playing {
+            let mut topology = self.topology.lock()
+                .map_err(|e| format!("Failed to lock topology: {}", e))?;
+            topology.check_bypass(uuid)?;
+            self.commands.send(EngineCommand::SetBypass { node_id: uuid, bypass })?;
+            return topology.set_bypass(uuid, bypass);
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
 
-        if let Some(node) = graph.get_node_mut(node_id) {
-            if let Some(adsr_node) = node.as_any_mut().downcast_mut::<crate::nodes::ADSRNode>() {
-                adsr_node.trigger_gate();
-                return Ok(());
-            }
+        graph.set_bypass(uuid, bypass)
+    }
+
+    /// Play an output node on other device channels. Several output nodes may
+    /// share channels; they are mixed.
+    pub fn set_output_route(&self, node_id: &str, route: OutputRoute) -> Result<(), String> {
+        let uuid = Uuid::parse_str(node_id)
+            .map_err(|_| format!("Node not found: {}", node_id))?;
+        let channels = self.output_channels() as usize;
+        if route.channel_range().end > channels {
+            return Err(format!("{} has only {} output channels", self.backend.device_name(), channels));
         }
-        
-        Err(format!("ADSR node not found: {}", node_id))
+
+        if self.is_playing {
+            let mut topology = self.topology.lock()
+                .map_err(|e| format!("Failed to lock topology: {}", e))?;
+            topology.check_output_route(uuid, route)?;
+            self.commands.send(EngineCommand::SetOutputRoute { node_id: uuid, route })?;
+            return topology.set_output_route(uuid, route);
+        }
+
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+
+        graph.set_output_route(uuid, route)
+    }
+
+    /// Device channels of an output node
+    pub fn get_output_route(&self, node_id: &str) -> Option<OutputRoute> {
+        let uuid = Uuid::parse_str(node_id).ok()?;
+        self.read_topology(|topology| topology.output_route(uuid)).flatten()
+    }
+
+    /// Whether a node is bypassed
+    pub fn is_node_bypassed(&self, node_id: &str) -> bool {
+        Uuid::parse_str(node_id)
+            .ok()
+            .and_then(|uuid| self.read_topology(|topology| topology.is_bypassed(uuid)))
+            .unwrap_or(false)
+    }
+
+    /// Play `source` on an audio input node instead of its current source
+    pub fn set_audio_input_source(&self, node_id: &str, mut source: Box<dyn AudioSource>) -> Result<(), String> {
+        let uuid = Uuid::parse_str(node_id)
+            .map_err(|_| format!("Node not found: {}", node_id))?;
+        let node_type = self.read_topology(|t| t.node_info(uuid).map(|info| info.node_type.clone()))
+            .flatten()
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+        if node_type != "audio_input" {
+            return Err(format!("Node {} is not an audio input", node_id));
+        }
+
+        source.prepare(self.sample_rate, self.buffer_size);
+        if self.is_playing {
+            return self.commands.send(EngineCommand::SetAudioSource { node_id: uuid, source });
+        }
+
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+        let input = graph.get_node_mut(node_id)
+            .and_then(|node| node.as_any_mut().downcast_mut::<AudioInputNode>())
+            .ok_or_else(|| format!("Node {} is not an audio input", node_id))?;
+        input.set_source(source);
+        Ok(())
+    }
+
+    /// Play a WAV file on an audio input node
+    pub fn load_audio_input_file(&self, node_id: &str, path: &str, looping: bool) -> Result<(), String> {
+        let source = BufferSource::from_wav_file(path)?.with_looping(looping);
+        self.set_audio_input_source(node_id, Box::new(source))
+    }
+
+    /// Switch an audio input node back to the capture stream
+    pub fn use_capture_input(&self, node_id: &str) -> Result<(), String> {
+        self.set_audio_input_source(node_id, Box::new(CaptureSource::new(Arc::clone(&self.capture))))
     }
 
     /// Start audio processing
@@ -246,29 +834,42 @@ impl AudioEngine {
             return Ok(());
         }
 
-        let config = StreamConfig {
-            channels: 2, // Stereo output
-            sample_rate: cpal::SampleRate(self.sample_rate as u32),
-            buffer_size: cpal::BufferSize::Fixed(self.buffer_size as u32),
+        // From now on topology edits are prepared on this copy
+        let current = {
+            let mut graph = self.graph.lock()
+                .map_err(|e| format!("Failed to lock graph: {}", e))?;
+            graph.prepare(self.sample_rate, self.buffer_size);
+            graph.set_worker_threads(self.worker_threads);
+            // Read from the status while running, so start from the current state
+            self.status.position.store(graph.position(), Ordering::Relaxed);
+            if let Ok(mut transport) = self.status.transport.lock() {
+                *transport = graph.transport().info();
+            }
+            self.edit_entries(|entries| *entries = node_entries(&graph));
+            graph.topology().clone()
         };
+        // Prepared with the current latencies; reports from an earlier run are stale
+        self.commands.take_latency_changes();
+        self.latency_unsent.store(false, Ordering::Relaxed);
+        *self.topology.lock().map_err(|e| format!("Failed to lock topology: {}", e))? = current;
 
         let graph = Arc::clone(&self.graph);
+        let commands = Arc::clone(&self.command_receiver);
+        let status = Arc::clone(&self.status);
         let sample_rate = self.sample_rate;
+        let channels = self.backend.channels() as usize;
+        // At least a stereo pair, so mono devices can get the L/R average
+        let mut scratch = vec![vec![0.0; self.buffer_size]; channels.max(2)];
 
-        let stream = self.device.build_output_stream(
-            &config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                Self::audio_callback(data, &graph, sample_rate);
-            },
-            |err| {
-                eprintln!("Audio stream error: {}", err);
-            },
-            None,
-        ).map_err(|e| format!("Failed to create audio stream: {}", e))?;
+        self.backend.start(self.buffer_size, Box::new(move |data: &mut [f32]| {
+            Self::audio_callback(data, &graph, &commands, &status, &mut scratch, sample_rate, channels);
+        }))?;
 
-        stream.play().map_err(|e| format!("Failed to start audio stream: {}", e))?;
+        // The patch still plays without input; audio input nodes are silent then
+        if let Err(e) = self.backend.start_capture(Arc::clone(&self.capture)) {
+            println!("Audio input unavailable: {}", e);
+        }
 
-        self.stream = Some(stream);
         self.is_playing = true;
 
         println!("Audio engine started");
@@ -281,9 +882,13 @@ impl AudioEngine {
             return Ok(());
         }
 
-        if let Some(stream) = self.stream.take() {
-            stream.pause().map_err(|e| format!("Failed to stop audio stream: {}", e))?;
+        self.backend.stop()?;
+
+        // Apply what the audio thread did not get to
+        if let (Ok(mut receiver), Ok(mut graph)) = (self.command_receiver.lock(), self.graph.lock()) {
+            receiver.apply_pending(&mut graph);
         }
+        self.commands.collect_garbage();
 
         self.is_playing = false;
         println!("Audio engine stopped");
@@ -295,26 +900,142 @@ impl AudioEngine {
         self.is_playing
     }
 
+    /// Sample rate the graph is prepared for
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    /// Largest block the graph is prepared for
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Number of device output channels
+    pub fn output_channels(&self) -> u16 {
+        self.backend.channels()
+    }
+
+    /// Open another number of device output channels.
+    /// Restarts the stream if running; output routes are kept.
+    pub fn set_output_channels(&mut self, channels: u16) -> Result<(), String> {
+        let was_playing = self.is_playing;
+        self.stop()?;
+        let result = self.backend.set_channels(channels);
+        if result.is_ok() {
+            self.audio_config.output_channels = Some(channels);
+        }
+        if was_playing {
+            self.start()?;
+        }
+        result
+    }
+
+    /// Ask the device for blocks of `buffer_size` samples.
+    /// Restarts the stream if running; nodes keep their state.
+    pub fn set_buffer_size(&mut self, buffer_size: usize) -> Result<(), String> {
+        if buffer_size == 0 {
+            return Err("Buffer size must be at least 1 sample".to_string());
+        }
+        self.reconfigure(|engine| {
+            engine.buffer_size = buffer_size;
+            engine.audio_config.buffer_size = buffer_size;
+        })
+    }
+
+    /// Process independent graph branches on `threads` extra worker threads;
+    /// `0` processes on the audio thread only. Restarts the stream if running.
+    pub fn set_worker_threads(&mut self, threads: usize) -> Result<(), String> {
+        self.reconfigure(|engine| {
+            engine.worker_threads = threads;
+            engine.audio_config.worker_threads = Some(threads);
+        })
+    }
+
+    /// Number of extra worker threads the graph is processed on while running
+    pub fn worker_threads(&self) -> usize {
+        self.worker_threads
+    }
+
+    /// Device settings as last applied, for saving with `AudioConfig::save`
+    pub fn audio_config(&self) -> &AudioConfig {
+        &self.audio_config
+    }
+
+    /// Switch to the host, device, sample rate, buffer size and channel count
+    /// of `config`. The new device is opened before the current stream stops,
+    /// so a failure leaves the engine as it was. The graph is kept and
+    /// prepared for the new settings.
+    pub fn apply_audio_config(&mut self, config: AudioConfig) -> Result<(), String> {
+        config.validate()?;
+        let backend = CpalBackend::open(&config)?;
+        self.reconfigure(|engine| {
+            engine.sample_rate = backend.sample_rate();
+            engine.backend = Box::new(backend);
+            engine.buffer_size = config.buffer_size;
+            engine.worker_threads = config.worker_threads.unwrap_or_else(device::default_worker_threads);
+            engine.audio_config = config;
+        })
+    }
+
+    /// Switch to another backend (e.g. a different device) and its sample rate.
+    /// Restarts the stream if running; nodes keep their state.
+    pub fn set_backend(&mut self, backend: Box<dyn AudioBackend>) -> Result<(), String> {
+        self.reconfigure(|engine| {
+            engine.sample_rate = backend.sample_rate();
+            engine.backend = backend;
+        })
+    }
+
+    /// Stop the stream, change the settings and prepare the graph for them
+    fn reconfigure(&mut self, change: impl FnOnce(&mut Self)) -> Result<(), String> {
+        let was_playing = self.is_playing;
+        self.stop()?;
+        change(self);
+
+        // Starting prepares the graph as well
+        if was_playing {
+            return self.start();
+        }
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+        graph.prepare(self.sample_rate, self.buffer_size);
+        Ok(())
+    }
+
+    /// Total latency of the patch in samples, including delay compensation.
+    /// Read from the control-thread topology while running.
+    pub fn latency_samples(&self) -> u32 {
+        if self.is_playing {
+            if let Err(e) = self.update_latency_compensation() {
+                eprintln!("Failed to compensate latency: {}", e);
+            }
+            return self.read_topology(GraphTopology::total_latency).unwrap_or(0);
+        }
+        self.graph.lock().map(|graph| graph.latency()).unwrap_or(0)
+    }
+
+    /// Number of audio blocks skipped because the graph was busy
+    pub fn xrun_count(&self) -> u64 {
+        self.status.xruns.load(Ordering::Relaxed)
+    }
+
     /// List all nodes in the graph
     pub fn list_nodes(&self) -> Vec<String> {
-        if let Ok(graph) = self.graph.lock() {
-            graph.list_nodes()
-        } else {
-            Vec::new()
-        }
+        self.read_topology(|t| t.nodes().map(|info| info.id.to_string()).collect())
+            .unwrap_or_default()
     }
 
     /// Get node information by ID
     pub fn get_node_info(&self, node_id: &str) -> Option<crate::processing::NodeInfo> {
-        if let Ok(graph) = self.graph.lock() {
-            graph.get_node_info(node_id)
-        } else {
-            None
-        }
+        let uuid = Uuid::parse_str(node_id).ok()?;
+        self.read_topology(|t| t.node_info(uuid).cloned()).flatten()
     }
 
     /// Get node parameters by ID
     pub fn get_node_parameters(&self, node_id: &str) -> Option<std::collections::HashMap<String, f32>> {
+        if self.is_playing {
+            return self.read_entry(node_id, |entry| entry.parameters.clone());
+        }
         if let Ok(graph) = self.graph.lock() {
             if let Some(node) = graph.get_node(node_id) {
                 Some(node.get_all_parameters())
@@ -326,26 +1047,76 @@ impl AudioEngine {
         }
     }
 
+    /// State of a node beyond its parameters (see `AudioNode::save_state`)
+    pub fn get_node_state(&self, node_id: &str) -> Option<NodeState> {
+        if self.is_playing {
+            return self.read_entry(node_id, |entry| entry.state.clone()).flatten();
+        }
+        let graph = self.graph.lock().ok()?;
+        graph.get_node(node_id)?.save_state()
+    }
+
+    /// Restore node state saved with `get_node_state`
+    ///
+    /// While running, the state is loaded into a new instance of the node on
+    /// this thread, which then takes the place of the running one; anything the
+    /// node keeps besides its parameters and state starts over.
+    pub fn set_node_state(&self, node_id: &str, state: NodeState) -> Result<(), String> {
+        if self.is_playing {
+            let entry = self.read_entry(node_id, |entry| PatchNode { state: None, ..entry.clone() })
+                .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+            let mut node = self.instantiate_patch_node(&entry)?;
+            restore_patch_node(node.as_mut(), &entry);
+            let uuid = node.node_info().id;
+            if uuid.to_string() != entry.id {
+                return Err(format!("Node {} cannot be replaced with its ID", entry.id));
+            }
+            node.load_state(&state)?;
+            self.attach_capture(node.as_mut());
+            node.prepare(self.sample_rate, self.buffer_size);
+
+            self.commands.send(EngineCommand::ReplaceNode(node))?;
+            self.edit_entries(|entries| entries.get_mut(&uuid).map(|entry| entry.patch.state = Some(state)));
+            return Ok(());
+        }
+
+        let mut graph = self.graph.lock()
+            .map_err(|e| format!("Failed to lock graph: {}", e))?;
+
+        match graph.get_node_mut(node_id) {
+            Some(node) => node.load_state(&state),
+            None => Err(format!("Node not found: {}", node_id)),
+        }
+    }
+
     /// Find node by name
     pub fn find_node_by_name(&self, name: &str) -> Option<Uuid> {
-        if let Ok(graph) = self.graph.lock() {
-            graph.find_node_by_name(name)
-        } else {
-            None
-        }
+        self.read_topology(|t| t.nodes().find(|info| info.name == name).map(|info| info.id))
+            .flatten()
     }
 
     /// Find node name by ID
     pub fn find_node_name_by_id(&self, node_id: Uuid) -> Option<String> {
-        if let Ok(graph) = self.graph.lock() {
-            graph.find_node_name_by_id(node_id)
-        } else {
-            None
-        }
+        self.read_topology(|t| t.node_info(node_id).map(|info| info.name.clone()))
+            .flatten()
     }
 
-    /// Clear the entire graph
+    /// Clear the entire graph. The edit history is cleared as well.
     pub fn clear_graph(&self) -> Result<(), String> {
+        self.clear_history();
+        if self.is_playing {
+            self.edit_topology(
+                |topology| {
+                    topology.clear();
+                    Ok(())
+                },
+                |topology| EngineCommand::Clear { topology },
+            )?;
+            self.edit_entries(HashMap::clear);
+            return Ok(());
+        }
+
         let mut graph = self.graph.lock()
             .map_err(|e| format!("Failed to lock graph: {}", e))?;
         
@@ -353,59 +1124,162 @@ impl AudioEngine {
         Ok(())
     }
 
-    /// Save graph to file
+    /// Save graph to file (see `to_patch`)
     pub fn save_to_file(&self, filename: &str) -> Result<(), String> {
-        if let Ok(graph) = self.graph.lock() {
-            graph.save_to_file(filename)
+        self.to_patch()?.save(filename)
+    }
+
+    /// The graph as a patch. While running, it is built from the
+    /// control-thread copies, so the audio thread is not interrupted.
+    pub fn to_patch(&self) -> Result<PatchFile, String> {
+        if self.is_playing {
+            let topology = self.topology.lock()
+                .map_err(|e| format!("Failed to lock topology: {}", e))?;
+            let entries = self.node_entries.lock()
+                .map_err(|e| format!("Failed to lock node entries: {}", e))?;
+            return Ok(topology_patch(&topology, |node_id| entries.get(&node_id).map(|entry| entry.patch.clone())));
+        }
+
+        let graph = self.graph.lock()
+            .map_err(|_| "Failed to lock graph".to_string())?;
+        Ok(graph.to_patch())
+    }
+
+    /// Load graph from file - built-in, subpatch and plugin nodes.
+    /// Returns what was changed to bring an older file up to date.
+    ///
+    /// The graph is built and prepared on the calling thread; while running it
+    /// replaces the live graph in one command, after the edits queued before it.
+    pub fn load_from_file(&self, filename: &str) -> Result<MigrationReport, String> {
+        let (patch, report) = PatchFile::load(filename)?;
+
+        // Create the nodes before locking the graph; plugin creation locks the plugin manager
+        let mut created = HashMap::new();
+        for patch_node in &patch.nodes {
+            let mut node = self.instantiate_patch_node(patch_node)?;
+            self.attach_capture(node.as_mut());
+            created.insert(patch_node.id.as_str(), node);
+        }
+        let create_node = |patch_node: &PatchNode| {
+            created.remove(patch_node.id.as_str())
+                .ok_or_else(|| format!("Duplicate node ID: {}", patch_node.id))
+        };
+
+        if self.is_playing {
+            let mut loaded = ProcessingGraph::new();
+            loaded.load_patch(&patch, create_node)?;
+            loaded.prepare(self.sample_rate, self.buffer_size);
+
+            let mut topology = self.topology.lock()
+                .map_err(|e| format!("Failed to lock topology: {}", e))?;
+            let entries = node_entries(&loaded);
+            let control = loaded.topology().clone();
+            let (nodes, compiled) = loaded.into_parts();
+            self.commands.send(EngineCommand::ReplaceGraph { nodes, topology: compiled })?;
+            *topology = control;
+            self.edit_entries(|current| *current = entries);
         } else {
-            Err("Failed to lock graph".to_string())
+            let mut graph = self.graph.lock()
+                .map_err(|e| format!("Failed to lock graph: {}", e))?;
+            graph.load_patch(&patch, create_node)?;
+            graph.prepare(self.sample_rate, self.buffer_size);
         }
+
+        self.clear_history();
+        Ok(report)
     }
 
-    /// Load graph from file
-    pub fn load_from_file(&self, filename: &str) -> Result<(), String> {
-        let mut graph = self.graph.lock()
-            .map_err(|e| format!("Failed to lock graph: {}", e))?;
-        
-        graph.load_from_file(filename, self.sample_rate)
+    /// Render the current graph to a WAV file without using the audio device.
+    ///
+    /// Renders a copy rebuilt from the patch, so the live nodes keep their state.
+    /// The copy takes bypass, feedback delay and transport (tempo, time
+    /// signature, play state and loop) from the live graph.
+    /// Refused while playing: the audio thread owns the live graph then.
+    pub fn render_to_wav(&self, filename: &str, duration_seconds: f32, format: WavFormat) -> Result<(), String> {
+        if self.is_playing {
+            return Err("Stop the audio engine before rendering".to_string());
+        }
+
+        let mut graph = self.render_copy()?;
+
+        let settings = RenderSettings::new(self.sample_rate, duration_seconds)
+            .with_block_size(self.buffer_size)
+            .with_format(format);
+        render_to_wav(&mut graph, filename, &settings)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to render: {}", e))
+    }
+
+    /// Copy of the graph rebuilt from its patch, with the transport of the live graph
+    fn render_copy(&self) -> Result<ProcessingGraph, String> {
+        let (patch, transport) = {
+            let graph = self.graph.lock()
+                .map_err(|e| format!("Failed to lock graph: {}", e))?;
+            (graph.to_patch(), graph.transport().clone())
+        };
+        let mut graph = ProcessingGraph::new();
+        graph.load_patch(&patch, |patch_node| self.instantiate_patch_node(patch_node))?;
+        // Not part of the patch
+        *graph.transport_mut() = transport;
+        Ok(graph)
     }
 
     /// Audio callback function
-    fn audio_callback(output: &mut [f32], graph: &Arc<Mutex<ProcessingGraph>>, sample_rate: f32) {
+    fn audio_callback(output: &mut [f32], graph: &Mutex<ProcessingGraph>, commands: &Mutex<CommandReceiver>,
+                      status: &EngineStatus, scratch: &mut [Vec<f32>], sample_rate: f32, channels: usize) {
         // Clear output buffer
         for sample in output.iter_mut() {
             *sample = 0.0;
         }
 
-        let mut graph = match graph.lock() {
+        // Never wait for the control thread - skip the block if the graph is busy
+        let mut graph = match graph.try_lock() {
             Ok(g) => g,
-            Err(_) => return, // Skip this buffer if we can't lock
+            Err(_) => {
+                status.xruns.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
         };
 
-        let buffer_size = output.len() / 2; // Stereo output
-
-        // Create process context
-        let mut inputs = InputPorts::new();
-        let mut outputs = OutputPorts::new();
+        // Only the audio thread takes the receiver while running
+        if let Ok(mut commands) = commands.try_lock() {
+            commands.apply_pending(&mut graph);
+        }
 
-        // Process the audio graph
-        if let Err(e) = graph.process_audio(&mut inputs, &mut outputs, sample_rate, buffer_size) {
-            eprintln!("Audio processing error: {}", e);
+        let block_size = scratch.first().map_or(0, Vec::len);
+        if channels == 0 || block_size == 0 {
             return;
         }
 
-        // Get final output from the graph
-        if let Some(left_output) = outputs.get_audio("main_left") {
-            if let Some(right_output) = outputs.get_audio("main_right") {
-                // Interleave stereo output
-                for (i, (left, right)) in left_output.iter().zip(right_output.iter()).enumerate() {
-                    if i * 2 + 1 < output.len() {
-                        output[i * 2] = *left;
-                        output[i * 2 + 1] = *right;
+        // Hosts may hand over any number of frames (441, 4096, ...); render them in
+        // blocks of the prepared size so the scratch buffers never grow here
+        let mut result = Ok(());
+        for chunk in output.chunks_mut(block_size * channels) {
+            let frames = chunk.len() / channels;
+            result = graph.process_channels(scratch, frames, sample_rate);
+            if result.is_err() {
+                break;
+            }
+
+            // Interleave (mono devices get the L/R average of the first pair)
+            for (i, frame) in chunk.chunks_mut(channels).enumerate() {
+                if channels == 1 {
+                    frame[0] = (scratch[0][i] + scratch[1][i]) * 0.5;
+                } else {
+                    for (sample, channel) in frame.iter_mut().zip(scratch.iter()) {
+                        *sample = channel[i];
                     }
                 }
             }
         }
+
+        status.position.store(graph.position(), Ordering::Relaxed);
+        if let Ok(mut transport) = status.transport.try_lock() {
+            *transport = graph.transport().info();
+        }
+        if let Err(e) = result {
+            eprintln!("Audio processing error: {}", e);
+        }
     }
 
     /// List all available node types (built-in + plugins)
@@ -415,6 +1289,7 @@ impl AudioEngine {
             "sine_oscillator".to_string(),
             "oscillator".to_string(),
             "noise".to_string(),
+            "audio_input".to_string(),
             "vcf".to_string(),
             "vca".to_string(),
             "delay".to_string(),
@@ -433,6 +1308,12 @@ impl AudioEngine {
             "output".to_string(),
             "oscilloscope".to_string(),
             "spectrum_analyzer".to_string(),
+            "voice_allocator".to_string(),
+            "poly_sum".to_string(),
+            "poly_oscillator".to_string(),
+            "poly_vcf".to_string(),
+            "poly_vca".to_string(),
+            "poly_adsr".to_string(),
         ];
 
         // Add plugin types
@@ -479,8 +1360,11 @@ impl AudioEngine {
         info.insert("sample_rate".to_string(), self.sample_rate.to_string());
         info.insert("buffer_size".to_string(), self.buffer_size.to_string());
         info.insert("is_playing".to_string(), self.is_playing.to_string());
-        info.insert("device_name".to_string(), 
-                   self.device.name().unwrap_or("Unknown".to_string()));
+        info.insert("device_name".to_string(), self.backend.device_name());
+        info.insert("output_channels".to_string(), self.output_channels().to_string());
+        info.insert("xruns".to_string(), self.xrun_count().to_string());
+        info.insert("latency_samples".to_string(), self.latency_samples().to_string());
+        info.insert("worker_threads".to_string(), self.worker_threads.to_string());
 
         // Add plugin info
         if let Ok(manager) = self.plugin_manager.lock() {
@@ -493,6 +1377,59 @@ impl AudioEngine {
     }
 }
 
+/// Control-thread copy of a node while running
+struct NodeEntry {
+    patch: PatchNode,
+    /// Ranges of the node's parameters, to check changes before they are queued
+    descriptors: Vec<Box<dyn ParameterDescriptor>>,
+}
+
+impl NodeEntry {
+    fn new(node: &dyn AudioNode) -> Self {
+        Self { patch: patch_node_entry(node), descriptors: node.get_parameter_descriptors() }
+    }
+
+    /// Reject what the node's `set_parameter` would: unknown names and values
+    /// out of range. Parameters without a descriptor are only checked by name.
+    fn check_parameter(&self, param_name: &str, value: f32) -> Result<(), ParameterError> {
+        let name = entry_parameter_name(&self.patch, param_name);
+        match self.descriptors.iter().find(|descriptor| descriptor.name() == name) {
+            Some(descriptor) => descriptor.validate(value).map(|_| ()),
+            None if self.patch.parameters.contains_key(name) => Ok(()),
+            None => Err(ParameterError::NotFound { name: name.to_string() }),
+        }
+    }
+}
+
+/// Control-thread entries of every node in `graph`
+fn node_entries(graph: &ProcessingGraph) -> HashMap<Uuid, NodeEntry> {
+    graph.topology().nodes()
+        .filter_map(|info| {
+            let node = graph.audio_nodes.get(&info.id)?;
+            let patch = graph.node_to_patch(info.id)?;
+            Some((info.id, NodeEntry { patch, descriptors: node.get_parameter_descriptors() }))
+        })
+        .collect()
+}
+
+/// Name a parameter is stored under in `entry`; oscilloscopes take UI aliases
+fn entry_parameter_name<'a>(entry: &PatchNode, param_name: &'a str) -> &'a str {
+    if entry.node_type == "oscilloscope" {
+        OscilloscopeNode::parameter_name(param_name)
+    } else {
+        param_name
+    }
+}
+
+fn patch_connection(source_id: &str, source_port: &str, target_id: &str, target_port: &str) -> PatchConnection {
+    PatchConnection {
+        source_node: source_id.to_string(),
+        source_port: source_port.to_string(),
+        target_node: target_id.to_string(),
+        target_port: target_port.to_string(),
+    }
+}
+
 impl Drop for AudioEngine {
     fn drop(&mut self) {
         let _ = self.stop();
@@ -500,9 +1437,349 @@ impl Drop for AudioEngine {
 }
 
 // SAFETY: AudioEngine is thread-safe because:
-// 1. The stream is only accessed from the main thread during creation/destruction
+// 1. The backend (and its stream) is only accessed from the main thread during creation/destruction
 // 2. The graph and plugin_manager are protected by Mutex
 // 3. The stream callbacks only read from shared data, never modify AudioEngine itself
 // 4. Primitive types (sample_rate, buffer_size, is_playing) are atomic or only modified under mutex
 unsafe impl Send for AudioEngine {}
-unsafe impl Sync for AudioEngine {}
\ No newline at end of file
+unsafe impl Sync for AudioEngine {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::StereoBuffer;
+
+    #[test]
+    fn test_headless_engine_pulls_blocks() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+
+        // Nothing is rendered before start
+        assert!(clock.tick(64).iter().all(|&s| s == 0.0));
+
+        engine.start().unwrap();
+        assert!(engine.is_running());
+        let block = clock.tick(64);
+        assert_eq!(block.len(), 128);
+        assert!(block.iter().any(|&s| s.abs() > 0.001));
+        assert_eq!(clock.frames_elapsed(), 64);
+
+        engine.stop().unwrap();
+        assert!(!clock.is_running());
+        assert_eq!(engine.get_info().get("device_name").unwrap(), "Null");
+    }
+
+    #[test]
+    fn test_render_to_wav_leaves_live_graph_alone() {
+        let (mut engine, _clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        let path = std::env::temp_dir().join(format!("orbital_engine_render_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        engine.start().unwrap();
+        assert!(engine.render_to_wav(path, 0.1, WavFormat::Pcm16).is_err());
+        engine.stop().unwrap();
+
+        engine.render_to_wav(path, 0.1, WavFormat::Pcm16).unwrap();
+        assert!(std::fs::metadata(path).unwrap().len() > 44);
+        assert_eq!(engine.graph.lock().unwrap().position(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_render_to_wav_keeps_bypass_and_transport() {
+        let (engine, _clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let vca_id = engine.create_builtin_node("vca", "vca".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &vca_id, "audio_in").unwrap();
+        engine.connect_nodes(&vca_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        engine.set_node_parameter(&vca_id, "gain", 0.0).unwrap();
+        engine.transport(TransportCommand::SetTempo { tempo: 90.0 }).unwrap();
+        let path = std::env::temp_dir().join(format!("orbital_engine_bypass_{}.wav", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        // The closed VCA is bypassed, so the oscillator is heard in the bounce
+        engine.set_node_bypass(&vca_id, true).unwrap();
+        engine.render_to_wav(path, 0.1, WavFormat::Float32).unwrap();
+        let data = wav::read_wav(&mut std::fs::File::open(path).unwrap()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(data.samples.iter().any(|&s| s.abs() > 0.1));
+
+        let copy = engine.render_copy().unwrap();
+        assert!(copy.is_bypassed(Uuid::parse_str(&vca_id).unwrap()));
+        assert_eq!(copy.transport().info().tempo, 90.0);
+    }
+
+    #[test]
+    fn test_audio_input_plays_capture_and_buffers() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let in_id = engine.create_builtin_node("audio_input", "in".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&in_id, "audio_out_l", &out_id, "audio_in_l").unwrap();
+        engine.connect_nodes(&in_id, "audio_out_r", &out_id, "audio_in_r").unwrap();
+        assert!(engine.set_audio_input_source(&out_id, Box::new(CaptureSource::new(Arc::default()))).is_err());
+
+        engine.start().unwrap();
+        clock.capture(&[0.5, -0.5].repeat(64));
+        let block = clock.tick(64);
+        assert!(block.chunks(2).skip(32).all(|frame| frame[0] > 0.1 && frame[1] < -0.1));
+
+        // Swapped while running; the new source plays from the next block
+        let silence = BufferSource::new("silence", StereoBuffer::new(256), 48000.0);
+        engine.set_audio_input_source(&in_id, Box::new(silence)).unwrap();
+        clock.capture(&[0.5, -0.5].repeat(64));
+        assert!(clock.tick(64).iter().all(|&s| s == 0.0));
+
+        engine.use_capture_input(&in_id).unwrap();
+        clock.capture(&[0.5, -0.5].repeat(64));
+        assert!(clock.tick(64)[64] > 0.1);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_graph_and_config() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        engine.start().unwrap();
+
+        // The stream restarts with the new settings; nodes and cables stay
+        engine.set_buffer_size(128).unwrap();
+        engine.set_output_channels(2).unwrap();
+        assert_eq!(engine.get_info().get("worker_threads").unwrap(), "0");
+        engine.set_worker_threads(2).unwrap();
+        assert_eq!(engine.graph.lock().unwrap().worker_threads(), 2);
+        assert!(engine.is_running());
+        assert_eq!(engine.list_nodes().len(), 2);
+        assert!(clock.tick(64).iter().any(|&s| s.abs() > 0.001));
+
+        let config = engine.audio_config();
+        assert_eq!((config.buffer_size, config.output_channels, config.worker_threads), (128, Some(2), Some(2)));
+        assert!(engine.set_buffer_size(0).is_err());
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_output_routes_reach_device_channels() {
+        let backend = NullBackend::new(48000.0, 4);
+        let clock = backend.clock();
+        let mut engine = AudioEngine::with_backend(Box::new(backend));
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        assert_eq!(engine.output_channels(), 4);
+        assert!(engine.set_output_route(&out_id, OutputRoute::stereo(3)).is_err());
+        assert!(engine.set_output_channels(8).is_err());
+
+        let heard = |block: &[f32], channel: usize| block.chunks(4).any(|frame| frame[channel].abs() > 0.01);
+        engine.start().unwrap();
+        let block = clock.tick(64);
+        assert!(heard(&block, 0) && !heard(&block, 2));
+
+        // Moved while running: the next block plays on channels 3/4
+        engine.set_output_route(&out_id, OutputRoute::stereo(2)).unwrap();
+        let block = clock.tick(64);
+        assert!(!heard(&block, 0) && heard(&block, 2));
+        assert_eq!(engine.get_output_route(&out_id), Some(OutputRoute::stereo(2)));
+        assert_eq!(engine.get_output_route(&osc_id), None);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_edits_while_running_are_queued() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        engine.start().unwrap();
+
+        // Nodes and cables go through the command queue
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 220.0).unwrap();
+        assert_eq!(engine.find_node_by_name("osc").unwrap().to_string(), osc_id);
+        assert!(engine.graph.lock().unwrap().audio_nodes.is_empty());
+
+        // Applied at the start of the next block
+        assert!(clock.tick(64).iter().any(|&s| s.abs() > 0.001));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 220.0);
+        assert_eq!(engine.graph.lock().unwrap().connections().len(), 1);
+
+        // Validation happens on the control thread
+        assert!(engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").is_err());
+        assert!(engine.set_node_parameter(&Uuid::new_v4().to_string(), "frequency", 1.0).is_err());
+        for (param, value) in [("pitch", 1.0), ("frequency", -5.0)] {
+            let error = engine.set_node_parameter(&osc_id, param, value).unwrap_err();
+            assert!(error.starts_with("Failed to set parameter"), "{}", error);
+        }
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 220.0);
+
+        // Pending commands are applied on stop
+        engine.disconnect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        engine.stop().unwrap();
+        assert!(engine.graph.lock().unwrap().connections().is_empty());
+        assert_eq!(engine.xrun_count(), 0);
+    }
+
+    #[test]
+    fn test_save_while_running_reads_control_copy() {
+        let (mut engine, _clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let vca_id = engine.create_builtin_node("vca", "vca".to_string()).unwrap();
+        engine.start().unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &vca_id, "audio_in").unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 220.0).unwrap();
+        engine.set_node_bypass(&vca_id, true).unwrap();
+        let path = std::env::temp_dir().join(format!("orbital_engine_save_{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        // Saved while the audio thread holds the graph, including edits it has not applied yet
+        let graph = Arc::clone(&engine.graph);
+        let held = graph.lock().unwrap();
+        engine.save_to_file(path).unwrap();
+        assert_eq!(engine.latency_samples(), 0);
+        assert_eq!(engine.get_info().get("latency_samples").unwrap(), "0");
+        drop(held);
+        engine.stop().unwrap();
+
+        let (patch, _) = PatchFile::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let osc = patch.nodes.iter().find(|node| node.id == osc_id).unwrap();
+        assert_eq!(osc.parameters["frequency"], 220.0);
+        assert!(patch.nodes.iter().find(|node| node.id == vca_id).unwrap().bypassed);
+        assert_eq!(patch.connections.len(), 1);
+        assert_eq!(engine.xrun_count(), 0);
+    }
+
+    #[test]
+    fn test_load_while_running_replaces_queued_edits() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        let path = std::env::temp_dir().join(format!("orbital_engine_load_{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        engine.save_to_file(path).unwrap();
+        engine.clear_graph().unwrap();
+
+        // Loaded while the audio thread holds the graph, after edits it has not applied yet
+        engine.start().unwrap();
+        engine.create_builtin_node("noise", "noise".to_string()).unwrap();
+        let graph = Arc::clone(&engine.graph);
+        let held = graph.lock().unwrap();
+        engine.load_from_file(path).unwrap();
+        drop(held);
+        std::fs::remove_file(path).unwrap();
+
+        assert!(clock.tick(64).iter().any(|&s| s.abs() > 0.001));
+        let mut nodes = engine.graph.lock().unwrap().list_nodes();
+        let mut expected = vec![osc_id, out_id];
+        nodes.sort();
+        expected.sort();
+        assert_eq!(nodes, expected);
+        assert_eq!(engine.list_nodes().len(), 2);
+        assert_eq!(engine.graph.lock().unwrap().connections().len(), 1);
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_node_state_while_running_replaces_node() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let seq_id = engine.create_builtin_node("sequencer", "seq".to_string()).unwrap();
+        engine.set_node_parameter(&seq_id, "bpm", 150.0).unwrap();
+        engine.start().unwrap();
+
+        let mut state = engine.get_node_state(&seq_id).unwrap();
+        state[0]["note"] = serde_json::json!(523.25);
+        engine.set_node_state(&seq_id, state.clone()).unwrap();
+        assert!(engine.set_node_state(&seq_id, serde_json::json!("steps")).is_err());
+        clock.tick(64);
+
+        // The running node was swapped for one with the state and the parameters
+        let graph = engine.graph.lock().unwrap();
+        let node = graph.get_node(&seq_id).unwrap();
+        assert_eq!(node.save_state(), Some(state.clone()));
+        assert_eq!(node.get_parameter("bpm").unwrap(), 150.0);
+        drop(graph);
+        assert_eq!(engine.get_node_state(&seq_id), Some(state));
+        engine.stop().unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let (engine, _clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        let out_id = engine.create_builtin_node("output", "out".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &out_id, "audio_in_l").unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 220.0).unwrap();
+
+        // Removing a node takes its cables along; undo brings both back under the same ID
+        engine.remove_node(Uuid::parse_str(&osc_id).unwrap()).unwrap();
+        assert!(engine.graph.lock().unwrap().connections().is_empty());
+        assert_eq!(engine.undo().unwrap().as_deref(), Some("Remove osc"));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 220.0);
+        assert_eq!(engine.graph.lock().unwrap().connections().len(), 1);
+
+        assert_eq!(engine.undo().unwrap().as_deref(), Some("Set frequency"));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 440.0);
+        assert_eq!(engine.history_labels(), (Some("Connect".to_string()), Some("Set frequency".to_string())));
+        assert_eq!(engine.redo().unwrap().as_deref(), Some("Set frequency"));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 220.0);
+
+        // A transaction is one step; aborting it reverts its edits
+        engine.begin_transaction("Paste").unwrap();
+        let vcf_id = engine.create_builtin_node("vcf", "vcf".to_string()).unwrap();
+        engine.connect_nodes(&osc_id, "audio_out", &vcf_id, "audio_in").unwrap();
+        assert!(engine.undo().is_err());
+        engine.end_transaction().unwrap();
+        assert_eq!(engine.undo().unwrap().as_deref(), Some("Paste"));
+        assert_eq!(engine.list_nodes().len(), 2);
+        assert_eq!(engine.redo().unwrap().as_deref(), Some("Paste"));
+        assert_eq!(engine.graph.lock().unwrap().connections().len(), 2);
+
+        engine.begin_transaction("Discarded").unwrap();
+        engine.remove_node(Uuid::parse_str(&vcf_id).unwrap()).unwrap();
+        engine.abort_transaction().unwrap();
+        assert_eq!(engine.list_nodes().len(), 3);
+        assert_eq!(engine.history_labels().0.as_deref(), Some("Paste"));
+
+        // Redo is gone after a new edit, and everything unwinds to an empty graph
+        engine.undo().unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 110.0).unwrap();
+        assert_eq!(engine.redo().unwrap(), None);
+        while engine.undo().unwrap().is_some() {}
+        assert!(engine.list_nodes().is_empty());
+    }
+
+    #[test]
+    fn test_undo_while_running_reads_control_copy() {
+        let (mut engine, clock) = AudioEngine::new_headless(48000.0);
+        let osc_id = engine.create_builtin_node("oscillator", "osc".to_string()).unwrap();
+        engine.start().unwrap();
+        engine.clear_history();
+
+        // The graph is held as if the audio thread were in the middle of a block
+        let graph = Arc::clone(&engine.graph);
+        let held = graph.lock().unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 220.0).unwrap();
+        engine.set_node_parameter(&osc_id, "frequency", 330.0).unwrap();
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 330.0);
+        engine.remove_node(Uuid::parse_str(&osc_id).unwrap()).unwrap();
+        assert_eq!(engine.undo().unwrap().as_deref(), Some("Remove osc"));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 330.0);
+
+        // The knob turn is one step back to where it started
+        assert_eq!(engine.undo().unwrap().as_deref(), Some("Set frequency"));
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 440.0);
+        assert_eq!(engine.undo().unwrap(), None);
+        drop(held);
+
+        clock.tick(64);
+        engine.stop().unwrap();
+        assert_eq!(engine.get_node_parameter(&osc_id, "frequency").unwrap(), 440.0);
+    }
+}
@@ -0,0 +1,231 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Offline (faster-than-realtime) rendering of a `ProcessingGraph`.
+//!
+//! The renderer drives `ProcessingGraph::process_stereo` block by block without
+//! an audio device and collects the left/right outputs.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::audio::wav::{write_wav, WavFormat};
+use crate::errors::{AudioEngineError, AudioEngineResult};
+use crate::graph::ProcessingGraph;
+use crate::processing::StereoBuffer;
+
+/// オフラインレンダリング設定
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub sample_rate: f32,
+    pub block_size: usize,
+    pub duration_seconds: f32,
+    pub format: WavFormat,
+}
+
+impl RenderSettings {
+    pub fn new(sample_rate: f32, duration_seconds: f32) -> Self {
+        Self {
+            sample_rate,
+            block_size: 512,
+            duration_seconds,
+            format: WavFormat::Float32,
+        }
+    }
+
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn with_format(mut self, format: WavFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Total number of frames to render
+    pub fn total_frames(&self) -> usize {
+        (self.duration_seconds.max(0.0) * self.sample_rate).round() as usize
+    }
+
+    fn validate(&self) -> AudioEngineResult<()> {
+        if self.sample_rate <= 0.0 || !self.sample_rate.is_finite() {
+            return Err(AudioEngineError::internal(&format!("Invalid render sample rate: {}", self.sample_rate)));
+        }
+        if self.block_size == 0 {
+            return Err(AudioEngineError::internal("Render block size must be at least 1"));
+        }
+        Ok(())
+    }
+}
+
+/// Rendered stereo audio
+#[derive(Debug, Clone, Default)]
+pub struct RenderedAudio {
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+    pub sample_rate: f32,
+}
+
+impl RenderedAudio {
+    pub fn frames(&self) -> usize {
+        self.left.len()
+    }
+
+    /// Interleave L/R into a single `L R L R ...` buffer
+    pub fn interleaved(&self) -> Vec<f32> {
+        let mut samples = Vec::with_capacity(self.left.len() * 2);
+        for (left, right) in self.left.iter().zip(self.right.iter()) {
+            samples.push(*left);
+            samples.push(*right);
+        }
+        samples
+    }
+
+    /// Write the rendered audio to a stereo WAV file
+    pub fn write_wav<P: AsRef<Path>>(&self, path: P, format: WavFormat) -> AudioEngineResult<()> {
+        let path_str = path.as_ref().display().to_string();
+        let file_error = |e: std::io::Error| AudioEngineError::FileIo {
+            operation: "write_wav".to_string(),
+            path: path_str.clone(),
+            reason: e.to_string(),
+        };
+
+        let file = File::create(path.as_ref()).map_err(file_error)?;
+        let mut writer = BufWriter::new(file);
+        write_wav(&mut writer, &self.interleaved(), 2, self.sample_rate.round() as u32, format)
+            .map_err(file_error)
+    }
+}
+
+/// Render the graph into memory.
+///
+/// The graph is prepared for the render settings first (`ProcessingGraph::prepare`)
+/// and stays prepared for them afterwards.
+pub fn render_graph(graph: &mut ProcessingGraph, settings: &RenderSettings) -> AudioEngineResult<RenderedAudio> {
+    settings.validate()?;
+    graph.prepare(settings.sample_rate, settings.block_size);
+
+    let total_frames = settings.total_frames();
+    let mut rendered = RenderedAudio {
+        left: Vec::with_capacity(total_frames),
+        right: Vec::with_capacity(total_frames),
+        sample_rate: settings.sample_rate,
+    };
+
+    let mut buffer = StereoBuffer::new(settings.block_size);
+    let mut position = 0;
+    while position < total_frames {
+        let block = settings.block_size.min(total_frames - position);
+
+        // Graphs without an output node render silence
+        buffer.resize(block);
+        graph.process_stereo(&mut buffer.left, &mut buffer.right, settings.sample_rate)?;
+        rendered.left.extend_from_slice(&buffer.left);
+        rendered.right.extend_from_slice(&buffer.right);
+
+        position += block;
+    }
+
+    Ok(rendered)
+}
+
+/// Render the graph and write the result to a WAV file
+pub fn render_to_wav<P: AsRef<Path>>(
+    graph: &mut ProcessingGraph,
+    path: P,
+    settings: &RenderSettings,
+) -> AudioEngineResult<RenderedAudio> {
+    let rendered = render_graph(graph, settings)?;
+    rendered.write_wav(path, settings.format)?;
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{OscillatorNode, OutputNode};
+    use crate::processing::AudioNode;
+
+    fn build_tone_graph(sample_rate: f32) -> ProcessingGraph {
+        let mut graph = ProcessingGraph::new();
+        let osc = OscillatorNode::new(sample_rate, "osc".to_string());
+        let output = OutputNode::new(sample_rate, "out".to_string());
+        let osc_id = osc.node_info().id.to_string();
+        let output_id = output.node_info().id.to_string();
+
+        graph.add_node_instance(Box::new(osc)).unwrap();
+        graph.add_node_instance(Box::new(output)).unwrap();
+        graph.connect_by_id(&osc_id, "audio_out", &output_id, "audio_in_l").unwrap();
+        graph.connect_by_id(&osc_id, "audio_out", &output_id, "audio_in_r").unwrap();
+        graph
+    }
+
+    #[test]
+    fn test_render_length_and_signal() {
+        let mut graph = build_tone_graph(48000.0);
+        // 0.1s at 48kHz is not a multiple of the block size
+        let settings = RenderSettings::new(48000.0, 0.1).with_block_size(256);
+
+        let rendered = render_graph(&mut graph, &settings).unwrap();
+        assert_eq!(rendered.frames(), 4800);
+        assert_eq!(rendered.right.len(), 4800);
+        assert!(rendered.left.iter().any(|&s| s.abs() > 0.01));
+    }
+
+    #[test]
+    fn test_render_prepares_graph_for_its_sample_rate() {
+        // Built for 44.1 kHz, rendered at 22.05 kHz: still 440 cycles per second
+        let mut graph = build_tone_graph(44100.0);
+        let rendered = render_graph(&mut graph, &RenderSettings::new(22050.0, 1.0)).unwrap();
+
+        let cycles = rendered.left.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count();
+        assert!((439..=441).contains(&cycles), "{} cycles", cycles);
+    }
+
+    #[test]
+    fn test_render_without_output_node_is_silent() {
+        let mut graph = ProcessingGraph::new();
+        graph.add_node_instance(Box::new(OscillatorNode::new(44100.0, "osc".to_string()))).unwrap();
+
+        let rendered = render_graph(&mut graph, &RenderSettings::new(44100.0, 0.01)).unwrap();
+        assert_eq!(rendered.frames(), 441);
+        assert!(rendered.left.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let mut graph = ProcessingGraph::new();
+        assert!(render_graph(&mut graph, &RenderSettings::new(44100.0, 1.0).with_block_size(0)).is_err());
+        assert!(render_graph(&mut graph, &RenderSettings::new(0.0, 1.0)).is_err());
+    }
+
+    #[test]
+    fn test_render_to_wav_file() {
+        let mut graph = build_tone_graph(44100.0);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bounce.wav");
+        let settings = RenderSettings::new(44100.0, 0.05).with_format(WavFormat::Pcm24);
+
+        let rendered = render_to_wav(&mut graph, &path, &settings).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes.len(), 44 + rendered.frames() * 2 * 3);
+    }
+}
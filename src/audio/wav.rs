@@ -0,0 +1,316 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Minimal RIFF/WAVE reader and writer, used by the offline renderer and
+//! file-backed audio inputs.
+
+use std::io::{self, Read, Write};
+
+/// WAVファイルのサンプルフォーマット
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavFormat {
+    /// 16-bit integer PCM
+    Pcm16,
+    /// 24-bit integer PCM
+    Pcm24,
+    /// 32-bit IEEE float
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Pcm24 => 24,
+            WavFormat::Float32 => 32,
+        }
+    }
+
+    fn bytes_per_sample(&self) -> u16 {
+        self.bits_per_sample() / 8
+    }
+
+    fn format_tag(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 | WavFormat::Pcm24 => 1, // WAVE_FORMAT_PCM
+            WavFormat::Float32 => 3,                  // WAVE_FORMAT_IEEE_FLOAT
+        }
+    }
+}
+
+/// Write interleaved samples as a WAV stream.
+///
+/// `samples` must be interleaved frame by frame (`L R L R ...` for stereo).
+/// Integer formats clip the input to [-1.0, 1.0].
+pub fn write_wav<W: Write>(
+    writer: &mut W,
+    samples: &[f32],
+    channels: u16,
+    sample_rate: u32,
+    format: WavFormat,
+) -> io::Result<()> {
+    if channels == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "WAV channel count must be at least 1"));
+    }
+
+    let block_align = channels
+        .checked_mul(format.bytes_per_sample())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "WAV channel count too large"))?;
+    let byte_rate = sample_rate
+        .checked_mul(block_align as u32)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "WAV byte rate exceeds 4 GiB per second"))?;
+    let frames = (samples.len() / channels as usize) as u32;
+
+    // Non-PCM formats carry a cbSize field and a fact chunk
+    let is_float = format == WavFormat::Float32;
+    let fmt_len: u32 = if is_float { 18 } else { 16 };
+    let (data_len, riff_len) = chunk_sizes(samples.len() as u64, format)?;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_len.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&fmt_len.to_le_bytes())?;
+    writer.write_all(&format.format_tag().to_le_bytes())?;
+    writer.write_all(&channels.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&format.bits_per_sample().to_le_bytes())?;
+    if is_float {
+        writer.write_all(&0u16.to_le_bytes())?;
+        writer.write_all(b"fact")?;
+        writer.write_all(&4u32.to_le_bytes())?;
+        writer.write_all(&frames.to_le_bytes())?;
+    }
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+
+    for &sample in samples {
+        match format {
+            WavFormat::Pcm16 => {
+                let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            WavFormat::Pcm24 => {
+                let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0).round() as i32;
+                writer.write_all(&value.to_le_bytes()[..3])?;
+            }
+            WavFormat::Float32 => {
+                writer.write_all(&sample.to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// Sizes of the `data` chunk and of the whole RIFF chunk for `sample_count`
+/// samples. Both header fields are 32-bit, so longer renders are rejected
+/// instead of writing a wrapped length.
+fn chunk_sizes(sample_count: u64, format: WavFormat) -> io::Result<(u32, u32)> {
+    let is_float = format == WavFormat::Float32;
+    let fmt_len: u64 = if is_float { 18 } else { 16 };
+    let fact_len: u64 = if is_float { 12 } else { 0 };
+    let data_len = sample_count * format.bytes_per_sample() as u64;
+    let riff_len = 4 + (8 + fmt_len) + fact_len + (8 + data_len);
+
+    if riff_len > u32::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("WAV data too large: {} bytes exceeds the 4 GiB RIFF limit", data_len),
+        ));
+    }
+    Ok((data_len as u32, riff_len as u32))
+}
+
+/// Decoded WAV stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct WavData {
+    /// Interleaved samples in [-1.0, 1.0]
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+impl WavData {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+}
+
+/// Read a WAV stream: 8/16/24/32-bit integer PCM or 32-bit float
+/// (including WAVE_FORMAT_EXTENSIBLE). Unknown chunks are skipped.
+pub fn read_wav<R: Read>(reader: &mut R) -> io::Result<WavData> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid("Not a RIFF/WAVE stream"));
+    }
+
+    // (format tag, channels, sample rate, bits per sample)
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let len = u32::from_le_bytes([bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]]) as usize;
+        let body = &bytes[offset + 8..(offset + 8 + len).min(bytes.len())];
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let u16_at = |at: usize| u16::from_le_bytes([body[at], body[at + 1]]);
+                let mut tag = u16_at(0);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real tag at the start of the sub-format GUID
+                if tag == 0xFFFE && body.len() >= 26 {
+                    tag = u16_at(24);
+                }
+                let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                format = Some((tag, u16_at(2), sample_rate, u16_at(14)));
+            }
+            b"data" => {
+                let (tag, channels, sample_rate, bits) = format.ok_or_else(|| invalid("WAV data before fmt chunk"))?;
+                if channels == 0 {
+                    return Err(invalid("WAV channel count must be at least 1"));
+                }
+                let samples = decode_samples(body, tag, bits)
+                    .ok_or_else(|| invalid(&format!("Unsupported WAV format: tag {}, {} bits", tag, bits)))?;
+                return Ok(WavData { samples, channels, sample_rate });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        offset += 8 + len + (len & 1);
+    }
+
+    Err(invalid("WAV stream has no data chunk"))
+}
+
+fn decode_samples(data: &[u8], tag: u16, bits: u16) -> Option<Vec<f32>> {
+    let samples = match (tag, bits) {
+        (1, 8) => data.iter().map(|&b| (b as f32 - 128.0) / 128.0).collect(),
+        (1, 16) => data.chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        (1, 24) => data.chunks_exact(3)
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+            .collect(),
+        (1, 32) => data.chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        (3, 32) => data.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        _ => return None,
+    };
+    Some(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+    }
+
+    fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    #[test]
+    fn test_pcm16_header() {
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.0, 0.5, -0.5, 1.0], 2, 48000, WavFormat::Pcm16).unwrap();
+
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(read_u16(&bytes, 20), 1); // PCM
+        assert_eq!(read_u16(&bytes, 22), 2); // channels
+        assert_eq!(read_u32(&bytes, 24), 48000);
+        assert_eq!(read_u16(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40), 8);
+        assert_eq!(bytes.len(), 44 + 8);
+
+        // Full-scale positive sample
+        assert_eq!(i16::from_le_bytes([bytes[50], bytes[51]]), i16::MAX);
+    }
+
+    #[test]
+    fn test_pcm24_sample_packing() {
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[1.0, -1.0], 1, 44100, WavFormat::Pcm24).unwrap();
+
+        assert_eq!(read_u16(&bytes, 34), 24);
+        assert_eq!(read_u32(&bytes, 40), 6);
+        assert_eq!(&bytes[44..47], &[0xFF, 0xFF, 0x7F]);
+        assert_eq!(&bytes[47..50], &[0x01, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_float32_has_fact_chunk() {
+        let mut bytes = Vec::new();
+        write_wav(&mut bytes, &[0.25, -0.25], 2, 44100, WavFormat::Float32).unwrap();
+
+        assert_eq!(read_u32(&bytes, 16), 18);
+        assert_eq!(read_u16(&bytes, 20), 3); // IEEE float
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(read_u32(&bytes, 46), 1); // one frame
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(f32::from_le_bytes([bytes[58], bytes[59], bytes[60], bytes[61]]), 0.25);
+    }
+
+    #[test]
+    fn test_read_back_written_formats() {
+        let samples = [0.0, 0.5, -0.5, 0.25];
+        for format in [WavFormat::Pcm16, WavFormat::Pcm24, WavFormat::Float32] {
+            let mut bytes = Vec::new();
+            write_wav(&mut bytes, &samples, 2, 48000, format).unwrap();
+
+            let data = read_wav(&mut bytes.as_slice()).unwrap();
+            assert_eq!((data.channels, data.sample_rate, data.frames()), (2, 48000, 2));
+            for (read, written) in data.samples.iter().zip(samples) {
+                assert!((read - written).abs() < 1e-4, "{:?}: {} != {}", format, read, written);
+            }
+        }
+
+        assert!(read_wav(&mut b"RIFF\0\0\0\0AVI ".as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_zero_channels_rejected() {
+        let mut bytes = Vec::new();
+        assert!(write_wav(&mut bytes, &[0.0], 0, 44100, WavFormat::Pcm16).is_err());
+    }
+
+    #[test]
+    fn test_oversized_data_rejected() {
+        // 16-bit: the RIFF chunk adds 36 header bytes to the data
+        let max_samples = (u32::MAX as u64 - 36) / 2;
+        assert_eq!(chunk_sizes(max_samples, WavFormat::Pcm16).unwrap().1, u32::MAX - 1);
+        assert!(chunk_sizes(max_samples + 1, WavFormat::Pcm16).is_err());
+        assert!(chunk_sizes(u32::MAX as u64, WavFormat::Float32).is_err());
+    }
+}
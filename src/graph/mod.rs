@@ -17,22 +17,98 @@
  */
 
 use std::collections::HashMap;
+use std::ops::Range;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, InputPorts, OutputPorts};
+use crate::nodes::{create_patch_node, SubpatchNode};
+use crate::patch::{PatchConnection, PatchFile, PatchNode, PatchPosition};
+use crate::patch::migration::{MigrationReport, PATCH_FORMAT_VERSION};
+use crate::processing::{AudioNode, EventKind, NodeEvent, ProcessingError, InputPorts, OutputPorts};
+use crate::transport::Transport;
+
+pub mod parallel;
+pub mod plan;
+pub mod topology;
+
+pub use parallel::WorkerPool;
+pub use plan::{ExecutionPlan, DEFAULT_MAX_BLOCK_SIZE};
+pub use topology::GraphTopology;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum PortType {
     AudioMono,
     AudioStereo,
     CV,
+    /// One mono channel per voice (see `PolyBuffer`)
+    Poly,
+}
+
+/// How an input port combines several incoming connections
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum FanIn {
+    /// Add all sources together
+    #[default]
+    Sum,
+    /// Mean of all sources
+    Average,
+    /// Only one connection allowed
+    Single,
+}
+
+/// Most device channels output nodes can be routed to
+pub const MAX_OUTPUT_CHANNELS: usize = 64;
+
+/// 出力ノードのデバイスチャンネル割り当て
+///
+/// Stereo routes send L/R to `first_channel` and the channel after it. Mono
+/// routes send only the left side, e.g. DC-coupled CV to a single channel.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OutputRoute {
+    /// 0-based device channel
+    pub first_channel: usize,
+    /// 1 (mono) or 2 (stereo)
+    pub channels: usize,
+}
+
+impl OutputRoute {
+    pub fn stereo(first_channel: usize) -> Self {
+        Self { first_channel, channels: 2 }
+    }
+
+    pub fn mono(first_channel: usize) -> Self {
+        Self { first_channel, channels: 1 }
+    }
+
+    /// Device channels the route covers
+    pub fn channel_range(&self) -> Range<usize> {
+        self.first_channel..self.first_channel + self.channels
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=2).contains(&self.channels) {
+            return Err(format!("Output routes cover 1 or 2 channels, not {}", self.channels));
+        }
+        if self.channel_range().end > MAX_OUTPUT_CHANNELS {
+            return Err(format!("Output channel {} is beyond the {} supported channels",
+                               self.channel_range().end, MAX_OUTPUT_CHANNELS));
+        }
+        Ok(())
+    }
+}
+
+impl Default for OutputRoute {
+    fn default() -> Self {
+        Self::stereo(0)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Port {
     pub name: String,
     pub port_type: PortType,
+    #[serde(default)]
+    pub fan_in: FanIn,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +117,46 @@ pub struct Connection {
     pub source_port: String,
     pub target_node: Uuid,
     pub target_port: String,
+    /// Cycle-closing edge - reads the source output of the previous block
+    #[serde(default)]
+    pub feedback: bool,
+}
+
+/// Mark the edges that close a cycle as feedback edges.
+///
+/// Connections are visited in insertion order; an edge becomes a feedback edge
+/// if the non-feedback edges before it already form a path back to its source.
+/// The remaining edges always form a DAG.
+pub fn classify_feedback_connections(connections: &mut [Connection]) {
+    for i in 0..connections.len() {
+        let (accepted, rest) = connections.split_at_mut(i);
+        let connection = &mut rest[0];
+        connection.feedback = connection.source_node == connection.target_node ||
+            has_forward_path(accepted, connection.target_node, connection.source_node);
+    }
+}
+
+/// Whether `to` is reachable from `from` over non-feedback edges
+fn has_forward_path(connections: &[Connection], from: Uuid, to: Uuid) -> bool {
+    let mut visited = std::collections::HashSet::new();
+    let mut stack = vec![from];
+
+    while let Some(current) = stack.pop() {
+        if current == to {
+            return true;
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+
+        for connection in connections {
+            if !connection.feedback && connection.source_node == current {
+                stack.push(connection.target_node);
+            }
+        }
+    }
+
+    false
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,11 +210,6 @@ impl AudioGraph {
         let target_node = self.nodes.get(&connection.target_node)
             .ok_or("Target node not found")?;
 
-        // Prevent self-connection
-        if connection.source_node == connection.target_node {
-            return Err("Cannot connect node to itself".to_string());
-        }
-
         // Check if ports exist and types match
         let source_port = source_node.output_ports.iter()
             .find(|p| p.name == connection.source_port)
@@ -111,60 +222,30 @@ impl AudioGraph {
             return Err("Port types do not match".to_string());
         }
 
-        // Check for existing connection to target port (only one input allowed)
+        // Reject duplicate cables
         if self.connections.iter().any(|conn| {
-            conn.target_node == connection.target_node && 
+            conn.source_node == connection.source_node &&
+            conn.source_port == connection.source_port &&
+            conn.target_node == connection.target_node &&
             conn.target_port == connection.target_port
         }) {
-            return Err("Target port already connected".to_string());
+            return Err("Connection already exists".to_string());
         }
 
-        // Check for cycles before adding connection
-        if self.would_create_cycle(&connection) {
-            return Err("Connection would create a cycle".to_string());
+        // Single-source ports accept only one cable
+        if target_port.fan_in == FanIn::Single && self.connections.iter().any(|conn| {
+            conn.target_node == connection.target_node &&
+            conn.target_port == connection.target_port
+        }) {
+            return Err("Target port already connected".to_string());
         }
 
+        // Cycle-closing connections (including self-connections) become feedback edges
         self.connections.push(connection);
         self.update_processing_order()?;
         Ok(())
     }
 
-    fn would_create_cycle(&self, new_connection: &Connection) -> bool {
-        // Check if adding this connection would create a cycle
-        // We need to check if there's already a path from target to source
-        self.has_path(new_connection.target_node, new_connection.source_node)
-    }
-
-    fn has_path(&self, from: Uuid, to: Uuid) -> bool {
-        if from == to {
-            return true;
-        }
-
-        let mut visited = std::collections::HashSet::new();
-        let mut stack = vec![from];
-
-        while let Some(current) = stack.pop() {
-            if visited.contains(&current) {
-                continue;
-            }
-            visited.insert(current);
-
-            // Find all nodes this node connects to
-            for connection in &self.connections {
-                if connection.source_node == current {
-                    if connection.target_node == to {
-                        return true;
-                    }
-                    if !visited.contains(&connection.target_node) {
-                        stack.push(connection.target_node);
-                    }
-                }
-            }
-        }
-
-        false
-    }
-
     pub fn remove_connection(&mut self, source_node: Uuid, source_port: &str, 
                            target_node: Uuid, target_port: &str) -> bool {
         let initial_len = self.connections.len();
@@ -200,6 +281,8 @@ impl AudioGraph {
     }
 
     fn update_processing_order(&mut self) -> Result<(), String> {
+        classify_feedback_connections(&mut self.connections);
+
         // Simple topological sort for audio processing order
         self.processing_order.clear();
         let mut visited = std::collections::HashSet::new();
@@ -231,9 +314,9 @@ impl AudioGraph {
 
         temp_visited.insert(node_id);
 
-        // Visit all nodes that this node depends on (inputs)
+        // Visit all nodes that this node depends on (inputs, except feedback edges)
         let dependencies: Vec<Uuid> = self.connections.iter()
-            .filter(|conn| conn.target_node == node_id)
+            .filter(|conn| conn.target_node == node_id && !conn.feedback)
             .map(|conn| conn.source_node)
             .collect();
         
@@ -277,12 +360,11 @@ impl AudioGraph {
 
         temp_visited.insert(node_id);
 
-        // Check all nodes that this node connects to (outputs)
+        // Check all nodes that this node connects to (outputs, except feedback edges)
         for connection in &self.connections {
-            if connection.source_node == node_id {
-                if !self.check_node_for_cycles(connection.target_node, visited, temp_visited) {
-                    return false;
-                }
+            if connection.source_node == node_id && !connection.feedback
+                && !self.check_node_for_cycles(connection.target_node, visited, temp_visited) {
+                return false;
             }
         }
 
@@ -306,108 +388,274 @@ impl AudioGraph {
     }
 }
 
+/// Number of scheduled events the graph holds without allocating
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// ノード宛ての、サンプル位置付きイベント
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledEvent {
+    pub node_id: Uuid,
+    /// Sample position (see `ProcessingGraph::position`)
+    pub time: u64,
+    pub kind: EventKind,
+}
+
 /// Extended AudioGraph for modern ProcessContext integration
 pub struct ProcessingGraph {
     pub audio_nodes: HashMap<Uuid, Box<dyn AudioNode>>,
-    pub connections: Vec<Connection>,
-    processing_order: Vec<Uuid>,
+    topology: Box<GraphTopology>,
+    /// Workers for independent branches; `None` processes on the calling thread only
+    pool: Option<WorkerPool>,
+    /// Samples processed so far
+    position: u64,
+    /// Events not due yet, sorted by time
+    events: Vec<ScheduledEvent>,
+    /// Tempo and song position handed to every node
+    transport: Transport,
+    /// Largest block nodes are processed in; longer host blocks are split
+    max_block_size: usize,
 }
 
 impl ProcessingGraph {
     pub fn new() -> Self {
         Self {
             audio_nodes: HashMap::new(),
-            connections: Vec::new(),
-            processing_order: Vec::new(),
+            topology: Box::default(),
+            pool: None,
+            position: 0,
+            events: Vec::with_capacity(DEFAULT_EVENT_CAPACITY),
+            transport: Transport::new(),
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
         }
     }
 
-    /// Add a node instance to the processing graph
-    pub fn add_node_instance(&mut self, node: Box<dyn AudioNode>) -> Result<(), String> {
-        let node_id = node.node_info().id;
-        self.audio_nodes.insert(node_id, node);
-        self.update_processing_order()?;
-        Ok(())
+    /// All connections of the graph
+    pub fn connections(&self) -> &[Connection] {
+        self.topology.connections()
     }
 
-    /// Remove a node instance
-    pub fn remove_node_instance(&mut self, node_id: &str) -> Result<(), String> {
-        let uuid = Uuid::parse_str(node_id)
-            .map_err(|e| format!("Invalid UUID: {}", e))?;
-        
-        // Remove all connections involving this node
-        self.connections.retain(|conn| {
-            conn.source_node != uuid && conn.target_node != uuid
-        });
-        
-        self.audio_nodes.remove(&uuid);
-        self.update_processing_order()?;
-        Ok(())
+    /// Current topology (connections and processing order)
+    pub fn topology(&self) -> &GraphTopology {
+        &self.topology
     }
 
-    /// Connect two nodes by ID
-    pub fn connect_by_id(&mut self, source_id: &str, source_port: &str, 
-                         target_id: &str, target_port: &str) -> Result<(), String> {
-        let source_uuid = Uuid::parse_str(source_id)
-            .map_err(|e| format!("Invalid source UUID: {}", e))?;
-        let target_uuid = Uuid::parse_str(target_id)
-            .map_err(|e| format!("Invalid target UUID: {}", e))?;
+    /// Install a topology prepared elsewhere and return the previous one.
+    ///
+    /// Only swaps a pointer, so it is safe on the audio thread as long as the
+    /// topology was compiled beforehand. Nodes in the topology that are not
+    /// attached to this graph are skipped when processing.
+    pub fn swap_topology(&mut self, topology: Box<GraphTopology>) -> Box<GraphTopology> {
+        std::mem::replace(&mut self.topology, topology)
+    }
 
-        // Validate nodes exist
-        if !self.audio_nodes.contains_key(&source_uuid) {
-            return Err("Source node not found".to_string());
+    /// Compile the execution plan for blocks of up to `max_block_size` samples.
+    /// Longer blocks passed to `process_stereo` are split to that size.
+    ///
+    /// Picks up the current `AudioNode::latency` of every node. Not real-time
+    /// safe: call it after editing the graph, before processing.
+    pub fn compile_plan(&mut self, max_block_size: usize) {
+        self.max_block_size = max_block_size.max(1);
+        self.sync_latencies();
+        self.topology.compile(self.max_block_size);
+    }
+
+    /// Largest block nodes are processed in
+    pub fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    /// Tell every node the sample rate and largest block to expect
+    /// (`AudioNode::prepare`) and compile the plan for that block size.
+    ///
+    /// Not real-time safe: call it while the audio thread is not processing this graph.
+    pub fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        for node in self.audio_nodes.values_mut() {
+            node.prepare(sample_rate, max_block_size);
         }
-        if !self.audio_nodes.contains_key(&target_uuid) {
-            return Err("Target node not found".to_string());
+        // Also takes over latencies counted in samples, which may change with the rate
+        self.compile_plan(max_block_size);
+    }
+
+    /// Mono or CV output of a node port from the last processed block
+    pub fn last_output(&self, node_id: Uuid, port: &str) -> Option<&[f32]> {
+        self.topology.plan()?.output(node_id, port)
+    }
+
+    /// Take the graph apart into its node instances and topology, e.g. to
+    /// hand a graph built on the control thread to a running engine
+    pub(crate) fn into_parts(self) -> (HashMap<Uuid, Box<dyn AudioNode>>, Box<GraphTopology>) {
+        (self.audio_nodes, self.topology)
+    }
+
+    /// Insert a node instance without touching the topology
+    pub fn attach_node(&mut self, node: Box<dyn AudioNode>) {
+        self.audio_nodes.insert(node.node_info().id, node);
+    }
+
+    /// Like `attach_node`, but without allocating: if the node map is full,
+    /// its nodes move into `spare` (prepared with room for all of them) first.
+    /// Returns the map that is no longer used, to be dropped elsewhere.
+    pub fn attach_node_with(&mut self, node: Box<dyn AudioNode>,
+                            mut spare: HashMap<Uuid, Box<dyn AudioNode>>) -> HashMap<Uuid, Box<dyn AudioNode>> {
+        if self.audio_nodes.len() >= self.audio_nodes.capacity() {
+            spare.extend(self.audio_nodes.drain());
+            std::mem::swap(&mut self.audio_nodes, &mut spare);
         }
+        self.attach_node(node);
+        spare
+    }
+
+    /// Take a node instance out without touching the topology
+    pub fn detach_node(&mut self, node_id: Uuid) -> Option<Box<dyn AudioNode>> {
+        self.audio_nodes.remove(&node_id)
+    }
 
-        // Prevent self-connection
-        if source_uuid == target_uuid {
-            return Err("Cannot connect node to itself".to_string());
+    /// Delay feedback edges by exactly `samples`, at most `max_block_size`.
+    ///
+    /// Feedback edges read a fixed-length delay line, so a loop is delayed by
+    /// the same amount however the host slices its blocks. When the graph
+    /// contains feedback edges, blocks are processed in sub-blocks of this size.
+    /// `None` restores the default delay of `max_block_size` samples.
+    /// Takes effect with the next `compile_plan`.
+    pub fn set_feedback_delay(&mut self, samples: Option<usize>) -> Result<(), String> {
+        if samples == Some(0) {
+            return Err("Feedback delay must be at least 1 sample".to_string());
         }
+        self.topology.set_feedback_delay(samples);
+        Ok(())
+    }
 
-        let connection = Connection {
-            source_node: source_uuid,
-            source_port: source_port.to_string(),
-            target_node: target_uuid,
-            target_port: target_port.to_string(),
-        };
+    /// Current feedback delay setting
+    pub fn feedback_delay(&self) -> Option<usize> {
+        self.topology.feedback_delay()
+    }
 
-        // Check for cycles
-        let mut temp_connections = self.connections.clone();
-        temp_connections.push(connection.clone());
-        if self.would_create_cycle(&temp_connections, source_uuid, target_uuid) {
-            return Err("Connection would create a cycle".to_string());
+    /// Process independent nodes on `threads` extra worker threads.
+    ///
+    /// The output is identical to serial processing. `0` stops the workers.
+    pub fn set_worker_threads(&mut self, threads: usize) {
+        if threads != self.worker_threads() {
+            self.pool = (threads > 0).then(|| WorkerPool::new(threads));
         }
+    }
 
-        self.connections.push(connection);
-        self.update_processing_order()?;
-        Ok(())
+    /// Number of extra worker threads
+    pub fn worker_threads(&self) -> usize {
+        self.pool.as_ref().map_or(0, WorkerPool::threads)
     }
 
-    /// Disconnect two nodes by ID
-    pub fn disconnect_by_id(&mut self, source_id: &str, source_port: &str,
-                            target_id: &str, target_port: &str) -> Result<(), String> {
-        let source_uuid = Uuid::parse_str(source_id)
-            .map_err(|e| format!("Invalid source UUID: {}", e))?;
-        let target_uuid = Uuid::parse_str(target_id)
-            .map_err(|e| format!("Invalid target UUID: {}", e))?;
+    /// Total latency of the patch in samples (see `GraphTopology::total_latency`)
+    pub fn latency(&self) -> u32 {
+        self.topology.total_latency()
+    }
 
-        let initial_len = self.connections.len();
-        self.connections.retain(|conn| {
-            !(conn.source_node == source_uuid && 
-              conn.source_port == source_port &&
-              conn.target_node == target_uuid && 
-              conn.target_port == target_port)
-        });
+    /// Copy the current `AudioNode::latency` of every node into the topology.
+    /// Returns true if any latency changed; the plan then has to be compiled again.
+    pub fn sync_latencies(&mut self) -> bool {
+        let mut changed = false;
+        for (&node_id, node) in &self.audio_nodes {
+            changed |= self.topology.set_node_latency(node_id, node.latency());
+        }
+        changed
+    }
 
-        if self.connections.len() != initial_len {
-            self.update_processing_order()?;
+    /// Bypass a node with a short crossfade (see `GraphTopology::set_bypass`).
+    /// Does not allocate once the node is validated, so it can run on the audio thread.
+    pub fn set_bypass(&mut self, node_id: Uuid, bypass: bool) -> Result<(), String> {
+        self.topology.set_bypass(node_id, bypass)
+    }
+
+    pub fn is_bypassed(&self, node_id: Uuid) -> bool {
+        self.topology.is_bypassed(node_id)
+    }
+
+    /// Send an output node to other device channels (see `process_channels`)
+    pub fn set_output_route(&mut self, node_id: Uuid, route: OutputRoute) -> Result<(), String> {
+        self.topology.set_output_route(node_id, route)
+    }
+
+    pub fn output_route(&self, node_id: Uuid) -> Option<OutputRoute> {
+        self.topology.output_route(node_id)
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    /// Transport, advanced by `process_stereo` after every block
+    pub fn transport_mut(&mut self) -> &mut Transport {
+        &mut self.transport
+    }
+
+    /// Number of samples processed so far - the time base of `schedule_event`
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Deliver an event to a node at its sample position. Events in the past
+    /// land on the first sample of the next block.
+    ///
+    /// Does not allocate while fewer than `DEFAULT_EVENT_CAPACITY` events are
+    /// pending, so it can run on the audio thread.
+    pub fn schedule_event(&mut self, event: ScheduledEvent) {
+        let index = self.events.partition_point(|pending| pending.time <= event.time);
+        self.events.insert(index, event);
+    }
+
+    /// Number of events not delivered yet
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Hand the events delivered in the last block to `spent`, e.g. to free them off the audio thread
+    pub(crate) fn drain_spent_events(&mut self, spent: impl FnMut(NodeEvent)) {
+        if let Some(plan) = self.topology.plan_mut() {
+            plan.drain_events(spent);
         }
+    }
+
+    /// Hand the node latencies that changed since the last call to `changed`,
+    /// e.g. to report them from the audio thread (see `ExecutionPlan::latency_changed`)
+    pub(crate) fn drain_latency_changes(&mut self, changed: impl FnMut(Uuid, u32)) {
+        if let Some(plan) = self.topology.plan_mut() {
+            plan.drain_latency_changes(changed);
+        }
+    }
+
+    /// Whether the graph contains any feedback edges
+    pub fn has_feedback(&self) -> bool {
+        self.connections().iter().any(|conn| conn.feedback)
+    }
 
+    /// Add a node instance to the processing graph
+    pub fn add_node_instance(&mut self, node: Box<dyn AudioNode>) -> Result<(), String> {
+        self.topology.add_audio_node(node.as_ref())?;
+        self.attach_node(node);
         Ok(())
     }
 
+    /// Remove a node instance
+    pub fn remove_node_instance(&mut self, node_id: &str) -> Result<(), String> {
+        let uuid = Uuid::parse_str(node_id)
+            .map_err(|e| format!("Invalid UUID: {}", e))?;
+        
+        // Removing from the topology also drops its connections
+        self.detach_node(uuid);
+        self.topology.remove_node(uuid)
+    }
+
+    /// Connect two nodes by ID
+    pub fn connect_by_id(&mut self, source_id: &str, source_port: &str, 
+                         target_id: &str, target_port: &str) -> Result<(), String> {
+        self.topology.connect(source_id, source_port, target_id, target_port)
+    }
+
+    /// Disconnect two nodes by ID
+    pub fn disconnect_by_id(&mut self, source_id: &str, source_port: &str,
+                            target_id: &str, target_port: &str) -> Result<(), String> {
+        self.topology.disconnect(source_id, source_port, target_id, target_port)
+    }
+
     /// Get a node by ID
     pub fn get_node(&self, node_id: &str) -> Option<&dyn AudioNode> {
         let uuid = Uuid::parse_str(node_id).ok()?;
@@ -424,222 +672,74 @@ impl ProcessingGraph {
         }
     }
 
-    /// Process audio through the entire graph
+    /// Process audio through the entire graph into `main_left`/`main_right`.
+    ///
+    /// Convenience wrapper that allocates the output buffers and compiles the
+    /// plan when the graph or a node latency changed; real-time callers use
+    /// `process_stereo` with buffers of their own.
     pub fn process_audio(&mut self, _inputs: &mut InputPorts, final_outputs: &mut OutputPorts, 
                          sample_rate: f32, buffer_size: usize) -> Result<(), ProcessingError> {
-        
-        // Storage for node outputs (for signal routing)
-        let mut node_output_storage: HashMap<(Uuid, String), Vec<f32>> = HashMap::new();
-        
-        // Process nodes in dependency order
-        for &node_id in &self.processing_order {
-            // Prepare input buffers for this node
-            let mut node_inputs = InputPorts::new();
-            let mut node_outputs = OutputPorts::new();
-
-            // Get node info for buffer initialization
-            let node_info = if let Some(node) = self.audio_nodes.get(&node_id) {
-                node.node_info().clone()
-            } else {
-                continue;
-            };
-
-            // Initialize output buffers based on node's output ports
-            for output_port in &node_info.output_ports {
-                match output_port.port_type {
-                    crate::graph::PortType::AudioMono => {
-                        node_outputs.allocate_audio(output_port.name.clone(), buffer_size);
-                    }
-                    crate::graph::PortType::AudioStereo => {
-                        node_outputs.allocate_audio(format!("{}_left", output_port.name), buffer_size);
-                        node_outputs.allocate_audio(format!("{}_right", output_port.name), buffer_size);
-                    }
-                    crate::graph::PortType::CV => {
-                        node_outputs.allocate_cv(output_port.name.clone(), buffer_size);
-                    }
-                }
-            }
-
-            // Route inputs from connected nodes
-            for connection in &self.connections {
-                if connection.target_node == node_id {
-                    // This connection feeds INTO the current node
-                    let source_key = (connection.source_node, connection.source_port.clone());
-                    
-                    if let Some(source_data) = node_output_storage.get(&source_key) {
-                        // Determine the target port type from node info
-                        let target_port_type = node_info.input_ports.iter()
-                            .find(|p| p.name == connection.target_port)
-                            .map(|p| p.port_type);
-                        
-                        match target_port_type {
-                            Some(crate::graph::PortType::AudioMono) => {
-                                node_inputs.add_audio(connection.target_port.clone(), source_data.clone());
-                            }
-                            Some(crate::graph::PortType::CV) => {
-                                node_inputs.add_cv(connection.target_port.clone(), source_data.clone());
-                            }
-                            Some(crate::graph::PortType::AudioStereo) => {
-                                // For stereo, add to both channels
-                                node_inputs.add_audio(format!("{}_left", connection.target_port), source_data.clone());
-                                node_inputs.add_audio(format!("{}_right", connection.target_port), source_data.clone());
-                            }
-                            None => {
-                                eprintln!("Warning: Unknown target port {} for node {}", 
-                                         connection.target_port, node_id);
-                            }
-                        }
-                    }
-                }
-            }
-
-            // Create process context and process the node
-            let mut ctx = ProcessContext::new(node_inputs, node_outputs, sample_rate, buffer_size);
-
-            if let Some(node) = self.audio_nodes.get_mut(&node_id) {
-                // Process the node
-                node.process(&mut ctx)?;
-
-                // Store outputs for routing to other nodes
-                for output_port in &node_info.output_ports {
-                    match output_port.port_type {
-                        crate::graph::PortType::AudioMono => {
-                            if let Some(output_data) = ctx.outputs.get_audio(&output_port.name) {
-                                let key = (node_id, output_port.name.clone());
-                                node_output_storage.insert(key, output_data.to_vec());
-                            }
-                        }
-                        crate::graph::PortType::CV => {
-                            if let Some(output_data) = ctx.outputs.get_cv(&output_port.name) {
-                                let key = (node_id, output_port.name.clone());
-                                node_output_storage.insert(key, output_data.to_vec());
-                            }
-                        }
-                        crate::graph::PortType::AudioStereo => {
-                            // Handle stereo outputs
-                            if let Some(left_data) = ctx.outputs.get_audio(&format!("{}_left", output_port.name)) {
-                                let key = (node_id, format!("{}_left", output_port.name));
-                                node_output_storage.insert(key, left_data.to_vec());
-                            }
-                            if let Some(right_data) = ctx.outputs.get_audio(&format!("{}_right", output_port.name)) {
-                                let key = (node_id, format!("{}_right", output_port.name));
-                                node_output_storage.insert(key, right_data.to_vec());
-                            }
-                        }
-                    }
-                }
-
-                // Special handling for OutputNode - route to final output buffers
-                if node_info.node_type == "output" {
-                    // Route OutputNode's processed inputs (which are stored in mixed_output) to main_left/main_right
-                    // Since OutputNode processes stereo to mono in mixed_output, we'll use that for both channels
-                    if let Some(mixed_output) = ctx.outputs.get_audio("mixed_output") {
-                        final_outputs.allocate_audio("main_left".to_string(), buffer_size);
-                        final_outputs.allocate_audio("main_right".to_string(), buffer_size);
-                        
-                        if let Some(main_left) = final_outputs.get_audio_mut("main_left") {
-                            main_left.copy_from_slice(mixed_output);
-                        }
-                        if let Some(main_right) = final_outputs.get_audio_mut("main_right") {
-                            main_right.copy_from_slice(mixed_output);
-                        }
-                    } else {
-                        // Fallback: route inputs directly if no mixed output
-                        if let Some(left_input) = ctx.inputs.get_audio("audio_in_l") {
-                            final_outputs.allocate_audio("main_left".to_string(), buffer_size);
-                            if let Some(main_left) = final_outputs.get_audio_mut("main_left") {
-                                main_left.copy_from_slice(left_input);
-                            }
-                        }
-                        if let Some(right_input) = ctx.inputs.get_audio("audio_in_r") {
-                            final_outputs.allocate_audio("main_right".to_string(), buffer_size);
-                            if let Some(main_right) = final_outputs.get_audio_mut("main_right") {
-                                main_right.copy_from_slice(right_input);
-                            }
-                        }
-                    }
-                }
-            }
+        if self.topology.plan().is_none_or(ExecutionPlan::latency_changed) {
+            self.compile_plan(self.max_block_size);
         }
-
-        Ok(())
-    }
-
-    /// Check if adding a connection would create a cycle
-    fn would_create_cycle(&self, connections: &[Connection], from: Uuid, to: Uuid) -> bool {
-        let mut visited = std::collections::HashSet::new();
-        let mut stack = vec![to];
-
-        while let Some(current) = stack.pop() {
-            if current == from {
-                return true; // Cycle detected
-            }
-            if visited.contains(&current) {
-                continue;
-            }
-            visited.insert(current);
-
-            // Find all nodes this node connects to
-            for connection in connections {
-                if connection.source_node == current {
-                    if !visited.contains(&connection.target_node) {
-                        stack.push(connection.target_node);
-                    }
-                }
-            }
+        let mut left = vec![0.0; buffer_size];
+        let mut right = vec![0.0; buffer_size];
+        self.process_stereo(&mut left, &mut right, sample_rate)?;
+
+        final_outputs.allocate_audio("main_left".to_string(), buffer_size);
+        final_outputs.allocate_audio("main_right".to_string(), buffer_size);
+        if let Some(main_left) = final_outputs.get_audio_mut("main_left") {
+            main_left.copy_from_slice(&left);
         }
-
-        false
-    }
-
-    /// Update processing order using topological sort
-    fn update_processing_order(&mut self) -> Result<(), String> {
-        self.processing_order.clear();
-        let mut visited = std::collections::HashSet::new();
-        let mut temp_visited = std::collections::HashSet::new();
-
-        let node_ids: Vec<Uuid> = self.audio_nodes.keys().copied().collect();
-        for node_id in node_ids {
-            if !visited.contains(&node_id) {
-                if !self.visit_node(node_id, &mut visited, &mut temp_visited) {
-                    self.processing_order.clear();
-                    return Err("Failed to create processing order due to cycles".to_string());
-                }
-            }
+        if let Some(main_right) = final_outputs.get_audio_mut("main_right") {
+            main_right.copy_from_slice(&right);
         }
         Ok(())
     }
 
-    /// Visit node for topological sort
-    fn visit_node(&mut self, node_id: Uuid, 
-                  visited: &mut std::collections::HashSet<Uuid>,
-                  temp_visited: &mut std::collections::HashSet<Uuid>) -> bool {
-        if temp_visited.contains(&node_id) {
-            return false; // Cycle detected
+    /// Process one block of `left.len()` samples into device channels 1/2
+    /// (see `process_channels`). Output nodes routed elsewhere are not heard.
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32],
+                          sample_rate: f32) -> Result<(), ProcessingError> {
+        let frames = left.len().min(right.len());
+        left.fill(0.0);
+        right.fill(0.0);
+        self.process_channels(&mut [left, right], frames, sample_rate)
+    }
+
+    /// Process one block of `frames` samples into the device `channels`.
+    ///
+    /// Every output node is mixed into the channels of its `OutputRoute`;
+    /// routes past the end of `channels` are dropped. Any block length is
+    /// accepted: nodes see sub-blocks of at most `max_block_size` samples
+    /// (at most the feedback delay with feedback edges), so the output is the
+    /// same however the host slices it. Runs the compiled plan without allocating, so the graph
+    /// renders silence until `compile_plan` or `prepare` is called after an edit.
+    /// A changed node latency is compensated from the next `compile_plan` on.
+    pub fn process_channels<C: AsMut<[f32]>>(&mut self, channels: &mut [C], frames: usize,
+                                             sample_rate: f32) -> Result<(), ProcessingError> {
+        for channel in channels.iter_mut() {
+            channel.as_mut()[..frames].fill(0.0);
         }
-        if visited.contains(&node_id) {
-            return true; // Already processed
+        if frames == 0 {
+            return Ok(());
         }
 
-        temp_visited.insert(node_id);
-
-        // Visit all dependencies (nodes that feed into this node)
-        let dependencies: Vec<Uuid> = self.connections.iter()
-            .filter(|conn| conn.target_node == node_id)
-            .map(|conn| conn.source_node)
-            .collect();
-        
-        for dep_node in dependencies {
-            if !self.visit_node(dep_node, visited, temp_visited) {
-                temp_visited.remove(&node_id);
-                return false;
-            }
+        let Some(plan) = self.topology.plan_mut() else {
+            return Ok(());
+        };
+        // Taken from the plan: a topology compiled elsewhere may have been swapped in
+        let sub_block_size = plan.sub_block_size();
+
+        for start in (0..frames).step_by(sub_block_size) {
+            let len = sub_block_size.min(frames - start);
+            plan.begin_block(self.position, len, &mut self.events, &self.transport.info());
+            plan.run(&mut self.audio_nodes, self.pool.as_mut(), channels, start..start + len, sample_rate)?;
+            self.position += len as u64;
+            self.transport.advance(len, sample_rate);
         }
 
-        temp_visited.remove(&node_id);
-        visited.insert(node_id);
-        self.processing_order.push(node_id);
-        true
+        Ok(())
     }
 
     /// Remove a node by UUID (wrapper for remove_node_instance)
@@ -676,28 +776,785 @@ impl ProcessingGraph {
         self.audio_nodes.get(&node_id).map(|node| node.node_info().name.clone())
     }
 
-    /// Clear all nodes and connections
+    /// Clear all nodes, connections and pending events
     pub fn clear(&mut self) {
         self.audio_nodes.clear();
-        self.connections.clear();
-        self.processing_order.clear();
+        self.topology.clear();
+        self.events.clear();
     }
 
-    /// Save graph to file (placeholder implementation)
-    pub fn save_to_file(&self, _filename: &str) -> Result<(), String> {
-        // TODO: Implement actual save functionality
-        Ok(())
+    /// Patch entry of one node, identified by its UUID
+    pub fn node_to_patch(&self, node_id: Uuid) -> Option<PatchNode> {
+        let node = self.audio_nodes.get(&node_id)?;
+        Some(topology_node_entry(&self.topology, node_id, patch_node_entry(node.as_ref())))
+    }
+
+    /// Patch of the graph: node types, names, IDs, parameters, bypass, connections
+    /// and the feedback delay. Nodes are identified by their UUID and listed in processing order.
+    pub fn to_patch(&self) -> PatchFile {
+        topology_patch(&self.topology, |node_id| {
+            self.audio_nodes.get(&node_id).map(|node| patch_node_entry(node.as_ref()))
+        })
     }
 
-    /// Load graph from file (placeholder implementation)
-    pub fn load_from_file(&mut self, _filename: &str, _sample_rate: f32) -> Result<(), String> {
-        // TODO: Implement actual load functionality
+    /// Replace the graph with `patch`, creating each node with `create_node`.
+    ///
+    /// Nodes keep the ID of the patch when it is a UUID and the node allows it
+    /// (see `AudioNode::node_info_mut`). The graph is left untouched on error.
+    pub fn load_patch<F>(&mut self, patch: &PatchFile, mut create_node: F) -> Result<(), String>
+    where
+        F: FnMut(&PatchNode) -> Result<Box<dyn AudioNode>, String>,
+    {
+        let mut loaded = ProcessingGraph::new();
+        let mut ids: HashMap<&str, Uuid> = HashMap::new();
+
+        for patch_node in &patch.nodes {
+            let mut node = create_node(patch_node)
+                .map_err(|e| format!("Failed to create node {}: {}", patch_node.id, e))?;
+            restore_patch_node(node.as_mut(), patch_node);
+
+            let id = node.node_info().id;
+            if loaded.audio_nodes.contains_key(&id) || ids.insert(&patch_node.id, id).is_some() {
+                return Err(format!("Duplicate node ID: {}", patch_node.id));
+            }
+            loaded.add_node_instance(node)?;
+            if let Some(route) = patch_node.output_route {
+                loaded.set_output_route(id, route)
+                    .map_err(|e| format!("Failed to route node {}: {}", patch_node.id, e))?;
+            }
+        }
+
+        for conn in &patch.connections {
+            let node_id = |name: &str| {
+                ids.get(name).map(Uuid::to_string).ok_or_else(|| format!("Unknown node in connection: {}", name))
+            };
+            loaded.connect_by_id(&node_id(&conn.source_node)?, &conn.source_port,
+                                 &node_id(&conn.target_node)?, &conn.target_port)
+                .map_err(|e| format!("Failed to connect {}.{} -> {}.{}: {}",
+                                     conn.source_node, conn.source_port, conn.target_node, conn.target_port, e))?;
+        }
+
+        // Bypassed from the first block on, without a crossfade
+        for patch_node in patch.nodes.iter().filter(|patch_node| patch_node.bypassed) {
+            loaded.set_bypass(ids[patch_node.id.as_str()], true)
+                .map_err(|e| format!("Failed to bypass node {}: {}", patch_node.id, e))?;
+        }
+        loaded.set_feedback_delay(patch.feedback_delay)?;
+
+        self.audio_nodes = loaded.audio_nodes;
+        self.topology = loaded.topology;
         Ok(())
     }
+
+    /// Save the graph as a JSON patch (see `to_patch`)
+    pub fn save_to_file(&self, filename: &str) -> Result<(), String> {
+        self.to_patch().save(filename)
+    }
+
+    /// Load a JSON patch of built-in nodes and subpatches (see `load_patch`).
+    /// Returns what was changed to bring an older file up to date.
+    pub fn load_from_file(&mut self, filename: &str, sample_rate: f32) -> Result<MigrationReport, String> {
+        let (patch, report) = PatchFile::load(filename)?;
+        self.load_patch(&patch, |node| create_patch_node(node, sample_rate))?;
+        Ok(report)
+    }
+}
+
+/// Patch entry of a node on its own: ID, type, name, parameters, state and subpatch
+pub fn patch_node_entry(node: &dyn AudioNode) -> PatchNode {
+    let info = node.node_info();
+    PatchNode {
+        id: info.id.to_string(),
+        node_type: info.node_type.clone(),
+        name: info.name.clone(),
+        position: PatchPosition::default(),
+        parameters: node.get_all_parameters(),
+        state: node.save_state(),
+        subpatch: node.as_any().downcast_ref::<SubpatchNode>()
+            .map(|subpatch| Box::new(subpatch.definition())),
+        output_route: None,
+        bypassed: false,
+    }
+}
+
+/// `entry` with the output route and bypass the topology holds for the node
+fn topology_node_entry(topology: &GraphTopology, node_id: Uuid, entry: PatchNode) -> PatchNode {
+    PatchNode {
+        output_route: topology.output_route(node_id).filter(|route| *route != OutputRoute::default()),
+        bypassed: topology.is_bypassed(node_id),
+        ..entry
+    }
+}
+
+/// Patch of `topology` with the node entries `entry` returns, e.g. the
+/// control-thread copies of a running engine (see `ProcessingGraph::to_patch`)
+pub fn topology_patch(topology: &GraphTopology, mut entry: impl FnMut(Uuid) -> Option<PatchNode>) -> PatchFile {
+    let nodes = topology.processing_order().iter()
+        .filter_map(|&node_id| entry(node_id).map(|node| topology_node_entry(topology, node_id, node)))
+        .collect();
+
+    let connections = topology.connections().iter()
+        .map(|conn| PatchConnection {
+            source_node: conn.source_node.to_string(),
+            source_port: conn.source_port.clone(),
+            target_node: conn.target_node.to_string(),
+            target_port: conn.target_port.clone(),
+        })
+        .collect();
+
+    PatchFile {
+        format_version: PATCH_FORMAT_VERSION,
+        patch_name: None,
+        description: None,
+        nodes,
+        connections,
+        notes: None,
+        feedback_delay: topology.feedback_delay(),
+    }
+}
+
+/// Apply the ID, name, parameters and state of `patch_node` to a freshly created node.
+/// The ID is kept when it is a UUID and the node allows it (see `AudioNode::node_info_mut`).
+pub fn restore_patch_node(node: &mut dyn AudioNode, patch_node: &PatchNode) {
+    if let Some(info) = node.node_info_mut() {
+        if let Ok(id) = Uuid::parse_str(&patch_node.id) {
+            info.id = id;
+        }
+        info.name = patch_node.name.clone();
+    }
+    for (name, value) in &patch_node.parameters {
+        if let Err(e) = node.set_parameter(name, *value) {
+            eprintln!("Failed to set {}.{}: {}", patch_node.id, name, e);
+        }
+    }
+    if let Some(state) = &patch_node.state {
+        if let Err(e) = node.load_state(state) {
+            eprintln!("Failed to load state of {}: {}", patch_node.id, e);
+        }
+    }
 }
 
 impl Default for ProcessingGraph {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{create_builtin_node, MixerNode, MultipleNode, OscillatorNode, OutputNode, SequencerNode, VoiceAllocatorNode};
+    use crate::parameters::{ParameterDescriptor, ParameterError, Parameterizable};
+    use crate::processing::{NodeCategory, NodeInfo, PortInfo, ProcessContext};
+
+    /// Test node: `out = in + offset`, remembers the last input block
+    struct ProbeNode {
+        info: NodeInfo,
+        offset: f32,
+        last_input: Vec<f32>,
+        /// Reported latency (the output is not actually delayed)
+        latency: u32,
+        /// Panic in `process`
+        panics: bool,
+    }
+
+    impl ProbeNode {
+        fn new(offset: f32, fan_in: FanIn) -> Self {
+            Self {
+                info: NodeInfo {
+                    id: Uuid::new_v4(),
+                    name: "probe".to_string(),
+                    node_type: "probe".to_string(),
+                    category: NodeCategory::Utility,
+                    description: String::new(),
+                    input_ports: vec![PortInfo::new("in", PortType::CV).with_fan_in(fan_in)],
+                    output_ports: vec![PortInfo::new("out", PortType::CV)],
+                    latency_samples: 0,
+                    supports_bypass: false,
+                },
+                offset,
+                last_input: Vec::new(),
+                latency: 0,
+                panics: false,
+            }
+        }
+
+        /// Probe with bypassable `audio_in`/`audio_out` ports
+        fn bypassable(offset: f32) -> Self {
+            let mut probe = Self::new(offset, FanIn::Sum);
+            probe.info.input_ports = vec![PortInfo::new("audio_in", PortType::AudioMono)];
+            probe.info.output_ports = vec![PortInfo::new("audio_out", PortType::AudioMono)];
+            probe.info.supports_bypass = true;
+            probe
+        }
+    }
+
+    impl Parameterizable for ProbeNode {
+        fn set_parameter(&mut self, name: &str, value: f32) -> Result<(), ParameterError> {
+            if name != "offset" {
+                return Err(ParameterError::NotFound { name: name.to_string() });
+            }
+            self.offset = value;
+            Ok(())
+        }
+
+        fn get_parameter(&self, name: &str) -> Result<f32, ParameterError> {
+            match name {
+                "offset" => Ok(self.offset),
+                _ => Err(ParameterError::NotFound { name: name.to_string() }),
+            }
+        }
+
+        fn get_all_parameters(&self) -> HashMap<String, f32> {
+            HashMap::new()
+        }
+
+        fn get_parameter_descriptors(&self) -> Vec<Box<dyn ParameterDescriptor>> {
+            Vec::new()
+        }
+    }
+
+    impl AudioNode for ProbeNode {
+        fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+            assert!(!self.panics, "probe panicked");
+            self.last_input = ctx.inputs.get_cv("in")
+                .or(ctx.inputs.get_audio("audio_in"))
+                .map(|b| b.to_vec())
+                .unwrap_or_default();
+            let output = match ctx.outputs.get_cv_mut("out") {
+                Some(output) => Some(output),
+                None => ctx.outputs.get_audio_mut("audio_out"),
+            };
+            if let Some(output) = output {
+                for (i, sample) in output.iter_mut().enumerate() {
+                    *sample = self.last_input.get(i).copied().unwrap_or(0.0) + self.offset;
+                }
+            }
+            Ok(())
+        }
+
+        fn node_info(&self) -> &NodeInfo {
+            &self.info
+        }
+
+        fn latency(&self) -> u32 {
+            self.latency
+        }
+
+        fn reset(&mut self) {
+            self.last_input.clear();
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    fn probe_input(graph: &ProcessingGraph, id: &str) -> Vec<f32> {
+        graph.get_node(id).unwrap().as_any().downcast_ref::<ProbeNode>().unwrap().last_input.clone()
+    }
+
+    fn add(graph: &mut ProcessingGraph, node: Box<dyn AudioNode>) -> String {
+        let id = node.node_info().id.to_string();
+        graph.add_node_instance(node).unwrap();
+        id
+    }
+
+    fn position(graph: &ProcessingGraph, id: &str) -> usize {
+        let uuid = Uuid::parse_str(id).unwrap();
+        graph.topology().processing_order().iter().position(|&n| n == uuid).unwrap()
+    }
+
+    /// osc -> out, out.mixed_output -> osc.amplitude_cv (feedback)
+    fn build_feedback_graph() -> (ProcessingGraph, String, String) {
+        let mut graph = ProcessingGraph::new();
+        let osc = add(&mut graph, Box::new(OscillatorNode::new(44100.0, "osc".to_string())));
+        let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+        graph.connect_by_id(&osc, "audio_out", &out, "audio_in_l").unwrap();
+        graph.connect_by_id(&osc, "audio_out", &out, "audio_in_r").unwrap();
+        graph.connect_by_id(&out, "mixed_output", &osc, "amplitude_cv").unwrap();
+        (graph, osc, out)
+    }
+
+    #[test]
+    fn test_cycle_is_marked_as_feedback() {
+        let (graph, osc, out) = build_feedback_graph();
+
+        assert!(!graph.connections()[0].feedback);
+        assert!(graph.connections()[2].feedback);
+        assert!(graph.has_feedback());
+        // The forward edge still decides the order
+        assert!(position(&graph, &osc) < position(&graph, &out));
+    }
+
+    #[test]
+    fn test_self_connection_and_reclassification() {
+        let mut graph = ProcessingGraph::new();
+        let a = add(&mut graph, Box::new(MultipleNode::new(44100.0, "a".to_string(), 2)));
+        let b = add(&mut graph, Box::new(MultipleNode::new(44100.0, "b".to_string(), 2)));
+
+        graph.connect_by_id(&a, "out_2", &a, "signal_in").unwrap();
+        assert!(graph.connections()[0].feedback);
+
+        graph.connect_by_id(&a, "out_1", &b, "signal_in").unwrap();
+        graph.connect_by_id(&b, "out_1", &a, "signal_in").unwrap();
+        assert!(graph.connections()[2].feedback);
+
+        // Breaking the forward edge turns the former feedback edge into a normal one
+        graph.disconnect_by_id(&a, "out_1", &b, "signal_in").unwrap();
+        assert!(!graph.connections()[1].feedback);
+        assert!(position(&graph, &b) < position(&graph, &a));
+    }
+
+    #[test]
+    fn test_feedback_delay_is_fixed() {
+        // a -> b -> a: every trip around the loop adds a's offset of 1.0,
+        // so a's input counts the feedback delays elapsed
+        let mut graph = ProcessingGraph::new();
+        let a = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let b = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&a, "out", &b, "in").unwrap();
+        graph.connect_by_id(&b, "out", &a, "in").unwrap();
+        graph.set_feedback_delay(Some(4)).unwrap();
+        graph.compile_plan(16);
+
+        // Host blocks of 10, 3 and 7 samples; the delay stays 4 across their edges
+        let mut heard = Vec::new();
+        for frames in [10, 3, 7] {
+            let (mut left, mut right) = (vec![0.0; frames], vec![0.0; frames]);
+            for (start, end) in (0..frames).step_by(4).map(|start| (start, (start + 4).min(frames))) {
+                graph.process_stereo(&mut left[start..end], &mut right[start..end], 44100.0).unwrap();
+                heard.extend(probe_input(&graph, &a));
+            }
+        }
+        let expected: Vec<f32> = (0..20).map(|t| (t / 4) as f32).collect();
+        assert_eq!(heard, expected);
+    }
+
+    #[test]
+    fn test_feedback_delay_splits_block() {
+        let (mut graph, _osc, out) = build_feedback_graph();
+        let out = Uuid::parse_str(&out).unwrap();
+
+        assert!(graph.set_feedback_delay(Some(0)).is_err());
+        graph.set_feedback_delay(Some(32)).unwrap();
+
+        let mut outputs = OutputPorts::new();
+        graph.process_audio(&mut InputPorts::new(), &mut outputs, 44100.0, 100).unwrap();
+
+        assert_eq!(outputs.get_audio("main_left").unwrap().len(), 100);
+        // Last sub-block is 100 - 3 * 32 samples
+        assert_eq!(graph.last_output(out, "mixed_output").unwrap().len(), 4);
+    }
+
+    fn build_fan_in_graph(fan_in: FanIn) -> (ProcessingGraph, String) {
+        let mut graph = ProcessingGraph::new();
+        let a = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let b = add(&mut graph, Box::new(ProbeNode::new(3.0, FanIn::Sum)));
+        let target = add(&mut graph, Box::new(ProbeNode::new(0.0, fan_in)));
+        graph.connect_by_id(&a, "out", &target, "in").unwrap();
+        let second = graph.connect_by_id(&b, "out", &target, "in");
+        assert_eq!(second.is_ok(), fan_in != FanIn::Single);
+        (graph, target)
+    }
+
+    #[test]
+    fn test_fan_in_modes() {
+        for (fan_in, expected) in [(FanIn::Sum, 4.0), (FanIn::Average, 2.0), (FanIn::Single, 1.0)] {
+            let (mut graph, target) = build_fan_in_graph(fan_in);
+            graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+            assert_eq!(probe_input(&graph, &target), vec![expected; 8], "{:?}", fan_in);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_connection_rejected() {
+        let mut graph = ProcessingGraph::new();
+        let a = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let b = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&a, "out", &b, "in").unwrap();
+        assert!(graph.connect_by_id(&a, "out", &b, "in").is_err());
+    }
+
+    #[test]
+    fn test_output_keeps_left_and_right_separate() {
+        let mut graph = ProcessingGraph::new();
+        let osc = add(&mut graph, Box::new(OscillatorNode::new(44100.0, "osc".to_string())));
+        let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+        graph.connect_by_id(&osc, "audio_out", &out, "audio_in_l").unwrap();
+
+        let mut outputs = OutputPorts::new();
+        graph.process_audio(&mut InputPorts::new(), &mut outputs, 44100.0, 256).unwrap();
+
+        assert!(outputs.get_audio("main_left").unwrap().iter().any(|&s| s.abs() > 0.01));
+        assert!(outputs.get_audio("main_right").unwrap().iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_plan_follows_edits_and_block_size() {
+        let mut graph = ProcessingGraph::new();
+        let a = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let b = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&a, "out", &b, "in").unwrap();
+        assert!(graph.topology().plan().is_none());
+
+        graph.compile_plan(16);
+        let (mut left, mut right) = (vec![0.0; 16], vec![0.0; 16]);
+        graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+        assert_eq!(graph.topology().plan().unwrap().max_block_size(), 16);
+        assert_eq!(probe_input(&graph, &b), vec![1.0; 16]);
+
+        // A larger block than compiled for is split; the last sub-block is 600 - 37 * 16 samples
+        let (mut left, mut right) = (vec![0.0; 600], vec![0.0; 600]);
+        graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+        assert_eq!(graph.topology().plan().unwrap().max_block_size(), 16);
+        assert_eq!(probe_input(&graph, &b), vec![1.0; 8]);
+
+        // Edits drop the plan; blocks are silent until it is compiled again
+        graph.disconnect_by_id(&a, "out", &b, "in").unwrap();
+        assert!(graph.topology().plan().is_none());
+        graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+        assert!(graph.topology().plan().is_none());
+        graph.compile_plan(16);
+        graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+        assert!(probe_input(&graph, &b).is_empty());
+    }
+
+    #[test]
+    fn test_host_block_size_does_not_change_output() {
+        let render = |host_block: usize| {
+            let mut graph = ProcessingGraph::new();
+            let osc = add(&mut graph, Box::new(OscillatorNode::new(44100.0, "osc".to_string())));
+            let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+            graph.connect_by_id(&osc, "audio_out", &out, "audio_in_l").unwrap();
+            graph.prepare(44100.0, 128);
+
+            let mut rendered = Vec::new();
+            let (mut left, mut right) = (vec![0.0; host_block], vec![0.0; host_block]);
+            while rendered.len() < 2048 {
+                graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+                rendered.extend_from_slice(&left);
+            }
+            rendered.truncate(2048);
+            rendered
+        };
+
+        // Odd and oversized host blocks render exactly what prepared-size blocks do
+        let reference = render(128);
+        assert_eq!(render(441), reference);
+        assert_eq!(render(1), reference);
+        assert_eq!(render(2048), reference);
+    }
+
+    #[test]
+    fn test_host_block_size_does_not_change_feedback() {
+        let render = |host_block: usize| {
+            let (mut graph, _osc, _out) = build_feedback_graph();
+            graph.prepare(44100.0, 128);
+
+            let mut rendered = Vec::new();
+            let (mut left, mut right) = (vec![0.0; host_block], vec![0.0; host_block]);
+            while rendered.len() < 2048 {
+                graph.process_stereo(&mut left, &mut right, 44100.0).unwrap();
+                rendered.extend_from_slice(&left);
+            }
+            rendered.truncate(2048);
+            rendered
+        };
+
+        // The loop is delayed by 128 samples whatever the host block is
+        let reference = render(128);
+        assert!(reference.iter().any(|s| s.abs() > 0.01));
+        assert_eq!(render(441), reference);
+        assert_eq!(render(1), reference);
+        assert_eq!(render(2048), reference);
+    }
+
+    #[test]
+    fn test_output_nodes_play_on_their_routes() {
+        let mut graph = ProcessingGraph::new();
+        let mut outputs = Vec::new();
+        let mut osc = String::new();
+        for name in ["main", "stem", "cv"] {
+            osc = add(&mut graph, Box::new(OscillatorNode::new(44100.0, format!("{}_osc", name))));
+            let out = add(&mut graph, Box::new(OutputNode::new(44100.0, name.to_string())));
+            graph.connect_by_id(&osc, "audio_out", &out, "audio_in_l").unwrap();
+            outputs.push(Uuid::parse_str(&out).unwrap());
+        }
+        graph.set_output_route(outputs[1], OutputRoute::stereo(2)).unwrap();
+        graph.set_output_route(outputs[2], OutputRoute::mono(5)).unwrap();
+        assert!(graph.set_output_route(outputs[2], OutputRoute { first_channel: 0, channels: 3 }).is_err());
+        assert!(graph.set_output_route(Uuid::parse_str(&osc).unwrap(), OutputRoute::stereo(2)).is_err());
+        assert_eq!(graph.topology().output_channel_count(), 6);
+
+        // Only the left inputs are connected, so every right side stays silent
+        let mut channels = vec![vec![0.0; 64]; 6];
+        graph.compile_plan(64);
+        graph.process_channels(&mut channels, 64, 44100.0).unwrap();
+        let heard: Vec<bool> = channels.iter().map(|channel| channel.iter().any(|s| s.abs() > 0.01)).collect();
+        assert_eq!(heard, [true, false, true, false, false, true]);
+
+        // Routes are saved with the patch
+        let patch = graph.to_patch();
+        assert_eq!(patch.nodes.iter().filter(|node| node.output_route.is_some()).count(), 2);
+        let mut loaded = ProcessingGraph::new();
+        loaded.load_patch(&patch, |node| create_patch_node(node, 44100.0)).unwrap();
+        assert_eq!(loaded.output_route(outputs[2]), Some(OutputRoute::mono(5)));
+        assert_eq!(loaded.output_route(outputs[0]), Some(OutputRoute::default()));
+    }
+
+    /// Eight oscillators into the mixer channels, mixer into the output
+    fn build_mixer_graph(worker_threads: usize) -> ProcessingGraph {
+        let mut graph = ProcessingGraph::new();
+        graph.set_worker_threads(worker_threads);
+        let mixer = add(&mut graph, Box::new(MixerNode::new(44100.0, "mixer".to_string())));
+        let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+        for channel in 1..=8 {
+            let mut osc = OscillatorNode::new(44100.0, format!("osc{}", channel));
+            osc.set_parameter("frequency", 110.0 * channel as f32).unwrap();
+            let osc = add(&mut graph, Box::new(osc));
+            graph.connect_by_id(&osc, "audio_out", &mixer, &format!("ch{}_l", channel)).unwrap();
+            graph.connect_by_id(&osc, "audio_out", &mixer, &format!("ch{}_r", channel)).unwrap();
+        }
+        graph.connect_by_id(&mixer, "mix_l", &out, "audio_in_l").unwrap();
+        graph.connect_by_id(&mixer, "mix_r", &out, "audio_in_r").unwrap();
+        graph.compile_plan(DEFAULT_MAX_BLOCK_SIZE);
+        graph
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let mut serial = build_mixer_graph(0);
+        let mut parallel = build_mixer_graph(3);
+        assert_eq!(parallel.worker_threads(), 3);
+
+        for _ in 0..4 {
+            let (mut serial_l, mut serial_r) = (vec![0.0; 256], vec![0.0; 256]);
+            let (mut parallel_l, mut parallel_r) = (vec![0.0; 256], vec![0.0; 256]);
+            serial.process_stereo(&mut serial_l, &mut serial_r, 44100.0).unwrap();
+            parallel.process_stereo(&mut parallel_l, &mut parallel_r, 44100.0).unwrap();
+
+            assert!(serial_l.iter().any(|&s| s.abs() > 0.01));
+            assert_eq!(serial_l, parallel_l);
+            assert_eq!(serial_r, parallel_r);
+        }
+
+        // The oscillators form one level, mixer and output one each
+        let widths: Vec<usize> = parallel.topology().plan().unwrap().level_widths().collect();
+        assert_eq!(widths, vec![8, 1, 1]);
+
+        parallel.set_worker_threads(0);
+        assert_eq!(parallel.worker_threads(), 0);
+    }
+
+    #[test]
+    fn test_attach_node_with_spare_map() {
+        let mut graph = ProcessingGraph::new();
+
+        // A full map moves into the spare one
+        let spare = HashMap::with_capacity(4);
+        let capacity = spare.capacity();
+        let unused = graph.attach_node_with(Box::new(ProbeNode::new(0.0, FanIn::Sum)), spare);
+        assert_eq!(graph.audio_nodes.capacity(), capacity);
+        assert_eq!(unused.capacity(), 0);
+
+        // With room left the spare goes back unused
+        let unused = graph.attach_node_with(Box::new(ProbeNode::new(0.0, FanIn::Sum)), HashMap::with_capacity(8));
+        assert!(unused.capacity() >= 8);
+        assert_eq!(graph.audio_nodes.capacity(), capacity);
+        assert_eq!(graph.audio_nodes.len(), 2);
+    }
+
+    #[test]
+    fn test_node_panic_is_an_error_on_both_paths() {
+        for worker_threads in [0, 2] {
+            let mut graph = ProcessingGraph::new();
+            graph.set_worker_threads(worker_threads);
+            let mut faulty = ProbeNode::new(0.0, FanIn::Sum);
+            faulty.panics = true;
+            add(&mut graph, Box::new(faulty));
+            add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+            graph.compile_plan(DEFAULT_MAX_BLOCK_SIZE);
+
+            let (mut left, mut right) = (vec![0.0; 64], vec![0.0; 64]);
+            let error = graph.process_stereo(&mut left, &mut right, 44100.0).unwrap_err();
+            assert!(matches!(&error, ProcessingError::Internal { message } if message == "Node probe panicked"),
+                    "{} workers: {:?}", worker_threads, error);
+        }
+    }
+
+    #[test]
+    fn test_latency_compensation() {
+        let mut graph = ProcessingGraph::new();
+        let source = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let mut lookahead = ProbeNode::new(0.0, FanIn::Sum);
+        lookahead.latency = 5;
+        let lookahead = add(&mut graph, Box::new(lookahead));
+        let target = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+
+        // source -> target directly and through the 5-sample lookahead node
+        graph.connect_by_id(&source, "out", &target, "in").unwrap();
+        graph.connect_by_id(&source, "out", &lookahead, "in").unwrap();
+        graph.connect_by_id(&lookahead, "out", &target, "in").unwrap();
+        graph.connect_by_id(&target, "out", &out, "audio_in_l").unwrap();
+        assert_eq!(graph.latency(), 5);
+
+        // The direct path is delayed to line up with the lookahead path
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert_eq!(probe_input(&graph, &target), vec![1.0, 1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0]);
+
+        // A latency change is reported by the block that sees it and
+        // compensated once the plan is compiled again
+        let lookahead_id = Uuid::parse_str(&lookahead).unwrap();
+        graph.audio_nodes.get_mut(&lookahead_id).unwrap()
+            .as_any_mut().downcast_mut::<ProbeNode>().unwrap().latency = 2;
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert!(graph.topology().plan().unwrap().latency_changed());
+        assert_eq!(graph.latency(), 5);
+        let mut changes = Vec::new();
+        graph.drain_latency_changes(|node_id, samples| changes.push((node_id, samples)));
+        graph.drain_latency_changes(|node_id, samples| changes.push((node_id, samples)));
+        assert_eq!(changes, vec![(lookahead_id, 2)]);
+        graph.compile_plan(graph.max_block_size());
+        assert_eq!(graph.latency(), 2);
+    }
+
+    #[test]
+    fn test_bypass_crossfades_to_input() {
+        let mut graph = ProcessingGraph::new();
+        let source = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let mut effect = ProbeNode::bypassable(10.0);
+        effect.latency = 4;
+        let effect = add(&mut graph, Box::new(effect));
+        let target = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&source, "out", &effect, "audio_in").unwrap();
+        graph.connect_by_id(&effect, "audio_out", &target, "in").unwrap();
+
+        let effect_id = Uuid::parse_str(&effect).unwrap();
+        assert!(graph.set_bypass(Uuid::parse_str(&source).unwrap(), true).is_err());
+
+        // Bypassed from the start: the input comes through, delayed by the node's latency,
+        // and the node itself is not processed
+        graph.set_bypass(effect_id, true).unwrap();
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert_eq!(probe_input(&graph, &target), vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+        assert!(probe_input(&graph, &effect).is_empty());
+
+        // Brought back: a 10 ms crossfade from the input (1.0) to the processed signal (11.0)
+        graph.set_bypass(effect_id, false).unwrap();
+        assert!(!graph.is_bypassed(effect_id));
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 512).unwrap();
+        let fade = probe_input(&graph, &target);
+        assert!(fade[0] > 1.0 && fade[0] < 1.1);
+        assert!(fade.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(fade[511], 11.0);
+    }
+
+    #[test]
+    fn test_events_land_on_exact_samples() {
+        let mut graph = ProcessingGraph::new();
+        let source = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        let middle = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        let target = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&source, "out", &middle, "in").unwrap();
+        graph.connect_by_id(&middle, "out", &target, "in").unwrap();
+
+        let set_offset = |node: &str, time: u64, value: f32| ScheduledEvent {
+            node_id: Uuid::parse_str(node).unwrap(),
+            time,
+            kind: EventKind::Parameter { name: "offset".to_string(), value },
+        };
+        // Out of order; the last one is due in the second block
+        graph.schedule_event(set_offset(&middle, 6, 10.0));
+        graph.schedule_event(set_offset(&source, 3, 1.0));
+        graph.schedule_event(set_offset(&source, 10, 2.0));
+
+        // `middle` is split too, so its input has to come through intact
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert_eq!(probe_input(&graph, &target), vec![0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 11.0, 11.0]);
+        assert_eq!(graph.pending_events(), 1);
+
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert_eq!(probe_input(&graph, &target), vec![11.0, 11.0, 12.0, 12.0, 12.0, 12.0, 12.0, 12.0]);
+        assert_eq!(graph.position(), 16);
+    }
+
+    #[test]
+    fn test_poly_cables() {
+        let mut graph = ProcessingGraph::new();
+        let mut allocator = VoiceAllocatorNode::new(44100.0, "alloc".to_string());
+        allocator.note_on(0.5, 1.0);
+        allocator.note_on(0.25, 1.0);
+        let alloc = add(&mut graph, Box::new(allocator));
+        let vca = add(&mut graph, create_builtin_node("poly_vca", 44100.0, "vca".to_string()).unwrap());
+        let sum = add(&mut graph, create_builtin_node("poly_sum", 44100.0, "sum".to_string()).unwrap());
+        let target = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&alloc, "pitch_out", &vca, "audio_in").unwrap();
+        graph.connect_by_id(&vca, "audio_out", &sum, "poly_in").unwrap();
+        graph.connect_by_id(&sum, "audio_out", &target, "in").unwrap();
+
+        // A mono cable into a poly input carries one voice
+        let mono = add(&mut graph, Box::new(ProbeNode::new(1.0, FanIn::Sum)));
+        let mono_sum = add(&mut graph, create_builtin_node("poly_sum", 44100.0, "sum".to_string()).unwrap());
+        let mono_target = add(&mut graph, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        graph.connect_by_id(&mono, "out", &mono_sum, "poly_in").unwrap();
+        graph.connect_by_id(&mono_sum, "audio_out", &mono_target, "in").unwrap();
+
+        graph.process_audio(&mut InputPorts::new(), &mut OutputPorts::new(), 44100.0, 8).unwrap();
+        assert_eq!(probe_input(&graph, &target), vec![0.75; 8]);
+        assert_eq!(probe_input(&graph, &mono_target), vec![1.0; 8]);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let mut graph = ProcessingGraph::new();
+        let mut osc = OscillatorNode::new(44100.0, "lead".to_string());
+        osc.set_parameter("frequency", 220.0).unwrap();
+        let osc = add(&mut graph, Box::new(osc));
+        let vca = add(&mut graph, create_builtin_node("poly_vca", 44100.0, "voices".to_string()).unwrap());
+        let out = add(&mut graph, Box::new(OutputNode::new(44100.0, "out".to_string())));
+        let mut seq = SequencerNode::new(44100.0, "seq".to_string());
+        seq.set_step(5, 110.0, false, 0.5);
+        let seq = add(&mut graph, Box::new(seq));
+        let amp = add(&mut graph, create_builtin_node("vca", 44100.0, "amp".to_string()).unwrap());
+        graph.connect_by_id(&osc, "audio_out", &vca, "audio_in").unwrap();
+        graph.connect_by_id(&osc, "audio_out", &out, "audio_in_l").unwrap();
+        graph.set_bypass(Uuid::parse_str(&amp).unwrap(), true).unwrap();
+        graph.set_feedback_delay(Some(32)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("orbital_graph_{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        graph.save_to_file(path).unwrap();
+
+        let mut loaded = ProcessingGraph::new();
+        add(&mut loaded, Box::new(ProbeNode::new(0.0, FanIn::Sum)));
+        loaded.load_from_file(path, 44100.0).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut ids = loaded.list_nodes();
+        let mut expected = vec![osc.clone(), vca.clone(), out.clone(), seq.clone(), amp.clone()];
+        ids.sort();
+        expected.sort();
+        assert_eq!(ids, expected);
+        assert_eq!(loaded.get_node(&osc).unwrap().node_info().name, "lead");
+        assert_eq!(loaded.get_node(&osc).unwrap().get_parameter("frequency").unwrap(), 220.0);
+        assert_eq!(loaded.get_node(&vca).unwrap().node_info().node_type, "poly_vca");
+        assert_eq!(loaded.connections().len(), 2);
+        assert_eq!(loaded.get_node(&seq).unwrap().save_state(), graph.get_node(&seq).unwrap().save_state());
+        assert_eq!(loaded.to_patch().connections.len(), graph.to_patch().connections.len());
+        assert!(loaded.is_bypassed(Uuid::parse_str(&amp).unwrap()));
+        assert!(!loaded.is_bypassed(Uuid::parse_str(&vca).unwrap()));
+        assert_eq!(loaded.feedback_delay(), Some(32));
+
+        // A broken patch leaves the graph as it was
+        let mut patch = graph.to_patch();
+        patch.connections[0].target_node = "missing".to_string();
+        assert!(loaded.load_patch(&patch, |node| create_patch_node(node, 44100.0)).is_err());
+        assert_eq!(loaded.list_nodes().len(), 5);
+    }
+}
@@ -0,0 +1,209 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Real-time worker pool for processing independent nodes in parallel.
+//!
+//! Workers are started once and park between batches. Publishing a batch and
+//! claiming jobs only uses atomics, so the audio thread never allocates or
+//! takes a lock; it also works on the batch itself and spins until the last
+//! job is done. Workers ask for real-time scheduling where the platform
+//! grants it, like the audio thread they work for.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::processing::{AudioNode, ProcessContext, ProcessingError};
+
+const INDEX_MASK: u64 = u32::MAX as u64;
+
+/// One node to process - only valid for the duration of `WorkerPool::execute`
+#[derive(Debug)]
+pub(crate) struct NodeJob {
+    node: *mut dyn AudioNode,
+    context: *mut ProcessContext,
+    scratch: *mut ProcessContext,
+    result: Result<(), ProcessingError>,
+}
+
+// The pointers are only dereferenced while `execute` holds the borrows they came from
+unsafe impl Send for NodeJob {}
+// Shared references cannot reach the pointers, and jobs never outlive a batch
+unsafe impl Sync for NodeJob {}
+
+impl NodeJob {
+    pub(crate) fn new(node: &mut (dyn AudioNode + 'static), context: &mut ProcessContext,
+                      scratch: &mut ProcessContext) -> Self {
+        Self { node, context, scratch, result: Ok(()) }
+    }
+
+    /// Result of processing the node (`ProcessContext::process_node`)
+    pub(crate) fn take_result(&mut self) -> Result<(), ProcessingError> {
+        std::mem::replace(&mut self.result, Ok(()))
+    }
+
+    fn run(&mut self) {
+        // SAFETY: each job is claimed by exactly one thread and the borrows
+        // behind the pointers outlive the batch
+        let (node, context, scratch) = unsafe { (&mut *self.node, &mut *self.context, &mut *self.scratch) };
+        self.result = process_node_caught(node, context, scratch);
+    }
+}
+
+/// `ProcessContext::process_node`, reporting a panicking node as an error
+/// instead of unwinding into the thread that runs it. Used by the worker
+/// pool and the serial path alike.
+pub(crate) fn process_node_caught(node: &mut dyn AudioNode, context: &mut ProcessContext,
+                                  scratch: &mut ProcessContext) -> Result<(), ProcessingError> {
+    panic::catch_unwind(AssertUnwindSafe(|| context.process_node(node, scratch)))
+        .unwrap_or_else(|_| Err(ProcessingError::Internal {
+            message: format!("Node {} panicked", node.node_info().name),
+        }))
+}
+
+struct Shared {
+    /// Batch length in the high half, next job index in the low half
+    cursor: AtomicU64,
+    jobs: AtomicPtr<NodeJob>,
+    /// Jobs not finished yet
+    remaining: AtomicUsize,
+    shutdown: AtomicBool,
+}
+
+impl Shared {
+    /// Claim and run jobs of the current batch until none are left
+    fn work(&self) {
+        loop {
+            let cursor = self.cursor.load(Ordering::Acquire);
+            let index = cursor & INDEX_MASK;
+            if index >= cursor >> 32 {
+                return;
+            }
+
+            if self.cursor.compare_exchange_weak(cursor, cursor + 1, Ordering::AcqRel, Ordering::Relaxed).is_err() {
+                continue;
+            }
+
+            // SAFETY: a batch is only replaced once all of its jobs are finished,
+            // so a successful claim always refers to the batch `jobs` points to
+            let job = unsafe { &mut *self.jobs.load(Ordering::Relaxed).add(index as usize) };
+            job.run();
+            self.remaining.fetch_sub(1, Ordering::Release);
+        }
+    }
+}
+
+/// 並列処理用のワーカースレッド群
+pub struct WorkerPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    /// Start `threads` workers. The calling thread always helps, so a pool of
+    /// `n` workers processes up to `n + 1` nodes at once.
+    pub fn new(threads: usize) -> Self {
+        let shared = Arc::new(Shared {
+            cursor: AtomicU64::new(0),
+            jobs: AtomicPtr::new(std::ptr::null_mut()),
+            remaining: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+        });
+
+        let workers = (0..threads)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("orbital-worker-{}", i))
+                    .spawn(move || {
+                        promote_to_realtime();
+                        while !shared.shutdown.load(Ordering::Acquire) {
+                            shared.work();
+                            thread::park();
+                        }
+                    })
+                    .expect("Failed to spawn audio worker thread")
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    /// Number of worker threads (not counting the caller)
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run every job and return once all of them are done
+    pub(crate) fn execute(&mut self, jobs: &mut [NodeJob]) {
+        if jobs.is_empty() {
+            return;
+        }
+
+        // The previous batch is fully claimed, so nobody reads `jobs` until the cursor opens
+        self.shared.jobs.store(jobs.as_mut_ptr(), Ordering::Relaxed);
+        self.shared.remaining.store(jobs.len(), Ordering::Relaxed);
+        self.shared.cursor.store((jobs.len() as u64) << 32, Ordering::Release);
+
+        // The caller takes one job itself, so more helpers than the rest would only spin
+        for worker in self.workers.iter().take(jobs.len() - 1) {
+            worker.thread().unpark();
+        }
+
+        self.shared.work();
+        while self.shared.remaining.load(Ordering::Acquire) > 0 {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Run the current thread with `SCHED_FIFO` at a medium real-time priority.
+/// Needs the privilege to do so (e.g. an rtprio limit); the thread keeps its
+/// normal priority otherwise. Returns whether the priority was raised.
+#[cfg(unix)]
+fn promote_to_realtime() -> bool {
+    // SAFETY: only reads and sets the scheduling of the calling thread
+    unsafe {
+        let policy = libc::SCHED_FIFO;
+        let mut param: libc::sched_param = std::mem::zeroed();
+        param.sched_priority = (libc::sched_get_priority_min(policy) + libc::sched_get_priority_max(policy)) / 2;
+        libc::pthread_setschedparam(libc::pthread_self(), policy, &param) == 0
+    }
+}
+
+#[cfg(not(unix))]
+fn promote_to_realtime() -> bool {
+    false
+}
+
+impl std::fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WorkerPool").field("threads", &self.threads()).finish()
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::Release);
+        for worker in self.workers.drain(..) {
+            worker.thread().unpark();
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,872 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Compiled execution plan.
+//!
+//! Compiling a `GraphTopology` resolves port names and connections once:
+//! every node output gets an index-addressed signal slot, every node gets a
+//! `ProcessContext` whose buffers are allocated up front, and every input
+//! knows which slots it mixes. Running the plan only copies between those
+//! buffers - no heap allocation and no port name lookups per block.
+//!
+//! Steps are grouped into levels: a node only reads nodes of lower levels
+//! (feedback edges read higher levels through a delay line of a fixed length,
+//! so they only see samples from earlier sub-blocks). The
+//! nodes of one level can therefore be processed on a `WorkerPool`, while
+//! mixing and publishing stay serial so the result matches serial processing.
+//!
+//! Connections that arrive earlier than the slowest path into the same node
+//! (by the sum of `AudioNode::latency` along the way) get a compensating
+//! delay line, so parallel branches stay in phase.
+//!
+//! Nodes that support bypass carry a crossfade from their processed output to
+//! their primary input (delayed by the node's latency), which can be switched
+//! while the plan runs.
+//!
+//! Scheduled events are handed to the step of their node at the start of each
+//! block. A step with events inside the block processes its node in sub-blocks
+//! split at the event offsets (`ProcessContext::process_node`).
+
+use std::collections::HashMap;
+use std::ops::Range;
+use uuid::Uuid;
+
+use super::parallel::{process_node_caught, NodeJob, WorkerPool};
+use super::{FanIn, GraphTopology, OutputRoute, PortType, ScheduledEvent};
+use crate::processing::{
+    AudioNode, BufferSlot, EventList, InputBuffers, NodeEvent, OutputBuffers, PolyBuffer, ProcessContext,
+    ProcessingError, SlotMut, SlotRef, StereoBuffer,
+};
+use crate::transport::TransportInfo;
+
+/// Block size a plan is compiled for when nothing else is known
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 512;
+
+/// Events a node can receive per block without allocating
+pub(crate) const EVENT_CAPACITY: usize = 64;
+
+/// Length of the crossfade when a node is bypassed or brought back
+const BYPASS_FADE_SECONDS: f32 = 0.01;
+
+/// Signal flowing through one output port.
+///
+/// Adaptation rules when the port types differ:
+/// - mono (audio or CV) into a stereo port is copied to both channels
+/// - stereo into a mono or CV port is folded down to (L + R) / 2
+/// - mono or stereo into a poly port is copied to every voice (as mono)
+/// - poly into a mono, CV or stereo port is the sum of its voices
+#[derive(Debug, Clone)]
+enum PortSignal {
+    Mono(Vec<f32>),
+    Stereo(StereoBuffer),
+    Poly(PolyBuffer),
+}
+
+impl PortSignal {
+    /// Empty signal shaped for a port of `port_type`
+    fn silence(port_type: PortType, size: usize) -> Self {
+        match port_type {
+            PortType::AudioStereo => PortSignal::Stereo(StereoBuffer::new(size)),
+            PortType::AudioMono | PortType::CV => PortSignal::Mono(vec![0.0; size]),
+            PortType::Poly => PortSignal::Poly(PolyBuffer::new(size)),
+        }
+    }
+
+    /// Add this signal into an input buffer, adapting mono/stereo/poly as needed
+    fn mix_into(&self, target: &mut SlotMut) {
+        match (target, self) {
+            (SlotMut::Mono(mix), PortSignal::Mono(data)) => add(mix, data),
+            (SlotMut::Mono(mix), PortSignal::Stereo(data)) => {
+                for (m, (l, r)) in mix.iter_mut().zip(data.left.iter().zip(data.right.iter())) {
+                    *m += (l + r) * 0.5;
+                }
+            }
+            (SlotMut::Mono(mix), PortSignal::Poly(data)) => {
+                data.active().iter().for_each(|voice| add(mix, voice));
+            }
+            (SlotMut::Stereo(mix), PortSignal::Mono(data)) => {
+                add(&mut mix.left, data);
+                add(&mut mix.right, data);
+            }
+            (SlotMut::Stereo(mix), PortSignal::Stereo(data)) => {
+                add(&mut mix.left, &data.left);
+                add(&mut mix.right, &data.right);
+            }
+            (SlotMut::Stereo(mix), PortSignal::Poly(data)) => {
+                for voice in data.active() {
+                    add(&mut mix.left, voice);
+                    add(&mut mix.right, voice);
+                }
+            }
+            (SlotMut::Poly(mix), PortSignal::Poly(data)) => {
+                mix.channels.iter_mut().zip(data.active()).for_each(|(m, voice)| add(m, voice));
+                mix.voices = mix.voices.max(data.voices);
+            }
+            (SlotMut::Poly(mix), source) => {
+                for channel in mix.channels.iter_mut() {
+                    source.mix_into(&mut SlotMut::Mono(channel));
+                }
+                mix.voices = mix.voices.max(1);
+            }
+        }
+    }
+
+    fn as_slot(&self) -> SlotRef<'_> {
+        match self {
+            PortSignal::Mono(data) => SlotRef::Mono(data),
+            PortSignal::Stereo(data) => SlotRef::Stereo(data),
+            PortSignal::Poly(data) => SlotRef::Poly(data),
+        }
+    }
+
+    /// Take over a node output (within the preallocated capacity)
+    fn copy_from(&mut self, source: SlotRef) {
+        match (self, source) {
+            (PortSignal::Mono(slot), SlotRef::Mono(data)) => copy_within_capacity(slot, data),
+            (PortSignal::Stereo(slot), SlotRef::Stereo(data)) => {
+                copy_within_capacity(&mut slot.left, &data.left);
+                copy_within_capacity(&mut slot.right, &data.right);
+            }
+            (PortSignal::Poly(slot), SlotRef::Poly(data)) => {
+                for (channel, source) in slot.channels.iter_mut().zip(data.channels.iter()) {
+                    copy_within_capacity(channel, source);
+                }
+                slot.voices = data.voices;
+            }
+            // Slots are shaped after the port they belong to
+            _ => {}
+        }
+    }
+}
+
+fn add(target: &mut [f32], source: &[f32]) {
+    target.iter_mut().zip(source.iter()).for_each(|(t, s)| *t += s);
+}
+
+fn copy_within_capacity(target: &mut Vec<f32>, source: &[f32]) {
+    target.clear();
+    target.extend_from_slice(source);
+}
+
+/// Zero a buffer and set its length to `frames` (within the preallocated capacity)
+fn reset_slot(slot: &mut SlotMut, frames: usize) {
+    match slot {
+        SlotMut::Mono(data) => {
+            data.resize(frames, 0.0);
+            data.fill(0.0);
+        }
+        SlotMut::Stereo(data) => {
+            data.resize(frames);
+            data.fill(0.0);
+        }
+        SlotMut::Poly(data) => {
+            data.resize(frames);
+            data.fill(0.0);
+            data.voices = 0;
+        }
+    }
+}
+
+/// Read the next `frames` samples leaving a ring buffer, before they are pushed
+fn read_channel(ring: &[f32], write: usize, delay: usize, frames: usize, output: &mut Vec<f32>) {
+    let len = ring.len();
+    output.clear();
+    output.extend((0..frames).map(|i| ring[(write + i + len - delay) % len]));
+}
+
+/// Delay one channel through a ring buffer of at least `delay + input.len()` samples
+fn delay_channel(ring: &mut [f32], write: usize, delay: usize, input: &[f32], output: &mut Vec<f32>) {
+    let len = ring.len();
+    output.clear();
+    for (i, &sample) in input.iter().enumerate() {
+        let position = (write + i) % len;
+        ring[position] = sample;
+        output.push(ring[(position + len - delay) % len]);
+    }
+}
+
+/// Latency compensation in front of a signal slot or a bypassed input
+#[derive(Debug)]
+struct DelayLine {
+    delay: usize,
+    ring: PortSignal,
+    write: usize,
+    /// Delayed copy of the source's last block
+    output: PortSignal,
+}
+
+impl DelayLine {
+    fn new(delay: usize, port_type: PortType, max_block_size: usize) -> Self {
+        Self {
+            delay,
+            ring: PortSignal::silence(port_type, delay + max_block_size),
+            write: 0,
+            output: PortSignal::silence(port_type, max_block_size),
+        }
+    }
+
+    fn push(&mut self, input: SlotRef) {
+        let (frames, ring_len) = match (&mut self.ring, &mut self.output, input) {
+            (PortSignal::Mono(ring), PortSignal::Mono(output), SlotRef::Mono(data)) => {
+                delay_channel(ring, self.write, self.delay, data, output);
+                (data.len(), ring.len())
+            }
+            (PortSignal::Stereo(ring), PortSignal::Stereo(output), SlotRef::Stereo(data)) => {
+                delay_channel(&mut ring.left, self.write, self.delay, &data.left, &mut output.left);
+                delay_channel(&mut ring.right, self.write, self.delay, &data.right, &mut output.right);
+                (data.len(), ring.len())
+            }
+            (PortSignal::Poly(ring), PortSignal::Poly(output), SlotRef::Poly(data)) => {
+                let channels = ring.channels.iter_mut().zip(output.channels.iter_mut()).zip(data.channels.iter());
+                for ((ring, output), input) in channels {
+                    delay_channel(ring, self.write, self.delay, input, output);
+                }
+                output.voices = data.voices;
+                (data.len(), ring.len())
+            }
+            // Shaped after the source slot at compile time
+            _ => return,
+        };
+        self.write = (self.write + frames) % ring_len;
+    }
+
+    /// Fill `output` for the next `frames` samples ahead of `push`, for feedback
+    /// edges whose source runs later. Only valid for `frames <= delay`.
+    fn read_ahead(&mut self, frames: usize) {
+        match (&self.ring, &mut self.output) {
+            (PortSignal::Mono(ring), PortSignal::Mono(output)) => {
+                read_channel(ring, self.write, self.delay, frames, output);
+            }
+            (PortSignal::Stereo(ring), PortSignal::Stereo(output)) => {
+                read_channel(&ring.left, self.write, self.delay, frames, &mut output.left);
+                read_channel(&ring.right, self.write, self.delay, frames, &mut output.right);
+            }
+            (PortSignal::Poly(ring), PortSignal::Poly(output)) => {
+                for (ring, output) in ring.channels.iter().zip(output.channels.iter_mut()) {
+                    read_channel(ring, self.write, self.delay, frames, output);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Crossfade `output` towards `dry` by `mix`, moving `mix` to `target` by `step` per sample.
+/// Returns the mix reached at the end of the block.
+fn crossfade(output: &mut [f32], dry: Option<&[f32]>, mut mix: f32, target: f32, step: f32) -> f32 {
+    for (i, sample) in output.iter_mut().enumerate() {
+        mix = if target > mix { (mix + step).min(target) } else { (mix - step).max(target) };
+        let dry = dry.and_then(|data| data.get(i)).copied().unwrap_or(0.0);
+        *sample = *sample * (1.0 - mix) + dry * mix;
+    }
+    mix
+}
+
+/// Engine-level bypass of one node
+#[derive(Debug)]
+struct Bypass {
+    /// Primary input buffer - None if the input is not connected
+    input: Option<BufferSlot>,
+    output: BufferSlot,
+    engaged: bool,
+    /// 0.0 = processed signal, 1.0 = input passed through
+    mix: f32,
+    /// Input delayed by the node's latency, so both signals line up
+    dry: DelayLine,
+}
+
+impl Bypass {
+    /// Once fully bypassed the node is not processed at all
+    fn needs_processing(&self) -> bool {
+        !self.engaged || self.mix < 1.0
+    }
+
+    /// Blend the input into the primary output of the block in `context`
+    fn apply(&mut self, context: &mut ProcessContext) {
+        // Keep the delay line filled so engaging does not replay a stale input
+        if let Some(input) = self.input {
+            self.dry.push(context.inputs.slot(input));
+        }
+
+        if !self.engaged && self.mix == 0.0 {
+            return;
+        }
+
+        let target = if self.engaged { 1.0 } else { 0.0 };
+
+        let step = 1.0 / (BYPASS_FADE_SECONDS * context.sample_rate).max(1.0);
+        let connected = self.input.is_some();
+        let mix = self.mix;
+        self.mix = match (context.outputs.slot_mut(self.output), &self.dry.output) {
+            (SlotMut::Mono(output), PortSignal::Mono(dry)) => {
+                crossfade(output, connected.then_some(dry.as_slice()), mix, target, step)
+            }
+            (SlotMut::Stereo(output), PortSignal::Stereo(dry)) => {
+                crossfade(&mut output.left, connected.then_some(dry.left.as_slice()), mix, target, step);
+                crossfade(&mut output.right, connected.then_some(dry.right.as_slice()), mix, target, step)
+            }
+            // Both ports have the same type (GraphTopology::bypass_ports)
+            _ => mix,
+        };
+    }
+}
+
+/// Where an input reads from
+#[derive(Debug, Clone, Copy)]
+enum SignalSource {
+    Slot(usize),
+    Delayed(usize),
+}
+
+/// Input port fed from one or more signal slots
+#[derive(Debug)]
+struct InputRoute {
+    target: BufferSlot,
+    sources: Vec<SignalSource>,
+    /// 1 / source count for averaging ports
+    gain: f32,
+}
+
+/// One node in processing order
+#[derive(Debug)]
+struct PlanStep {
+    node_id: Uuid,
+    context: ProcessContext,
+    /// Same ports as `context`, for blocks split at event offsets
+    scratch: ProcessContext,
+    inputs: Vec<InputRoute>,
+    /// Output buffer and the signal slot it is published to
+    outputs: Vec<(BufferSlot, usize)>,
+    /// Buffer that goes to the device (output nodes only)
+    main_output: Option<BufferSlot>,
+    /// Device channels `main_output` is mixed into
+    route: OutputRoute,
+    /// Delay lines fed from this node's outputs, with the slot feeding each
+    delays: Vec<(usize, usize)>,
+    /// `AudioNode::latency` the plan was compiled with
+    latency: u32,
+    /// `AudioNode::latency` in the last block
+    current_latency: u32,
+    /// `current_latency` as last handed out by `drain_latency_changes`
+    reported_latency: u32,
+    /// Present if the node supports bypass
+    bypass: Option<Bypass>,
+}
+
+impl PlanStep {
+    /// Mix the input slots and clear the outputs for a block of `frames`
+    fn prepare(&mut self, slots: &[PortSignal], delay_lines: &[DelayLine], frames: usize, sample_rate: f32) {
+        for route in &self.inputs {
+            let mut target = self.context.inputs.slot_mut(route.target);
+            reset_slot(&mut target, frames);
+
+            for &source in &route.sources {
+                match source {
+                    SignalSource::Slot(slot) => slots[slot].mix_into(&mut target),
+                    SignalSource::Delayed(line) => delay_lines[line].output.mix_into(&mut target),
+                }
+            }
+
+            if route.gain != 1.0 {
+                match target {
+                    SlotMut::Mono(data) => data.iter_mut().for_each(|s| *s *= route.gain),
+                    SlotMut::Stereo(data) => {
+                        data.left.iter_mut().for_each(|s| *s *= route.gain);
+                        data.right.iter_mut().for_each(|s| *s *= route.gain);
+                    }
+                    SlotMut::Poly(data) => {
+                        data.channels.iter_mut().flatten().for_each(|s| *s *= route.gain);
+                    }
+                }
+            }
+        }
+
+        for &(buffer, _) in &self.outputs {
+            reset_slot(&mut self.context.outputs.slot_mut(buffer), frames);
+        }
+
+        self.context.sample_rate = sample_rate;
+        self.context.buffer_size = frames;
+    }
+
+    /// Note the current latency of the node. Returns true if it differs from the compiled one.
+    fn observe_latency(&mut self, node: &dyn AudioNode) -> bool {
+        self.current_latency = node.latency();
+        self.current_latency != self.latency
+    }
+
+    fn needs_processing(&self) -> bool {
+        self.bypass.as_ref().is_none_or(Bypass::needs_processing)
+    }
+
+    /// Apply the bypass, copy the outputs into their signal slots and feed the delay lines
+    fn publish(&mut self, slots: &mut [PortSignal], delay_lines: &mut [DelayLine]) {
+        if let Some(bypass) = &mut self.bypass {
+            bypass.apply(&mut self.context);
+        }
+        for &(buffer, slot) in &self.outputs {
+            slots[slot].copy_from(self.context.outputs.slot(buffer));
+        }
+        for &(slot, line) in &self.delays {
+            delay_lines[line].push(slots[slot].as_slot());
+        }
+    }
+}
+
+/// ノードの処理順序とバッファ割り当てをコンパイルしたもの
+#[derive(Debug)]
+pub struct ExecutionPlan {
+    /// Sorted by level
+    steps: Vec<PlanStep>,
+    /// Step ranges that can run at the same time
+    levels: Vec<Range<usize>>,
+    /// Reused for every parallel level (capacity of the widest level)
+    jobs: Vec<NodeJob>,
+    /// Latest output of every node port. Feedback edges read a slot before its
+    /// source runs in the current block, so they see the previous block.
+    slots: Vec<PortSignal>,
+    slot_index: HashMap<(Uuid, String), usize>,
+    delay_lines: Vec<DelayLine>,
+    /// Delay lines of feedback edges, read ahead at the start of every sub-block
+    feedback_lines: Vec<usize>,
+    /// Length of the feedback delay lines (at most `max_block_size`)
+    feedback_delay: usize,
+    max_block_size: usize,
+    /// Total latency of the patch in samples
+    latency: u32,
+    /// Set when a node reports a different latency than compiled for
+    latency_changed: bool,
+}
+
+impl ExecutionPlan {
+    /// Compile `topology` for blocks of up to `max_block_size` samples
+    pub fn compile(topology: &GraphTopology, max_block_size: usize) -> Self {
+        let max_block_size = max_block_size.max(1);
+        let feedback_delay = topology.feedback_delay().map_or(max_block_size, |delay| delay.clamp(1, max_block_size));
+        let order = topology.processing_order();
+
+        // One slot per output port
+        let mut slots = Vec::new();
+        let mut slot_index = HashMap::new();
+        let mut slot_types = Vec::new();
+        for info in order.iter().filter_map(|&node_id| topology.node_info(node_id)) {
+            for port in &info.output_ports {
+                slot_index.insert((info.id, port.name.clone()), slots.len());
+                slots.push(PortSignal::silence(port.port_type, max_block_size));
+                slot_types.push(port.port_type);
+            }
+        }
+
+        // Signals arriving earlier than the slowest path into a node are delayed
+        let arrival = topology.input_latencies();
+        let mut delay_lines: Vec<DelayLine> = Vec::new();
+        let mut delay_index: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut step_index: HashMap<Uuid, usize> = HashMap::new();
+        // Feedback line per source slot, fed by a step compiled later
+        let mut feedback_index: HashMap<usize, usize> = HashMap::new();
+        let mut feedback_sources: Vec<(Uuid, usize, usize)> = Vec::new();
+
+        let mut steps: Vec<PlanStep> = Vec::with_capacity(order.len());
+        for info in order.iter().filter_map(|&node_id| topology.node_info(node_id)) {
+            let bypass_ports = GraphTopology::bypass_ports(info).filter(|_| info.supports_bypass);
+            let mut bypass_input = None;
+            let mut bypass_output = None;
+
+            let mut outputs = OutputBuffers::new();
+            let mut output_routes = Vec::with_capacity(info.output_ports.len());
+            let mut main_output = None;
+            for port in &info.output_ports {
+                let buffer = outputs.allocate_slot(port.name.clone(), port.port_type, max_block_size);
+                output_routes.push((buffer, slot_index[&(info.id, port.name.clone())]));
+                if bypass_ports.is_some_and(|(_, output)| output.name == port.name) {
+                    bypass_output = Some(buffer);
+                }
+
+                // Output nodes send their stereo output to the device, falling back to the mono mix
+                if info.node_type == "output" &&
+                   (port.name == "audio_out" || (port.name == "mixed_output" && main_output.is_none())) {
+                    main_output = Some(buffer);
+                }
+            }
+
+            // Only connected inputs get a buffer, so nodes can still tell them apart
+            let mut inputs = InputBuffers::new();
+            let mut input_routes = Vec::new();
+            for port in &info.input_ports {
+                let mut sources = Vec::new();
+                for conn in topology.connections().iter()
+                    .filter(|conn| conn.target_node == info.id && conn.target_port == port.name) {
+                    let Some(&slot) = slot_index.get(&(conn.source_node, conn.source_port.clone())) else {
+                        continue;
+                    };
+
+                    // Feedback edges go through a fixed delay and are not compensated
+                    if conn.feedback {
+                        let line = *feedback_index.entry(slot).or_insert_with(|| {
+                            delay_lines.push(DelayLine::new(feedback_delay, slot_types[slot], max_block_size));
+                            feedback_sources.push((conn.source_node, slot, delay_lines.len() - 1));
+                            delay_lines.len() - 1
+                        });
+                        sources.push(SignalSource::Delayed(line));
+                        continue;
+                    }
+
+                    let ready = topology.node_info(conn.source_node)
+                        .map(|source| arrival.get(&source.id).copied().unwrap_or(0) + source.latency_samples)
+                        .unwrap_or(0);
+                    let delay = arrival.get(&info.id).copied().unwrap_or(0).saturating_sub(ready) as usize;
+                    let source_step = step_index.get(&conn.source_node).copied();
+                    match source_step {
+                        Some(source_step) if delay > 0 => {
+                            let line = *delay_index.entry((slot, delay)).or_insert_with(|| {
+                                delay_lines.push(DelayLine::new(delay, slot_types[slot], max_block_size));
+                                steps[source_step].delays.push((slot, delay_lines.len() - 1));
+                                delay_lines.len() - 1
+                            });
+                            sources.push(SignalSource::Delayed(line));
+                        }
+                        _ => sources.push(SignalSource::Slot(slot)),
+                    }
+                }
+
+                if port.fan_in == FanIn::Single {
+                    sources.truncate(1);
+                }
+                if sources.is_empty() {
+                    continue;
+                }
+
+                let gain = match port.fan_in {
+                    FanIn::Average => 1.0 / sources.len() as f32,
+                    FanIn::Sum | FanIn::Single => 1.0,
+                };
+                let target = inputs.allocate_slot(port.name.clone(), port.port_type, max_block_size);
+                if bypass_ports.is_some_and(|(input, _)| input.name == port.name) {
+                    bypass_input = Some(target);
+                }
+                input_routes.push(InputRoute { target, sources, gain });
+            }
+
+            let bypass = bypass_ports.zip(bypass_output).map(|((input, _), output)| {
+                let engaged = topology.is_bypassed(info.id);
+                Bypass {
+                    input: bypass_input,
+                    output,
+                    engaged,
+                    mix: if engaged { 1.0 } else { 0.0 },
+                    dry: DelayLine::new(info.latency_samples as usize, input.port_type, max_block_size),
+                }
+            });
+
+            let mut context = ProcessContext::new(inputs, outputs, 44100.0, max_block_size);
+            context.events = EventList::with_capacity(EVENT_CAPACITY);
+            let scratch = context.sub_block_context();
+
+            step_index.insert(info.id, steps.len());
+            steps.push(PlanStep {
+                node_id: info.id,
+                context,
+                scratch,
+                inputs: input_routes,
+                outputs: output_routes,
+                main_output,
+                route: topology.output_route(info.id).unwrap_or_default(),
+                delays: Vec::new(),
+                latency: info.latency_samples,
+                current_latency: info.latency_samples,
+                reported_latency: info.latency_samples,
+                bypass,
+            });
+        }
+
+        for &(source_node, slot, line) in &feedback_sources {
+            if let Some(&source_step) = step_index.get(&source_node) {
+                steps[source_step].delays.push((slot, line));
+            }
+        }
+        let feedback_lines = feedback_sources.iter().map(|&(_, _, line)| line).collect();
+
+        // Level = longest chain of non-feedback connections leading to the node
+        let mut level_of: HashMap<Uuid, usize> = HashMap::new();
+        for &node_id in order {
+            let level = topology.connections().iter()
+                .filter(|conn| conn.target_node == node_id && !conn.feedback)
+                .filter_map(|conn| level_of.get(&conn.source_node))
+                .map(|level| level + 1)
+                .max()
+                .unwrap_or(0);
+            level_of.insert(node_id, level);
+        }
+        steps.sort_by_key(|step| level_of[&step.node_id]);
+
+        let mut levels: Vec<Range<usize>> = Vec::new();
+        for (index, step) in steps.iter().enumerate() {
+            match levels.last_mut() {
+                Some(range) if level_of[&steps[range.start].node_id] == level_of[&step.node_id] => {
+                    range.end = index + 1;
+                }
+                _ => levels.push(index..index + 1),
+            }
+        }
+        let widest = levels.iter().map(|range| range.len()).max().unwrap_or(0);
+
+        Self {
+            steps,
+            levels,
+            jobs: Vec::with_capacity(widest),
+            slots,
+            slot_index,
+            delay_lines,
+            feedback_lines,
+            feedback_delay,
+            max_block_size,
+            latency: topology.total_latency(),
+            latency_changed: false,
+        }
+    }
+
+    /// Total latency of the patch in samples, as compiled
+    pub fn latency(&self) -> u32 {
+        self.latency
+    }
+
+    /// Whether a node reported a latency other than the one compiled for.
+    /// The plan keeps running with the old compensation until it is rebuilt.
+    pub fn latency_changed(&self) -> bool {
+        self.latency_changed
+    }
+
+    /// Number of steps that can run at the same time, per level
+    pub fn level_widths(&self) -> impl Iterator<Item = usize> + '_ {
+        self.levels.iter().map(|range| range.len())
+    }
+
+    /// Switch the bypass of a node; it crossfades over the following blocks
+    pub(crate) fn set_bypass(&mut self, node_id: Uuid, bypass: bool) {
+        let state = self.steps.iter_mut()
+            .find(|step| step.node_id == node_id)
+            .and_then(|step| step.bypass.as_mut());
+        if let Some(state) = state {
+            state.engaged = bypass;
+        }
+    }
+
+    /// Send an output node to other device channels from the next block on
+    pub(crate) fn set_output_route(&mut self, node_id: Uuid, route: OutputRoute) {
+        if let Some(step) = self.steps.iter_mut().find(|step| step.node_id == node_id) {
+            step.route = route;
+        }
+    }
+
+    /// Largest block the plan can run without recompiling
+    pub fn max_block_size(&self) -> usize {
+        self.max_block_size
+    }
+
+    /// Largest block `run` takes: the feedback delay if there are feedback edges
+    pub fn sub_block_size(&self) -> usize {
+        if self.feedback_lines.is_empty() {
+            self.max_block_size
+        } else {
+            self.feedback_delay
+        }
+    }
+
+    /// Mono or CV output of a node port from the last processed block
+    pub fn output(&self, node_id: Uuid, port: &str) -> Option<&[f32]> {
+        match &self.slots[*self.slot_index.get(&(node_id, port.to_string()))?] {
+            PortSignal::Mono(data) => Some(data),
+            PortSignal::Stereo(_) | PortSignal::Poly(_) => None,
+        }
+    }
+
+    /// Start a block of `frames` samples at sample `position`: set the
+    /// timestamps and transport, and move the events due in the block from
+    /// `pending` (sorted by time) to their nodes. Events that are already late
+    /// land on the first sample; events for nodes without a step are dropped.
+    pub(crate) fn begin_block(&mut self, position: u64, frames: usize, pending: &mut Vec<ScheduledEvent>,
+                              transport: &TransportInfo) {
+        for step in &mut self.steps {
+            step.context.timestamp = position;
+            step.context.bpm = transport.tempo;
+            step.context.transport = *transport;
+            step.context.events.clear();
+        }
+
+        let end = position + frames as u64;
+        let due = pending.partition_point(|event| event.time < end);
+        for event in pending.drain(..due) {
+            if let Some(step) = self.steps.iter_mut().find(|step| step.node_id == event.node_id) {
+                let offset = event.time.saturating_sub(position) as usize;
+                step.context.events.push(offset, event.kind);
+            }
+        }
+    }
+
+    /// Take the events of the last block out of the steps, so they can be
+    /// freed somewhere other than the audio thread
+    pub(crate) fn drain_events(&mut self, mut spent: impl FnMut(NodeEvent)) {
+        for step in &mut self.steps {
+            step.context.events.drain().for_each(&mut spent);
+        }
+    }
+
+    /// Hand out every node latency that changed since the last call, e.g. to
+    /// recompile the topology on the control thread. Does not allocate.
+    pub(crate) fn drain_latency_changes(&mut self, mut changed: impl FnMut(Uuid, u32)) {
+        for step in self.steps.iter_mut().filter(|step| step.current_latency != step.reported_latency) {
+            step.reported_latency = step.current_latency;
+            changed(step.node_id, step.current_latency);
+        }
+    }
+
+    /// Run one block covering `block` of the device `channels` (at most `sub_block_size` samples).
+    ///
+    /// Nodes missing from `nodes` are skipped. Output nodes are mixed into the
+    /// channels of their `OutputRoute`, so the block must be zeroed by the
+    /// caller; channels no output node is routed to stay silent.
+    /// With a `pool`, levels with more than one node are processed in parallel.
+    pub(crate) fn run<C: AsMut<[f32]>>(&mut self, nodes: &mut HashMap<Uuid, Box<dyn AudioNode>>,
+                                       mut pool: Option<&mut WorkerPool>, channels: &mut [C],
+                                       block: Range<usize>, sample_rate: f32) -> Result<(), ProcessingError> {
+        let frames = block.len().min(self.sub_block_size());
+        let block = block.start..block.start + frames;
+
+        // Feedback sources run later in the block; their lines only hold earlier samples
+        for &line in &self.feedback_lines {
+            self.delay_lines[line].read_ahead(frames);
+        }
+
+        for level in &self.levels {
+            let steps = &mut self.steps[level.clone()];
+            for step in steps.iter_mut() {
+                step.prepare(&self.slots, &self.delay_lines, frames, sample_rate);
+            }
+
+            match pool.as_deref_mut() {
+                Some(pool) if steps.len() > 1 => {
+                    for step in steps.iter_mut() {
+                        if let Some(node) = nodes.get_mut(&step.node_id) {
+                            self.latency_changed |= step.observe_latency(node.as_ref());
+                            if step.needs_processing() {
+                                self.jobs.push(NodeJob::new(node.as_mut(), &mut step.context, &mut step.scratch));
+                            } else {
+                                step.context.apply_events(node.as_mut());
+                            }
+                        }
+                    }
+                    pool.execute(&mut self.jobs);
+
+                    let result = self.jobs.iter_mut().try_for_each(NodeJob::take_result);
+                    self.jobs.clear();
+                    result?;
+                }
+                _ => {
+                    for step in steps.iter_mut() {
+                        if let Some(node) = nodes.get_mut(&step.node_id) {
+                            self.latency_changed |= step.observe_latency(node.as_ref());
+                            if step.needs_processing() {
+                                process_node_caught(node.as_mut(), &mut step.context, &mut step.scratch)?;
+                            } else {
+                                step.context.apply_events(node.as_mut());
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Publish in step order so the result does not depend on scheduling
+            for step in steps.iter_mut() {
+                step.publish(&mut self.slots, &mut self.delay_lines);
+
+                if let Some(buffer) = step.main_output {
+                    let (main_left, main_right) = match step.context.outputs.slot(buffer) {
+                        SlotRef::Stereo(stereo) => (stereo.left.as_slice(), stereo.right.as_slice()),
+                        SlotRef::Mono(mixed) => (mixed, mixed),
+                        // Output nodes have no poly ports
+                        SlotRef::Poly(_) => continue,
+                    };
+                    // Mono routes only carry the left side
+                    let sides = [main_left, main_right];
+                    for (channel, side) in step.route.channel_range().zip(sides) {
+                        let Some(channel) = channels.get_mut(channel) else {
+                            break;
+                        };
+                        let target = &mut channel.as_mut()[block.clone()];
+                        target.iter_mut().zip(&side[..frames]).for_each(|(out, s)| *out += s);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::{OscillatorNode, OutputNode};
+
+    #[test]
+    fn test_mono_stereo_adaptation() {
+        let mut stereo = StereoBuffer::new(2);
+        PortSignal::Mono(vec![1.0, 2.0]).mix_into(&mut SlotMut::Stereo(&mut stereo));
+        assert_eq!(stereo.right, vec![1.0, 2.0]);
+
+        let mut mono = vec![0.0; 2];
+        PortSignal::Stereo(StereoBuffer { left: vec![1.0, 0.0], right: vec![0.0, 0.0] })
+            .mix_into(&mut SlotMut::Mono(&mut mono));
+        assert_eq!(mono, vec![0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_run_reuses_preallocated_buffers() {
+        let mut nodes: HashMap<Uuid, Box<dyn AudioNode>> = HashMap::new();
+        let mut topology = GraphTopology::new();
+        let osc = OscillatorNode::new(44100.0, "osc".to_string());
+        let out = OutputNode::new(44100.0, "out".to_string());
+        let (osc_id, out_id) = (osc.node_info().id, out.node_info().id);
+        topology.add_node(osc.node_info().clone()).unwrap();
+        topology.add_node(out.node_info().clone()).unwrap();
+        topology.connect(&osc_id.to_string(), "audio_out", &out_id.to_string(), "audio_in_l").unwrap();
+        nodes.insert(osc_id, Box::new(osc));
+        nodes.insert(out_id, Box::new(out));
+
+        let mut plan = ExecutionPlan::compile(&topology, 64);
+        let slot_pointers = |plan: &ExecutionPlan| -> Vec<*const f32> {
+            plan.slots.iter().map(|slot| match slot {
+                PortSignal::Mono(data) => data.as_ptr(),
+                PortSignal::Stereo(data) => data.left.as_ptr(),
+                PortSignal::Poly(data) => data.channels[0].as_ptr(),
+            }).collect()
+        };
+        let before = slot_pointers(&plan);
+
+        let mut channels = [vec![0.0; 64], vec![0.0; 64]];
+        plan.run(&mut nodes, None, &mut channels, 0..64, 44100.0).unwrap();
+        plan.run(&mut nodes, None, &mut channels, 0..16, 44100.0).unwrap();
+        let [left, right] = channels;
+
+        // Every buffer stays where it was allocated at compile time
+        assert_eq!(slot_pointers(&plan), before);
+        assert_eq!(plan.output(osc_id, "audio_out").unwrap().len(), 16);
+        assert!(left.iter().any(|&s| s.abs() > 0.01));
+        assert!(right.iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,397 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Graph topology - connections and processing order without node instances.
+//!
+//! A `GraphTopology` can be edited, sorted and compiled away from the audio
+//! thread and then swapped into a running `ProcessingGraph` in one pointer swap.
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use super::{classify_feedback_connections, Connection, ExecutionPlan, FanIn, OutputRoute, PortType};
+use crate::processing::{AudioNode, NodeInfo, PortInfo};
+
+/// Registered node and its engine-side state
+#[derive(Debug, Clone)]
+struct NodeEntry {
+    info: NodeInfo,
+    bypassed: bool,
+    /// Device channels of an output node
+    output_route: OutputRoute,
+}
+
+/// ノード接続と処理順序
+#[derive(Debug, Default)]
+pub struct GraphTopology {
+    nodes: HashMap<Uuid, NodeEntry>,
+    connections: Vec<Connection>,
+    processing_order: Vec<Uuid>,
+    /// Delay of feedback edges in samples; `None` for the plan's block size
+    feedback_delay: Option<usize>,
+    /// Compiled form of the above; dropped by every edit
+    plan: Option<Box<ExecutionPlan>>,
+}
+
+// The plan owns live buffers, so a clone starts without one
+impl Clone for GraphTopology {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            connections: self.connections.clone(),
+            processing_order: self.processing_order.clone(),
+            feedback_delay: self.feedback_delay,
+            plan: None,
+        }
+    }
+}
+
+impl GraphTopology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a node so it can be connected and gets a place in the processing order
+    pub fn add_node(&mut self, info: NodeInfo) -> Result<(), String> {
+        self.nodes.insert(info.id, NodeEntry { info, bypassed: false, output_route: OutputRoute::default() });
+        self.update_processing_order()
+    }
+
+    /// Register a node instance (see `node_entry`)
+    pub fn add_audio_node(&mut self, node: &dyn AudioNode) -> Result<(), String> {
+        self.add_node(Self::node_entry(node))
+    }
+
+    /// Topology entry for a node instance - its info with the latency taken from `AudioNode::latency`
+    pub fn node_entry(node: &dyn AudioNode) -> NodeInfo {
+        let mut info = node.node_info().clone();
+        info.latency_samples = node.latency();
+        info
+    }
+
+    /// Update the latency of a node. Returns true if it changed.
+    pub fn set_node_latency(&mut self, node_id: Uuid, samples: u32) -> bool {
+        match self.nodes.get_mut(&node_id) {
+            Some(entry) if entry.info.latency_samples != samples => {
+                entry.info.latency_samples = samples;
+                self.plan = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Remove a node and every connection involving it
+    pub fn remove_node(&mut self, node_id: Uuid) -> Result<(), String> {
+        self.connections.retain(|conn| {
+            conn.source_node != node_id && conn.target_node != node_id
+        });
+        self.nodes.remove(&node_id);
+        self.update_processing_order()
+    }
+
+    /// Connect two nodes by ID
+    pub fn connect(&mut self, source_id: &str, source_port: &str,
+                   target_id: &str, target_port: &str) -> Result<(), String> {
+        let source_uuid = Uuid::parse_str(source_id)
+            .map_err(|e| format!("Invalid source UUID: {}", e))?;
+        let target_uuid = Uuid::parse_str(target_id)
+            .map_err(|e| format!("Invalid target UUID: {}", e))?;
+
+        // Validate nodes exist
+        if !self.nodes.contains_key(&source_uuid) {
+            return Err("Source node not found".to_string());
+        }
+        let target_fan_in = match self.nodes.get(&target_uuid) {
+            Some(entry) => entry.info.input_ports.iter()
+                .find(|p| p.name == target_port)
+                .map(|p| p.fan_in)
+                .unwrap_or_default(),
+            None => return Err("Target node not found".to_string()),
+        };
+
+        // Reject duplicate cables
+        if self.connections.iter().any(|conn| {
+            conn.source_node == source_uuid &&
+            conn.source_port == source_port &&
+            conn.target_node == target_uuid &&
+            conn.target_port == target_port
+        }) {
+            return Err("Connection already exists".to_string());
+        }
+
+        // Several cables into one port are summed unless the port is single-source
+        if target_fan_in == FanIn::Single && self.connections.iter().any(|conn| {
+            conn.target_node == target_uuid && conn.target_port == target_port
+        }) {
+            return Err("Target port already connected".to_string());
+        }
+
+        // Cycle-closing connections (including self-connections) are allowed;
+        // they are marked as feedback edges by update_processing_order
+        self.connections.push(Connection {
+            source_node: source_uuid,
+            source_port: source_port.to_string(),
+            target_node: target_uuid,
+            target_port: target_port.to_string(),
+            feedback: false,
+        });
+        self.update_processing_order()
+    }
+
+    /// Disconnect two nodes by ID
+    pub fn disconnect(&mut self, source_id: &str, source_port: &str,
+                      target_id: &str, target_port: &str) -> Result<(), String> {
+        let source_uuid = Uuid::parse_str(source_id)
+            .map_err(|e| format!("Invalid source UUID: {}", e))?;
+        let target_uuid = Uuid::parse_str(target_id)
+            .map_err(|e| format!("Invalid target UUID: {}", e))?;
+
+        let initial_len = self.connections.len();
+        self.connections.retain(|conn| {
+            !(conn.source_node == source_uuid &&
+              conn.source_port == source_port &&
+              conn.target_node == target_uuid &&
+              conn.target_port == target_port)
+        });
+
+        if self.connections.len() != initial_len {
+            self.update_processing_order()?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove all nodes and connections
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.connections.clear();
+        self.processing_order.clear();
+        self.plan = None;
+    }
+
+    /// Delay feedback edges by `samples` (at most the compiled block size).
+    /// `None` delays them by the block size the plan is compiled for.
+    pub fn set_feedback_delay(&mut self, samples: Option<usize>) {
+        self.feedback_delay = samples;
+        self.plan = None;
+    }
+
+    pub fn feedback_delay(&self) -> Option<usize> {
+        self.feedback_delay
+    }
+
+    /// Compile the execution plan for blocks of up to `max_block_size` samples.
+    ///
+    /// Allocates every buffer the plan needs, so do it before the topology is
+    /// handed to the audio thread.
+    pub fn compile(&mut self, max_block_size: usize) {
+        self.plan = Some(Box::new(ExecutionPlan::compile(self, max_block_size)));
+    }
+
+    /// Compiled plan, if the topology has not been edited since `compile`
+    pub fn plan(&self) -> Option<&ExecutionPlan> {
+        self.plan.as_deref()
+    }
+
+    pub(crate) fn plan_mut(&mut self) -> Option<&mut ExecutionPlan> {
+        self.plan.as_deref_mut()
+    }
+
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// Node IDs in dependency order
+    pub fn processing_order(&self) -> &[Uuid] {
+        &self.processing_order
+    }
+
+    pub fn contains_node(&self, node_id: Uuid) -> bool {
+        self.nodes.contains_key(&node_id)
+    }
+
+    pub fn node_info(&self, node_id: Uuid) -> Option<&NodeInfo> {
+        self.nodes.get(&node_id).map(|entry| &entry.info)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeInfo> {
+        self.nodes.values().map(|entry| &entry.info)
+    }
+
+    /// Bypass a node: its primary audio input is crossfaded to its primary output.
+    ///
+    /// A compiled plan is updated in place rather than dropped, so this is
+    /// cheap enough for the audio thread and the crossfade stays continuous.
+    pub fn set_bypass(&mut self, node_id: Uuid, bypass: bool) -> Result<(), String> {
+        self.check_bypass(node_id)?;
+        if let Some(entry) = self.nodes.get_mut(&node_id) {
+            entry.bypassed = bypass;
+        }
+        if let Some(plan) = self.plan.as_deref_mut() {
+            plan.set_bypass(node_id, bypass);
+        }
+        Ok(())
+    }
+
+    /// Whether `set_bypass` would accept the node
+    pub fn check_bypass(&self, node_id: Uuid) -> Result<(), String> {
+        let info = self.node_info(node_id).ok_or("Node not found")?;
+        if !info.supports_bypass {
+            return Err(format!("Node {} does not support bypass", info.name));
+        }
+        if Self::bypass_ports(info).is_none() {
+            return Err(format!("Node {} has no audio input and output to bypass", info.name));
+        }
+        Ok(())
+    }
+
+    pub fn is_bypassed(&self, node_id: Uuid) -> bool {
+        self.nodes.get(&node_id).is_some_and(|entry| entry.bypassed)
+    }
+
+    /// Send an output node to other device channels. Like a bypass, a
+    /// compiled plan is updated in place.
+    pub fn set_output_route(&mut self, node_id: Uuid, route: OutputRoute) -> Result<(), String> {
+        self.check_output_route(node_id, route)?;
+        if let Some(entry) = self.nodes.get_mut(&node_id) {
+            entry.output_route = route;
+        }
+        if let Some(plan) = self.plan.as_deref_mut() {
+            plan.set_output_route(node_id, route);
+        }
+        Ok(())
+    }
+
+    /// Whether `set_output_route` would accept the route
+    pub fn check_output_route(&self, node_id: Uuid, route: OutputRoute) -> Result<(), String> {
+        let info = self.node_info(node_id).ok_or("Node not found")?;
+        if info.node_type != "output" {
+            return Err(format!("Node {} is not an output node", info.name));
+        }
+        route.validate()
+    }
+
+    /// Device channels of an output node; `None` for other nodes
+    pub fn output_route(&self, node_id: Uuid) -> Option<OutputRoute> {
+        self.nodes.get(&node_id)
+            .filter(|entry| entry.info.node_type == "output")
+            .map(|entry| entry.output_route)
+    }
+
+    /// Device channels needed to hear every output node
+    pub fn output_channel_count(&self) -> usize {
+        self.nodes.values()
+            .filter(|entry| entry.info.node_type == "output")
+            .map(|entry| entry.output_route.channel_range().end)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Primary input and output that a bypass connects: `audio_in`/`audio_out`,
+    /// otherwise the first audio port of each side. Both must have the same type.
+    pub fn bypass_ports(info: &NodeInfo) -> Option<(&PortInfo, &PortInfo)> {
+        let primary = |ports: &[PortInfo], name: &str| -> Option<usize> {
+            ports.iter().position(|p| p.name == name)
+                .or_else(|| ports.iter().position(|p| matches!(p.port_type, PortType::AudioMono | PortType::AudioStereo)))
+        };
+        let input = &info.input_ports[primary(&info.input_ports, "audio_in")?];
+        let output = &info.output_ports[primary(&info.output_ports, "audio_out")?];
+        let audio = matches!(input.port_type, PortType::AudioMono | PortType::AudioStereo);
+        (audio && input.port_type == output.port_type).then_some((input, output))
+    }
+
+    /// Latency at which each node's inputs are aligned: the slowest path of
+    /// non-feedback connections leading to it, summing node latencies
+    pub fn input_latencies(&self) -> HashMap<Uuid, u32> {
+        let mut arrival: HashMap<Uuid, u32> = HashMap::new();
+        for &node_id in &self.processing_order {
+            let latency = self.connections.iter()
+                .filter(|conn| conn.target_node == node_id && !conn.feedback)
+                .filter_map(|conn| {
+                    let source = self.node_info(conn.source_node)?;
+                    Some(arrival.get(&source.id)? + source.latency_samples)
+                })
+                .max()
+                .unwrap_or(0);
+            arrival.insert(node_id, latency);
+        }
+        arrival
+    }
+
+    /// Total latency of the patch in samples - the slowest path through an output node
+    pub fn total_latency(&self) -> u32 {
+        let arrival = self.input_latencies();
+        self.nodes()
+            .filter(|info| info.node_type == "output")
+            .filter_map(|info| Some(arrival.get(&info.id)? + info.latency_samples))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Update processing order using topological sort
+    fn update_processing_order(&mut self) -> Result<(), String> {
+        self.plan = None;
+        classify_feedback_connections(&mut self.connections);
+
+        self.processing_order.clear();
+        let mut visited = HashSet::new();
+        let mut temp_visited = HashSet::new();
+
+        let node_ids: Vec<Uuid> = self.nodes.keys().copied().collect();
+        for node_id in node_ids {
+            if !visited.contains(&node_id) && !self.visit_node(node_id, &mut visited, &mut temp_visited) {
+                self.processing_order.clear();
+                return Err("Failed to create processing order due to cycles".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Visit node for topological sort
+    fn visit_node(&mut self, node_id: Uuid,
+                  visited: &mut HashSet<Uuid>,
+                  temp_visited: &mut HashSet<Uuid>) -> bool {
+        if temp_visited.contains(&node_id) {
+            return false; // Cycle detected
+        }
+        if visited.contains(&node_id) {
+            return true; // Already processed
+        }
+
+        temp_visited.insert(node_id);
+
+        // Visit all dependencies (nodes that feed into this node, except feedback edges)
+        let dependencies: Vec<Uuid> = self.connections.iter()
+            .filter(|conn| conn.target_node == node_id && !conn.feedback)
+            .map(|conn| conn.source_node)
+            .collect();
+
+        for dep_node in dependencies {
+            if !self.visit_node(dep_node, visited, temp_visited) {
+                temp_visited.remove(&node_id);
+                return false;
+            }
+        }
+
+        temp_visited.remove(&node_id);
+        visited.insert(node_id);
+        self.processing_order.push(node_id);
+        true
+    }
+}
@@ -19,17 +19,20 @@
 pub mod audio;
 pub mod graph;
 pub mod nodes;
+pub mod patch;
 pub mod tauri_commands;
 pub mod parameters;
 pub mod processing;
 pub mod errors;
 pub mod plugin;
+pub mod transport;
 
 pub use audio::AudioEngine;
-pub use graph::{AudioGraph, Node, Port, PortType, Connection, ProcessingGraph};
+pub use graph::{AudioGraph, Node, Port, PortType, FanIn, Connection, ProcessingGraph, ScheduledEvent};
 // Node exports moved to audio::AudioEngine for unified architecture
 pub use parameters::{Parameterizable, ParameterDescriptor, ParameterError};
-pub use processing::{ProcessContext, ProcessingError, NodeInfo, NodeCategory, InputPorts, OutputPorts};
+pub use processing::{ProcessContext, ProcessingError, NodeInfo, NodeCategory, InputPorts, OutputPorts, StereoBuffer, EventKind, EventList};
+pub use transport::{Transport, TransportCommand, TransportInfo, TimeSignature};
 pub use errors::{AudioEngineError, AudioEngineResult, Logger, ConsoleLogger, LogLevel};
 pub use plugin::{PluginManager, PluginError, PluginResult, PluginConfig, PluginStats};
 
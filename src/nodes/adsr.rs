@@ -18,8 +18,8 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, EventKind, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
 
@@ -59,6 +59,7 @@ pub struct ADSRNode {
     gate_was_high: bool,     // Previous gate state for edge detection
     release_start_level: f32, // Level when release phase started
     velocity: f32,           // Current note velocity (0.0 - 1.0)
+    event_gate: Option<f32>, // Velocity of a gate held open by GateOn/NoteOn events
     
     sample_rate: f32,
 }
@@ -108,22 +109,22 @@ impl ADSRNode {
         let attack_param = ModulatableParameter::new(
             BasicParameter::new("attack", 0.001, 10.0, 0.1),
             0.8  // 80% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT); // ステージ時間はブロック単位で十分
 
         let decay_param = ModulatableParameter::new(
             BasicParameter::new("decay", 0.001, 10.0, 0.3),
             0.8  // 80% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT);
 
         let sustain_param = ModulatableParameter::new(
             BasicParameter::new("sustain", 0.0, 1.0, 0.7),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let release_param = ModulatableParameter::new(
             BasicParameter::new("release", 0.001, 10.0, 0.5),
             0.8  // 80% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -146,6 +147,7 @@ impl ADSRNode {
             gate_was_high: false,
             release_start_level: 0.0,
             velocity: 1.0,
+            event_gate: None,
             
             sample_rate,
         }
@@ -333,12 +335,12 @@ impl AudioNode for ADSRNode {
             return Ok(());
         }
 
-        // Get input signals
-        let gate_input = ctx.inputs.get_audio("gate_in").unwrap_or(&[]);
-        let velocity_input = ctx.inputs.get_audio("velocity_in").unwrap_or(&[]);
+        // Get input signals (CV ports, audio buffers accepted as well)
+        let gate_input = ctx.inputs.get_cv("gate_in").or_else(|| ctx.inputs.get_audio("gate_in")).unwrap_or(&[]);
+        let velocity_input = ctx.inputs.get_cv("velocity_in").or_else(|| ctx.inputs.get_audio("velocity_in")).unwrap_or(&[]);
         
-        if gate_input.is_empty() {
-            // No gate input - output zero
+        if gate_input.is_empty() && self.event_gate.is_none() && self.state == EnvelopeState::Idle {
+            // No gate input or event - output zero
             if let Some(cv_output) = ctx.outputs.get_cv_mut("cv_out") {
                 cv_output.fill(0.0);
             }
@@ -351,17 +353,11 @@ impl AudioNode for ADSRNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let attack_cv = ctx.inputs.get_cv_value("attack_cv");
-        let decay_cv = ctx.inputs.get_cv_value("decay_cv");
-        let sustain_cv = ctx.inputs.get_cv_value("sustain_cv");
-        let release_cv = ctx.inputs.get_cv_value("release_cv");
-
-        // Apply CV modulation
-        let effective_attack = self.attack_param.modulate(self.attack, attack_cv);
-        let effective_decay = self.decay_param.modulate(self.decay, decay_cv);
-        let effective_sustain = self.sustain_param.modulate(self.sustain, sustain_cv);
-        let effective_release = self.release_param.modulate(self.release, release_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let attack_cv = ctx.inputs.get_cv("attack_cv");
+        let decay_cv = ctx.inputs.get_cv("decay_cv");
+        let sustain_cv = ctx.inputs.get_cv("sustain_cv");
+        let release_cv = ctx.inputs.get_cv("release_cv");
 
         // Get the buffer size from the first output
         let buffer_size = ctx.outputs.get_cv("cv_out")
@@ -382,19 +378,27 @@ impl AudioNode for ADSRNode {
             } else { 
                 0.0 
             };
+            // Events hold the gate open on top of the input
+            let gate_value = if self.event_gate.is_some() { gate_value.max(5.0) } else { gate_value };
             let gate_high = gate_value > 2.5; // Eurorack standard gate threshold
 
             // Get velocity (normalize from 10V scale)
             let velocity_value = if i < velocity_input.len() { 
                 velocity_input[i] 
             } else { 
-                10.0 // Default full velocity
+                self.event_gate.unwrap_or(1.0) * 10.0 // Event velocity, or full velocity
             };
             let velocity = (velocity_value / 10.0).clamp(0.0, 1.0);
 
             // Process gate signal and update envelope state
             self.process_gate(gate_high, velocity);
 
+            // Apply CV modulation per sample
+            let effective_attack = self.attack_param.modulate_smoothed(self.attack, attack_cv, i, self.sample_rate);
+            let effective_decay = self.decay_param.modulate_smoothed(self.decay, decay_cv, i, self.sample_rate);
+            let effective_sustain = self.sustain_param.modulate_smoothed(self.sustain, sustain_cv, i, self.sample_rate);
+            let effective_release = self.release_param.modulate_smoothed(self.release, release_cv, i, self.sample_rate);
+
             // Calculate and output envelope level
             let (envelope_level, end_of_cycle) = self.calculate_envelope_level(
                 effective_attack, effective_decay, effective_sustain, effective_release
@@ -438,6 +442,10 @@ impl AudioNode for ADSRNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset envelope state
         self.state = EnvelopeState::Idle;
@@ -446,6 +454,22 @@ impl AudioNode for ADSRNode {
         self.gate_was_high = false;
         self.release_start_level = 0.0;
         self.velocity = 1.0;
+        self.event_gate = None;
+    }
+
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn handle_event(&mut self, event: &EventKind) {
+        match event {
+            EventKind::GateOn => self.event_gate = Some(1.0),
+            EventKind::NoteOn { velocity, .. } => self.event_gate = Some(velocity.clamp(0.0, 1.0)),
+            EventKind::GateOff | EventKind::NoteOff { .. } => self.event_gate = None,
+            EventKind::Parameter { name, value } => {
+                let _ = self.set_parameter(name, *value);
+            }
+        }
     }
 
     fn latency(&self) -> u32 {
@@ -461,18 +485,6 @@ impl AudioNode for ADSRNode {
     }
 }
 
-impl ADSRNode {
-    /// Manually trigger the ADSR envelope (for testing/manual triggering)
-    pub fn trigger_gate(&mut self) {
-        // Trigger attack phase
-        self.state = EnvelopeState::Attack;
-        self.stage_progress = 0.0;
-        self.velocity = 1.0; // Full velocity
-        self.gate_was_high = false; // Reset gate state so it triggers properly
-        println!("🎹 ADSR gate manually triggered: {}", self.node_info.name);
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -522,6 +534,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -536,6 +550,27 @@ mod tests {
         assert_eq!(adsr.get_state(), EnvelopeState::Attack);
     }
 
+    #[test]
+    fn test_gate_events_without_gate_input() {
+        let mut adsr = ADSRNode::new(44100.0, "test".to_string());
+        adsr.set_parameter("attack", 0.001).unwrap();
+        adsr.set_parameter("release", 0.001).unwrap();
+
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_cv("cv_out".to_string(), 256);
+        let mut ctx = ProcessContext::new(InputBuffers::new(), outputs, 44100.0, 256);
+
+        adsr.handle_event(&EventKind::GateOn);
+        adsr.process(&mut ctx).unwrap();
+        assert!(ctx.outputs.get_cv("cv_out").unwrap()[255] > 0.0);
+
+        // The release still plays out after the gate closes
+        adsr.handle_event(&EventKind::GateOff);
+        adsr.process(&mut ctx).unwrap();
+        assert_eq!(adsr.get_state(), EnvelopeState::Idle);
+        assert_eq!(ctx.outputs.get_cv("cv_out").unwrap()[255], 0.0);
+    }
+
     #[test]
     fn test_adsr_full_cycle() {
         let mut adsr = ADSRNode::new(44100.0, "test".to_string());
@@ -558,6 +593,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -580,6 +617,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -609,6 +648,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -637,6 +678,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -665,6 +708,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -683,6 +728,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
@@ -707,6 +754,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(adsr.process(&mut ctx).is_ok());
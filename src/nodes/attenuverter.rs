@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -79,12 +79,12 @@ impl AttenuverterNode {
         let attenuation_param = ModulatableParameter::new(
             BasicParameter::new("attenuation", -2.0, 2.0, 1.0),
             1.0  // 100% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let offset_param = ModulatableParameter::new(
             BasicParameter::new("offset", -10.0, 10.0, 0.0),
             1.0  // 100% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -204,13 +204,9 @@ impl AudioNode for AttenuverterNode {
         // Get input signals
         let signal_input = ctx.inputs.get_audio("signal_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let attenuation_cv = ctx.inputs.get_cv_value("attenuation_cv");
-        let offset_cv = ctx.inputs.get_cv_value("offset_cv");
-
-        // Apply CV modulation
-        let effective_attenuation = self.attenuation_param.modulate(self.attenuation, attenuation_cv);
-        let effective_offset = self.offset_param.modulate(self.offset, offset_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let attenuation_cv = ctx.inputs.get_cv("attenuation_cv");
+        let offset_cv = ctx.inputs.get_cv("offset_cv");
 
         // Get buffer size
         let buffer_size = ctx.outputs.get_audio("signal_out")
@@ -231,6 +227,10 @@ impl AudioNode for AttenuverterNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_attenuation = self.attenuation_param.modulate_smoothed(self.attenuation, attenuation_cv, i, self.sample_rate);
+            let effective_offset = self.offset_param.modulate_smoothed(self.offset, offset_cv, i, self.sample_rate);
+
             // Process main output
             let main_output = self.process_attenuversion(
                 input_sample, 
@@ -287,10 +287,18 @@ impl AudioNode for AttenuverterNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // No internal state to reset for attenuverter
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for attenuation/offset
     }
@@ -355,6 +363,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -389,6 +399,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -422,6 +434,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -455,6 +469,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -490,6 +506,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -521,6 +539,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
@@ -556,6 +576,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(atten.process(&mut ctx).is_ok());
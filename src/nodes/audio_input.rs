@@ -0,0 +1,260 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use uuid::Uuid;
+
+use crate::audio::input::AudioSource;
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, StereoBuffer};
+use crate::graph::PortType;
+use crate::define_parameters;
+
+/// 外部音声の入力ノード
+///
+/// Plays whatever `AudioSource` is attached: the engine attaches its capture
+/// stream when the node is added, and a `BufferSource` (file or memory) can
+/// replace it for headless use. Without a source the outputs are silent.
+pub struct AudioInputNode {
+    // Node identification
+    node_info: NodeInfo,
+
+    // Parameters
+    gain: f32,          // 0.0 ~ 4.0
+    mono: f32,          // 1.0 = L/R average on both sides
+    active: f32,
+
+    // CV Modulation parameters
+    gain_param: ModulatableParameter,
+
+    source: Option<Box<dyn AudioSource>>,
+    // Frames read from the source, sized in `prepare`
+    scratch: StereoBuffer,
+
+    sample_rate: f32,
+}
+
+impl AudioInputNode {
+    pub fn new(sample_rate: f32, name: String) -> Self {
+        let node_info = NodeInfo {
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            node_type: "audio_input".to_string(),
+            category: NodeCategory::Generator,
+            description: "External audio from the input device or a file".to_string(),
+            input_ports: vec![
+                PortInfo::new("gain_cv", PortType::CV)
+                    .with_description("Input gain modulation")
+                    .optional(),
+            ],
+            output_ports: vec![
+                PortInfo::new("audio_out_l", PortType::AudioMono),
+                PortInfo::new("audio_out_r", PortType::AudioMono),
+                PortInfo::new("audio_out", PortType::AudioStereo)
+                    .with_description("Both input channels as one stereo signal"),
+            ],
+            latency_samples: 0,
+            supports_bypass: false,
+        };
+
+        let gain_param = ModulatableParameter::new(
+            BasicParameter::new("gain", 0.0, 4.0, 1.0),
+            0.5  // 50% CV modulation range
+        ).with_smoothing(Smoothing::DEFAULT);
+
+        Self {
+            node_info,
+            gain: 1.0,
+            mono: 0.0,
+            active: 1.0,
+
+            gain_param,
+
+            source: None,
+            scratch: StereoBuffer::new(crate::graph::DEFAULT_MAX_BLOCK_SIZE),
+            sample_rate,
+        }
+    }
+
+    /// Play `source` from the next block on. Returns the previous source.
+    pub fn set_source(&mut self, mut source: Box<dyn AudioSource>) -> Option<Box<dyn AudioSource>> {
+        source.prepare(self.sample_rate, self.scratch.len());
+        self.source.replace(source)
+    }
+
+    pub fn take_source(&mut self) -> Option<Box<dyn AudioSource>> {
+        self.source.take()
+    }
+
+    pub fn has_source(&self) -> bool {
+        self.source.is_some()
+    }
+
+    pub fn source_name(&self) -> Option<String> {
+        self.source.as_ref().map(|source| source.name())
+    }
+}
+
+impl Parameterizable for AudioInputNode {
+    define_parameters! {
+        gain: BasicParameter::new("gain", 0.0, 4.0, 1.0),
+        mono: BasicParameter::new("mono", 0.0, 1.0, 0.0),
+        active: BasicParameter::new("active", 0.0, 1.0, 1.0)
+    }
+}
+
+impl AudioNode for AudioInputNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        let frames = ctx.buffer_size;
+        // Only allocates if the node was never prepared for this block size
+        if self.scratch.len() < frames {
+            self.scratch.resize(frames);
+        }
+
+        let left = &mut self.scratch.left[..frames];
+        let right = &mut self.scratch.right[..frames];
+        match self.source.as_mut() {
+            // Keep reading while inactive so a capture stream does not fall behind
+            Some(source) => source.read(left, right),
+            None => {
+                left.fill(0.0);
+                right.fill(0.0);
+            }
+        }
+
+        let active = self.active > 0.5;
+        let mono = self.mono > 0.5;
+        let gain_cv = ctx.inputs.get_cv("gain_cv");
+        for i in 0..frames {
+            let gain = if active {
+                self.gain_param.modulate_smoothed(self.gain, gain_cv, i, self.sample_rate)
+            } else {
+                0.0
+            };
+            let (l, r) = (left[i] * gain, right[i] * gain);
+            (left[i], right[i]) = if mono { ((l + r) * 0.5, (l + r) * 0.5) } else { (l, r) };
+        }
+
+        if let Some(output) = ctx.outputs.get_audio_mut("audio_out_l") {
+            output[..frames].copy_from_slice(left);
+        }
+        if let Some(output) = ctx.outputs.get_audio_mut("audio_out_r") {
+            output[..frames].copy_from_slice(right);
+        }
+        if let Some(output) = ctx.outputs.get_stereo_mut("audio_out") {
+            output.left[..frames].copy_from_slice(left);
+            output.right[..frames].copy_from_slice(right);
+        }
+
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.node_info
+    }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
+    fn reset(&mut self) {
+        self.scratch.fill(0.0);
+    }
+
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.scratch.resize(max_block_size);
+        if let Some(source) = self.source.as_mut() {
+            source.prepare(sample_rate, max_block_size);
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::input::BufferSource;
+    use crate::nodes::VCFNode;
+    use crate::processing::{InputBuffers, OutputBuffers};
+
+    fn context(frames: usize) -> ProcessContext {
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_audio("audio_out_l".to_string(), frames);
+        outputs.allocate_audio("audio_out_r".to_string(), frames);
+        outputs.allocate_stereo("audio_out".to_string(), frames);
+        ProcessContext::new(InputBuffers::new(), outputs, 44100.0, frames)
+    }
+
+    #[test]
+    fn test_plays_attached_source() {
+        let mut input = AudioInputNode::new(44100.0, "in".to_string());
+        input.prepare(44100.0, 4);
+        let mut ctx = context(4);
+
+        // No source: silence
+        input.process(&mut ctx).unwrap();
+        assert_eq!(ctx.outputs.get_audio("audio_out_l").unwrap(), [0.0; 4].as_slice());
+
+        let buffer = StereoBuffer { left: vec![0.5; 4], right: vec![-0.5; 4] };
+        assert!(input.set_source(Box::new(BufferSource::new("test", buffer, 44100.0))).is_none());
+        assert_eq!(input.source_name().as_deref(), Some("test"));
+
+        input.process(&mut ctx).unwrap();
+        assert_eq!(ctx.outputs.get_audio("audio_out_l").unwrap(), [0.5; 4].as_slice());
+        assert_eq!(ctx.outputs.get_audio("audio_out_r").unwrap(), [-0.5; 4].as_slice());
+        assert_eq!(ctx.outputs.get_stereo("audio_out").unwrap().right, vec![-0.5; 4]);
+
+        // Played to the end
+        input.process(&mut ctx).unwrap();
+        assert_eq!(ctx.outputs.get_audio("audio_out_l").unwrap(), [0.0; 4].as_slice());
+    }
+
+    #[test]
+    fn test_input_through_filter() {
+        let mut input = AudioInputNode::new(44100.0, "in".to_string());
+        input.set_parameter("mono", 1.0).unwrap();
+        input.prepare(44100.0, 256);
+
+        // A guitar stand-in: a 5 kHz square wave on the left side only
+        let left = (0..256).map(|i| if (i / 4) % 2 == 0 { 0.8 } else { -0.8 }).collect();
+        input.set_source(Box::new(BufferSource::new("square", StereoBuffer { left, right: vec![0.0; 256] }, 44100.0)));
+        let mut ctx = context(256);
+        input.process(&mut ctx).unwrap();
+        let signal = ctx.outputs.get_audio("audio_out_r").unwrap().to_vec();
+        assert_eq!(signal[0], 0.4);
+
+        let mut vcf = VCFNode::new(44100.0, "vcf".to_string());
+        vcf.set_parameter("cutoff_frequency", 200.0).unwrap();
+        let mut inputs = InputBuffers::new();
+        inputs.add_audio("audio_in".to_string(), signal.clone());
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_audio("audio_out".to_string(), 256);
+        let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 256);
+        vcf.process(&mut ctx).unwrap();
+
+        let peak = |samples: &[f32]| samples[128..].iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak(ctx.outputs.get_audio("audio_out").unwrap()) < peak(&signal) * 0.5);
+    }
+}
@@ -18,8 +18,8 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, NodeState};
 use crate::graph::PortType;
 
 /// リファクタリング済みClockDividerNode - プロ品質のクロック分周器
@@ -96,7 +96,7 @@ impl ClockDividerNode {
         let threshold_param = ModulatableParameter::new(
             BasicParameter::new("trigger_threshold", 0.1, 5.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT); // しきい値はブロック単位
 
         Self {
             node_info,
@@ -360,11 +360,8 @@ impl AudioNode for ClockDividerNode {
         let clock_input = ctx.inputs.get_audio("clock_in").unwrap_or(&[]);
         let reset_input = ctx.inputs.get_audio("reset_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let threshold_cv = ctx.inputs.get_cv_value("threshold_cv");
-
-        // Apply CV modulation
-        let effective_threshold = self.threshold_param.modulate(self.trigger_threshold, threshold_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let threshold_cv = ctx.inputs.get_cv("threshold_cv");
 
         // Get buffer size
         let buffer_size = clock_input.len();
@@ -387,6 +384,9 @@ impl AudioNode for ClockDividerNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_threshold = self.threshold_param.modulate_smoothed(self.trigger_threshold, threshold_cv, i, self.sample_rate);
+
             // Process clock division
             self.process_clock_division(clock_signal, reset_signal, effective_threshold);
 
@@ -415,6 +415,10 @@ impl AudioNode for ClockDividerNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset clock divider state
         self.reset_all_counters();
@@ -422,6 +426,25 @@ impl AudioNode for ClockDividerNode {
         self.reset_pending = false;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        // Gates still open keep their length in seconds
+        let scale = sample_rate / self.sample_rate;
+        for counter in self.gate_counters.iter_mut() {
+            *counter *= scale;
+        }
+        self.sample_rate = sample_rate;
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        serde_json::to_value(&self.div_ratios).ok()
+    }
+
+    fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+        let ratios: Vec<u32> = serde_json::from_value(state.clone())
+            .map_err(|e| format!("Invalid division ratios: {}", e))?;
+        ratios.iter().enumerate().try_for_each(|(index, &ratio)| self.set_division_ratio(index, ratio))
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for clock division
     }
@@ -495,6 +518,8 @@ mod tests {
             buffer_size: 16,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(divider.process(&mut ctx).is_ok());
@@ -545,6 +570,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(divider.process(&mut ctx).is_ok());
@@ -575,6 +602,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(divider.process(&mut ctx).is_ok());
@@ -624,6 +653,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(divider.process(&mut ctx).is_ok());
@@ -655,6 +686,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(divider.process(&mut ctx).is_ok());
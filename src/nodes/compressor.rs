@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -99,27 +99,27 @@ impl CompressorNode {
         let threshold_param = ModulatableParameter::new(
             BasicParameter::new("threshold", -60.0, 0.0, -20.0).with_unit("dB"),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let ratio_param = ModulatableParameter::new(
             BasicParameter::new("ratio", 1.0, 20.0, 4.0),
             0.6  // 60% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let attack_param = ModulatableParameter::new(
             BasicParameter::new("attack", 0.0001, 1.0, 0.003).with_unit("s"),
             0.5  // 50% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT); // 係数の再計算はブロック単位
 
         let release_param = ModulatableParameter::new(
             BasicParameter::new("release", 0.001, 10.0, 0.1).with_unit("s"),
             0.5  // 50% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT);
 
         let makeup_gain_param = ModulatableParameter::new(
             BasicParameter::new("makeup_gain", -20.0, 20.0, 0.0).with_unit("dB"),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let mut compressor = Self {
             node_info,
@@ -271,19 +271,12 @@ impl AudioNode for CompressorNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let threshold_cv = ctx.inputs.get_cv_value("threshold_cv");
-        let ratio_cv = ctx.inputs.get_cv_value("ratio_cv");
-        let attack_cv = ctx.inputs.get_cv_value("attack_cv");
-        let release_cv = ctx.inputs.get_cv_value("release_cv");
-        let makeup_gain_cv = ctx.inputs.get_cv_value("makeup_gain_cv");
-
-        // Apply CV modulation
-        let effective_threshold = self.threshold_param.modulate(self.threshold, threshold_cv);
-        let effective_ratio = self.ratio_param.modulate(self.ratio, ratio_cv);
-        let effective_attack = self.attack_param.modulate(self.attack, attack_cv);
-        let effective_release = self.release_param.modulate(self.release, release_cv);
-        let effective_makeup_gain = self.makeup_gain_param.modulate(self.makeup_gain, makeup_gain_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let threshold_cv = ctx.inputs.get_cv("threshold_cv");
+        let ratio_cv = ctx.inputs.get_cv("ratio_cv");
+        let attack_cv = ctx.inputs.get_cv("attack_cv");
+        let release_cv = ctx.inputs.get_cv("release_cv");
+        let makeup_gain_cv = ctx.inputs.get_cv("makeup_gain_cv");
 
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
@@ -301,6 +294,13 @@ impl AudioNode for CompressorNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample (attack/release are control rate)
+            let effective_threshold = self.threshold_param.modulate_smoothed(self.threshold, threshold_cv, i, self.sample_rate);
+            let effective_ratio = self.ratio_param.modulate_smoothed(self.ratio, ratio_cv, i, self.sample_rate);
+            let effective_attack = self.attack_param.modulate_smoothed(self.attack, attack_cv, i, self.sample_rate);
+            let effective_release = self.release_param.modulate_smoothed(self.release, release_cv, i, self.sample_rate);
+            let effective_makeup_gain = self.makeup_gain_param.modulate_smoothed(self.makeup_gain, makeup_gain_cv, i, self.sample_rate);
+
             *output_sample = self.process_compression(
                 input_sample, 
                 effective_threshold, 
@@ -329,12 +329,21 @@ impl AudioNode for CompressorNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset compressor state
         self.envelope = -60.0;
         self.gain_reduction = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients(self.attack, self.release);
+    }
+
     fn latency(&self) -> u32 {
         // Compressor has minimal latency (lookahead could be added later)
         0
@@ -395,6 +404,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -431,6 +442,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
@@ -479,6 +492,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
@@ -510,6 +525,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
@@ -540,6 +557,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
@@ -570,6 +589,8 @@ mod tests {
             buffer_size: 2048,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
@@ -603,6 +624,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(compressor.process(&mut ctx).is_ok());
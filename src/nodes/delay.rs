@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -83,17 +83,17 @@ impl DelayNode {
         let delay_time_param = ModulatableParameter::new(
             BasicParameter::new("delay_time", 1.0, 2000.0, 250.0).with_unit("ms"),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let feedback_param = ModulatableParameter::new(
             BasicParameter::new("feedback", 0.0, 0.95, 0.3),
             0.6  // 60% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let mix_param = ModulatableParameter::new(
             BasicParameter::new("mix", 0.0, 1.0, 0.5),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         // Initialize delay buffer for maximum delay time (2 seconds)
         let max_delay_samples = (2.0 * sample_rate) as usize;
@@ -227,15 +227,10 @@ impl AudioNode for DelayNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let delay_time_cv = ctx.inputs.get_cv_value("delay_time_cv");
-        let feedback_cv = ctx.inputs.get_cv_value("feedback_cv");
-        let mix_cv = ctx.inputs.get_cv_value("mix_cv");
-
-        // Apply CV modulation
-        let effective_delay_time = self.delay_time_param.modulate(self.delay_time, delay_time_cv);
-        let effective_feedback = self.feedback_param.modulate(self.feedback, feedback_cv);
-        let effective_mix = self.mix_param.modulate(self.mix, mix_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let delay_time_cv = ctx.inputs.get_cv("delay_time_cv");
+        let feedback_cv = ctx.inputs.get_cv("feedback_cv");
+        let mix_cv = ctx.inputs.get_cv("mix_cv");
 
         // Collect processed samples first to avoid borrowing conflicts
         let mut main_samples = Vec::new();
@@ -248,6 +243,11 @@ impl AudioNode for DelayNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_delay_time = self.delay_time_param.modulate_smoothed(self.delay_time, delay_time_cv, i, self.sample_rate);
+            let effective_feedback = self.feedback_param.modulate_smoothed(self.feedback, feedback_cv, i, self.sample_rate);
+            let effective_mix = self.mix_param.modulate_smoothed(self.mix, mix_cv, i, self.sample_rate);
+
             let (mixed, wet) = self.process_delay_sample(
                 input_sample, 
                 effective_delay_time, 
@@ -283,15 +283,30 @@ impl AudioNode for DelayNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Clear delay buffer
         self.delay_buffer.fill(0.0);
         self.write_position = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        // Two seconds of buffer at the new rate; the old contents no longer line up
+        let max_delay_samples = (2.0 * sample_rate) as usize;
+        if max_delay_samples != self.max_delay_samples {
+            self.delay_buffer = vec![0.0; max_delay_samples];
+            self.max_delay_samples = max_delay_samples;
+            self.write_position = 0.0;
+        }
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
-        // Delay has inherent latency equal to delay time
-        (self.delay_time / 1000.0 * self.sample_rate) as u32
+        // The echo is the effect itself; the dry signal passes through immediately
+        0
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -348,6 +363,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -364,6 +381,40 @@ mod tests {
         assert!(has_delayed_signal, "Should have delayed signal later");
     }
 
+    #[test]
+    fn test_mix_change_is_smoothed() {
+        let mut delay = DelayNode::new(44100.0, "test".to_string());
+        delay.set_parameter("delay_time", 100.0).unwrap(); // Wet stays silent throughout
+        delay.set_parameter("mix", 0.0).unwrap();
+
+        let run = |delay: &mut DelayNode| {
+            let mut inputs = InputBuffers::new();
+            inputs.add_audio("audio_in".to_string(), vec![1.0; 64]);
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_audio("audio_out".to_string(), 64);
+            let mut ctx = ProcessContext {
+                inputs,
+                outputs,
+                sample_rate: 44100.0,
+                buffer_size: 64,
+                timestamp: 0,
+                bpm: 120.0,
+                events: Default::default(),
+                transport: Default::default(),
+            };
+            delay.process(&mut ctx).unwrap();
+            ctx.outputs.get_audio("audio_out").unwrap().to_vec()
+        };
+        assert_eq!(run(&mut delay)[63], 1.0);
+
+        // Fully wet is approached over the smoothing time rather than at once
+        delay.set_parameter("mix", 1.0).unwrap();
+        let output = run(&mut delay);
+        assert!(output[0] > 0.9);
+        assert!(output.windows(2).all(|pair| pair[1] < pair[0]));
+        assert!(output[63] > 0.0);
+    }
+
     #[test]
     fn test_delay_feedback() {
         let mut delay = DelayNode::new(44100.0, "test".to_string());
@@ -384,6 +435,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(delay.process(&mut ctx).is_ok());
@@ -420,6 +473,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(delay.process(&mut ctx).is_ok());
@@ -450,6 +505,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(delay.process(&mut ctx).is_ok());
@@ -492,6 +549,8 @@ mod tests {
             buffer_size: 2048,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(delay.process(&mut ctx).is_ok());
@@ -525,6 +584,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(delay.process(&mut ctx).is_ok());
@@ -18,8 +18,8 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, cv_sample_at};
 use crate::graph::PortType;
 use crate::define_parameters;
 
@@ -78,6 +78,8 @@ pub struct LFONode {
     pulse_width: f32,    // 0.1 ~ 0.9 (square wave duty cycle)
     rate_cv_sensitivity: f32, // 0.0 ~ 1.0 (how much rate CV affects frequency)
     bipolar: f32,        // 0.0 = unipolar (0 to +1), 1.0 = bipolar (-1 to +1)
+    tempo_sync: f32,     // 1.0 = one cycle every `sync_beats` beats of the transport
+    sync_beats: f32,     // 1/16 ~ 64 beats per cycle
     active: f32,
     
     // CV Modulation parameters  
@@ -137,17 +139,17 @@ impl LFONode {
         let frequency_param = ModulatableParameter::new(
             BasicParameter::new("frequency", 0.01, 20.0, 1.0),
             0.8  // 80% CV modulation range
-        ).with_curve(ModulationCurve::Exponential); // Exponential for musical frequency response
+        ).with_curve(ModulationCurve::Exponential).with_smoothing(Smoothing::DEFAULT); // Exponential for musical frequency response
 
         let amplitude_param = ModulatableParameter::new(
             BasicParameter::new("amplitude", 0.0, 1.0, 1.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let phase_offset_param = ModulatableParameter::new(
             BasicParameter::new("phase_offset", 0.0, 1.0, 0.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -158,6 +160,8 @@ impl LFONode {
             pulse_width: 0.5,      // 50% duty cycle
             rate_cv_sensitivity: 1.0, // Full CV sensitivity
             bipolar: 1.0,          // Bipolar output default
+            tempo_sync: 0.0,       // Free running
+            sync_beats: 1.0,       // One cycle per beat when synced
             active: 1.0,
 
             frequency_param,
@@ -239,6 +243,8 @@ impl Parameterizable for LFONode {
         pulse_width: BasicParameter::new("pulse_width", 0.1, 0.9, 0.5),
         rate_cv_sensitivity: BasicParameter::new("rate_cv_sensitivity", 0.0, 1.0, 1.0),
         bipolar: BasicParameter::new("bipolar", 0.0, 1.0, 1.0),
+        tempo_sync: BasicParameter::new("tempo_sync", 0.0, 1.0, 0.0),
+        sync_beats: BasicParameter::new("sync_beats", 0.0625, 64.0, 1.0),
         active: BasicParameter::new("active", 0.0, 1.0, 1.0)
     }
 }
@@ -262,19 +268,13 @@ impl AudioNode for LFONode {
         // Get input signals
         let sync_input = ctx.inputs.get_audio("sync_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let frequency_cv = ctx.inputs.get_cv_value("frequency_cv");
-        let amplitude_cv = ctx.inputs.get_cv_value("amplitude_cv");
-        let phase_offset_cv = ctx.inputs.get_cv_value("phase_offset_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let frequency_cv = ctx.inputs.get_cv("frequency_cv");
+        let amplitude_cv = ctx.inputs.get_cv("amplitude_cv");
+        let phase_offset_cv = ctx.inputs.get_cv("phase_offset_cv");
         let waveform_cv = ctx.inputs.get_cv_value("waveform_cv");
 
-        // Apply CV modulation
-        let effective_frequency = self.frequency_param.modulate(self.frequency, 
-            frequency_cv * self.rate_cv_sensitivity);
-        let effective_amplitude = self.amplitude_param.modulate(self.amplitude, amplitude_cv);
-        let effective_phase_offset = self.phase_offset_param.modulate(self.phase_offset, phase_offset_cv);
-
-        // Update waveform from CV if provided
+        // Update waveform from CV if provided (control rate)
         let current_waveform = if waveform_cv != 0.0 {
             LFOWaveform::from_f32(waveform_cv.clamp(0.0, 4.0))
         } else {
@@ -287,6 +287,14 @@ impl AudioNode for LFONode {
                 port_name: "cv_out".to_string() 
             })?.len();
 
+        // Synced: the rate follows the tempo and, while the transport plays,
+        // the phase follows the song position
+        let synced = self.tempo_sync > 0.5;
+        if synced && ctx.transport.playing {
+            self.phase = (ctx.transport.beat_position / self.sync_beats as f64).fract() as f32;
+        }
+        let synced_frequency = ctx.transport.tempo / 60.0 / self.sync_beats;
+
         // Generate samples
         let mut cv_samples = Vec::with_capacity(buffer_size);
         let mut inv_samples = Vec::with_capacity(buffer_size);
@@ -301,6 +309,16 @@ impl AudioNode for LFONode {
             };
             self.process_sync(sync_signal);
 
+            // Apply CV modulation per sample
+            let effective_frequency = if synced {
+                synced_frequency
+            } else {
+                let frequency = self.frequency_param.smooth(self.frequency, self.sample_rate);
+                self.frequency_param.modulate(frequency, cv_sample_at(frequency_cv, i) * self.rate_cv_sensitivity)
+            };
+            let effective_amplitude = self.amplitude_param.modulate_smoothed(self.amplitude, amplitude_cv, i, self.sample_rate);
+            let effective_phase_offset = self.phase_offset_param.modulate_smoothed(self.phase_offset, phase_offset_cv, i, self.sample_rate);
+
             // Calculate phase increment
             let phase_increment = effective_frequency / self.sample_rate;
             
@@ -373,6 +391,10 @@ impl AudioNode for LFONode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset LFO state
         self.phase = 0.0;
@@ -381,6 +403,10 @@ impl AudioNode for LFONode {
         self.sync_triggered = false;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for LFO generation
     }
@@ -442,6 +468,8 @@ mod tests {
             buffer_size: 44100,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -486,6 +514,8 @@ mod tests {
                 buffer_size: 512,
                 timestamp: 0,
                 bpm: 120.0,
+                events: Default::default(),
+                transport: Default::default(),
             };
             
             assert!(lfo.process(&mut ctx).is_ok());
@@ -517,6 +547,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
@@ -551,6 +583,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
@@ -559,6 +593,37 @@ mod tests {
         assert!(lfo.get_phase() >= 0.0 && lfo.get_phase() < 1.0, "Phase should be valid after sync");
     }
 
+    #[test]
+    fn test_tempo_sync_follows_transport() {
+        let mut lfo = LFONode::new(44100.0, "test".to_string());
+        lfo.set_parameter("tempo_sync", 1.0).unwrap();
+        lfo.set_parameter("sync_beats", 2.0).unwrap();
+
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_cv("cv_out".to_string(), 441);
+
+        let mut ctx = ProcessContext {
+            inputs: InputBuffers::new(),
+            outputs,
+            sample_rate: 44100.0,
+            buffer_size: 441,
+            timestamp: 0,
+            bpm: 120.0,
+            events: Default::default(),
+            transport: crate::transport::TransportInfo {
+                playing: true,
+                tempo: 120.0,
+                beat_position: 5.0,
+                ..Default::default()
+            },
+        };
+
+        assert!(lfo.process(&mut ctx).is_ok());
+
+        // Beat 5 of a 2-beat cycle is halfway; 120 BPM / 2 beats = 1 Hz for 10 ms
+        assert!((lfo.get_phase() - 0.51).abs() < 1e-4, "phase {}", lfo.get_phase());
+    }
+
     #[test]
     fn test_unipolar_vs_bipolar() {
         let mut lfo = LFONode::new(44100.0, "test".to_string());
@@ -582,6 +647,8 @@ mod tests {
             buffer_size: 4410,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
@@ -607,6 +674,8 @@ mod tests {
             buffer_size: 4410,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
@@ -633,6 +702,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
@@ -664,6 +735,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(lfo.process(&mut ctx).is_ok());
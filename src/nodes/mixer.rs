@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 
@@ -81,8 +81,8 @@ impl MixerNode {
             // Input ports: 8 stereo inputs + CV inputs
             input_ports: vec![
                 // Audio inputs (8 stereo pairs)
-                PortInfo { name: "ch1_l".to_string(), port_type: PortType::AudioMono, description: "Channel 1 Left".to_string(), is_optional: true },
-                PortInfo { name: "ch1_r".to_string(), port_type: PortType::AudioMono, description: "Channel 1 Right".to_string(), is_optional: true },
+                PortInfo::new("ch1_l", PortType::AudioMono).with_description("Channel 1 Left").optional(),
+                PortInfo::new("ch1_r", PortType::AudioMono).with_description("Channel 1 Right").optional(),
                 PortInfo::new("ch2_l", PortType::AudioMono),
                 PortInfo::new("ch2_r", PortType::AudioMono),
                 PortInfo::new("ch3_l", PortType::AudioMono),
@@ -124,36 +124,36 @@ impl MixerNode {
         let master_gain_param = ModulatableParameter::new(
             BasicParameter::new("master_gain", 0.0, 2.0, 0.8),
             1.0  // 100% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
         
         let high_freq_param = ModulatableParameter::new(
             BasicParameter::new("high_freq_gain", 0.0, 2.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
         
         let mid_freq_param = ModulatableParameter::new(
             BasicParameter::new("mid_freq_gain", 0.0, 2.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
         
         let low_freq_param = ModulatableParameter::new(
             BasicParameter::new("low_freq_gain", 0.0, 2.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         // Create per-channel modulation parameters
         let channel_gain_params = [0; 8].map(|_| 
             ModulatableParameter::new(
                 BasicParameter::new("channel_gain", 0.0, 2.0, 0.7),
                 0.8  // 80% CV modulation range
-            )
+            ).with_smoothing(Smoothing::DEFAULT)
         );
         
         let channel_pan_params = [0; 8].map(|_| 
             ModulatableParameter::new(
                 BasicParameter::new("channel_pan", -1.0, 1.0, 0.0),
                 0.8  // 80% CV modulation range
-            )
+            ).with_smoothing(Smoothing::DEFAULT)
         );
 
         Self {
@@ -210,7 +210,7 @@ impl MixerNode {
     }
     
     /// Simple 3-band EQ processing
-    fn apply_eq(&mut self, left: f32, right: f32) -> (f32, f32) {
+    fn apply_eq(&mut self, left: f32, right: f32, high_gain: f32, mid_gain: f32, low_gain: f32) -> (f32, f32) {
         // High-pass filter (simple 1-pole)
         let high_cutoff = 0.1; // Normalized frequency
         self.high_freq_state += high_cutoff * (left - self.high_freq_state);
@@ -225,9 +225,9 @@ impl MixerNode {
         let mid_component = left - high_component - low_component;
         
         // Apply EQ gains
-        let eq_left = high_component * self.high_freq_gain + 
-                      mid_component * self.mid_freq_gain + 
-                      low_component * self.low_freq_gain;
+        let eq_left = high_component * high_gain + 
+                      mid_component * mid_gain + 
+                      low_component * low_gain;
         
         let eq_right = right; // For simplicity, apply same EQ to right
         
@@ -484,17 +484,11 @@ impl AudioNode for MixerNode {
             return Ok(());
         }
 
-        // Get CV inputs for master controls
-        let master_gain_cv = ctx.inputs.get_cv_value("master_gain_cv");
-        let high_freq_cv = ctx.inputs.get_cv_value("high_freq_cv");
-        let mid_freq_cv = ctx.inputs.get_cv_value("mid_freq_cv");
-        let low_freq_cv = ctx.inputs.get_cv_value("low_freq_cv");
-
-        // Apply CV modulation to master controls
-        let effective_master_gain = self.master_gain_param.modulate(self.master_gain, master_gain_cv);
-        self.high_freq_gain = self.high_freq_param.modulate(self.high_freq_gain, high_freq_cv);
-        self.mid_freq_gain = self.mid_freq_param.modulate(self.mid_freq_gain, mid_freq_cv);
-        self.low_freq_gain = self.low_freq_param.modulate(self.low_freq_gain, low_freq_cv);
+        // Get CV inputs for master controls (full buffers for per-sample modulation)
+        let master_gain_cv = ctx.inputs.get_cv("master_gain_cv");
+        let high_freq_cv = ctx.inputs.get_cv("high_freq_cv");
+        let mid_freq_cv = ctx.inputs.get_cv("mid_freq_cv");
+        let low_freq_cv = ctx.inputs.get_cv("low_freq_cv");
 
         // Get buffer size
         let buffer_size = ctx.outputs.get_audio("mix_l")
@@ -522,24 +516,24 @@ impl AudioNode for MixerNode {
             let ch_left = ctx.inputs.get_audio(&ch_l_name).unwrap_or(&[]);
             let ch_right = ctx.inputs.get_audio(&ch_r_name).unwrap_or(&[]);
 
-            // Get current channel parameters
-            let ch_gain = self.channel_gains[ch];
-            let ch_pan = self.channel_pans[ch];
-            let ch_mute = self.channel_mutes[ch];
-
-            // Skip if muted
-            if ch_mute < 0.5 {
+            // Skip if muted; the smoothers jump to the current values so
+            // unmuting does not replay a ramp from before the mute
+            if self.channel_mutes[ch] < 0.5 {
+                self.channel_gain_params[ch].snap(self.channel_gains[ch]);
+                self.channel_pan_params[ch].snap(self.channel_pans[ch]);
                 continue;
             }
 
-            // Calculate pan gains
-            let (left_gain, right_gain) = self.calculate_pan_gains(ch_pan);
-
             // Mix samples
             for i in 0..buffer_size {
                 let left_sample = if i < ch_left.len() { ch_left[i] } else { 0.0 };
                 let right_sample = if i < ch_right.len() { ch_right[i] } else { 0.0 };
 
+                // Smoothed channel gain and pan
+                let ch_gain = self.channel_gain_params[ch].modulate_smoothed(self.channel_gains[ch], None, i, self.sample_rate);
+                let ch_pan = self.channel_pan_params[ch].modulate_smoothed(self.channel_pans[ch], None, i, self.sample_rate);
+                let (left_gain, right_gain) = self.calculate_pan_gains(ch_pan);
+
                 // Apply channel gain and pan
                 let gained_left = left_sample * ch_gain;
                 let gained_right = right_sample * ch_gain;
@@ -552,8 +546,14 @@ impl AudioNode for MixerNode {
 
         // Apply master processing
         for i in 0..buffer_size {
+            // Apply CV modulation to master controls per sample
+            let effective_master_gain = self.master_gain_param.modulate_smoothed(self.master_gain, master_gain_cv, i, self.sample_rate);
+            let high_gain = self.high_freq_param.modulate_smoothed(self.high_freq_gain, high_freq_cv, i, self.sample_rate);
+            let mid_gain = self.mid_freq_param.modulate_smoothed(self.mid_freq_gain, mid_freq_cv, i, self.sample_rate);
+            let low_gain = self.low_freq_param.modulate_smoothed(self.low_freq_gain, low_freq_cv, i, self.sample_rate);
+
             // Apply EQ
-            let (eq_left, eq_right) = self.apply_eq(self.temp_left[i], self.temp_right[i]);
+            let (eq_left, eq_right) = self.apply_eq(self.temp_left[i], self.temp_right[i], high_gain, mid_gain, low_gain);
             
             // Apply master gain
             let final_left = eq_left * effective_master_gain;
@@ -605,6 +605,10 @@ impl AudioNode for MixerNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset internal processing state
         self.temp_left.fill(0.0);
@@ -616,6 +620,12 @@ impl AudioNode for MixerNode {
         // Note: Individual parameter reset methods could be added if needed
     }
 
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.temp_left.resize(max_block_size, 0.0);
+        self.temp_right.resize(max_block_size, 0.0);
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for mixing
     }
@@ -701,6 +711,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mixer.process(&mut ctx).is_ok());
@@ -762,6 +774,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mixer.process(&mut ctx).is_ok());
@@ -772,4 +786,31 @@ mod tests {
         assert_eq!(mix_l[0], 0.0);
         assert_eq!(mix_l[1], 0.0);
     }
+
+    #[test]
+    fn test_muted_channel_does_not_ramp_on_unmute() {
+        // 20ms smoothing = 20 samples at 1kHz
+        let mut mixer = MixerNode::new(1000.0, "test".to_string());
+        mixer.set_parameter("ch1_gain", 0.0).unwrap();
+        let render = |mixer: &mut MixerNode| {
+            let mut inputs = InputBuffers::new();
+            inputs.add_audio("ch1_l".to_string(), vec![0.5; 32]);
+            inputs.add_audio("ch1_r".to_string(), vec![0.5; 32]);
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_audio("mix_l".to_string(), 32);
+            outputs.allocate_audio("mix_r".to_string(), 32);
+            let mut ctx = ProcessContext::new(inputs, outputs, 1000.0, 32);
+            mixer.process(&mut ctx).unwrap();
+            ctx.outputs.get_audio("mix_l").unwrap().to_vec()
+        };
+        render(&mut mixer);
+
+        // Turned up while muted: the gain is already there when unmuted
+        mixer.set_parameter("ch1_mute", 0.0).unwrap();
+        mixer.set_parameter("ch1_gain", 1.0).unwrap();
+        render(&mut mixer);
+        mixer.set_parameter("ch1_mute", 1.0).unwrap();
+        let unmuted = render(&mut mixer);
+        assert!((unmuted[0] - unmuted[31]).abs() < 0.05);
+    }
 }
\ No newline at end of file
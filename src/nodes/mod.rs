@@ -36,6 +36,7 @@
 pub mod oscillator;
 pub mod sine_oscillator;
 pub mod noise;
+pub mod audio_input;
 
 // Processor Nodes  
 pub mod vcf;
@@ -65,12 +66,21 @@ pub mod output;
 pub mod oscilloscope;
 pub mod spectrum_analyzer;
 
+// Polyphony Nodes
+pub mod poly;
+pub mod voice_allocator;
+pub mod poly_sum;
+
+// Container Nodes
+pub mod subpatch;
+
 // === Node Exports ===
 
 // Generator Nodes
 pub use oscillator::{OscillatorNode, WaveformType};
 pub use sine_oscillator::SineOscillatorNode;
 pub use noise::{NoiseNode, NoiseType};
+pub use audio_input::AudioInputNode;
 
 // Processor Nodes
 pub use vcf::{VCFNode, FilterType};
@@ -100,6 +110,75 @@ pub use output::OutputNode;
 pub use oscilloscope::{OscilloscopeNode, TriggerMode, TriggerSlope, Measurements};
 pub use spectrum_analyzer::{SpectrumAnalyzerNode, WindowType};
 
+// Polyphony Nodes
+pub use poly::{PolyNode, DEFAULT_POLY_VOICES, POLY_PREFIX};
+pub use voice_allocator::{VoiceAllocatorNode, VoiceAllocator, AllocationMode};
+pub use poly_sum::PolySumNode;
+
+// Container Nodes
+pub use subpatch::SubpatchNode;
+
+use crate::patch::PatchNode;
+use crate::processing::AudioNode;
+
 // === Node Creation ===
-// Note: Node creation is now handled by AudioEngine::create_builtin_node
-// This module only exports node types for the unified ProcessContext architecture
\ No newline at end of file
+
+/// Create a built-in node by its `node_type`.
+/// `poly_<type>` wraps `DEFAULT_POLY_VOICES` instances of `<type>` in a `PolyNode`.
+/// Subpatches need a definition and are built with `SubpatchNode::from_definition`.
+pub fn create_builtin_node(node_type: &str, sample_rate: f32, name: String) -> Result<Box<dyn AudioNode>, String> {
+    let node: Box<dyn AudioNode> = match node_type {
+        // Generator Nodes
+        "sine_oscillator" => Box::new(SineOscillatorNode::new(sample_rate, name)),
+        "oscillator" => Box::new(OscillatorNode::new(sample_rate, name)),
+        "noise" => Box::new(NoiseNode::new(sample_rate, name)),
+        "audio_input" => Box::new(AudioInputNode::new(sample_rate, name)),
+
+        // Processor Nodes
+        "vcf" => Box::new(VCFNode::new(sample_rate, name)),
+        "vca" => Box::new(VCANode::new(sample_rate, name)),
+        "delay" => Box::new(DelayNode::new(sample_rate, name)),
+        "compressor" => Box::new(CompressorNode::new(sample_rate, name)),
+        "waveshaper" => Box::new(WaveshaperNode::new(sample_rate, name)),
+        "ring_modulator" => Box::new(RingModulatorNode::new(sample_rate, name)),
+
+        // Controller Nodes
+        "adsr" => Box::new(ADSRNode::new(sample_rate, name)),
+        "lfo" => Box::new(LFONode::new(sample_rate, name)),
+        "sequencer" => Box::new(SequencerNode::new(sample_rate, name)),
+
+        // Utility Nodes
+        "sample_hold" => Box::new(SampleHoldNode::new(sample_rate, name)),
+        "quantizer" => Box::new(QuantizerNode::new(sample_rate, name)),
+        "attenuverter" => Box::new(AttenuverterNode::new(sample_rate, name)),
+        "multiple" => Box::new(MultipleNode::new(sample_rate, name, 4)),
+        "clock_divider" => Box::new(ClockDividerNode::new(sample_rate, name)),
+
+        // Mixing/Routing Nodes
+        "mixer" => Box::new(MixerNode::new(sample_rate, name)),
+        "output" => Box::new(OutputNode::new(sample_rate, name)),
+
+        // Analysis Nodes
+        "oscilloscope" => Box::new(OscilloscopeNode::new(sample_rate, name)),
+        "spectrum_analyzer" => Box::new(SpectrumAnalyzerNode::new(sample_rate, name)),
+
+        // Polyphony Nodes
+        "voice_allocator" => Box::new(VoiceAllocatorNode::new(sample_rate, name)),
+        "poly_sum" => Box::new(PolySumNode::new(sample_rate, name)),
+
+        _ => match node_type.strip_prefix(POLY_PREFIX) {
+            Some(inner) => Box::new(PolyNode::from_type(inner, DEFAULT_POLY_VOICES, sample_rate, name)?),
+            None => return Err(format!("Unknown built-in node type: {}", node_type)),
+        },
+    };
+    Ok(node)
+}
+
+/// Create the node for a patch entry: a subpatch from its definition,
+/// anything else as a built-in node named after the entry
+pub fn create_patch_node(patch_node: &PatchNode, sample_rate: f32) -> Result<Box<dyn AudioNode>, String> {
+    match &patch_node.subpatch {
+        Some(definition) => Ok(Box::new(SubpatchNode::from_definition(definition, sample_rate, patch_node.name.clone())?)),
+        None => create_builtin_node(&patch_node.node_type, sample_rate, patch_node.name.clone()),
+    }
+}
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 
@@ -70,7 +70,7 @@ impl MultipleNode {
             .map(|_i| ModulatableParameter::new(
                 BasicParameter::new("gain", 0.0, 2.0, 1.0),
                 0.8  // 80% CV modulation range
-            ))
+            ).with_smoothing(Smoothing::DEFAULT))
             .collect();
 
         Self {
@@ -181,7 +181,7 @@ impl Parameterizable for MultipleNode {
                         ModulatableParameter::new(
                             BasicParameter::new("gain", 0.0, 2.0, 1.0),
                             0.8
-                        )
+                        ).with_smoothing(Smoothing::DEFAULT)
                     );
                 }
                 self.channel_count = count as f32;
@@ -297,13 +297,7 @@ impl AudioNode for MultipleNode {
             if let Some(output) = ctx.outputs.get_audio_mut(&output_name) {
                 // Get CV modulation for this channel if available
                 let gain_cv_name = format!("gain_{}_cv", channel);
-                let gain_cv = ctx.inputs.get_cv_value(&gain_cv_name);
-                
-                // Apply CV modulation to gain
-                let effective_gain = self.gain_params[channel].modulate(
-                    self.output_gains[channel], 
-                    gain_cv
-                );
+                let gain_cv = ctx.inputs.get_cv(&gain_cv_name);
 
                 // Process each sample
                 for i in 0..buffer_size.min(output.len()) {
@@ -313,6 +307,13 @@ impl AudioNode for MultipleNode {
                         0.0 
                     };
 
+                    // Apply CV modulation to gain per sample
+                    let effective_gain = self.gain_params[channel].modulate_smoothed(
+                        self.output_gains[channel], 
+                        gain_cv,
+                        i, self.sample_rate
+                    );
+
                     // Process distribution
                     let distributed_sample = self.process_distribution(
                         input_sample, 
@@ -333,10 +334,18 @@ impl AudioNode for MultipleNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // No internal state to reset for signal distribution
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for signal distribution
     }
@@ -403,6 +412,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mult.process(&mut ctx).is_ok());
@@ -443,6 +454,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mult.process(&mut ctx).is_ok());
@@ -475,6 +488,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mult.process(&mut ctx).is_ok());
@@ -507,6 +522,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mult.process(&mut ctx).is_ok());
@@ -538,6 +555,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(mult.process(&mut ctx).is_ok());
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -111,7 +111,7 @@ impl NoiseNode {
         let amplitude_param = ModulatableParameter::new(
             BasicParameter::new("amplitude", 0.0, 1.0, 0.5),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -203,14 +203,11 @@ impl AudioNode for NoiseNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let amplitude_cv = ctx.inputs.get_cv_value("amplitude_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let amplitude_cv = ctx.inputs.get_cv("amplitude_cv");
         let type_cv = ctx.inputs.get_cv_value("type_cv");
-
-        // Apply CV modulation
-        let effective_amplitude = self.amplitude_param.modulate(self.amplitude, amplitude_cv);
         
-        // Update noise type from CV if provided
+        // Update noise type from CV if provided (control rate)
         let current_noise_type = if type_cv != 0.0 {
             NoiseType::from_f32(type_cv.clamp(0.0, 3.0))
         } else {
@@ -224,7 +221,8 @@ impl AudioNode for NoiseNode {
             })?;
 
         // Generate noise samples
-        for sample in output.iter_mut() {
+        for (i, sample) in output.iter_mut().enumerate() {
+            let effective_amplitude = self.amplitude_param.modulate_smoothed(self.amplitude, amplitude_cv, i, self.sample_rate);
             let noise_sample = self.generate_noise_sample(current_noise_type);
             *sample = noise_sample * effective_amplitude;
         }
@@ -236,6 +234,10 @@ impl AudioNode for NoiseNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset noise generation state
         self.rng_state = 1;
@@ -244,6 +246,10 @@ impl AudioNode for NoiseNode {
         self.blue_state = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for noise generator
     }
@@ -294,6 +300,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise.process(&mut ctx).is_ok());
@@ -326,6 +334,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Test each noise type
@@ -364,6 +374,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise.process(&mut ctx).is_ok());
@@ -392,6 +404,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise.process(&mut ctx).is_ok());
@@ -424,6 +438,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         let mut ctx2 = ProcessContext {
@@ -433,6 +449,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise1.process(&mut ctx1).is_ok());
@@ -463,6 +481,8 @@ mod tests {
             buffer_size: 4096,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise.process(&mut ctx).is_ok());
@@ -495,6 +515,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(noise.process(&mut ctx).is_ok());
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -97,17 +97,17 @@ impl OscillatorNode {
         let frequency_param = ModulatableParameter::new(
             BasicParameter::new("frequency", 20.0, 20000.0, 440.0).with_unit("Hz"),
             1.0  // 100% CV modulation
-        ).with_curve(ModulationCurve::Exponential); // 周波数は指数的変化
+        ).with_curve(ModulationCurve::Exponential).with_smoothing(Smoothing::DEFAULT); // 周波数は指数的変化
 
         let amplitude_param = ModulatableParameter::new(
             BasicParameter::new("amplitude", 0.0, 1.0, 0.5),
             0.5  // 50% CV modulation
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let pulse_width_param = ModulatableParameter::new(
             BasicParameter::new("pulse_width", 0.1, 0.9, 0.5),
             0.4  // 40% CV modulation
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -183,25 +183,19 @@ impl AudioNode for OscillatorNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let frequency_cv = ctx.inputs.get_cv_value("frequency_cv");
-        let amplitude_cv = ctx.inputs.get_cv_value("amplitude_cv");
-        let waveform_cv = ctx.inputs.get_cv_value("waveform_cv");
-        let pulse_width_cv = ctx.inputs.get_cv_value("pulse_width_cv");
-
-        // Apply CV modulation
-        let effective_frequency = self.frequency_param.modulate(self.frequency, frequency_cv);
-        let effective_amplitude = self.amplitude_param.modulate(self.amplitude, amplitude_cv);
-        let effective_pulse_width = self.pulse_width_param.modulate(self.pulse_width, pulse_width_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let frequency_cv = ctx.inputs.get_cv("frequency_cv");
+        let amplitude_cv = ctx.inputs.get_cv("amplitude_cv");
+        let pulse_width_cv = ctx.inputs.get_cv("pulse_width_cv");
 
-        // Update waveform from CV if provided
+        // Update waveform from CV if provided (control rate)
+        let waveform_cv = ctx.inputs.get_cv_value("waveform_cv");
         if waveform_cv != 0.0 {
             self.waveform = (waveform_cv * 4.0).clamp(0.0, 3.0);
         }
 
-        // Update pulse width for current processing
+        // Keep the original pulse width; generate_sample reads the field
         let original_pulse_width = self.pulse_width;
-        self.pulse_width = effective_pulse_width;
 
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
@@ -210,13 +204,14 @@ impl AudioNode for OscillatorNode {
             })?;
 
         for (i, sample) in output.iter_mut().enumerate() {
-            let phase_increment = 2.0 * std::f32::consts::PI * effective_frequency / ctx.sample_rate;
-            let current_phase = self.phase + phase_increment * i as f32;
-            *sample = self.generate_sample(current_phase) * effective_amplitude;
-        }
+            // Apply CV modulation per sample
+            let effective_frequency = self.frequency_param.modulate_smoothed(self.frequency, frequency_cv, i, self.sample_rate);
+            let effective_amplitude = self.amplitude_param.modulate_smoothed(self.amplitude, amplitude_cv, i, self.sample_rate);
+            self.pulse_width = self.pulse_width_param.modulate_smoothed(original_pulse_width, pulse_width_cv, i, self.sample_rate);
 
-        // Advance phase for next buffer
-        self.advance_phase(effective_frequency, ctx.buffer_size);
+            *sample = self.generate_sample(self.phase) * effective_amplitude;
+            self.advance_phase(effective_frequency, 1);
+        }
 
         // Restore original pulse width
         self.pulse_width = original_pulse_width;
@@ -228,10 +223,18 @@ impl AudioNode for OscillatorNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         self.phase = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for oscillator
     }
@@ -278,6 +281,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -306,12 +311,37 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(osc.process(&mut ctx).is_ok());
         // With exponential CV curve, +1V should significantly increase frequency
     }
 
+    #[test]
+    fn test_audio_rate_cv_modulation() {
+        let render = |cv: Vec<f32>| {
+            let mut osc = OscillatorNode::new(44100.0, "test".to_string());
+            let mut inputs = InputBuffers::new();
+            inputs.add_cv("frequency_cv".to_string(), cv);
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_audio("audio_out".to_string(), 512);
+            let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 512);
+            osc.process(&mut ctx).unwrap();
+            ctx.outputs.get_audio("audio_out").unwrap().to_vec()
+        };
+
+        // CV jumps mid-block: the first half must match the unmodulated signal
+        let mut step_cv = vec![0.0; 256];
+        step_cv.extend(vec![1.0; 256]);
+        let stepped = render(step_cv);
+        let flat = render(vec![0.0; 512]);
+
+        assert_eq!(&stepped[..256], &flat[..256]);
+        assert_ne!(&stepped[256..], &flat[256..]);
+    }
+
     #[test]
     fn test_waveform_generation() {
         let osc = OscillatorNode::new(44100.0, "test".to_string());
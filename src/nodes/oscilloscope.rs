@@ -19,8 +19,8 @@
 use uuid::Uuid;
 use std::collections::VecDeque;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, cv_sample_at};
 use crate::graph::PortType;
 use crate::define_parameters;
 
@@ -145,7 +145,7 @@ impl OscilloscopeNode {
         let trigger_level_param = ModulatableParameter::new(
             BasicParameter::new("trigger_level", -10.0, 10.0, 0.0),
             1.0  // 100% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT); // トリガーレベルはブロック単位
         
         let time_scale_param = ModulatableParameter::new(
             BasicParameter::new("time_scale", 0.001, 1.0, 0.01),
@@ -351,20 +351,21 @@ impl AudioNode for OscilloscopeNode {
 
         // Get input signals
         let audio_input = ctx.inputs.get_audio("audio_in").unwrap_or(&[]);
-        let trigger_level_cv = ctx.inputs.get_cv_value("trigger_level_cv");
+        let trigger_level_cv = ctx.inputs.get_cv("trigger_level_cv");
         let time_scale_cv = ctx.inputs.get_cv_value("time_scale_cv");
         let voltage_scale_cv = ctx.inputs.get_cv_value("voltage_scale_cv");
-        let external_trigger = ctx.inputs.get_cv_value("external_trigger");
+        let external_trigger = ctx.inputs.get_cv("external_trigger");
 
-        // Apply CV modulation
-        let effective_trigger_level = self.trigger_level_param.modulate(self.trigger_level, trigger_level_cv);
+        // Apply CV modulation (display scales are control rate)
         let _effective_time_scale = self.time_scale_param.modulate(self.time_scale, time_scale_cv);
         let _effective_voltage_scale = self.voltage_scale_param.modulate(self.voltage_scale, voltage_scale_cv);
 
         // Process each sample
         let mut trigger_out_value = 0.0;
         
-        for &sample in audio_input {
+        for (i, &sample) in audio_input.iter().enumerate() {
+            let effective_trigger_level = self.trigger_level_param.modulate_smoothed(self.trigger_level, trigger_level_cv, i, self.sample_rate);
+
             // Add to sample buffer
             self.sample_buffer.push_back(sample);
             if self.sample_buffer.len() > 8192 {
@@ -404,7 +405,7 @@ impl AudioNode for OscilloscopeNode {
             }
             
             // Handle external trigger
-            if external_trigger > 2.5 {
+            if cv_sample_at(external_trigger, i) > 2.5 {
                 self.triggered = true;
                 trigger_out_value = 5.0;
             }
@@ -454,6 +455,10 @@ impl AudioNode for OscilloscopeNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset internal processing state
         self.sample_buffer.clear();
@@ -470,6 +475,10 @@ impl AudioNode for OscilloscopeNode {
         self.measurements = Measurements::default();
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for oscilloscope analysis
     }
@@ -585,6 +594,8 @@ mod tests {
             buffer_size: 8,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(scope.process(&mut ctx).is_ok());
@@ -653,6 +664,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(scope.process(&mut ctx).is_ok());
@@ -669,29 +682,24 @@ mod tests {
 
 
 impl OscilloscopeNode {
-    /// Override parameter setting to handle UI aliases
-    pub fn set_parameter_override(&mut self, param: &str, value: f32) -> Result<(), String> {
+    /// Parameter behind a UI alias; other names are returned as they are
+    pub fn parameter_name(param: &str) -> &str {
         match param {
-            // UI alias mappings
-            "time_div" => self.set_parameter("time_scale", value).map_err(|e| e.to_string()),
-            "volt_div" => self.set_parameter("voltage_scale", value).map_err(|e| e.to_string()),
-            "position_h" => self.set_parameter("horizontal_position", value).map_err(|e| e.to_string()),
-            "position_v" => self.set_parameter("vertical_position", value).map_err(|e| e.to_string()),
-            // Default parameter handling
-            _ => self.set_parameter(param, value).map_err(|e| e.to_string()),
+            "time_div" => "time_scale",
+            "volt_div" => "voltage_scale",
+            "position_h" => "horizontal_position",
+            "position_v" => "vertical_position",
+            _ => param,
         }
     }
+
+    /// Override parameter setting to handle UI aliases
+    pub fn set_parameter_override(&mut self, param: &str, value: f32) -> Result<(), String> {
+        self.set_parameter(Self::parameter_name(param), value).map_err(|e| e.to_string())
+    }
     
     /// Override parameter getting to handle UI aliases
     pub fn get_parameter_override(&self, param: &str) -> Result<f32, String> {
-        match param {
-            // UI alias mappings
-            "time_div" => self.get_parameter("time_scale").map_err(|e| e.to_string()),
-            "volt_div" => self.get_parameter("voltage_scale").map_err(|e| e.to_string()),
-            "position_h" => self.get_parameter("horizontal_position").map_err(|e| e.to_string()),
-            "position_v" => self.get_parameter("vertical_position").map_err(|e| e.to_string()),
-            // Default parameter handling
-            _ => self.get_parameter(param).map_err(|e| e.to_string()),
-        }
+        self.get_parameter(Self::parameter_name(param)).map_err(|e| e.to_string())
     }
 }
\ No newline at end of file
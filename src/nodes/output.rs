@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -27,6 +27,8 @@ use crate::define_parameters;
 /// 
 /// 特徴:
 /// - ステレオ入力（L/R）対応
+/// - L/Rを分離したままデバイスへ出力（audio_out）
+/// - 出力先のデバイスチャンネルを指定可能（`OutputRoute`、複数のOutputNodeでステム出力）
 /// - マスター音量制御（CV変調対応）
 /// - ミュート機能
 /// - ピークリミッター内蔵
@@ -72,11 +74,16 @@ impl OutputNode {
             input_ports: vec![
                 PortInfo::new("audio_in_l", PortType::AudioMono),
                 PortInfo::new("audio_in_r", PortType::AudioMono),
+                PortInfo::new("audio_in", PortType::AudioStereo)
+                    .with_description("Stereo input, summed with audio_in_l/audio_in_r")
+                    .optional(),
                 PortInfo::new("master_volume_cv", PortType::CV),
             ],
             
-            // Output ports: final mixed output + meter signals
+            // Output ports: stereo device output, mono downmix + meter signals
             output_ports: vec![
+                PortInfo::new("audio_out", PortType::AudioStereo)
+                    .with_description("Final stereo output sent to the device"),
                 PortInfo::new("mixed_output", PortType::AudioMono),
                 PortInfo::new("peak_level_l_cv", PortType::CV),
                 PortInfo::new("peak_level_r_cv", PortType::CV),
@@ -94,7 +101,7 @@ impl OutputNode {
         let master_volume_param = ModulatableParameter::new(
             BasicParameter::new("master_volume", 0.0, 2.0, 0.7),
             1.0  // 100% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -193,16 +200,17 @@ impl AudioNode for OutputNode {
             if let Some(mixed_output) = ctx.outputs.get_audio_mut("mixed_output") {
                 mixed_output.fill(0.0);
             }
+            ctx.outputs.clear_stereo("audio_out");
             return Ok(());
         }
 
         // Get input signals
         let left_input = ctx.inputs.get_audio("audio_in_l").unwrap_or(&[]);
         let right_input = ctx.inputs.get_audio("audio_in_r").unwrap_or(&[]);
-        let master_volume_cv = ctx.inputs.get_cv_value("master_volume_cv");
-
-        // Apply CV modulation to master volume
-        let effective_master_volume = self.master_volume_param.modulate(self.master_volume, master_volume_cv);
+        let stereo_input = ctx.inputs.get_stereo("audio_in");
+        let stereo_left = stereo_input.map(|s| s.left.as_slice()).unwrap_or(&[]);
+        let stereo_right = stereo_input.map(|s| s.right.as_slice()).unwrap_or(&[]);
+        let master_volume_cv = ctx.inputs.get_cv("master_volume_cv");
 
         // Get buffer size
         let buffer_size = ctx.outputs.get_audio("mixed_output")
@@ -211,18 +219,19 @@ impl AudioNode for OutputNode {
             })?
             .len();
 
-        // Process each sample
-        let mut output_samples = Vec::with_capacity(buffer_size);
-        
+        // Process each sample, writing straight into the outputs
+        let (mut mixed_output, mut audio_out) = ctx.outputs.get_audio_and_stereo_mut("mixed_output", "audio_out");
+
         for i in 0..buffer_size {
-            let left_sample = if i < left_input.len() { left_input[i] } else { 0.0 };
-            let right_sample = if i < right_input.len() { right_input[i] } else { 0.0 };
+            let left_sample = left_input.get(i).copied().unwrap_or(0.0) + stereo_left.get(i).copied().unwrap_or(0.0);
+            let right_sample = right_input.get(i).copied().unwrap_or(0.0) + stereo_right.get(i).copied().unwrap_or(0.0);
 
             // Apply mute
             let left_muted = if self.mute > 0.5 { left_sample } else { 0.0 };
             let right_muted = if self.mute > 0.5 { right_sample } else { 0.0 };
 
-            // Apply master volume
+            // Apply master volume (CV modulated per sample)
+            let effective_master_volume = self.master_volume_param.modulate_smoothed(self.master_volume, master_volume_cv, i, self.sample_rate);
             let left_gained = left_muted * effective_master_volume;
             let right_gained = right_muted * effective_master_volume;
 
@@ -258,17 +267,15 @@ impl AudioNode for OutputNode {
             let left_final = self.safety_clip(left_limited);
             let right_final = self.safety_clip(right_limited);
 
-            // Mix to mono for final output (simple sum and attenuate)
-            let mixed_sample = (left_final + right_final) * 0.5;
-            output_samples.push(mixed_sample);
-        }
+            // Stereo output, keeping L/R separate
+            if let Some(audio_out) = audio_out.as_deref_mut().filter(|out| i < out.len()) {
+                audio_out.left[i] = left_final;
+                audio_out.right[i] = right_final;
+            }
 
-        // Write to output
-        if let Some(mixed_output) = ctx.outputs.get_audio_mut("mixed_output") {
-            for (i, &sample) in output_samples.iter().enumerate() {
-                if i < mixed_output.len() {
-                    mixed_output[i] = sample;
-                }
+            // Mono downmix (simple sum and attenuate)
+            if let Some(mixed_output) = mixed_output.as_deref_mut() {
+                mixed_output[i] = (left_final + right_final) * 0.5;
             }
         }
 
@@ -301,6 +308,10 @@ impl AudioNode for OutputNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset internal processing state
         self.limiter_gain_reduction = 1.0;
@@ -311,6 +322,10 @@ impl AudioNode for OutputNode {
         self.envelope_follower = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for output
     }
@@ -327,7 +342,7 @@ impl AudioNode for OutputNode {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::processing::{InputBuffers, OutputBuffers};
+    use crate::processing::{InputBuffers, OutputBuffers, StereoBuffer};
 
     #[test]
     fn test_output_creation() {
@@ -410,6 +425,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(output.process(&mut ctx).is_ok());
@@ -425,6 +442,27 @@ mod tests {
         assert!(ctx.outputs.get_cv("gain_reduction_cv").is_some());
     }
 
+    #[test]
+    fn test_stereo_output_keeps_channels_separate() {
+        let mut output = OutputNode::new(44100.0, "test".to_string());
+        output.set_parameter("master_volume", 1.0).unwrap();
+        
+        let mut inputs = InputBuffers::new();
+        inputs.add_audio("audio_in_l".to_string(), vec![0.5, 0.5]);
+        inputs.add_stereo("audio_in".to_string(), StereoBuffer { left: vec![0.0, 0.25], right: vec![-0.5, -0.5] });
+        
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_stereo("audio_out".to_string(), 2);
+        outputs.allocate_audio("mixed_output".to_string(), 2);
+        
+        let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 2);
+        assert!(output.process(&mut ctx).is_ok());
+        
+        let stereo = ctx.outputs.get_stereo("audio_out").unwrap();
+        assert_eq!(stereo.left, vec![0.5, 0.75]);
+        assert_eq!(stereo.right, vec![-0.5, -0.5]);
+    }
+
     #[test]
     fn test_mute_functionality() {
         let mut output = OutputNode::new(44100.0, "test".to_string());
@@ -444,6 +482,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(output.process(&mut ctx).is_ok());
@@ -475,6 +515,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(output.process(&mut ctx).is_ok());
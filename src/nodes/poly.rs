@@ -0,0 +1,464 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Polyphonic wrapper - one instance of a monophonic node per voice.
+//!
+//! Mono audio and CV ports of the wrapped node become poly ports, channel
+//! `v` of every poly cable driving voice `v`. Stereo inputs are shared by all
+//! voices and stereo outputs are summed. The number of voices processed in a
+//! block follows the poly inputs, so idle voices cost nothing.
+
+use uuid::Uuid;
+
+use crate::graph::plan::EVENT_CAPACITY;
+use crate::graph::{PortType, DEFAULT_MAX_BLOCK_SIZE};
+use crate::parameters::{ParameterDescriptor, ParameterError, Parameterizable};
+use crate::processing::{
+    AudioNode, EventKind, EventList, InputBuffers, NodeInfo, NodeState, OutputBuffers, PortInfo, ProcessContext,
+    ProcessingError, SlotMut, SlotRef, BufferSlot, MAX_VOICES,
+};
+
+/// `node_type` prefix of wrapped nodes, e.g. `poly_vcf`
+pub const POLY_PREFIX: &str = "poly_";
+
+/// Voices of a poly node created by `node_type`
+pub const DEFAULT_POLY_VOICES: usize = 8;
+
+/// Port of the wrapped node and its buffer in the voice contexts
+#[derive(Debug, Clone)]
+struct VoicePort {
+    name: String,
+    port_type: PortType,
+    /// Inputs only get a buffer while connected, like in the execution plan
+    slot: Option<BufferSlot>,
+}
+
+impl VoicePort {
+    fn is_connected(&self, inputs: &InputBuffers) -> bool {
+        match self.port_type {
+            PortType::AudioStereo => inputs.get_stereo(&self.name).is_some(),
+            _ => inputs.get_poly(&self.name).is_some(),
+        }
+    }
+}
+
+/// ポリフォニックノード - ボイスごとに内部ノードを持つ
+pub struct PolyNode {
+    node_info: NodeInfo,
+    voices: Vec<Box<dyn AudioNode>>,
+    contexts: Vec<ProcessContext>,
+    /// Buffers of the unconnected inputs, one set per voice
+    parked: Vec<InputBuffers>,
+    inputs: Vec<VoicePort>,
+    outputs: Vec<VoicePort>,
+}
+
+impl PolyNode {
+    /// Wrap one instance per voice. All instances must be of the same type
+    /// and none may already have poly ports.
+    pub fn new(name: String, voices: Vec<Box<dyn AudioNode>>) -> Result<Self, String> {
+        let first = voices.first().ok_or("A poly node needs at least one voice")?;
+        if voices.len() > MAX_VOICES {
+            return Err(format!("A poly node has at most {} voices", MAX_VOICES));
+        }
+        let inner = first.node_info().clone();
+        if voices.iter().any(|voice| voice.node_info().node_type != inner.node_type) {
+            return Err("All voices of a poly node must have the same type".to_string());
+        }
+        let ports = inner.input_ports.iter().chain(inner.output_ports.iter());
+        if ports.clone().any(|port| port.port_type == PortType::Poly) {
+            return Err(format!("{} is already polyphonic", inner.node_type));
+        }
+
+        let poly_port = |port: &PortInfo| {
+            let mut port = port.clone();
+            if matches!(port.port_type, PortType::AudioMono | PortType::CV) {
+                port.port_type = PortType::Poly;
+            }
+            port
+        };
+        let node_info = NodeInfo {
+            id: Uuid::new_v4(),
+            name,
+            node_type: format!("{}{}", POLY_PREFIX, inner.node_type),
+            category: inner.category.clone(),
+            description: format!("{} ({} voices)", inner.description, voices.len()),
+            input_ports: inner.input_ports.iter().map(poly_port).collect(),
+            output_ports: inner.output_ports.iter().map(poly_port).collect(),
+            latency_samples: first.latency(),
+            supports_bypass: false,
+        };
+
+        let voice_port = |port: &PortInfo| VoicePort {
+            name: port.name.clone(),
+            port_type: port.port_type,
+            slot: None,
+        };
+        let inputs = inner.input_ports.iter().map(voice_port).collect();
+        let outputs = inner.output_ports.iter().map(voice_port).collect();
+        let contexts = voices.iter()
+            .map(|_| {
+                let mut context = ProcessContext::new(InputBuffers::new(), OutputBuffers::new(), 44100.0, DEFAULT_MAX_BLOCK_SIZE);
+                context.events = EventList::with_capacity(EVENT_CAPACITY);
+                context
+            })
+            .collect();
+
+        let mut poly = Self { node_info, voices, contexts, parked: Vec::new(), inputs, outputs };
+        poly.allocate_buffers(DEFAULT_MAX_BLOCK_SIZE);
+        Ok(poly)
+    }
+
+    /// Wrap `voices` instances of a built-in node type
+    pub fn from_type(inner_type: &str, voices: usize, sample_rate: f32, name: String) -> Result<Self, String> {
+        let instances = (0..voices)
+            .map(|_| super::create_builtin_node(inner_type, sample_rate, name.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::new(name, instances)
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Instance processing voice `index`
+    pub fn voice(&self, index: usize) -> Option<&dyn AudioNode> {
+        self.voices.get(index).map(|voice| voice.as_ref())
+    }
+
+    pub fn voice_mut(&mut self, index: usize) -> Option<&mut (dyn AudioNode + 'static)> {
+        self.voices.get_mut(index).map(|voice| voice.as_mut())
+    }
+
+    /// Give every voice a buffer of `block_size` for each port (allocates).
+    /// Input buffers start parked and are moved into the voice contexts by
+    /// `connect_inputs`; every voice gets the same layout, so one slot per
+    /// port serves all.
+    fn allocate_buffers(&mut self, block_size: usize) {
+        self.parked.clear();
+        for context in self.contexts.iter_mut() {
+            let mut outputs = OutputBuffers::new();
+            for port in self.outputs.iter_mut() {
+                port.slot = Some(outputs.allocate_slot(port.name.clone(), port.port_type, block_size));
+            }
+
+            // Allocated in the context first, so it keeps room for every input
+            let mut inputs = InputBuffers::new();
+            let mut parked = InputBuffers::new();
+            for port in &self.inputs {
+                inputs.allocate_slot(port.name.clone(), port.port_type, block_size);
+            }
+            for port in &self.inputs {
+                inputs.move_port(&port.name, port.port_type, &mut parked);
+            }
+
+            context.inputs = inputs;
+            context.outputs = outputs;
+            self.parked.push(parked);
+        }
+        self.inputs.iter_mut().for_each(|port| port.slot = None);
+    }
+
+    /// Move the buffers of inputs connected or disconnected since the last
+    /// block between the voice contexts and `parked`. Does not allocate.
+    fn connect_inputs(&mut self, inputs: &InputBuffers) {
+        for port in &self.inputs {
+            let connected = port.is_connected(inputs);
+            if port.slot.is_some() == connected {
+                continue;
+            }
+            for (context, parked) in self.contexts.iter_mut().zip(self.parked.iter_mut()) {
+                if connected {
+                    parked.move_port(&port.name, port.port_type, &mut context.inputs);
+                } else {
+                    context.inputs.move_port(&port.name, port.port_type, parked);
+                }
+            }
+        }
+
+        let layout = &self.contexts[0].inputs;
+        for port in self.inputs.iter_mut() {
+            port.slot = layout.find_slot(&port.name, port.port_type);
+        }
+    }
+
+    /// Voices to process: the widest poly input, at least one
+    fn active_voices(&self, inputs: &InputBuffers) -> usize {
+        self.inputs.iter()
+            .filter_map(|port| inputs.get_poly(&port.name))
+            .map(|buffer| buffer.voices)
+            .max()
+            .unwrap_or(0)
+            .clamp(1, self.voices.len())
+    }
+}
+
+fn copy_prefix(target: &mut [f32], source: &[f32]) {
+    let len = target.len().min(source.len());
+    target[..len].copy_from_slice(&source[..len]);
+}
+
+impl Parameterizable for PolyNode {
+    fn get_parameter_descriptors(&self) -> Vec<Box<dyn ParameterDescriptor>> {
+        self.voices[0].get_parameter_descriptors()
+    }
+
+    /// Parameters are shared by all voices
+    fn set_parameter(&mut self, name: &str, value: f32) -> Result<(), ParameterError> {
+        self.voices.iter_mut().try_for_each(|voice| voice.set_parameter(name, value))
+    }
+
+    fn get_parameter(&self, name: &str) -> Result<f32, ParameterError> {
+        self.voices[0].get_parameter(name)
+    }
+
+    fn get_all_parameters(&self) -> std::collections::HashMap<String, f32> {
+        self.voices[0].get_all_parameters()
+    }
+}
+
+impl AudioNode for PolyNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        if self.inputs.iter().any(|port| port.slot.is_some() != port.is_connected(&ctx.inputs)) {
+            self.connect_inputs(&ctx.inputs);
+        }
+
+        let frames = ctx.buffer_size;
+        let active = self.active_voices(&ctx.inputs);
+
+        for (v, (voice, context)) in self.voices.iter_mut().zip(self.contexts.iter_mut()).take(active).enumerate() {
+            context.sample_rate = ctx.sample_rate;
+            context.buffer_size = frames;
+            context.timestamp = ctx.timestamp;
+            context.bpm = ctx.bpm;
+            context.transport = ctx.transport;
+            // Parameter events already reached every voice through `handle_event`
+            // (and copying their names would allocate)
+            context.events.clear();
+            for event in ctx.events.iter().filter(|event| !matches!(event.kind, EventKind::Parameter { .. })) {
+                context.events.push(event.offset, event.kind.clone());
+            }
+
+            for port in &self.inputs {
+                let Some(slot) = port.slot else { continue };
+                match context.inputs.slot_mut(slot) {
+                    SlotMut::Mono(target) => {
+                        target.clear();
+                        // A single-voice cable drives every voice
+                        let source = ctx.inputs.get_poly(&port.name)
+                            .and_then(|poly| poly.active().get(if poly.voices == 1 { 0 } else { v }));
+                        match source {
+                            Some(data) => target.extend_from_slice(data),
+                            None => target.resize(frames, 0.0),
+                        }
+                    }
+                    SlotMut::Stereo(target) => {
+                        if let Some(data) = ctx.inputs.get_stereo(&port.name) {
+                            target.left.clear();
+                            target.left.extend_from_slice(&data.left);
+                            target.right.clear();
+                            target.right.extend_from_slice(&data.right);
+                        }
+                    }
+                    SlotMut::Poly(_) => {}
+                }
+            }
+
+            for port in &self.outputs {
+                let Some(slot) = port.slot else { continue };
+                match context.outputs.slot_mut(slot) {
+                    SlotMut::Mono(target) => {
+                        target.resize(frames, 0.0);
+                        target.fill(0.0);
+                    }
+                    SlotMut::Stereo(target) => {
+                        target.resize(frames);
+                        target.fill(0.0);
+                    }
+                    SlotMut::Poly(_) => {}
+                }
+            }
+
+            voice.process(context)?;
+
+            for port in &self.outputs {
+                let Some(slot) = port.slot else { continue };
+                match context.outputs.slot(slot) {
+                    SlotRef::Mono(data) => {
+                        if let Some(target) = ctx.outputs.get_poly_mut(&port.name) {
+                            copy_prefix(&mut target.channels[v], data);
+                        }
+                    }
+                    SlotRef::Stereo(data) => {
+                        if let Some(target) = ctx.outputs.get_stereo_mut(&port.name) {
+                            target.left.iter_mut().zip(data.left.iter()).for_each(|(t, s)| *t += s);
+                            target.right.iter_mut().zip(data.right.iter()).for_each(|(t, s)| *t += s);
+                        }
+                    }
+                    SlotRef::Poly(_) => {}
+                }
+            }
+        }
+
+        for port in &self.outputs {
+            if let Some(target) = ctx.outputs.get_poly_mut(&port.name) {
+                target.voices = active;
+            }
+        }
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.node_info
+    }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
+    fn reset(&mut self) {
+        self.voices.iter_mut().for_each(|voice| voice.reset());
+    }
+
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.allocate_buffers(max_block_size);
+        self.voices.iter_mut().for_each(|voice| voice.prepare(sample_rate, max_block_size));
+    }
+
+    /// Gates, notes and parameters go to every voice
+    fn handle_event(&mut self, event: &EventKind) {
+        self.voices.iter_mut().for_each(|voice| voice.handle_event(event));
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        self.voices[0].save_state()
+    }
+
+    fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+        self.voices.iter_mut().try_for_each(|voice| voice.load_state(state))
+    }
+
+    fn latency(&self) -> u32 {
+        self.voices[0].latency()
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::VCANode;
+    use crate::processing::{EventKind, PolyBuffer};
+
+    #[test]
+    fn test_voices_match_mono_instances() {
+        let mut poly = PolyNode::from_type("vca", 4, 44100.0, "poly_vca".to_string()).unwrap();
+        assert_eq!(poly.node_info().node_type, "poly_vca");
+        assert_eq!(poly.node_info().input_ports[0].port_type, PortType::Poly);
+        poly.set_parameter("gain", 0.5).unwrap();
+
+        let mut audio = PolyBuffer::new(64);
+        audio.voices = 3;
+        for (v, channel) in audio.channels.iter_mut().enumerate().take(3) {
+            channel.fill(0.2 * (v + 1) as f32);
+        }
+        let mut inputs = InputBuffers::new();
+        inputs.add_poly("audio_in".to_string(), audio.clone());
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_poly("audio_out".to_string(), 64);
+        outputs.allocate_poly("gain_cv_out".to_string(), 64);
+        let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 64);
+        poly.process(&mut ctx).unwrap();
+
+        let result = ctx.outputs.get_poly("audio_out").unwrap();
+        assert_eq!(result.voices, 3);
+        for v in 0..3 {
+            let mut mono = VCANode::new(44100.0, "vca".to_string());
+            mono.set_parameter("gain", 0.5).unwrap();
+            let mut inputs = InputBuffers::new();
+            inputs.add_audio("audio_in".to_string(), audio.channels[v].clone());
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_audio("audio_out".to_string(), 64);
+            let mut mono_ctx = ProcessContext::new(inputs, outputs, 44100.0, 64);
+            mono.process(&mut mono_ctx).unwrap();
+
+            assert_eq!(result.channels[v], mono_ctx.outputs.get_audio("audio_out").unwrap());
+            assert!(result.channels[v][0] > 0.0);
+        }
+        assert!(result.channels[3].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_inputs_follow_connections() {
+        let mut poly = PolyNode::from_type("vca", 2, 44100.0, "poly_vca".to_string()).unwrap();
+        poly.prepare(44100.0, 64);
+        let mut audio = PolyBuffer::new(64);
+        audio.voices = 2;
+        audio.channels.iter_mut().take(2).for_each(|channel| channel.fill(0.5));
+
+        let run = |connected: bool, poly: &mut PolyNode| {
+            let mut inputs = InputBuffers::new();
+            if connected {
+                inputs.add_poly("audio_in".to_string(), audio.clone());
+            }
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_poly("audio_out".to_string(), 64);
+            let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 64);
+            poly.process(&mut ctx).unwrap();
+            ctx.outputs.get_poly("audio_out").unwrap().channels[1][63]
+        };
+
+        let level = run(true, &mut poly);
+        assert!(level > 0.0);
+        assert_eq!(run(false, &mut poly), 0.0);
+        assert!(poly.contexts[1].inputs.get_audio("audio_in").is_none());
+        assert_eq!(run(true, &mut poly), level);
+        assert_eq!(poly.parked[1].find_slot("audio_in", PortType::AudioMono), None);
+    }
+
+    #[test]
+    fn test_events_reach_every_voice() {
+        let mut poly = PolyNode::from_type("adsr", 2, 44100.0, "poly_adsr".to_string()).unwrap();
+        poly.prepare(44100.0, 64);
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_poly("cv_out".to_string(), 64);
+        let mut ctx = ProcessContext::new(InputBuffers::new(), outputs, 44100.0, 64);
+        ctx.events.push(0, EventKind::GateOn);
+        ctx.events.push(0, EventKind::Parameter { name: "attack".to_string(), value: 0.001 });
+        ctx.process_node(&mut poly, &mut ctx.sub_block_context()).unwrap();
+
+        assert_eq!(poly.contexts[0].events.len(), 1);
+        assert_eq!(poly.voice(1).unwrap().get_parameter("attack").unwrap(), 0.001);
+        // Without poly inputs only the first voice runs
+        assert!(ctx.outputs.get_poly("cv_out").unwrap().channels[0][63] > 0.0);
+    }
+
+    #[test]
+    fn test_rejects_poly_inner_node() {
+        let inner = PolyNode::from_type("vca", 2, 44100.0, "inner".to_string()).unwrap();
+        assert!(PolyNode::new("outer".to_string(), vec![Box::new(inner)]).is_err());
+        assert!(PolyNode::new("empty".to_string(), Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use uuid::Uuid;
+
+use crate::parameters::{BasicParameter, Parameterizable, ParameterDescriptor};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::graph::PortType;
+use crate::define_parameters;
+
+/// ポリ→モノラル ミックスダウン
+pub struct PolySumNode {
+    node_info: NodeInfo,
+
+    // Parameters
+    level: f32,
+    active: f32,
+}
+
+impl PolySumNode {
+    pub fn new(_sample_rate: f32, name: String) -> Self {
+        let node_info = NodeInfo {
+            id: Uuid::new_v4(),
+            name,
+            node_type: "poly_sum".to_string(),
+            category: NodeCategory::Mixing,
+            description: "Sums all voices of a poly cable into one mono signal".to_string(),
+            input_ports: vec![
+                PortInfo::new("poly_in", PortType::Poly)
+                    .with_description("Polyphonic input"),
+            ],
+            output_ports: vec![
+                PortInfo::new("audio_out", PortType::AudioMono)
+                    .with_description("Sum of all voices"),
+            ],
+            latency_samples: 0,
+            supports_bypass: false,
+        };
+
+        Self {
+            node_info,
+            level: 1.0,
+            active: 1.0,
+        }
+    }
+}
+
+impl Parameterizable for PolySumNode {
+    define_parameters! {
+        level: BasicParameter::new("level", 0.0, 2.0, 1.0),
+        active: BasicParameter::new("active", 0.0, 1.0, 1.0)
+    }
+}
+
+impl AudioNode for PolySumNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        let output = ctx.outputs.get_audio_mut("audio_out")
+            .ok_or_else(|| ProcessingError::OutputBufferError {
+                port_name: "audio_out".to_string()
+            })?;
+
+        match ctx.inputs.get_poly("poly_in") {
+            Some(input) if self.active > 0.5 => {
+                input.sum_into(output);
+                output.iter_mut().for_each(|sample| *sample *= self.level);
+            }
+            _ => output.fill(0.0),
+        }
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.node_info
+    }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
+    fn reset(&mut self) {}
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
@@ -19,7 +19,7 @@
 use uuid::Uuid;
 
 use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, NodeState};
 use crate::graph::PortType;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -463,22 +463,12 @@ impl AudioNode for QuantizerNode {
         // Get input signals
         let cv_input = ctx.inputs.get_cv("cv_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let root_note_cv = ctx.inputs.get_cv_value("root_note_cv");
-        let transpose_cv = ctx.inputs.get_cv_value("transpose_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let root_note_cv = ctx.inputs.get_cv("root_note_cv");
+        let transpose_cv = ctx.inputs.get_cv("transpose_cv");
         let scale_cv = ctx.inputs.get_cv_value("scale_cv");
 
-        // Apply CV modulation
-        let effective_root = self.root_note_param.modulate(
-            self.root_note_param.get_base_value(), 
-            root_note_cv
-        );
-        let effective_transpose = self.transpose_param.modulate(
-            self.transpose_param.get_base_value(), 
-            transpose_cv
-        );
-
-        // Update scale from CV if provided
+        // Update scale from CV if provided (control rate)
         let current_scale_type = if scale_cv != 0.0 {
             ScaleType::from_f32(scale_cv.clamp(0.0, 7.0))
         } else {
@@ -504,6 +494,18 @@ impl AudioNode for QuantizerNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_root = self.root_note_param.modulate_at(
+                self.root_note_param.get_base_value(), 
+                root_note_cv,
+                i
+            );
+            let effective_transpose = self.transpose_param.modulate_at(
+                self.transpose_param.get_base_value(), 
+                transpose_cv,
+                i
+            );
+
             // Quantize the voltage
             let (quantized_cv, quantization_changed) = self.quantize_voltage(
                 input_cv, 
@@ -566,6 +568,10 @@ impl AudioNode for QuantizerNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset quantizer state
         self.last_quantized_output = 0.0;
@@ -573,6 +579,20 @@ impl AudioNode for QuantizerNode {
         self.last_trigger_state = false;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        serde_json::to_value(self.custom_scale).ok()
+    }
+
+    fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+        self.custom_scale = serde_json::from_value(state.clone())
+            .map_err(|e| format!("Invalid custom scale: {}", e))?;
+        Ok(())
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for quantization
     }
@@ -637,6 +657,8 @@ mod tests {
             buffer_size: 5,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -672,6 +694,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -713,6 +737,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -745,6 +771,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -783,6 +811,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -813,6 +843,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(quant.process(&mut ctx).is_ok());
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -85,17 +85,17 @@ impl RingModulatorNode {
         let mix_param = ModulatableParameter::new(
             BasicParameter::new("mix", 0.0, 1.0, 1.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let carrier_gain_param = ModulatableParameter::new(
             BasicParameter::new("carrier_gain", 0.0, 2.0, 1.0),
             0.6  // 60% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let modulator_gain_param = ModulatableParameter::new(
             BasicParameter::new("modulator_gain", 0.0, 2.0, 1.0),
             0.6  // 60% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -186,15 +186,10 @@ impl AudioNode for RingModulatorNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let mix_cv = ctx.inputs.get_cv_value("mix_cv");
-        let carrier_gain_cv = ctx.inputs.get_cv_value("carrier_gain_cv");
-        let modulator_gain_cv = ctx.inputs.get_cv_value("modulator_gain_cv");
-
-        // Apply CV modulation
-        let effective_mix = self.mix_param.modulate(self.mix, mix_cv);
-        let effective_carrier_gain = self.carrier_gain_param.modulate(self.carrier_gain, carrier_gain_cv);
-        let effective_modulator_gain = self.modulator_gain_param.modulate(self.modulator_gain, modulator_gain_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let mix_cv = ctx.inputs.get_cv("mix_cv");
+        let carrier_gain_cv = ctx.inputs.get_cv("carrier_gain_cv");
+        let modulator_gain_cv = ctx.inputs.get_cv("modulator_gain_cv");
 
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
@@ -216,6 +211,11 @@ impl AudioNode for RingModulatorNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_mix = self.mix_param.modulate_smoothed(self.mix, mix_cv, i, self.sample_rate);
+            let effective_carrier_gain = self.carrier_gain_param.modulate_smoothed(self.carrier_gain, carrier_gain_cv, i, self.sample_rate);
+            let effective_modulator_gain = self.modulator_gain_param.modulate_smoothed(self.modulator_gain, modulator_gain_cv, i, self.sample_rate);
+
             *output_sample = self.ring_modulate(
                 carrier_sample,
                 modulator_sample,
@@ -229,7 +229,7 @@ impl AudioNode for RingModulatorNode {
         if let Some(modulator_out) = ctx.outputs.get_audio_mut("modulator_out") {
             for (i, output_sample) in modulator_out.iter_mut().enumerate() {
                 *output_sample = if i < modulator_input.len() { 
-                    modulator_input[i] * effective_modulator_gain
+                    modulator_input[i] * self.modulator_gain_param.modulate_at(self.modulator_gain, modulator_gain_cv, i)
                 } else { 
                     0.0 
                 };
@@ -243,11 +243,19 @@ impl AudioNode for RingModulatorNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset DC filter state
         self.dc_filter_state = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for ring modulation
     }
@@ -306,6 +314,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -337,6 +347,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -368,6 +380,8 @@ mod tests {
             buffer_size: 128,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -399,6 +413,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -429,6 +445,8 @@ mod tests {
             buffer_size: 128,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -458,6 +476,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -488,6 +508,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
@@ -518,6 +540,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(ring_mod.process(&mut ctx).is_ok());
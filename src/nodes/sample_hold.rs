@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -84,7 +84,7 @@ impl SampleHoldNode {
         let threshold_param = ModulatableParameter::new(
             BasicParameter::new("trigger_threshold", 0.1, 5.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_control_rate().with_smoothing(Smoothing::DEFAULT); // しきい値はブロック単位
 
         Self {
             node_info,
@@ -218,11 +218,8 @@ impl AudioNode for SampleHoldNode {
         let signal_input = ctx.inputs.get_audio("signal_in").unwrap_or(&[]);
         let trigger_input = ctx.inputs.get_audio("trigger_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let threshold_cv = ctx.inputs.get_cv_value("threshold_cv");
-
-        // Apply CV modulation
-        let effective_threshold = self.threshold_param.modulate(self.trigger_threshold, threshold_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let threshold_cv = ctx.inputs.get_cv("threshold_cv");
 
         // Get buffer size
         let buffer_size = ctx.outputs.get_audio("signal_out")
@@ -248,6 +245,9 @@ impl AudioNode for SampleHoldNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_threshold = self.threshold_param.modulate_smoothed(self.trigger_threshold, threshold_cv, i, self.sample_rate);
+
             // Process sample and hold
             let output_sample = self.process_sample_hold(input_sample, trigger_sample, effective_threshold);
             output_samples.push(output_sample);
@@ -291,6 +291,10 @@ impl AudioNode for SampleHoldNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset sample and hold state
         self.held_value = 0.0;
@@ -300,6 +304,10 @@ impl AudioNode for SampleHoldNode {
         self.slew_current = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for sample and hold
     }
@@ -362,6 +370,8 @@ mod tests {
             buffer_size: 5,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -403,6 +413,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Trigger manual trigger
@@ -442,6 +454,8 @@ mod tests {
             buffer_size: 5,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(sh.process(&mut ctx).is_ok());
@@ -478,6 +492,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(sh.process(&mut ctx).is_ok());
@@ -510,6 +526,8 @@ mod tests {
             buffer_size: 3,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(sh.process(&mut ctx).is_ok());
@@ -545,6 +563,8 @@ mod tests {
             buffer_size: 1,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(sh.process(&mut ctx).is_ok());
@@ -16,13 +16,14 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, NodeState};
 use crate::graph::PortType;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SequenceStep {
     pub note: f32,      // Note in Hz (0.0 = rest)
     pub gate: bool,     // Gate on/off
@@ -81,6 +82,7 @@ pub struct SequencerNode {
     swing: f32,          // 0.0 ~ 1.0 (swing amount)
     gate_length: f32,    // 0.1 ~ 1.0 (gate length as fraction of step)
     transpose: f32,      // -24.0 ~ +24.0 semitones
+    tempo_sync: f32,     // 1.0 = follow the transport tempo instead of `bpm`
     active: f32,
     
     // CV Modulation parameters
@@ -152,7 +154,7 @@ impl SequencerNode {
         let bpm_param = ModulatableParameter::new(
             BasicParameter::new("bpm", 60.0, 200.0, 120.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let transpose_param = ModulatableParameter::new(
             BasicParameter::new("transpose", -24.0, 24.0, 0.0),
@@ -184,6 +186,7 @@ impl SequencerNode {
             swing: 0.0,          // No swing
             gate_length: 0.5,    // 50% gate length
             transpose: 0.0,      // No transpose
+            tempo_sync: 0.0,     // Own tempo
             active: 1.0,
 
             bpm_param,
@@ -493,6 +496,16 @@ impl Parameterizable for SequencerNode {
                     })
                 }
             },
+            "tempo_sync" => {
+                if (0.0..=1.0).contains(&value) {
+                    self.tempo_sync = value;
+                    Ok(())
+                } else {
+                    Err(crate::parameters::ParameterError::OutOfRange { 
+                        value, min: 0.0, max: 1.0
+                    })
+                }
+            },
             "active" => {
                 if value >= 0.0 && value <= 1.0 {
                     self.active = value;
@@ -541,6 +554,7 @@ impl Parameterizable for SequencerNode {
             "swing" => Ok(self.swing),
             "gate_length" => Ok(self.gate_length),
             "transpose" => Ok(self.transpose),
+            "tempo_sync" => Ok(self.tempo_sync),
             "active" => Ok(self.active),
             _ => Err(crate::parameters::ParameterError::NotFound { name: name.to_string() })
         }
@@ -555,6 +569,7 @@ impl Parameterizable for SequencerNode {
         params.insert("swing".to_string(), self.swing);
         params.insert("gate_length".to_string(), self.gate_length);
         params.insert("transpose".to_string(), self.transpose);
+        params.insert("tempo_sync".to_string(), self.tempo_sync);
         params.insert("active".to_string(), self.active);
         params.insert("current_step".to_string(), self.current_step as f32);
         params.insert("running".to_string(), if self.running { 1.0 } else { 0.0 });
@@ -578,6 +593,7 @@ impl Parameterizable for SequencerNode {
             Box::new(BasicParameter::new("swing", 0.0, 1.0, 0.0)),
             Box::new(BasicParameter::new("gate_length", 0.1, 1.0, 0.5)),
             Box::new(BasicParameter::new("transpose", -24.0, 24.0, 0.0)),
+            Box::new(BasicParameter::new("tempo_sync", 0.0, 1.0, 0.0)),
             Box::new(BasicParameter::new("active", 0.0, 1.0, 1.0)),
         ]
     }
@@ -610,16 +626,10 @@ impl AudioNode for SequencerNode {
         let reset_input = ctx.inputs.get_audio("reset_in").unwrap_or(&[]);
         let run_stop_input = ctx.inputs.get_audio("run_stop_in").unwrap_or(&[]);
         
-        // Get CV inputs
-        let bpm_cv = ctx.inputs.get_cv_value("bpm_cv");
-        let transpose_cv = ctx.inputs.get_cv_value("transpose_cv");
-
-        // Apply CV modulation
-        let effective_bpm = self.bpm_param.modulate(self.bpm, bpm_cv);
-        let effective_transpose = self.transpose_param.modulate(self.transpose, transpose_cv);
-
-        // Update timing
-        self.update_timing(effective_bpm);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let bpm_cv = ctx.inputs.get_cv("bpm_cv");
+        let transpose_cv = ctx.inputs.get_cv("transpose_cv");
+        let host_tempo = (self.tempo_sync > 0.5).then_some(ctx.transport.tempo);
 
         // Get buffer size
         let buffer_size = ctx.outputs.get_cv("note_cv")
@@ -635,6 +645,16 @@ impl AudioNode for SequencerNode {
         let mut eos_samples = Vec::with_capacity(buffer_size);
 
         for i in 0..buffer_size {
+            // Apply CV modulation per sample
+            let effective_bpm = match host_tempo {
+                Some(tempo) => tempo,
+                None => self.bpm_param.modulate_smoothed(self.bpm, bpm_cv, i, self.sample_rate),
+            };
+            let effective_transpose = self.transpose_param.modulate_at(self.transpose, transpose_cv, i);
+
+            // Update timing
+            self.update_timing(effective_bpm);
+
             // Process triggers
             let clock_signal = if i < clock_input.len() { clock_input[i] } else { 0.0 };
             let reset_signal = if i < reset_input.len() { reset_input[i] } else { 0.0 };
@@ -746,11 +766,36 @@ impl AudioNode for SequencerNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset sequencer state
         self.reset();
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.update_timing(self.bpm);
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        serde_json::to_value(&self.steps).ok()
+    }
+
+    fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+        let steps: Vec<SequenceStep> = serde_json::from_value(state.clone())
+            .map_err(|e| format!("Invalid sequencer steps: {}", e))?;
+        if steps.len() > self.steps.len() {
+            return Err(format!("Sequencer has at most {} steps, got {}", self.steps.len(), steps.len()));
+        }
+        for (i, step) in steps.iter().enumerate() {
+            self.set_step(i, step.note, step.gate, step.velocity);
+        }
+        Ok(())
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for sequencing
     }
@@ -833,6 +878,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -914,6 +961,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(seq.process(&mut ctx).is_ok());
@@ -945,6 +994,8 @@ mod tests {
             buffer_size: 8192,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(seq.process(&mut ctx).is_ok());
@@ -993,6 +1044,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(seq.process(&mut ctx).is_ok());
@@ -1006,4 +1059,20 @@ mod tests {
         assert!((avg_note - 0.0).abs() < 0.001, "Should output zero note CV when inactive: {}", avg_note);
         assert!((avg_gate - 0.0).abs() < 0.001, "Should output zero gate when inactive: {}", avg_gate);
     }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut seq = SequencerNode::new(44100.0, "test_seq".to_string());
+        seq.set_step(3, 110.0, false, 0.25);
+        let state = seq.save_state().unwrap();
+
+        let mut loaded = SequencerNode::new(44100.0, "loaded".to_string());
+        loaded.load_state(&state).unwrap();
+        assert_eq!(loaded.steps[3].note, 110.0);
+        assert!(!loaded.steps[3].gate);
+        assert_eq!(loaded.steps[3].velocity, 0.25);
+        assert_eq!(loaded.steps[0].note, seq.steps[0].note);
+
+        assert!(loaded.load_state(&serde_json::json!({"steps": 3})).is_err());
+    }
 }
\ No newline at end of file
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -69,12 +69,12 @@ impl SineOscillatorNode {
         let frequency_param = ModulatableParameter::new(
             BasicParameter::new("frequency", 20.0, 20000.0, 440.0).with_unit("Hz"),
             1.0  // 100% CV modulation for precise control
-        ).with_curve(ModulationCurve::Exponential); // 周波数は指数的変化
+        ).with_curve(ModulationCurve::Exponential).with_smoothing(Smoothing::DEFAULT); // 周波数は指数的変化
 
         let amplitude_param = ModulatableParameter::new(
             BasicParameter::new("amplitude", 0.0, 1.0, 0.5),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -122,13 +122,9 @@ impl AudioNode for SineOscillatorNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let frequency_cv = ctx.inputs.get_cv_value("frequency_cv");
-        let amplitude_cv = ctx.inputs.get_cv_value("amplitude_cv");
-
-        // Apply CV modulation with exponential frequency control
-        let effective_frequency = self.frequency_param.modulate(self.frequency, frequency_cv);
-        let effective_amplitude = self.amplitude_param.modulate(self.amplitude, amplitude_cv);
+        // Get CV inputs (full buffers for per-sample modulation)
+        let frequency_cv = ctx.inputs.get_cv("frequency_cv");
+        let amplitude_cv = ctx.inputs.get_cv("amplitude_cv");
 
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
@@ -138,13 +134,13 @@ impl AudioNode for SineOscillatorNode {
 
         // Generate high-quality sine wave with phase continuity
         for (i, sample) in output.iter_mut().enumerate() {
-            let phase_increment = 2.0 * std::f32::consts::PI * effective_frequency / ctx.sample_rate;
-            let current_phase = self.phase + phase_increment * i as f32;
-            *sample = self.generate_sine_sample(current_phase) * effective_amplitude;
-        }
+            // Apply CV modulation with exponential frequency control
+            let effective_frequency = self.frequency_param.modulate_smoothed(self.frequency, frequency_cv, i, self.sample_rate);
+            let effective_amplitude = self.amplitude_param.modulate_smoothed(self.amplitude, amplitude_cv, i, self.sample_rate);
 
-        // Advance phase for next buffer
-        self.advance_phase(effective_frequency, ctx.buffer_size);
+            *sample = self.generate_sine_sample(self.phase) * effective_amplitude;
+            self.advance_phase(effective_frequency, 1);
+        }
 
         Ok(())
     }
@@ -153,10 +149,18 @@ impl AudioNode for SineOscillatorNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         self.phase = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for oscillator
     }
@@ -207,6 +211,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -244,6 +250,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(osc.process(&mut ctx).is_ok());
@@ -272,6 +280,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(osc.process(&mut ctx).is_ok());
@@ -298,6 +308,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Process first buffer
@@ -335,6 +347,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(osc.process(&mut ctx).is_ok());
@@ -122,12 +122,12 @@ impl SpectrumAnalyzerNode {
         let smoothing_param = ModulatableParameter::new(
             BasicParameter::new("smoothing", 0.0, 1.0, 0.3),
             1.0  // 100% CV modulation range
-        );
+        ).with_control_rate(); // 解析はFFTフレーム単位
         
         let gain_param = ModulatableParameter::new(
             BasicParameter::new("gain", 0.1, 10.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_control_rate();
 
         let fft_size = 1024; // Default FFT size
         
@@ -419,12 +419,12 @@ impl AudioNode for SpectrumAnalyzerNode {
 
         // Get input signals
         let signal_input = ctx.inputs.get_audio("signal_in").unwrap_or(&[]);
-        let smoothing_cv = ctx.inputs.get_cv_value("smoothing_cv");
-        let gain_cv = ctx.inputs.get_cv_value("gain_cv");
+        let smoothing_cv = ctx.inputs.get_cv("smoothing_cv");
+        let gain_cv = ctx.inputs.get_cv("gain_cv");
 
-        // Apply CV modulation
-        let effective_smoothing = self.smoothing_param.modulate(self.smoothing, smoothing_cv);
-        let _effective_gain = self.gain_param.modulate(self.gain, gain_cv);
+        // Apply CV modulation (control rate)
+        let effective_smoothing = self.smoothing_param.modulate_at(self.smoothing, smoothing_cv, 0);
+        let _effective_gain = self.gain_param.modulate_at(self.gain, gain_cv, 0);
 
         // Add samples to input buffer
         for &sample in signal_input {
@@ -579,6 +579,10 @@ impl AudioNode for SpectrumAnalyzerNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset internal processing state
         self.input_buffer.clear();
@@ -597,6 +601,11 @@ impl AudioNode for SpectrumAnalyzerNode {
         self.generate_window();
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.frequency_bins = Self::calculate_frequency_bins(self.frequency_bins.len() * 2, sample_rate);
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for spectrum analysis
     }
@@ -720,6 +729,8 @@ mod tests {
             buffer_size: 2048,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(analyzer.process(&mut ctx).is_ok());
@@ -765,6 +776,8 @@ mod tests {
             buffer_size: 4,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(analyzer.process(&mut ctx).is_ok());
@@ -0,0 +1,690 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Subpatch (macro) node - a `ProcessingGraph` wrapped as one `AudioNode`.
+//!
+//! Every exposed port is backed by a hidden boundary node inside the graph:
+//! inputs are played back into the graph by a source node, outputs are
+//! recorded by a sink node. The inner graph therefore runs through its own
+//! execution plan like any other graph. Promoted parameters forward to the
+//! inner node they belong to.
+
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::graph::{PortType, ProcessingGraph, DEFAULT_MAX_BLOCK_SIZE};
+use crate::parameters::{ParameterDescriptor, ParameterError, Parameterizable};
+use crate::patch::{ExposedPort, PatchConnection, PatchNode, PatchPosition, PromotedParameter, SubpatchDefinition};
+use crate::processing::{
+    AudioNode, InputBuffers, NodeCategory, NodeInfo, OutputBuffers, PolyBuffer, PortInfo, ProcessContext,
+    ProcessingError, StereoBuffer,
+};
+
+const INPUT_NODE_TYPE: &str = "subpatch_input";
+const OUTPUT_NODE_TYPE: &str = "subpatch_output";
+
+/// Signal handed between the outer and the inner graph
+#[derive(Debug)]
+enum HeldSignal {
+    Mono(Vec<f32>),
+    Stereo(StereoBuffer),
+    Poly(PolyBuffer),
+}
+
+impl HeldSignal {
+    fn new(port_type: PortType) -> Self {
+        match port_type {
+            PortType::AudioStereo => HeldSignal::Stereo(StereoBuffer::new(DEFAULT_MAX_BLOCK_SIZE)),
+            PortType::AudioMono | PortType::CV => HeldSignal::Mono(vec![0.0; DEFAULT_MAX_BLOCK_SIZE]),
+            PortType::Poly => HeldSignal::Poly(PolyBuffer::new(DEFAULT_MAX_BLOCK_SIZE)),
+        }
+    }
+
+    /// Take over an input port - an unconnected port reads as silence
+    fn read(&mut self, inputs: &InputBuffers, port: &str, port_type: PortType, frames: usize) {
+        match self {
+            HeldSignal::Mono(held) => {
+                let source = match port_type {
+                    PortType::CV => inputs.get_cv(port),
+                    _ => inputs.get_audio(port),
+                };
+                held.clear();
+                match source {
+                    Some(data) => held.extend_from_slice(data),
+                    None => held.resize(frames, 0.0),
+                }
+            }
+            HeldSignal::Stereo(held) => match inputs.get_stereo(port) {
+                Some(data) => {
+                    held.left.clear();
+                    held.left.extend_from_slice(&data.left);
+                    held.right.clear();
+                    held.right.extend_from_slice(&data.right);
+                }
+                None => {
+                    held.resize(frames);
+                    held.fill(0.0);
+                }
+            },
+            HeldSignal::Poly(held) => match inputs.get_poly(port) {
+                Some(data) => {
+                    for (channel, source) in held.channels.iter_mut().zip(data.channels.iter()) {
+                        channel.clear();
+                        channel.extend_from_slice(source);
+                    }
+                    held.voices = data.voices;
+                }
+                None => {
+                    held.resize(frames);
+                    held.fill(0.0);
+                    held.voices = 0;
+                }
+            },
+        }
+    }
+
+    /// Copy into an output port
+    fn write(&self, outputs: &mut OutputBuffers, port: &str, port_type: PortType) {
+        match self {
+            HeldSignal::Mono(held) => {
+                let target = match port_type {
+                    PortType::CV => outputs.get_cv_mut(port),
+                    _ => outputs.get_audio_mut(port),
+                };
+                if let Some(target) = target {
+                    copy_prefix(target, held);
+                }
+            }
+            HeldSignal::Stereo(held) => {
+                if let Some(target) = outputs.get_stereo_mut(port) {
+                    copy_prefix(&mut target.left, &held.left);
+                    copy_prefix(&mut target.right, &held.right);
+                }
+            }
+            HeldSignal::Poly(held) => {
+                if let Some(target) = outputs.get_poly_mut(port) {
+                    for (channel, source) in target.channels.iter_mut().zip(held.channels.iter()) {
+                        copy_prefix(channel, source);
+                    }
+                    target.voices = held.voices;
+                }
+            }
+        }
+    }
+}
+
+fn copy_prefix(target: &mut [f32], source: &[f32]) {
+    let len = target.len().min(source.len());
+    target[..len].copy_from_slice(&source[..len]);
+}
+
+/// Hidden node standing for one exposed port inside the subpatch graph
+struct BoundaryNode {
+    info: NodeInfo,
+    port_type: PortType,
+    signal: HeldSignal,
+}
+
+impl BoundaryNode {
+    /// Source for an exposed input: plays the held signal on `out`
+    fn input(name: &str, port_type: PortType) -> Self {
+        Self::new(name, INPUT_NODE_TYPE, Vec::new(), vec![PortInfo::new("out", port_type)], port_type)
+    }
+
+    /// Sink for an exposed output: records `in` into the held signal
+    fn output(name: &str, port_type: PortType) -> Self {
+        Self::new(name, OUTPUT_NODE_TYPE, vec![PortInfo::new("in", port_type)], Vec::new(), port_type)
+    }
+
+    fn new(name: &str, node_type: &str, input_ports: Vec<PortInfo>, output_ports: Vec<PortInfo>,
+           port_type: PortType) -> Self {
+        Self {
+            info: NodeInfo {
+                id: Uuid::new_v4(),
+                name: name.to_string(),
+                node_type: node_type.to_string(),
+                category: NodeCategory::Utility,
+                description: "Subpatch port".to_string(),
+                input_ports,
+                output_ports,
+                latency_samples: 0,
+                supports_bypass: false,
+            },
+            port_type,
+            signal: HeldSignal::new(port_type),
+        }
+    }
+
+    fn is_boundary(info: &NodeInfo) -> bool {
+        info.node_type == INPUT_NODE_TYPE || info.node_type == OUTPUT_NODE_TYPE
+    }
+}
+
+impl Parameterizable for BoundaryNode {
+    fn set_parameter(&mut self, name: &str, _value: f32) -> Result<(), ParameterError> {
+        Err(ParameterError::NotFound { name: name.to_string() })
+    }
+
+    fn get_parameter(&self, name: &str) -> Result<f32, ParameterError> {
+        Err(ParameterError::NotFound { name: name.to_string() })
+    }
+
+    fn get_all_parameters(&self) -> HashMap<String, f32> {
+        HashMap::new()
+    }
+
+    fn get_parameter_descriptors(&self) -> Vec<Box<dyn ParameterDescriptor>> {
+        Vec::new()
+    }
+}
+
+impl AudioNode for BoundaryNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        if self.info.node_type == INPUT_NODE_TYPE {
+            self.signal.write(&mut ctx.outputs, "out", self.port_type);
+        } else {
+            self.signal.read(&ctx.inputs, "in", self.port_type, ctx.buffer_size);
+        }
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.info
+    }
+
+    fn reset(&mut self) {
+        self.signal = HeldSignal::new(self.port_type);
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Exposed port and the inner port behind it
+#[derive(Debug, Clone)]
+struct Exposed {
+    name: String,
+    port_type: PortType,
+    boundary: Uuid,
+    node: Uuid,
+    port: String,
+}
+
+#[derive(Debug, Clone)]
+struct Promoted {
+    name: String,
+    node: Uuid,
+    parameter: String,
+}
+
+/// サブパッチノード - 内部のProcessingGraphを一つのノードとして扱う
+pub struct SubpatchNode {
+    node_info: NodeInfo,
+    graph: ProcessingGraph,
+    inputs: Vec<Exposed>,
+    outputs: Vec<Exposed>,
+    parameters: Vec<Promoted>,
+    /// Main output of the inner graph - only exposed outputs leave the subpatch
+    scratch: StereoBuffer,
+    /// Slowest path to an exposed output, as of the last structural change
+    latency: u32,
+}
+
+impl SubpatchNode {
+    /// Wrap `graph`. Expose ports and promote parameters before the node is
+    /// added to a graph - the outer topology copies its `NodeInfo` on insertion.
+    pub fn new(name: String, graph: ProcessingGraph) -> Self {
+        let node_info = NodeInfo {
+            id: Uuid::new_v4(),
+            name,
+            node_type: "subpatch".to_string(),
+            category: NodeCategory::Utility,
+            description: "Container running an inner patch as a single module".to_string(),
+            input_ports: Vec::new(),
+            output_ports: Vec::new(),
+            latency_samples: 0,
+            supports_bypass: true,
+        };
+
+        let mut subpatch = Self {
+            node_info,
+            graph,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            parameters: Vec::new(),
+            scratch: StereoBuffer::new(DEFAULT_MAX_BLOCK_SIZE),
+            latency: 0,
+        };
+        subpatch.refresh();
+        subpatch
+    }
+
+    /// Build a subpatch (and any subpatches nested in it) from its saved definition.
+    /// Inner nodes are named after `name` and referenced by `id`.
+    pub fn from_definition(definition: &SubpatchDefinition, sample_rate: f32, name: String) -> Result<Self, String> {
+        let mut graph = ProcessingGraph::new();
+        let mut ids: HashMap<&str, Uuid> = HashMap::new();
+
+        for entry in &definition.nodes {
+            let mut node: Box<dyn AudioNode> = match &entry.subpatch {
+                Some(inner) => Box::new(Self::from_definition(inner, sample_rate, entry.name.clone())?),
+                None => super::create_builtin_node(&entry.node_type, sample_rate, entry.name.clone())?,
+            };
+            for (param, &value) in &entry.parameters {
+                if let Err(e) = node.set_parameter(param, value) {
+                    eprintln!("Failed to set parameter {} on {}: {}", param, entry.id, e);
+                }
+            }
+            if let Some(state) = &entry.state {
+                if let Err(e) = node.load_state(state) {
+                    eprintln!("Failed to load state of {}: {}", entry.id, e);
+                }
+            }
+            if ids.insert(&entry.id, node.node_info().id).is_some() {
+                return Err(format!("Duplicate node ID in subpatch: {}", entry.id));
+            }
+            graph.add_node_instance(node)?;
+        }
+
+        let lookup = |node: &str| ids.get(node).copied()
+            .ok_or_else(|| format!("Unknown node in subpatch: {}", node));
+
+        for conn in &definition.connections {
+            graph.connect_by_id(&lookup(&conn.source_node)?.to_string(), &conn.source_port,
+                                &lookup(&conn.target_node)?.to_string(), &conn.target_port)?;
+        }
+
+        let mut subpatch = Self::new(name, graph);
+        for port in &definition.inputs {
+            subpatch.expose_input(&port.name, lookup(&port.node)?, &port.port)?;
+        }
+        for port in &definition.outputs {
+            subpatch.expose_output(&port.name, lookup(&port.node)?, &port.port)?;
+        }
+        for param in &definition.parameters {
+            subpatch.promote_parameter(&param.name, lookup(&param.node)?, &param.parameter)?;
+        }
+        Ok(subpatch)
+    }
+
+    /// Definition for saving. Inner nodes are referenced by their ID, so
+    /// several of them may share a name.
+    pub fn definition(&self) -> SubpatchDefinition {
+        let is_boundary = |node_id: Uuid| {
+            self.graph.topology().node_info(node_id).is_some_and(BoundaryNode::is_boundary)
+        };
+
+        let nodes = self.graph.topology().processing_order().iter()
+            .filter(|&&node_id| !is_boundary(node_id))
+            .filter_map(|node_id| self.graph.audio_nodes.get(node_id))
+            .map(|node| {
+                let info = node.node_info();
+                PatchNode {
+                    id: info.id.to_string(),
+                    node_type: info.node_type.clone(),
+                    name: info.name.clone(),
+                    position: PatchPosition::default(),
+                    parameters: node.get_all_parameters(),
+                    state: node.save_state(),
+                    subpatch: node.as_any().downcast_ref::<SubpatchNode>()
+                        .map(|inner| Box::new(inner.definition())),
+                    output_route: None,
+                    bypassed: false,
+                }
+            })
+            .collect();
+
+        let connections = self.graph.connections().iter()
+            .filter(|conn| !is_boundary(conn.source_node) && !is_boundary(conn.target_node))
+            .map(|conn| PatchConnection {
+                source_node: conn.source_node.to_string(),
+                source_port: conn.source_port.clone(),
+                target_node: conn.target_node.to_string(),
+                target_port: conn.target_port.clone(),
+            })
+            .collect();
+
+        let exposed = |ports: &[Exposed]| -> Vec<ExposedPort> {
+            ports.iter().map(|port| ExposedPort {
+                name: port.name.clone(),
+                node: port.node.to_string(),
+                port: port.port.clone(),
+            }).collect()
+        };
+
+        SubpatchDefinition {
+            nodes,
+            connections,
+            inputs: exposed(&self.inputs),
+            outputs: exposed(&self.outputs),
+            parameters: self.parameters.iter().map(|param| PromotedParameter {
+                name: param.name.clone(),
+                node: param.node.to_string(),
+                parameter: param.parameter.clone(),
+            }).collect(),
+        }
+    }
+
+    /// Show the input `port` of the inner node `node_id` as input `name`
+    pub fn expose_input(&mut self, name: &str, node_id: Uuid, port: &str) -> Result<(), String> {
+        if self.inputs.iter().any(|input| input.name == name) {
+            return Err(format!("Input {} is already exposed", name));
+        }
+        let inner = self.inner_port(node_id, port, |info| &info.input_ports)?;
+
+        let boundary = BoundaryNode::input(name, inner.port_type);
+        let boundary_id = boundary.info.id;
+        self.graph.add_node_instance(Box::new(boundary))?;
+        self.graph.connect_by_id(&boundary_id.to_string(), "out", &node_id.to_string(), port)?;
+
+        self.node_info.input_ports.push(PortInfo::new(name, inner.port_type).with_description(&inner.description));
+        self.inputs.push(Exposed {
+            name: name.to_string(),
+            port_type: inner.port_type,
+            boundary: boundary_id,
+            node: node_id,
+            port: port.to_string(),
+        });
+        self.refresh();
+        Ok(())
+    }
+
+    /// Show the output `port` of the inner node `node_id` as output `name`
+    pub fn expose_output(&mut self, name: &str, node_id: Uuid, port: &str) -> Result<(), String> {
+        if self.outputs.iter().any(|output| output.name == name) {
+            return Err(format!("Output {} is already exposed", name));
+        }
+        let inner = self.inner_port(node_id, port, |info| &info.output_ports)?;
+
+        let boundary = BoundaryNode::output(name, inner.port_type);
+        let boundary_id = boundary.info.id;
+        self.graph.add_node_instance(Box::new(boundary))?;
+        self.graph.connect_by_id(&node_id.to_string(), port, &boundary_id.to_string(), "in")?;
+
+        self.node_info.output_ports.push(PortInfo::new(name, inner.port_type).with_description(&inner.description));
+        self.outputs.push(Exposed {
+            name: name.to_string(),
+            port_type: inner.port_type,
+            boundary: boundary_id,
+            node: node_id,
+            port: port.to_string(),
+        });
+        self.refresh();
+        Ok(())
+    }
+
+    /// Show the parameter `parameter` of the inner node `node_id` as parameter `name`
+    pub fn promote_parameter(&mut self, name: &str, node_id: Uuid, parameter: &str) -> Result<(), String> {
+        if self.parameters.iter().any(|param| param.name == name) {
+            return Err(format!("Parameter {} is already promoted", name));
+        }
+        let node = self.graph.get_node(&node_id.to_string())
+            .ok_or_else(|| format!("Node not found in subpatch: {}", node_id))?;
+        if !node.has_parameter(parameter) {
+            return Err(format!("Node {} has no parameter {}", node.node_info().name, parameter));
+        }
+
+        self.parameters.push(Promoted {
+            name: name.to_string(),
+            node: node_id,
+            parameter: parameter.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Inner graph (including the hidden boundary nodes)
+    pub fn graph(&self) -> &ProcessingGraph {
+        &self.graph
+    }
+
+    fn inner_port<F>(&self, node_id: Uuid, port: &str, ports: F) -> Result<PortInfo, String>
+    where
+        F: FnOnce(&NodeInfo) -> &Vec<PortInfo>,
+    {
+        let info = self.graph.topology().node_info(node_id)
+            .ok_or_else(|| format!("Node not found in subpatch: {}", node_id))?;
+        ports(info).iter()
+            .find(|p| p.name == port)
+            .cloned()
+            .ok_or_else(|| format!("Node {} has no port {}", info.name, port))
+    }
+
+    /// Recompile the inner plan and latency after a structural change
+    fn refresh(&mut self) {
+        self.graph.compile_plan(DEFAULT_MAX_BLOCK_SIZE);
+        let arrival = self.graph.topology().input_latencies();
+        self.latency = self.outputs.iter()
+            .filter_map(|output| arrival.get(&output.boundary).copied())
+            .max()
+            .unwrap_or(0);
+    }
+
+    fn promoted(&self, name: &str) -> Result<&Promoted, ParameterError> {
+        self.parameters.iter()
+            .find(|param| param.name == name)
+            .ok_or_else(|| ParameterError::NotFound { name: name.to_string() })
+    }
+}
+
+impl Parameterizable for SubpatchNode {
+    fn set_parameter(&mut self, name: &str, value: f32) -> Result<(), ParameterError> {
+        // Borrow the field directly so the inner graph can be borrowed mutably alongside
+        let promoted = self.parameters.iter()
+            .find(|param| param.name == name)
+            .ok_or_else(|| ParameterError::NotFound { name: name.to_string() })?;
+        match self.graph.audio_nodes.get_mut(&promoted.node) {
+            Some(node) => node.set_parameter(&promoted.parameter, value),
+            None => Err(ParameterError::NotFound { name: name.to_string() }),
+        }
+    }
+
+    fn get_parameter(&self, name: &str) -> Result<f32, ParameterError> {
+        let promoted = self.promoted(name)?;
+        match self.graph.audio_nodes.get(&promoted.node) {
+            Some(node) => node.get_parameter(&promoted.parameter),
+            None => Err(ParameterError::NotFound { name: name.to_string() }),
+        }
+    }
+
+    fn get_all_parameters(&self) -> HashMap<String, f32> {
+        self.parameters.iter()
+            .filter_map(|param| Some((param.name.clone(), self.get_parameter(&param.name).ok()?)))
+            .collect()
+    }
+
+    /// Descriptors of the promoted inner parameters (they keep their inner names)
+    fn get_parameter_descriptors(&self) -> Vec<Box<dyn ParameterDescriptor>> {
+        self.parameters.iter()
+            .filter_map(|param| {
+                self.graph.audio_nodes.get(&param.node)?
+                    .get_parameter_descriptors()
+                    .into_iter()
+                    .find(|descriptor| descriptor.name() == param.parameter)
+            })
+            .collect()
+    }
+}
+
+impl AudioNode for SubpatchNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        for input in &self.inputs {
+            let boundary = self.graph.audio_nodes.get_mut(&input.boundary)
+                .and_then(|node| node.as_any_mut().downcast_mut::<BoundaryNode>());
+            if let Some(boundary) = boundary {
+                boundary.signal.read(&ctx.inputs, &input.name, input.port_type, ctx.buffer_size);
+            }
+        }
+
+        self.graph.transport_mut().follow(ctx.transport);
+        self.scratch.resize(ctx.buffer_size);
+        self.graph.process_stereo(&mut self.scratch.left, &mut self.scratch.right, ctx.sample_rate)?;
+
+        for output in &self.outputs {
+            let boundary = self.graph.audio_nodes.get(&output.boundary)
+                .and_then(|node| node.as_any().downcast_ref::<BoundaryNode>());
+            if let Some(boundary) = boundary {
+                boundary.signal.write(&mut ctx.outputs, &output.name, output.port_type);
+            }
+        }
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.node_info
+    }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
+    fn reset(&mut self) {
+        for node in self.graph.audio_nodes.values_mut() {
+            node.reset();
+        }
+    }
+
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.graph.prepare(sample_rate, max_block_size);
+        self.scratch.resize(max_block_size);
+    }
+
+    fn latency(&self) -> u32 {
+        self.latency
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::VCANode;
+
+    fn run(node: &mut dyn AudioNode, input: &[f32]) -> Vec<f32> {
+        let mut inputs = InputBuffers::new();
+        inputs.add_audio("audio_in".to_string(), input.to_vec());
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_audio("audio_out".to_string(), input.len());
+
+        let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, input.len());
+        node.process(&mut ctx).unwrap();
+        ctx.outputs.get_audio("audio_out").unwrap().to_vec()
+    }
+
+    fn vca_subpatch(name: &str) -> SubpatchNode {
+        let mut graph = ProcessingGraph::new();
+        let vca = Box::new(VCANode::new(44100.0, "vca".to_string()));
+        let vca_id = vca.node_info().id;
+        graph.add_node_instance(vca).unwrap();
+
+        let mut subpatch = SubpatchNode::new(name.to_string(), graph);
+        subpatch.expose_input("audio_in", vca_id, "audio_in").unwrap();
+        subpatch.expose_output("audio_out", vca_id, "audio_out").unwrap();
+        subpatch.promote_parameter("level", vca_id, "gain").unwrap();
+        subpatch
+    }
+
+    #[test]
+    fn test_subpatch_matches_inner_node() {
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 * 0.1).sin()).collect();
+
+        let mut subpatch = vca_subpatch("voice");
+        subpatch.set_parameter("level", 0.5).unwrap();
+        assert_eq!(subpatch.get_parameter("level").unwrap(), 0.5);
+        assert!(subpatch.set_parameter("gain", 0.5).is_err());
+        assert!(subpatch.expose_input("audio_in", Uuid::new_v4(), "audio_in").is_err());
+
+        let ports: Vec<&str> = subpatch.node_info().input_ports.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(ports, vec!["audio_in"]);
+
+        let mut vca = VCANode::new(44100.0, "vca".to_string());
+        vca.set_parameter("gain", 0.5).unwrap();
+        let expected = run(&mut vca, &input);
+        assert!(expected.iter().any(|&s| s.abs() > 0.1));
+        assert_eq!(run(&mut subpatch, &input), expected);
+    }
+
+    #[test]
+    fn test_inner_nodes_may_share_a_name() {
+        // Two VCAs called "vca" in series
+        let mut graph = ProcessingGraph::new();
+        let mut first = VCANode::new(44100.0, "vca".to_string());
+        first.set_parameter("gain", 0.5).unwrap();
+        let second = VCANode::new(44100.0, "vca".to_string());
+        let (first_id, second_id) = (first.node_info().id, second.node_info().id);
+        graph.add_node_instance(Box::new(first)).unwrap();
+        graph.add_node_instance(Box::new(second)).unwrap();
+        graph.connect_by_id(&first_id.to_string(), "audio_out", &second_id.to_string(), "audio_in").unwrap();
+
+        let mut subpatch = SubpatchNode::new("pair".to_string(), graph);
+        subpatch.expose_input("audio_in", first_id, "audio_in").unwrap();
+        subpatch.expose_output("audio_out", second_id, "audio_out").unwrap();
+        subpatch.promote_parameter("level", second_id, "gain").unwrap();
+        subpatch.set_parameter("level", 0.25).unwrap();
+
+        let definition = subpatch.definition();
+        assert!(definition.nodes.iter().all(|node| node.name == "vca"));
+        assert_ne!(definition.nodes[0].id, definition.nodes[1].id);
+
+        let mut loaded = SubpatchNode::from_definition(&definition, 44100.0, "pair".to_string()).unwrap();
+        assert_eq!(loaded.get_parameter("level").unwrap(), 0.25);
+        let input = vec![0.5; 32];
+        assert_eq!(run(&mut loaded, &input), run(&mut subpatch, &input));
+    }
+
+    #[test]
+    fn test_nested_definition_round_trip() {
+        // A subpatch holding a subpatch, saved to JSON and rebuilt
+        let inner = vca_subpatch("inner");
+        let inner_json = serde_json::to_string(&inner.definition()).unwrap();
+        let inner_definition: SubpatchDefinition = serde_json::from_str(&inner_json).unwrap();
+        let inner = SubpatchNode::from_definition(&inner_definition, 44100.0, "inner".to_string()).unwrap();
+        let inner_id = inner.node_info().id;
+
+        let mut graph = ProcessingGraph::new();
+        graph.add_node_instance(Box::new(inner)).unwrap();
+        let mut outer = SubpatchNode::new("outer".to_string(), graph);
+        outer.expose_input("audio_in", inner_id, "audio_in").unwrap();
+        outer.expose_output("audio_out", inner_id, "audio_out").unwrap();
+        outer.promote_parameter("volume", inner_id, "level").unwrap();
+        outer.set_parameter("volume", 0.25).unwrap();
+
+        let json = serde_json::to_string(&outer.definition()).unwrap();
+        let definition: SubpatchDefinition = serde_json::from_str(&json).unwrap();
+        assert_eq!(definition.nodes.len(), 1);
+        assert!(definition.nodes[0].subpatch.is_some());
+        assert!(definition.connections.is_empty());
+
+        let mut loaded = SubpatchNode::from_definition(&definition, 44100.0, "outer".to_string()).unwrap();
+        assert_eq!(loaded.get_parameter("volume").unwrap(), 0.25);
+
+        let input = vec![0.5; 32];
+        assert_eq!(run(&mut loaded, &input), run(&mut outer, &input));
+    }
+}
@@ -18,8 +18,8 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
+use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, cv_sample_at};
 use crate::graph::PortType;
 use crate::define_parameters;
 
@@ -67,8 +67,10 @@ pub struct VCANode {
     // CV Modulation parameters
     gain_param: ModulatableParameter,
     cv_sensitivity_param: ModulatableParameter,
-    
-    #[allow(dead_code)]
+
+    // Gain per sample of the current block, for gain_cv_out
+    gain_levels: Vec<f32>,
+
     sample_rate: f32,
 }
 
@@ -108,12 +110,12 @@ impl VCANode {
         let gain_param = ModulatableParameter::new(
             BasicParameter::new("gain", 0.0, 2.0, 1.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let cv_sensitivity_param = ModulatableParameter::new(
             BasicParameter::new("cv_sensitivity", 0.0, 2.0, 1.0),
             0.5  // 50% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -125,6 +127,7 @@ impl VCANode {
             gain_param,
             cv_sensitivity_param,
             
+            gain_levels: Vec::new(),
             sample_rate,
         }
     }
@@ -202,30 +205,24 @@ impl AudioNode for VCANode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let gain_cv = ctx.inputs.get_cv_value("gain_cv");
-        let cv_cv = ctx.inputs.get_cv_value("cv_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let gain_cv = ctx.inputs.get_cv("gain_cv");
+        let cv_cv = ctx.inputs.get_cv("cv_cv");
         let response_cv = ctx.inputs.get_cv_value("response_cv");
 
-        // Apply CV modulation
-        let effective_gain = self.gain_param.modulate(self.gain, gain_cv);
-        let effective_cv_sensitivity = self.cv_sensitivity_param.modulate(self.cv_sensitivity, cv_cv);
-
-        // Update response curve from CV if provided
+        // Update response curve from CV if provided (control rate)
         let current_response = if response_cv != 0.0 {
             VCAResponse::from_f32(response_cv.clamp(0.0, 1.0))
         } else {
             VCAResponse::from_f32(self.response)
         };
 
-        // Calculate CV gain based on response curve
-        let cv_gain = self.calculate_cv_gain(gain_cv, effective_cv_sensitivity, current_response);
-
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
             .ok_or_else(|| ProcessingError::OutputBufferError { 
                 port_name: "audio_out".to_string() 
             })?;
+        self.gain_levels.resize(output.len(), 0.0);
 
         // Process each sample through the VCA
         for (i, output_sample) in output.iter_mut().enumerate() {
@@ -235,12 +232,21 @@ impl AudioNode for VCANode {
                 0.0 
             };
 
+            // Manual gain and CV gain
+            let effective_gain = self.gain_param.modulate_smoothed(self.gain, gain_cv, i, self.sample_rate);
+            let effective_cv_sensitivity = self.cv_sensitivity_param.modulate_smoothed(self.cv_sensitivity, cv_cv, i, self.sample_rate);
+            let cv_gain = self.calculate_cv_gain(cv_sample_at(gain_cv, i), effective_cv_sensitivity, current_response);
+
             *output_sample = self.process_vca_sample(input_sample, effective_gain, cv_gain);
+            self.gain_levels[i] = (effective_gain * cv_gain).clamp(0.0, 10.0);
         }
 
         // Output current gain level as CV
-        let gain_level = (effective_gain * cv_gain).clamp(0.0, 10.0);
-        ctx.outputs.set_cv_value("gain_cv_out", gain_level);
+        if let Some(gain_cv_out) = ctx.outputs.get_cv_mut("gain_cv_out") {
+            for (sample, level) in gain_cv_out.iter_mut().zip(&self.gain_levels) {
+                *sample = *level;
+            }
+        }
 
         Ok(())
     }
@@ -249,10 +255,18 @@ impl AudioNode for VCANode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // VCA has no internal state to reset
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for VCA
     }
@@ -305,6 +319,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -338,6 +354,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vca.process(&mut ctx).is_ok());
@@ -386,6 +404,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vca.process(&mut ctx).is_ok());
@@ -428,6 +448,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vca.process(&mut ctx).is_ok());
@@ -456,6 +478,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vca.process(&mut ctx).is_ok());
@@ -465,4 +489,27 @@ mod tests {
         let is_silent = output.iter().all(|&s| s.abs() < 0.001);
         assert!(is_silent);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_gain_changes_are_smoothed() {
+        // 20ms default smoothing = 20 samples at 1kHz
+        let mut vca = VCANode::new(1000.0, "test".to_string());
+        let render = |vca: &mut VCANode| {
+            let mut inputs = InputBuffers::new();
+            inputs.add_audio("audio_in".to_string(), vec![0.5; 32]);
+            let mut outputs = OutputBuffers::new();
+            outputs.allocate_audio("audio_out".to_string(), 32);
+            let mut ctx = ProcessContext::new(inputs, outputs, 1000.0, 32);
+            vca.process(&mut ctx).unwrap();
+            ctx.outputs.get_audio("audio_out").unwrap().to_vec()
+        };
+
+        assert!(render(&mut vca).iter().all(|&s| s == 0.5));
+
+        vca.set_parameter("gain", 0.0).unwrap();
+        let ramp = render(&mut vca);
+        assert!((ramp[0] - 0.475).abs() < 1e-4);
+        assert!(ramp.windows(2).take(19).all(|pair| pair[1] < pair[0]));
+        assert!(ramp[19..].iter().all(|&s| s == 0.0));
+    }
+}
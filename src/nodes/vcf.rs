@@ -18,11 +18,14 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ModulationCurve, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
 
+/// 変調中の係数の再計算間隔（サンプル数）
+const COEFFICIENT_INTERVAL: u32 = 16;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FilterType {
     Lowpass = 0,
@@ -85,6 +88,13 @@ pub struct VCFNode {
     b1: f32,
     b2: f32,
     
+    // Values the coefficients were last computed for
+    coeff_cutoff: f32,
+    coeff_resonance: f32,
+    coeff_filter_type: FilterType,
+    /// Samples since the coefficients were last computed
+    coeff_age: u32,
+    
     sample_rate: f32,
     coefficients_dirty: bool,
 }
@@ -122,12 +132,12 @@ impl VCFNode {
         let cutoff_param = ModulatableParameter::new(
             BasicParameter::new("cutoff_frequency", 20.0, 20000.0, 1000.0).with_unit("Hz"),
             1.0  // 100% CV modulation for precise control
-        ).with_curve(ModulationCurve::Exponential); // 周波数は指数的変化
+        ).with_curve(ModulationCurve::Exponential).with_smoothing(Smoothing::DEFAULT); // 周波数は指数的変化
 
         let resonance_param = ModulatableParameter::new(
             BasicParameter::new("resonance", 0.1, 10.0, 1.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -152,6 +162,11 @@ impl VCFNode {
             b1: 0.0,
             b2: 0.0,
             
+            coeff_cutoff: 0.0,
+            coeff_resonance: 0.0,
+            coeff_filter_type: FilterType::Lowpass,
+            coeff_age: 0,
+            
             sample_rate,
             coefficients_dirty: true,
         }
@@ -211,13 +226,23 @@ impl VCFNode {
             },
         }
 
+        self.coeff_cutoff = cutoff;
+        self.coeff_resonance = resonance;
+        self.coeff_filter_type = filter_type;
+        self.coeff_age = 0;
         self.coefficients_dirty = false;
     }
 
     /// 高品質Biquadフィルター処理
     fn process_sample(&mut self, input: f32, cutoff: f32, resonance: f32, filter_type: FilterType) -> f32 {
-        // Update coefficients if parameters changed
-        if self.coefficients_dirty {
+        // Modulated cutoff/resonance update the coefficients every
+        // COEFFICIENT_INTERVAL samples at most, and only on a noticeable change
+        self.coeff_age = self.coeff_age.saturating_add(1);
+        let moved = (cutoff - self.coeff_cutoff).abs() > self.coeff_cutoff * 1e-3 ||
+                    (resonance - self.coeff_resonance).abs() > 0.01;
+        if self.coefficients_dirty ||
+           filter_type != self.coeff_filter_type ||
+           (moved && self.coeff_age >= COEFFICIENT_INTERVAL) {
             self.update_coefficients(cutoff, resonance, filter_type);
         }
 
@@ -263,28 +288,18 @@ impl AudioNode for VCFNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let cutoff_cv = ctx.inputs.get_cv_value("cutoff_cv");
-        let resonance_cv = ctx.inputs.get_cv_value("resonance_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let cutoff_cv = ctx.inputs.get_cv("cutoff_cv");
+        let resonance_cv = ctx.inputs.get_cv("resonance_cv");
         let type_cv = ctx.inputs.get_cv_value("type_cv");
 
-        // Apply CV modulation
-        let effective_cutoff = self.cutoff_param.modulate(self.cutoff_frequency, cutoff_cv);
-        let effective_resonance = self.resonance_param.modulate(self.resonance, resonance_cv);
-
-        // Update filter type from CV if provided
+        // Update filter type from CV if provided (control rate)
         let current_filter_type = if type_cv != 0.0 {
             FilterType::from_f32(type_cv.clamp(0.0, 2.0))
         } else {
             FilterType::from_f32(self.filter_type)
         };
 
-        // Check if coefficients need updating
-        if (effective_cutoff - self.cutoff_frequency).abs() > 0.1 ||
-           (effective_resonance - self.resonance).abs() > 0.01 {
-            self.coefficients_dirty = true;
-        }
-
         // Process audio output
         let output = ctx.outputs.get_audio_mut("audio_out")
             .ok_or_else(|| ProcessingError::OutputBufferError { 
@@ -299,6 +314,10 @@ impl AudioNode for VCFNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample; coefficients follow the CV
+            let effective_cutoff = self.cutoff_param.modulate_smoothed(self.cutoff_frequency, cutoff_cv, i, self.sample_rate);
+            let effective_resonance = self.resonance_param.modulate_smoothed(self.resonance, resonance_cv, i, self.sample_rate);
+
             *output_sample = self.process_sample(
                 input_sample, 
                 effective_cutoff, 
@@ -314,6 +333,10 @@ impl AudioNode for VCFNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset filter state
         self.x1 = 0.0;
@@ -323,6 +346,11 @@ impl AudioNode for VCFNode {
         self.coefficients_dirty = true;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+        self.coefficients_dirty = true;
+    }
+
     fn latency(&self) -> u32 {
         0 // No significant latency for biquad filter
     }
@@ -375,6 +403,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -407,6 +437,8 @@ mod tests {
                 buffer_size: 64,
                 timestamp: 0,
                 bpm: 120.0,
+                events: Default::default(),
+                transport: Default::default(),
             };
             
             assert!(vcf.process(&mut ctx).is_ok());
@@ -436,6 +468,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vcf.process(&mut ctx).is_ok());
@@ -446,6 +480,27 @@ mod tests {
         assert!(has_modulated_output);
     }
 
+    #[test]
+    fn test_coefficient_update_rate() {
+        let mut vcf = VCFNode::new(44100.0, "test".to_string());
+
+        // A cutoff sweeping every sample recomputes once per interval
+        let mut updates = 0;
+        for i in 0..64 {
+            vcf.process_sample(0.0, 1000.0 + 10.0 * i as f32, 1.0, FilterType::Lowpass);
+            if vcf.coeff_age == 0 {
+                updates += 1;
+            }
+        }
+        assert_eq!(updates, 1 + 63 / COEFFICIENT_INTERVAL as usize);
+        assert_eq!(vcf.coeff_cutoff, 1000.0 + 10.0 * 48.0);
+
+        // Switching the filter type does not wait for the interval
+        vcf.process_sample(0.0, 2000.0, 1.0, FilterType::Highpass);
+        assert_eq!(vcf.coeff_filter_type, FilterType::Highpass);
+        assert_eq!(vcf.coeff_cutoff, 2000.0);
+    }
+
     #[test]
     fn test_resonance_cv_modulation() {
         let mut vcf = VCFNode::new(44100.0, "test".to_string());
@@ -464,6 +519,8 @@ mod tests {
             buffer_size: 64,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vcf.process(&mut ctx).is_ok());
@@ -491,6 +548,8 @@ mod tests {
             buffer_size: 1024,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vcf.process(&mut ctx).is_ok());
@@ -523,6 +582,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(vcf.process(&mut ctx).is_ok());
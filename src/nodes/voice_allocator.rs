@@ -0,0 +1,409 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use uuid::Uuid;
+
+use crate::parameters::{BasicParameter, Parameterizable, ParameterDescriptor};
+use crate::processing::{AudioNode, EventKind, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo, cv_sample_at, MAX_VOICES};
+use crate::graph::PortType;
+use crate::define_parameters;
+
+/// Gate level of a sounding voice
+const GATE_HIGH: f32 = 5.0;
+
+/// Pitches closer than this (in volts) are the same note
+const PITCH_TOLERANCE: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AllocationMode {
+    /// Cycle through the voices so releases ring out
+    RoundRobin = 0,
+    /// Always take the lowest free voice
+    Lowest = 1,
+}
+
+impl AllocationMode {
+    pub fn from_f32(value: f32) -> Self {
+        match value as i32 {
+            1 => AllocationMode::Lowest,
+            _ => AllocationMode::RoundRobin,
+        }
+    }
+}
+
+/// State of one voice
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Voice {
+    /// 1V/Oct
+    pub pitch: f32,
+    /// 0.0 - 1.0
+    pub velocity: f32,
+    pub gate: bool,
+    /// Stolen while sounding - the gate drops for one sample so envelopes restart
+    pub retrigger: bool,
+    /// Order of the note on, to find the oldest note
+    age: u64,
+}
+
+/// ボイスアロケーター - ノートをボイスに割り当てる
+///
+/// When every voice is busy the oldest note is stolen (last-note priority),
+/// unless stealing is off, in which case the new note is dropped.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator {
+    voices: [Voice; MAX_VOICES],
+    count: usize,
+    mode: AllocationMode,
+    steal: bool,
+    next: usize,
+    notes: u64,
+}
+
+impl VoiceAllocator {
+    pub fn new(count: usize) -> Self {
+        Self {
+            voices: [Voice::default(); MAX_VOICES],
+            count: count.clamp(1, MAX_VOICES),
+            mode: AllocationMode::RoundRobin,
+            steal: true,
+            next: 0,
+            notes: 0,
+        }
+    }
+
+    /// Change the number of voices; notes on removed voices are released
+    pub fn set_voice_count(&mut self, count: usize) {
+        self.count = count.clamp(1, MAX_VOICES);
+        self.voices[self.count..].iter_mut().for_each(|voice| voice.gate = false);
+        self.next %= self.count;
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.count
+    }
+
+    pub fn set_mode(&mut self, mode: AllocationMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_stealing(&mut self, steal: bool) {
+        self.steal = steal;
+    }
+
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices[..self.count]
+    }
+
+    /// Start a note. Returns the voice it was given, or None if it was dropped.
+    pub fn note_on(&mut self, pitch: f32, velocity: f32) -> Option<usize> {
+        let voices = &self.voices[..self.count];
+        let playing = voices.iter().position(|voice| voice.gate && (voice.pitch - pitch).abs() < PITCH_TOLERANCE);
+        let free = match self.mode {
+            AllocationMode::RoundRobin => (0..self.count)
+                .map(|offset| (self.next + offset) % self.count)
+                .find(|&index| !voices[index].gate),
+            AllocationMode::Lowest => voices.iter().position(|voice| !voice.gate),
+        };
+        let oldest = || (0..self.count).min_by_key(|&index| voices[index].age);
+
+        let index = match (playing, free) {
+            (Some(index), _) | (None, Some(index)) => index,
+            (None, None) if self.steal => oldest()?,
+            (None, None) => return None,
+        };
+
+        self.notes += 1;
+        self.voices[index] = Voice {
+            pitch,
+            velocity: velocity.clamp(0.0, 1.0),
+            gate: true,
+            retrigger: self.voices[index].gate,
+            age: self.notes,
+        };
+        self.next = (index + 1) % self.count;
+        Some(index)
+    }
+
+    /// Release every voice playing `pitch`
+    pub fn note_off(&mut self, pitch: f32) {
+        for voice in self.voices[..self.count].iter_mut() {
+            if voice.gate && (voice.pitch - pitch).abs() < PITCH_TOLERANCE {
+                voice.gate = false;
+            }
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        self.voices.iter_mut().for_each(|voice| voice.gate = false);
+    }
+
+    /// Gate level of a voice for the next sample
+    fn gate_sample(&mut self, index: usize) -> f32 {
+        let voice = &mut self.voices[index];
+        if voice.retrigger {
+            voice.retrigger = false;
+            0.0
+        } else if voice.gate {
+            GATE_HIGH
+        } else {
+            0.0
+        }
+    }
+}
+
+/// ボイスアロケーターノード - モノラルのゲート/ピッチをポリケーブルに振り分ける
+pub struct VoiceAllocatorNode {
+    node_info: NodeInfo,
+
+    // Parameters
+    voices: f32,
+    mode: f32,
+    steal: f32,
+    active: f32,
+
+    allocator: VoiceAllocator,
+    gate_was_high: bool,
+    /// Pitch of the note started by the gate input
+    held_pitch: f32,
+}
+
+impl VoiceAllocatorNode {
+    pub fn new(_sample_rate: f32, name: String) -> Self {
+        let node_info = NodeInfo {
+            id: Uuid::new_v4(),
+            name,
+            node_type: "voice_allocator".to_string(),
+            category: NodeCategory::Controller,
+            description: "Polyphonic voice allocator with round-robin allocation and voice stealing".to_string(),
+            input_ports: vec![
+                PortInfo::new("pitch_in", PortType::CV)
+                    .with_description("Pitch of the next note (1V/Oct)"),
+                PortInfo::new("gate_in", PortType::CV)
+                    .with_description("Rising edge starts a note, falling edge ends it (>2.5V)"),
+                PortInfo::new("velocity_in", PortType::CV)
+                    .with_description("Velocity of the next note (0V to +10V)")
+                    .optional(),
+            ],
+            output_ports: vec![
+                PortInfo::new("pitch_out", PortType::Poly)
+                    .with_description("Pitch per voice (1V/Oct)"),
+                PortInfo::new("gate_out", PortType::Poly)
+                    .with_description("Gate per voice (5V)"),
+                PortInfo::new("velocity_out", PortType::Poly)
+                    .with_description("Velocity per voice (0V to +10V)")
+                    .optional(),
+            ],
+            latency_samples: 0,
+            supports_bypass: false,
+        };
+
+        Self {
+            node_info,
+            voices: 8.0,
+            mode: 0.0,
+            steal: 1.0,
+            active: 1.0,
+            allocator: VoiceAllocator::new(8),
+            gate_was_high: false,
+            held_pitch: 0.0,
+        }
+    }
+
+    /// Start a note from outside the graph (e.g. MIDI); applied from the next block
+    pub fn note_on(&mut self, pitch: f32, velocity: f32) -> Option<usize> {
+        self.configure();
+        self.allocator.note_on(pitch, velocity)
+    }
+
+    pub fn note_off(&mut self, pitch: f32) {
+        self.allocator.note_off(pitch);
+    }
+
+    pub fn allocator(&self) -> &VoiceAllocator {
+        &self.allocator
+    }
+
+    /// Apply the parameters to the allocator
+    fn configure(&mut self) {
+        if self.allocator.voice_count() != self.voices as usize {
+            self.allocator.set_voice_count(self.voices as usize);
+        }
+        self.allocator.set_mode(AllocationMode::from_f32(self.mode));
+        self.allocator.set_stealing(self.steal > 0.5);
+    }
+}
+
+impl Parameterizable for VoiceAllocatorNode {
+    define_parameters! {
+        voices: BasicParameter::new("voices", 1.0, MAX_VOICES as f32, 8.0),
+        mode: BasicParameter::new("mode", 0.0, 1.0, 0.0),
+        steal: BasicParameter::new("steal", 0.0, 1.0, 1.0),
+        active: BasicParameter::new("active", 0.0, 1.0, 1.0)
+    }
+}
+
+impl AudioNode for VoiceAllocatorNode {
+    fn process(&mut self, ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+        if !self.is_active() {
+            self.allocator.release_all();
+        }
+        self.configure();
+
+        let pitch_in = ctx.inputs.get_cv("pitch_in");
+        let gate_in = ctx.inputs.get_cv("gate_in");
+        let velocity_in = ctx.inputs.get_cv("velocity_in");
+        let count = self.allocator.voice_count();
+
+        for i in 0..ctx.buffer_size {
+            if gate_in.is_some() {
+                let gate_high = cv_sample_at(gate_in, i) > 2.5;
+                if gate_high && !self.gate_was_high {
+                    self.held_pitch = cv_sample_at(pitch_in, i);
+                    let velocity = velocity_in.map_or(1.0, |_| cv_sample_at(velocity_in, i) / 10.0);
+                    self.allocator.note_on(self.held_pitch, velocity);
+                } else if !gate_high && self.gate_was_high {
+                    self.allocator.note_off(self.held_pitch);
+                }
+                self.gate_was_high = gate_high;
+            }
+
+            for v in 0..count {
+                let gate = self.allocator.gate_sample(v);
+                let voice = self.allocator.voices[v];
+                if let Some(output) = ctx.outputs.get_poly_mut("pitch_out") {
+                    output.channels[v][i] = voice.pitch;
+                }
+                if let Some(output) = ctx.outputs.get_poly_mut("gate_out") {
+                    output.channels[v][i] = gate;
+                }
+                if let Some(output) = ctx.outputs.get_poly_mut("velocity_out") {
+                    output.channels[v][i] = voice.velocity * 10.0;
+                }
+            }
+        }
+
+        for port in ["pitch_out", "gate_out", "velocity_out"] {
+            if let Some(output) = ctx.outputs.get_poly_mut(port) {
+                output.voices = count;
+            }
+        }
+        Ok(())
+    }
+
+    fn node_info(&self) -> &NodeInfo {
+        &self.node_info
+    }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
+    fn reset(&mut self) {
+        self.allocator = VoiceAllocator::new(self.voices as usize);
+        self.gate_was_high = false;
+    }
+
+    fn handle_event(&mut self, event: &EventKind) {
+        match event {
+            EventKind::NoteOn { pitch, velocity } => {
+                self.note_on(*pitch, *velocity);
+            }
+            EventKind::NoteOff { pitch } => self.note_off(*pitch),
+            EventKind::Parameter { name, value } => {
+                let _ = self.set_parameter(name, *value);
+            }
+            EventKind::GateOn | EventKind::GateOff => {}
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::{InputBuffers, OutputBuffers};
+
+    #[test]
+    fn test_round_robin_allocation() {
+        let mut allocator = VoiceAllocator::new(3);
+        assert_eq!(allocator.note_on(0.0, 1.0), Some(0));
+        assert_eq!(allocator.note_on(1.0, 1.0), Some(1));
+        allocator.note_off(0.0);
+        // Voice 0 is free again, but round-robin moves on
+        assert_eq!(allocator.note_on(2.0, 1.0), Some(2));
+        assert_eq!(allocator.note_on(3.0, 1.0), Some(0));
+
+        // The same pitch again retriggers its voice
+        assert_eq!(allocator.note_on(1.0, 0.5), Some(1));
+        assert_eq!(allocator.voices()[1].velocity, 0.5);
+
+        let mut lowest = VoiceAllocator::new(3);
+        lowest.set_mode(AllocationMode::Lowest);
+        lowest.note_on(0.0, 1.0);
+        lowest.note_on(1.0, 1.0);
+        lowest.note_off(0.0);
+        assert_eq!(lowest.note_on(2.0, 1.0), Some(0));
+    }
+
+    #[test]
+    fn test_voice_stealing() {
+        let mut allocator = VoiceAllocator::new(2);
+        allocator.note_on(0.0, 1.0);
+        allocator.note_on(1.0, 1.0);
+
+        // The oldest note gives way to the newest
+        assert_eq!(allocator.note_on(2.0, 1.0), Some(0));
+        assert!(allocator.voices()[0].retrigger);
+        assert_eq!(allocator.gate_sample(0), 0.0);
+        assert_eq!(allocator.gate_sample(0), GATE_HIGH);
+        assert_eq!(allocator.note_on(3.0, 1.0), Some(1));
+
+        allocator.set_stealing(false);
+        assert_eq!(allocator.note_on(4.0, 1.0), None);
+        assert_eq!(allocator.voices()[0].pitch, 2.0);
+        assert_eq!(allocator.voices()[1].pitch, 3.0);
+    }
+
+    #[test]
+    fn test_gate_input_fills_poly_outputs() {
+        let mut node = VoiceAllocatorNode::new(44100.0, "alloc".to_string());
+        node.set_parameter("voices", 2.0).unwrap();
+
+        let mut inputs = InputBuffers::new();
+        inputs.add_cv("pitch_in".to_string(), vec![0.0, 0.0, 1.0, 1.0]);
+        inputs.add_cv("gate_in".to_string(), vec![5.0, 0.0, 5.0, 5.0]);
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_poly("pitch_out".to_string(), 4);
+        outputs.allocate_poly("gate_out".to_string(), 4);
+        let mut ctx = ProcessContext::new(inputs, outputs, 44100.0, 4);
+        node.process(&mut ctx).unwrap();
+
+        let gate = ctx.outputs.get_poly("gate_out").unwrap();
+        assert_eq!(gate.voices, 2);
+        assert_eq!(gate.channels[0], vec![5.0, 0.0, 0.0, 0.0]);
+        assert_eq!(gate.channels[1], vec![0.0, 0.0, 5.0, 5.0]);
+        assert_eq!(ctx.outputs.get_poly("pitch_out").unwrap().channels[1][3], 1.0);
+    }
+}
@@ -18,7 +18,7 @@
 
 use uuid::Uuid;
 
-use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor};
+use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, Smoothing};
 use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
 use crate::graph::PortType;
 use crate::define_parameters;
@@ -143,22 +143,22 @@ impl WaveshaperNode {
         let drive_param = ModulatableParameter::new(
             BasicParameter::new("drive", 0.1, 10.0, 1.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let shape_amount_param = ModulatableParameter::new(
             BasicParameter::new("shape_amount", 0.0, 1.0, 0.5),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let bias_param = ModulatableParameter::new(
             BasicParameter::new("bias", -1.0, 1.0, 0.0),
             0.8  // 80% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         let output_gain_param = ModulatableParameter::new(
             BasicParameter::new("output_gain", 0.1, 2.0, 1.0),
             0.6  // 60% CV modulation range
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
 
         Self {
             node_info,
@@ -311,20 +311,14 @@ impl AudioNode for WaveshaperNode {
             return Ok(());
         }
 
-        // Get CV inputs
-        let drive_cv = ctx.inputs.get_cv_value("drive_cv");
-        let shape_amount_cv = ctx.inputs.get_cv_value("shape_amount_cv");
-        let bias_cv = ctx.inputs.get_cv_value("bias_cv");
-        let output_gain_cv = ctx.inputs.get_cv_value("output_gain_cv");
+        // Get CV inputs (full buffers for per-sample modulation)
+        let drive_cv = ctx.inputs.get_cv("drive_cv");
+        let shape_amount_cv = ctx.inputs.get_cv("shape_amount_cv");
+        let bias_cv = ctx.inputs.get_cv("bias_cv");
+        let output_gain_cv = ctx.inputs.get_cv("output_gain_cv");
         let shape_type_cv = ctx.inputs.get_cv_value("shape_type_cv");
 
-        // Apply CV modulation
-        let effective_drive = self.drive_param.modulate(self.drive, drive_cv);
-        let effective_shape_amount = self.shape_amount_param.modulate(self.shape_amount, shape_amount_cv);
-        let effective_bias = self.bias_param.modulate(self.bias, bias_cv);
-        let effective_output_gain = self.output_gain_param.modulate(self.output_gain, output_gain_cv);
-
-        // Update shape type from CV if provided
+        // Update shape type from CV if provided (control rate)
         let current_shape_type = if shape_type_cv != 0.0 {
             WaveshaperType::from_f32(shape_type_cv.clamp(0.0, 7.0))
         } else {
@@ -345,6 +339,12 @@ impl AudioNode for WaveshaperNode {
                 0.0 
             };
 
+            // Apply CV modulation per sample
+            let effective_drive = self.drive_param.modulate_smoothed(self.drive, drive_cv, i, self.sample_rate);
+            let effective_shape_amount = self.shape_amount_param.modulate_smoothed(self.shape_amount, shape_amount_cv, i, self.sample_rate);
+            let effective_bias = self.bias_param.modulate_smoothed(self.bias, bias_cv, i, self.sample_rate);
+            let effective_output_gain = self.output_gain_param.modulate_smoothed(self.output_gain, output_gain_cv, i, self.sample_rate);
+
             // Apply drive/input gain
             let driven_sample = input_sample * effective_drive;
 
@@ -370,11 +370,19 @@ impl AudioNode for WaveshaperNode {
         &self.node_info
     }
 
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
+
     fn reset(&mut self) {
         // Reset filter state
         self.filter_state = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn latency(&self) -> u32 {
         0 // No latency for waveshaping
     }
@@ -433,6 +441,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         // Should process without error
@@ -466,6 +476,8 @@ mod tests {
                 buffer_size: 64,
                 timestamp: 0,
                 bpm: 120.0,
+                events: Default::default(),
+                transport: Default::default(),
             };
             
             assert!(waveshaper.process(&mut ctx).is_ok());
@@ -499,6 +511,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(waveshaper.process(&mut ctx).is_ok());
@@ -529,6 +543,8 @@ mod tests {
             buffer_size: 256,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(waveshaper.process(&mut ctx).is_ok());
@@ -560,6 +576,8 @@ mod tests {
             buffer_size: 128,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(waveshaper.process(&mut ctx).is_ok());
@@ -590,6 +608,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(waveshaper.process(&mut ctx).is_ok());
@@ -619,6 +639,8 @@ mod tests {
             buffer_size: 512,
             timestamp: 0,
             bpm: 120.0,
+            events: Default::default(),
+            transport: Default::default(),
         };
         
         assert!(waveshaper.process(&mut ctx).is_ok());
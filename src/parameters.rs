@@ -35,6 +35,9 @@ pub trait ParameterDescriptor: Send + Sync + fmt::Debug {
     
     /// 単位（Hz, dB, % など）
     fn unit(&self) -> &'static str { "" }
+
+    /// 値の変化を平滑化する方法（ジッパーノイズ対策）
+    fn smoothing(&self) -> Smoothing { Smoothing::NONE }
     
     /// 値の検証
     fn validate(&self, value: f32) -> Result<f32, ParameterError> {
@@ -60,6 +63,175 @@ pub trait ParameterDescriptor: Send + Sync + fmt::Debug {
     }
 }
 
+/// How a parameter moves to a new value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingMode {
+    /// Jump immediately
+    None,
+    /// Straight ramp that arrives after `time_ms`
+    Linear,
+    /// Exponential approach; `time_ms` is the time constant (~63% of the way)
+    OnePole,
+}
+
+/// パラメーター平滑化の設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoothing {
+    pub mode: SmoothingMode,
+    pub time_ms: f32,
+}
+
+impl Smoothing {
+    pub const NONE: Smoothing = Smoothing { mode: SmoothingMode::None, time_ms: 0.0 };
+
+    /// 20 ms ramp, for gain, pan, level and frequency parameters
+    pub const DEFAULT: Smoothing = Smoothing { mode: SmoothingMode::Linear, time_ms: 20.0 };
+
+    pub fn linear(time_ms: f32) -> Self {
+        Self { mode: SmoothingMode::Linear, time_ms }
+    }
+
+    pub fn one_pole(time_ms: f32) -> Self {
+        Self { mode: SmoothingMode::OnePole, time_ms }
+    }
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// サンプル単位で目標値に追従する値
+///
+/// The first target is taken over immediately, so a node does not ramp from
+/// zero when it starts. Without a sample rate every change is immediate.
+#[derive(Debug, Clone)]
+pub struct SmoothedValue {
+    smoothing: Smoothing,
+    sample_rate: f32,
+    current: f32,
+    target: f32,
+    initialized: bool,
+    // Linear ramp
+    step: f32,
+    remaining: u32,
+    // One-pole feedback coefficient
+    coefficient: f32,
+}
+
+impl SmoothedValue {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            sample_rate: 0.0,
+            current: 0.0,
+            target: 0.0,
+            initialized: false,
+            step: 0.0,
+            remaining: 0,
+            coefficient: 0.0,
+        }
+    }
+
+    /// Smoother that starts at `value`
+    pub fn with_value(smoothing: Smoothing, sample_rate: f32, value: f32) -> Self {
+        let mut smoothed = Self::new(smoothing);
+        smoothed.set_sample_rate(sample_rate);
+        smoothed.reset(value);
+        smoothed
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+        self.update_coefficient();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.update_coefficient();
+    }
+
+    fn update_coefficient(&mut self) {
+        let time_samples = self.smoothing.time_ms * 0.001 * self.sample_rate;
+        self.coefficient = if time_samples > 0.0 { (-1.0 / time_samples).exp() } else { 0.0 };
+    }
+
+    /// Start moving towards `target`
+    pub fn set_target(&mut self, target: f32) {
+        if !self.initialized {
+            self.reset(target);
+            return;
+        }
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+
+        let samples = (self.smoothing.time_ms * 0.001 * self.sample_rate).round() as u32;
+        match self.smoothing.mode {
+            _ if samples == 0 => self.reset(target),
+            SmoothingMode::None => self.reset(target),
+            SmoothingMode::Linear => {
+                self.step = (target - self.current) / samples as f32;
+                self.remaining = samples;
+            }
+            SmoothingMode::OnePole => {}
+        }
+    }
+
+    /// Jump to `value` without smoothing
+    pub fn reset(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        self.remaining = 0;
+        self.initialized = true;
+    }
+
+    /// Advance one sample and return the new value
+    pub fn advance(&mut self) -> f32 {
+        match self.smoothing.mode {
+            SmoothingMode::Linear if self.remaining > 0 => {
+                self.remaining -= 1;
+                self.current = if self.remaining == 0 { self.target } else { self.current + self.step };
+            }
+            SmoothingMode::OnePole if self.current != self.target => {
+                self.current = self.target + (self.current - self.target) * self.coefficient;
+                if (self.current - self.target).abs() <= 1e-6 * self.target.abs().max(1.0) {
+                    self.current = self.target;
+                }
+            }
+            _ => {}
+        }
+        self.current
+    }
+
+    /// `set_target` and `advance` in one call, for the per-sample loop
+    pub fn advance_towards(&mut self, target: f32, sample_rate: f32) -> f32 {
+        if sample_rate != self.sample_rate {
+            self.set_sample_rate(sample_rate);
+        }
+        self.set_target(target);
+        self.advance()
+    }
+
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn is_smoothing(&self) -> bool {
+        self.current != self.target
+    }
+}
+
 /// パラメーターエラー型
 #[derive(Debug, Clone)]
 pub enum ParameterError {
@@ -114,6 +286,7 @@ pub struct BasicParameter {
     pub max: f32,
     pub default: f32,
     pub unit: &'static str,
+    pub smoothing: Smoothing,
 }
 
 impl BasicParameter {
@@ -124,6 +297,7 @@ impl BasicParameter {
             max,
             default,
             unit: "",
+            smoothing: Smoothing::NONE,
         }
     }
     
@@ -131,6 +305,11 @@ impl BasicParameter {
         self.unit = unit;
         self
     }
+
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
 }
 
 impl ParameterDescriptor for BasicParameter {
@@ -153,6 +332,10 @@ impl ParameterDescriptor for BasicParameter {
     fn unit(&self) -> &'static str {
         self.unit
     }
+
+    fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
 }
 
 /// CV変調可能なパラメーター
@@ -161,6 +344,10 @@ pub struct ModulatableParameter {
     pub base: BasicParameter,
     pub cv_amount: f32,  // CV変調の強度 (0.0 - 1.0)
     pub curve: ModulationCurve,
+    /// ブロック先頭のCV値のみを使用（軽量なパラメーター用）
+    pub control_rate: bool,
+    /// ベース値の平滑化（`modulate_smoothed`）
+    smoother: SmoothedValue,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -171,19 +358,36 @@ pub enum ModulationCurve {
 }
 
 impl ModulatableParameter {
+    /// Base values follow `base.smoothing` (none by default, see `with_smoothing`)
     pub fn new(base: BasicParameter, cv_amount: f32) -> Self {
+        let smoother = SmoothedValue::new(base.smoothing);
         Self {
             base,
             cv_amount,
             curve: ModulationCurve::Linear,
+            control_rate: false,
+            smoother,
         }
     }
+
+    /// 平滑化の設定（`Smoothing::NONE` で無効）
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.base.smoothing = smoothing;
+        self.smoother.set_smoothing(smoothing);
+        self
+    }
     
     pub fn with_curve(mut self, curve: ModulationCurve) -> Self {
         self.curve = curve;
         self
     }
     
+    /// コントロールレート（ブロック単位）で変調する
+    pub fn with_control_rate(mut self) -> Self {
+        self.control_rate = true;
+        self
+    }
+    
     /// CV入力を適用して最終値を計算
     pub fn modulate(&self, base_value: f32, cv_input: f32) -> f32 {
         let range = self.base.max - self.base.min;
@@ -206,6 +410,32 @@ impl ModulatableParameter {
         modulated.clamp(self.base.min, self.base.max)
     }
     
+    /// CVバッファのi番目のサンプルで変調
+    /// 
+    /// コントロールレートの場合はブロック先頭の値を使う。
+    pub fn modulate_at(&self, base_value: f32, cv_buffer: Option<&[f32]>, index: usize) -> f32 {
+        let index = if self.control_rate { 0 } else { index };
+        self.modulate(base_value, crate::processing::cv_sample_at(cv_buffer, index))
+    }
+    
+    /// 平滑化したベース値をCVバッファのi番目のサンプルで変調
+    ///
+    /// Call once per sample: every call advances the smoother by one sample.
+    pub fn modulate_smoothed(&mut self, base_value: f32, cv_buffer: Option<&[f32]>, index: usize, sample_rate: f32) -> f32 {
+        let base_value = self.smooth(base_value, sample_rate);
+        self.modulate_at(base_value, cv_buffer, index)
+    }
+
+    /// ベース値を1サンプル分平滑化（CVを独自に扱うノード用）
+    pub fn smooth(&mut self, base_value: f32, sample_rate: f32) -> f32 {
+        self.smoother.advance_towards(base_value, sample_rate)
+    }
+
+    /// 平滑化を飛ばしてベース値に合わせる（処理を休んでいる間など）
+    pub fn snap(&mut self, base_value: f32) {
+        self.smoother.reset(base_value);
+    }
+
     /// Set the base value (used by set_parameter)
     pub fn set_base_value(&mut self, value: f32) -> Result<(), ParameterError> {
         self.base.validate(value)?;
@@ -240,6 +470,10 @@ impl ParameterDescriptor for ModulatableParameter {
     fn unit(&self) -> &'static str {
         self.base.unit()
     }
+
+    fn smoothing(&self) -> Smoothing {
+        self.base.smoothing()
+    }
 }
 
 /// パラメーター管理のヘルパーマクロ
@@ -358,4 +592,57 @@ mod tests {
         assert!(param.modulate(50.0, 2.0) <= 100.0);
         assert!(param.modulate(50.0, -2.0) >= 0.0);
     }
+
+    #[test]
+    fn test_modulate_at() {
+        let param = ModulatableParameter::new(
+            BasicParameter::new("test", 0.0, 100.0, 50.0),
+            0.5,
+        );
+        let cv = [0.0, 0.2, 0.4];
+        
+        // オーディオレート: サンプルごとに変化
+        assert_eq!(param.modulate_at(50.0, Some(&cv), 0), 50.0);
+        assert_eq!(param.modulate_at(50.0, Some(&cv), 2), param.modulate(50.0, 0.4));
+        // バッファ外は最後の値を保持
+        assert_eq!(param.modulate_at(50.0, Some(&cv), 100), param.modulate(50.0, 0.4));
+        // 未接続
+        assert_eq!(param.modulate_at(50.0, None, 1), 50.0);
+        
+        // コントロールレート: 先頭値のみ
+        let control = param.with_control_rate();
+        assert_eq!(control.modulate_at(50.0, Some(&cv), 2), 50.0);
+    }
+
+    #[test]
+    fn test_smoothing() {
+        // 1ms at 10kHz = 10 samples
+        let mut linear = SmoothedValue::with_value(Smoothing::linear(1.0), 10000.0, 0.0);
+        linear.set_target(1.0);
+        let ramp: Vec<f32> = (0..12).map(|_| linear.advance()).collect();
+        assert!((ramp[0] - 0.1).abs() < 1e-6);
+        assert!((ramp[4] - 0.5).abs() < 1e-6);
+        assert_eq!(ramp[9], 1.0);
+        assert!(!linear.is_smoothing());
+
+        let mut one_pole = SmoothedValue::with_value(Smoothing::one_pole(1.0), 10000.0, 0.0);
+        one_pole.set_target(1.0);
+        let after_time_constant = (0..10).map(|_| one_pole.advance()).last().unwrap();
+        assert!((after_time_constant - (1.0 - (-1.0f32).exp())).abs() < 1e-3);
+
+        // Smoothing is opt-in; the first value is taken over without a ramp
+        let mut stepped = ModulatableParameter::new(BasicParameter::new("ratio", 1.0, 20.0, 4.0), 0.5);
+        assert_eq!(stepped.smoothing(), Smoothing::NONE);
+        stepped.modulate_smoothed(4.0, None, 0, 1000.0);
+        assert_eq!(stepped.modulate_smoothed(8.0, None, 1, 1000.0), 8.0);
+
+        let mut param = ModulatableParameter::new(BasicParameter::new("gain", 0.0, 1.0, 0.5), 0.5)
+            .with_smoothing(Smoothing::DEFAULT);
+        assert_eq!(param.modulate_smoothed(0.5, None, 0, 1000.0), 0.5);
+        let next = param.modulate_smoothed(1.0, None, 1, 1000.0);
+        assert!(next > 0.5 && next < 1.0);
+
+        let mut instant = param.with_smoothing(Smoothing::NONE);
+        assert_eq!(instant.modulate_smoothed(0.2, None, 2, 1000.0), 0.2);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Patch format migrations.
+//!
+//! Migrations run on the raw JSON before it is deserialized, one step per
+//! format version:
+//!
+//! - 0: `AudioGraph` layout (`demo_setup.json`) - nodes in a map keyed by UUID
+//! - 1: `PatchFile` without `format_version` (`examples/*_patch.json`)
+//! - 2: renamed mixer and oscilloscope parameters and ports, `format_version`
+//!
+//! Every change is recorded in a `MigrationReport`.
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Format written by this version
+pub const PATCH_FORMAT_VERSION: u32 = 2;
+
+/// What a migration changed
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    /// Format the file was written in
+    pub from_version: u32,
+    /// One entry per change, in the order applied
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Upgraded patch from format {} to {}", self.from_version, PATCH_FORMAT_VERSION)?;
+        for change in &self.changes {
+            write!(f, "\n  - {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenameTarget {
+    Parameter,
+    Input,
+    Output,
+}
+
+/// A parameter or port of one node type that changed its name
+struct Rename {
+    node_type: &'static str,
+    target: RenameTarget,
+    from: &'static str,
+    to: &'static str,
+}
+
+const fn rename(node_type: &'static str, target: RenameTarget, from: &'static str, to: &'static str) -> Rename {
+    Rename { node_type, target, from, to }
+}
+
+/// Renamed in format 2: the mixer became stereo per channel, the oscilloscope
+/// parameters lost their UI abbreviations
+const RENAMES_V2: &[Rename] = &[
+    rename("mixer", RenameTarget::Parameter, "gain_1", "ch1_gain"),
+    rename("mixer", RenameTarget::Parameter, "gain_2", "ch2_gain"),
+    rename("mixer", RenameTarget::Parameter, "gain_3", "ch3_gain"),
+    rename("mixer", RenameTarget::Parameter, "gain_4", "ch4_gain"),
+    rename("mixer", RenameTarget::Parameter, "pan_1", "ch1_pan"),
+    rename("mixer", RenameTarget::Parameter, "pan_2", "ch2_pan"),
+    rename("mixer", RenameTarget::Parameter, "pan_3", "ch3_pan"),
+    rename("mixer", RenameTarget::Parameter, "pan_4", "ch4_pan"),
+    rename("mixer", RenameTarget::Input, "audio_in_1", "ch1_l"),
+    rename("mixer", RenameTarget::Input, "audio_in_2", "ch2_l"),
+    rename("mixer", RenameTarget::Input, "audio_in_3", "ch3_l"),
+    rename("mixer", RenameTarget::Input, "audio_in_4", "ch4_l"),
+    rename("mixer", RenameTarget::Output, "audio_out_l", "mix_l"),
+    rename("mixer", RenameTarget::Output, "audio_out_r", "mix_r"),
+    rename("oscilloscope", RenameTarget::Parameter, "time_div", "time_scale"),
+    rename("oscilloscope", RenameTarget::Parameter, "volt_div", "voltage_scale"),
+    rename("oscilloscope", RenameTarget::Parameter, "position_h", "horizontal_position"),
+    rename("oscilloscope", RenameTarget::Parameter, "position_v", "vertical_position"),
+];
+
+/// Format of a parsed patch: `format_version` if present, otherwise guessed from the layout
+pub fn detect_version(patch: &Value) -> u32 {
+    match patch.get("format_version").and_then(Value::as_u64) {
+        Some(version) => version as u32,
+        None if patch.get("nodes").is_some_and(Value::is_object) => 0,
+        None => 1,
+    }
+}
+
+/// Upgrade a parsed patch to `PATCH_FORMAT_VERSION` in place
+pub fn migrate(patch: &mut Value) -> Result<MigrationReport, String> {
+    let from_version = detect_version(patch);
+    if from_version > PATCH_FORMAT_VERSION {
+        return Err(format!("Patch format {} is newer than the supported format {}",
+                           from_version, PATCH_FORMAT_VERSION));
+    }
+
+    let mut report = MigrationReport { from_version, changes: Vec::new() };
+    for version in from_version..PATCH_FORMAT_VERSION {
+        match version {
+            0 => from_audio_graph(patch, &mut report)?,
+            1 => apply_renames(patch, RENAMES_V2, "", &mut report),
+            _ => unreachable!("no migration from format {}", version),
+        }
+    }
+
+    if let Some(patch) = patch.as_object_mut() {
+        patch.insert("format_version".to_string(), Value::from(PATCH_FORMAT_VERSION));
+    }
+    Ok(report)
+}
+
+/// 0 -> 1: node map with port lists to a `PatchFile` node list
+fn from_audio_graph(patch: &mut Value, report: &mut MigrationReport) -> Result<(), String> {
+    let graph = patch.as_object_mut().ok_or("Patch is not a JSON object")?;
+    let Some(Value::Object(nodes)) = graph.remove("nodes") else {
+        return Err("AudioGraph patch has no node map".to_string());
+    };
+
+    let mut list = Vec::with_capacity(nodes.len());
+    for (key, node) in nodes {
+        let Value::Object(node) = node else {
+            return Err(format!("Node {} is not a JSON object", key));
+        };
+        let field = |name: &str| node.get(name).cloned();
+        let mut entry = Map::new();
+        entry.insert("id".to_string(), field("id").unwrap_or_else(|| Value::from(key.clone())));
+        entry.insert("type".to_string(), field("node_type").ok_or_else(|| format!("Node {} has no node_type", key))?);
+        entry.insert("name".to_string(), field("name").unwrap_or_else(|| Value::from(key.clone())));
+        entry.insert("parameters".to_string(), field("parameters").unwrap_or_else(|| Value::Object(Map::new())));
+        list.push(Value::Object(entry));
+    }
+    report.changes.push(format!("Converted AudioGraph layout with {} nodes to a node list", list.len()));
+    graph.insert("nodes".to_string(), Value::Array(list));
+
+    if let Some(Value::Array(connections)) = graph.get_mut("connections") {
+        let mut dropped = 0;
+        for connection in connections.iter_mut().filter_map(Value::as_object_mut) {
+            // Feedback edges are classified again when the patch is connected
+            if connection.remove("feedback").is_some() {
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            report.changes.push(format!("Dropped the feedback flag of {} connections", dropped));
+        }
+    }
+    graph.entry("connections").or_insert_with(|| Value::Array(Vec::new()));
+    if graph.remove("processing_order").is_some() {
+        report.changes.push("Dropped the stored processing order".to_string());
+    }
+    Ok(())
+}
+
+/// Apply `renames` to the nodes and connections of a patch and of every subpatch in it
+fn apply_renames(patch: &mut Value, renames: &[Rename], scope: &str, report: &mut MigrationReport) {
+    let node_types: Vec<(String, String)> = patch.get("nodes")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|node| Some((node.get("id")?.as_str()?.to_string(), node.get("type")?.as_str()?.to_string())))
+        .collect();
+    let type_of = |id: &str| node_types.iter().find(|(node, _)| node == id).map(|(_, node_type)| node_type.as_str());
+    let renamed = |node_type: Option<&str>, target: RenameTarget, name: &str| {
+        renames.iter()
+            .find(|r| Some(r.node_type) == node_type && r.target == target && r.from == name)
+            .map(|r| r.to)
+    };
+
+    if let Some(Value::Array(nodes)) = patch.get_mut("nodes") {
+        for node in nodes.iter_mut().filter_map(Value::as_object_mut) {
+            let id = node.get("id").and_then(Value::as_str).unwrap_or_default().to_string();
+            let node_type = node.get("type").and_then(Value::as_str).map(str::to_string);
+
+            if let Some(Value::Object(parameters)) = node.get_mut("parameters") {
+                let names: Vec<String> = parameters.keys().cloned().collect();
+                for name in names {
+                    let Some(to) = renamed(node_type.as_deref(), RenameTarget::Parameter, &name) else { continue };
+                    if let Some(value) = parameters.remove(&name) {
+                        parameters.insert(to.to_string(), value);
+                        report.changes.push(format!("{}{}: renamed parameter {} to {}", scope, id, name, to));
+                    }
+                }
+            }
+
+            if let Some(subpatch) = node.get_mut("subpatch") {
+                apply_renames(subpatch, renames, &format!("{}{}/", scope, id), report);
+            }
+        }
+    }
+
+    let mut rename_port = |port: &mut Value, node: &str, target: RenameTarget| {
+        let Some(name) = port.as_str() else { return };
+        if let Some(to) = renamed(type_of(node), target, name) {
+            report.changes.push(format!("{}{}: renamed {} port {} to {}", scope, node,
+                                        if target == RenameTarget::Input { "input" } else { "output" }, name, to));
+            *port = Value::from(to);
+        }
+    };
+
+    if let Some(Value::Array(connections)) = patch.get_mut("connections") {
+        for connection in connections.iter_mut().filter_map(Value::as_object_mut) {
+            for (node_key, port_key, target) in [("source_node", "source_port", RenameTarget::Output),
+                                                 ("target_node", "target_port", RenameTarget::Input)] {
+                let node = connection.get(node_key).and_then(Value::as_str).unwrap_or_default().to_string();
+                if let Some(port) = connection.get_mut(port_key) {
+                    rename_port(port, &node, target);
+                }
+            }
+        }
+    }
+
+    // Ports and parameters a subpatch exposes refer to its inner nodes
+    for (list, target) in [("inputs", RenameTarget::Input), ("outputs", RenameTarget::Output)] {
+        if let Some(Value::Array(exposed)) = patch.get_mut(list) {
+            for entry in exposed.iter_mut().filter_map(Value::as_object_mut) {
+                let node = entry.get("node").and_then(Value::as_str).unwrap_or_default().to_string();
+                if let Some(port) = entry.get_mut("port") {
+                    rename_port(port, &node, target);
+                }
+            }
+        }
+    }
+    if let Some(Value::Array(promoted)) = patch.get_mut("parameters") {
+        for entry in promoted.iter_mut().filter_map(Value::as_object_mut) {
+            let node = entry.get("node").and_then(Value::as_str).unwrap_or_default().to_string();
+            let Some(Value::String(parameter)) = entry.get_mut("parameter") else { continue };
+            if let Some(to) = renamed(type_of(&node), RenameTarget::Parameter, parameter) {
+                report.changes.push(format!("{}{}: renamed promoted parameter {} to {}", scope, node, parameter, to));
+                *parameter = to.to_string();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::ProcessingGraph;
+    use crate::nodes::create_patch_node;
+    use crate::patch::PatchFile;
+    use serde_json::json;
+
+    #[test]
+    fn test_audio_graph_layout_upgrades() {
+        let json = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/demo_setup.json")).unwrap();
+        let (patch, report) = PatchFile::parse(&json).unwrap();
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.changes[0], "Converted AudioGraph layout with 2 nodes to a node list");
+        assert_eq!(patch.format_version, PATCH_FORMAT_VERSION);
+
+        let mut graph = ProcessingGraph::new();
+        graph.load_patch(&patch, |node| create_patch_node(node, 44100.0)).unwrap();
+        let osc = graph.get_node("02b2cf94-7ef4-4f2b-9ad0-9b1ce1018e48").unwrap();
+        assert_eq!(osc.node_info().name, "osc");
+        assert_eq!(graph.connections().len(), 2);
+    }
+
+    #[test]
+    fn test_renames_are_reported() {
+        let mut patch = json!({
+            "nodes": [
+                { "id": "mix", "type": "mixer", "name": "Mix", "parameters": { "gain_1": 0.5, "master_gain": 0.8 } },
+                { "id": "osc", "type": "oscillator", "name": "Osc", "parameters": {} }
+            ],
+            "connections": [
+                { "source_node": "osc", "source_port": "audio_out", "target_node": "mix", "target_port": "audio_in_1" },
+                { "source_node": "mix", "source_port": "audio_out_l", "target_node": "osc", "target_port": "amplitude_cv" }
+            ]
+        });
+        let report = migrate(&mut patch).unwrap();
+
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.changes, vec![
+            "mix: renamed parameter gain_1 to ch1_gain",
+            "mix: renamed input port audio_in_1 to ch1_l",
+            "mix: renamed output port audio_out_l to mix_l",
+        ]);
+        assert_eq!(patch["nodes"][0]["parameters"]["ch1_gain"], 0.5);
+        assert_eq!(patch["connections"][1]["source_port"], "mix_l");
+        assert_eq!(patch["format_version"], PATCH_FORMAT_VERSION);
+
+        // Current files pass through untouched
+        assert!(migrate(&mut patch).unwrap().is_empty());
+        patch["format_version"] = json!(PATCH_FORMAT_VERSION + 1);
+        assert!(migrate(&mut patch).is_err());
+    }
+
+    #[test]
+    fn test_example_patches_load() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in std::fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let (patch, _) = PatchFile::load(path.to_str().unwrap()).unwrap();
+            let mut graph = ProcessingGraph::new();
+            graph.load_patch(&patch, |node| create_patch_node(node, 44100.0))
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+}
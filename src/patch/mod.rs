@@ -0,0 +1,144 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Patch file format.
+//!
+//! Nodes are referenced by `id`. The editor uses the node name as `id` and
+//! creates nodes under that name; `ProcessingGraph::to_patch` writes node
+//! UUIDs instead, so IDs survive a save and load. A node of type `subpatch`
+//! carries its inner patch in `subpatch`, so subpatches nest to any depth.
+//!
+//! Files carry `format_version`; older files are upgraded on load (see `migration`).
+
+pub mod migration;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+use crate::graph::OutputRoute;
+use crate::processing::NodeState;
+use migration::MigrationReport;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchNode {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    pub name: String,
+    #[serde(default)]
+    pub position: PatchPosition,
+    pub parameters: HashMap<String, f32>,
+    /// State that parameters cannot express (see `AudioNode::save_state`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<NodeState>,
+    /// Inner patch of a `subpatch` node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subpatch: Option<Box<SubpatchDefinition>>,
+    /// Device channels of an `output` node, if not the first stereo pair
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_route: Option<OutputRoute>,
+    /// Engine-level bypass (see `ProcessingGraph::set_bypass`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bypassed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PatchPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchConnection {
+    pub source_node: String,
+    pub source_port: String,
+    pub target_node: String,
+    pub target_port: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchFile {
+    /// Format the file was written in (`migration::PATCH_FORMAT_VERSION`)
+    #[serde(default)]
+    pub format_version: u32,
+    pub patch_name: Option<String>,
+    pub description: Option<String>,
+    pub nodes: Vec<PatchNode>,
+    pub connections: Vec<PatchConnection>,
+    pub notes: Option<Vec<String>>,
+    /// Delay of feedback edges in samples, if not the block size (see `ProcessingGraph::set_feedback_delay`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback_delay: Option<usize>,
+}
+
+impl PatchFile {
+    /// Parse a JSON patch of any supported format, upgrading it to the current one
+    pub fn parse(json: &str) -> Result<(Self, MigrationReport), String> {
+        let mut value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+        let report = migration::migrate(&mut value)?;
+        let patch = serde_json::from_value(value)
+            .map_err(|e| format!("Failed to parse patch: {}", e))?;
+        Ok((patch, report))
+    }
+
+    /// Read a JSON patch (see `parse`)
+    pub fn load(path: &str) -> Result<(Self, MigrationReport), String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+        Self::parse(&json)
+    }
+
+    /// Write as pretty-printed JSON
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize patch: {}", e))?;
+        fs::write(path, json)
+            .map_err(|e| format!("Failed to write file {}: {}", path, e))
+    }
+}
+
+/// サブパッチの中身 - 内部ノード、接続、外部に見せるポートとパラメーター
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SubpatchDefinition {
+    pub nodes: Vec<PatchNode>,
+    pub connections: Vec<PatchConnection>,
+    #[serde(default)]
+    pub inputs: Vec<ExposedPort>,
+    #[serde(default)]
+    pub outputs: Vec<ExposedPort>,
+    #[serde(default)]
+    pub parameters: Vec<PromotedParameter>,
+}
+
+/// Inner port shown on the subpatch as `name`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExposedPort {
+    pub name: String,
+    pub node: String,
+    pub port: String,
+}
+
+/// Inner parameter shown on the subpatch as `name`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromotedParameter {
+    pub name: String,
+    pub node: String,
+    pub parameter: String,
+}
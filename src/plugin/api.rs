@@ -25,7 +25,7 @@ use std::collections::HashMap;
 use std::os::raw::{c_char, c_void};
 use serde::{Deserialize, Serialize};
 
-use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo};
+use crate::processing::{AudioNode, EventKind, ProcessContext, ProcessingError, NodeInfo, NodeState};
 use crate::parameters::Parameterizable;
 use crate::plugin::{PluginResult, PluginStats, PluginConfig};
 
@@ -260,11 +260,35 @@ impl AudioNode for PluginNodeWrapper {
     fn node_info(&self) -> &NodeInfo {
         self.inner.node_info()
     }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        self.inner.node_info_mut()
+    }
     
     fn reset(&mut self) {
         self.inner.reset();
     }
 
+    fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+        self.inner.prepare(sample_rate, max_block_size);
+    }
+
+    fn save_state(&self) -> Option<NodeState> {
+        self.inner.save_state()
+    }
+
+    fn handle_event(&mut self, event: &EventKind) {
+        self.inner.handle_event(event);
+    }
+
+    fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+        self.inner.load_state(state)
+    }
+
+    fn latency(&self) -> u32 {
+        self.inner.latency()
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -295,6 +319,110 @@ impl Parameterizable for PluginNodeWrapper {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parameters::{ParameterDescriptor, ParameterError};
+    use crate::processing::NodeCategory;
+
+    /// Node as a plugin would provide it
+    struct InnerNode {
+        info: NodeInfo,
+        latency: u32,
+        state: Option<NodeState>,
+        events: Vec<EventKind>,
+    }
+
+    impl InnerNode {
+        fn new() -> Self {
+            Self {
+                info: NodeInfo {
+                    id: uuid::Uuid::new_v4(),
+                    name: "inner".to_string(),
+                    node_type: "inner".to_string(),
+                    category: NodeCategory::Utility,
+                    description: String::new(),
+                    input_ports: Vec::new(),
+                    output_ports: Vec::new(),
+                    latency_samples: 0,
+                    supports_bypass: false,
+                },
+                latency: 64,
+                state: None,
+                events: Vec::new(),
+            }
+        }
+    }
+
+    impl Parameterizable for InnerNode {
+        fn set_parameter(&mut self, name: &str, _value: f32) -> Result<(), ParameterError> {
+            Err(ParameterError::NotFound { name: name.to_string() })
+        }
+
+        fn get_parameter(&self, name: &str) -> Result<f32, ParameterError> {
+            Err(ParameterError::NotFound { name: name.to_string() })
+        }
+
+        fn get_all_parameters(&self) -> HashMap<String, f32> {
+            HashMap::new()
+        }
+
+        fn get_parameter_descriptors(&self) -> Vec<Box<dyn ParameterDescriptor>> {
+            Vec::new()
+        }
+    }
+
+    impl AudioNode for InnerNode {
+        fn process(&mut self, _ctx: &mut ProcessContext) -> Result<(), ProcessingError> {
+            Ok(())
+        }
+
+        fn node_info(&self) -> &NodeInfo {
+            &self.info
+        }
+
+        fn reset(&mut self) {}
+
+        fn save_state(&self) -> Option<NodeState> {
+            self.state.clone()
+        }
+
+        fn load_state(&mut self, state: &NodeState) -> Result<(), String> {
+            self.state = Some(state.clone());
+            Ok(())
+        }
+
+        fn handle_event(&mut self, event: &EventKind) {
+            self.events.push(event.clone());
+        }
+
+        fn latency(&self) -> u32 {
+            self.latency
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+            self
+        }
+    }
+
+    fn wrap(inner: InnerNode) -> PluginNodeWrapper {
+        PluginNodeWrapper::new("plugin".to_string(), "inner".to_string(), Box::new(inner), PluginConfig::default())
+    }
+
+    #[test]
+    fn test_wrapper_forwards_to_inner_node() {
+        let mut wrapper = wrap(InnerNode::new());
+        assert_eq!(wrapper.latency(), 64);
+
+        let state = serde_json::json!({ "preset": 3 });
+        wrapper.load_state(&state).unwrap();
+        assert_eq!(wrapper.save_state(), Some(state));
+
+        wrapper.handle_event(&EventKind::NoteOn { pitch: 0.5, velocity: 1.0 });
+        let inner = wrapper.inner.as_any().downcast_ref::<InnerNode>().unwrap();
+        assert_eq!(inner.events, vec![EventKind::NoteOn { pitch: 0.5, velocity: 1.0 }]);
+    }
 
     #[test]
     fn test_plugin_metadata_serialization() {
@@ -20,12 +20,18 @@
 //! 
 //! This module provides convenience traits, macros, and utilities to make
 //! plugin development easier and more productive.
+//!
+//! Parameters created `with_smoothing` and read with `ModulatableParameter::modulate_smoothed`
+//! (or through a `SmoothedValue`) follow UI changes without zipper noise, like the built-in nodes.
 
 use std::collections::HashMap;
 use uuid::Uuid;
 
 pub use crate::processing::{AudioNode, ProcessContext, ProcessingError, NodeInfo, NodeCategory, PortInfo};
-pub use crate::parameters::{BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ParameterError};
+pub use crate::parameters::{
+    BasicParameter, ModulatableParameter, Parameterizable, ParameterDescriptor, ParameterError,
+    SmoothedValue, Smoothing, SmoothingMode,
+};
 pub use crate::graph::PortType;
 pub use crate::plugin::{
     PluginError, PluginResult, PluginConfig, PluginStats,
@@ -138,7 +144,7 @@ impl BaseOscillator {
         let frequency_param = ModulatableParameter::new(
             BasicParameter::new("frequency", 20.0, 20000.0, 440.0),
             1.0
-        );
+        ).with_smoothing(Smoothing::DEFAULT);
         
         Self {
             node_info,
@@ -151,7 +157,12 @@ impl BaseOscillator {
     
     /// Update the oscillator's phase for the next sample
     pub fn update_phase(&mut self) {
-        let phase_increment = self.frequency / self.sample_rate;
+        self.advance_phase(self.frequency);
+    }
+    
+    /// Advance the phase by one sample at the given (modulated) frequency
+    pub fn advance_phase(&mut self, frequency: f32) {
+        let phase_increment = frequency / self.sample_rate;
         self.phase += phase_increment;
         if self.phase >= 1.0 {
             self.phase -= 1.0;
@@ -166,8 +177,7 @@ impl AudioNode for BaseOscillator {
         }
         
         // Get CV input for frequency modulation
-        let frequency_cv = ctx.inputs.get_cv_value("frequency_cv");
-        let _effective_frequency = self.frequency_param.modulate(self.frequency, frequency_cv);
+        let frequency_cv = ctx.inputs.get_cv("frequency_cv");
         
         // Get output buffer
         let output = ctx.outputs.get_audio_mut("audio_out")
@@ -176,8 +186,9 @@ impl AudioNode for BaseOscillator {
             })?;
         
         // Generate samples (override in derived implementations)
-        for sample in output.iter_mut() {
-            self.update_phase();
+        for (i, sample) in output.iter_mut().enumerate() {
+            let effective_frequency = self.frequency_param.modulate_smoothed(self.frequency, frequency_cv, i, self.sample_rate);
+            self.advance_phase(effective_frequency);
             *sample = (self.phase * 2.0 * std::f32::consts::PI).sin();
         }
         
@@ -187,11 +198,19 @@ impl AudioNode for BaseOscillator {
     fn node_info(&self) -> &NodeInfo {
         &self.node_info
     }
+
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        Some(&mut self.node_info)
+    }
     
     fn reset(&mut self) {
         self.phase = 0.0;
     }
 
+    fn prepare(&mut self, sample_rate: f32, _max_block_size: usize) {
+        self.sample_rate = sample_rate;
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
@@ -266,16 +285,17 @@ macro_rules! create_oscillator_plugin {
                     return Ok(());
                 }
                 
-                let frequency_cv = ctx.inputs.get_cv_value("frequency_cv");
-                let _effective_frequency = self.base.frequency_param.modulate(self.base.frequency, frequency_cv);
+                let frequency_cv = ctx.inputs.get_cv("frequency_cv");
                 
                 let output = ctx.outputs.get_audio_mut("audio_out")
                     .ok_or_else(|| ProcessingError::OutputBufferError {
                         port_name: "audio_out".to_string()
                     })?;
                 
-                for sample in output.iter_mut() {
-                    self.base.update_phase();
+                for (i, sample) in output.iter_mut().enumerate() {
+                    let effective_frequency = self.base.frequency_param.modulate_smoothed(
+                        self.base.frequency, frequency_cv, i, self.base.sample_rate);
+                    self.base.advance_phase(effective_frequency);
                     *sample = $generate_fn(self.base.phase);
                 }
                 
@@ -285,10 +305,18 @@ macro_rules! create_oscillator_plugin {
             fn node_info(&self) -> &NodeInfo {
                 self.base.node_info()
             }
+
+            fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+                self.base.node_info_mut()
+            }
             
             fn reset(&mut self) {
                 self.base.reset();
             }
+
+            fn prepare(&mut self, sample_rate: f32, max_block_size: usize) {
+                self.base.prepare(sample_rate, max_block_size);
+            }
             
             fn latency(&self) -> u32 {
                 0
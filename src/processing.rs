@@ -16,11 +16,16 @@
  * along with this program. If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::collections::HashMap;
 use crate::parameters::{Parameterizable, ParameterError};
-use crate::graph::{Node, Port, PortType};
+use crate::graph::{FanIn, Node, Port, PortType};
+use crate::transport::TransportInfo;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use uuid::Uuid;
 
+/// ノード固有の状態 - 中身はノードごとに自由なserdeデータ
+pub type NodeState = serde_json::Value;
+
 /// オーディオ処理のコンテキスト - すべての処理情報を統一
 #[derive(Debug)]
 pub struct ProcessContext {
@@ -34,8 +39,12 @@ pub struct ProcessContext {
     pub buffer_size: usize,
     /// 処理タイムスタンプ（サンプル数）
     pub timestamp: u64,
-    /// BPM（シーケンサー等で使用）
+    /// BPM（シーケンサー等で使用）- `transport.tempo` と同じ
     pub bpm: f32,
+    /// このブロック内のイベント（オフセット順）
+    pub events: EventList,
+    /// ブロック開始時点のトランスポート
+    pub transport: TransportInfo,
 }
 
 impl ProcessContext {
@@ -48,6 +57,8 @@ impl ProcessContext {
             buffer_size,
             timestamp: 0,
             bpm: 120.0,
+            events: EventList::new(),
+            transport: TransportInfo::default(),
         }
     }
     
@@ -60,6 +71,158 @@ impl ProcessContext {
     pub fn inputs(&self) -> &InputBuffers {
         &self.inputs
     }
+
+    /// Context with the same ports, used to process part of a block
+    pub(crate) fn sub_block_context(&self) -> Self {
+        let size = self.buffer_size;
+        Self::new(
+            InputBuffers { buffers: self.inputs.buffers.like(size) },
+            OutputBuffers { buffers: self.outputs.buffers.like(size) },
+            self.sample_rate,
+            size,
+        )
+    }
+
+    /// Hand every event of the block to `node` without processing
+    pub(crate) fn apply_events(&self, node: &mut dyn AudioNode) {
+        self.events.iter().for_each(|event| node.handle_event(&event.kind));
+    }
+
+    /// Process `node` for this block, applying `events` at their offsets.
+    ///
+    /// Between two events the node runs on a sub-block copied through
+    /// `scratch` (a `sub_block_context` of this context), so a change lands on
+    /// its exact sample without the node having to look at the events.
+    pub(crate) fn process_node(&mut self, node: &mut dyn AudioNode, scratch: &mut ProcessContext) -> Result<(), ProcessingError> {
+        // Events at the start of the block need no splitting
+        if self.events.iter().all(|event| event.offset == 0) {
+            self.apply_events(node);
+            return node.process(self);
+        }
+
+        scratch.sample_rate = self.sample_rate;
+        scratch.bpm = self.bpm;
+        for (range, events) in self.events.segments(self.buffer_size) {
+            scratch.transport = self.transport.advanced(range.start, self.sample_rate);
+            events.iter().for_each(|event| node.handle_event(&event.kind));
+
+            scratch.inputs.buffers.copy_range(&self.inputs.buffers, range.clone());
+            scratch.outputs.buffers.reset(range.len());
+            scratch.buffer_size = range.len();
+            scratch.timestamp = self.timestamp + range.start as u64;
+            node.process(scratch)?;
+
+            self.outputs.buffers.write_at(&scratch.outputs.buffers, range.start);
+        }
+        Ok(())
+    }
+}
+
+/// ノードへのイベントの種類
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    /// Set a parameter from this sample on
+    Parameter { name: String, value: f32 },
+    GateOn,
+    GateOff,
+    /// Note with its pitch in V/oct and a velocity of 0.0 - 1.0
+    NoteOn { pitch: f32, velocity: f32 },
+    NoteOff { pitch: f32 },
+}
+
+/// ブロック内の位置（サンプル）付きイベント
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeEvent {
+    pub offset: usize,
+    pub kind: EventKind,
+}
+
+/// オフセット順のイベント列
+///
+/// Events at the same offset keep the order they were pushed in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventList {
+    events: Vec<NodeEvent>,
+}
+
+impl EventList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { events: Vec::with_capacity(capacity) }
+    }
+
+    /// Add an event at `offset` samples into the block
+    pub fn push(&mut self, offset: usize, kind: EventKind) {
+        let index = self.events.partition_point(|event| event.offset <= offset);
+        self.events.insert(index, NodeEvent { offset, kind });
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, NodeEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Take the events out, keeping the capacity
+    pub fn drain(&mut self) -> std::vec::Drain<'_, NodeEvent> {
+        self.events.drain(..)
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Split a block of `frames` at the event offsets. Each sub-block comes
+    /// with the events due at its start; events at or past `frames` are left out.
+    pub fn segments(&self, frames: usize) -> EventSegments<'_> {
+        EventSegments { events: &self.events, start: 0, frames }
+    }
+}
+
+impl<'a> IntoIterator for &'a EventList {
+    type Item = &'a NodeEvent;
+    type IntoIter = std::slice::Iter<'a, NodeEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator returned by `EventList::segments`
+#[derive(Debug)]
+pub struct EventSegments<'a> {
+    events: &'a [NodeEvent],
+    start: usize,
+    frames: usize,
+}
+
+impl<'a> Iterator for EventSegments<'a> {
+    type Item = (Range<usize>, &'a [NodeEvent]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start >= self.frames {
+            return None;
+        }
+
+        let due = self.events.iter().take_while(|event| event.offset <= self.start).count();
+        let (now, rest) = self.events.split_at(due);
+        let end = rest.first().map_or(self.frames, |event| event.offset.min(self.frames));
+
+        let range = self.start..end;
+        self.events = rest;
+        self.start = end;
+        Some((range, now))
+    }
 }
 
 /// 入力ポート（バッファ）の管理
@@ -68,11 +231,344 @@ pub type InputPorts = InputBuffers;
 /// 出力ポート（バッファ）の管理  
 pub type OutputPorts = OutputBuffers;
 
+/// ステレオバッファ（L/R独立）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StereoBuffer {
+    pub left: Vec<f32>,
+    pub right: Vec<f32>,
+}
+
+impl StereoBuffer {
+    pub fn new(size: usize) -> Self {
+        Self {
+            left: vec![0.0; size],
+            right: vec![0.0; size],
+        }
+    }
+
+    /// モノラル→ステレオ: 同じ信号を両チャンネルへ
+    pub fn from_mono(mono: &[f32]) -> Self {
+        Self {
+            left: mono.to_vec(),
+            right: mono.to_vec(),
+        }
+    }
+
+    /// ステレオ→モノラル: L/Rの平均
+    pub fn to_mono(&self) -> Vec<f32> {
+        self.left.iter().zip(self.right.iter())
+            .map(|(l, r)| (l + r) * 0.5)
+            .collect()
+    }
+
+    /// フレーム数
+    pub fn len(&self) -> usize {
+        self.left.len().min(self.right.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        self.left.fill(value);
+        self.right.fill(value);
+    }
+
+    /// フレーム数を変更（増えた分は0.0）
+    pub fn resize(&mut self, frames: usize) {
+        self.left.resize(frames, 0.0);
+        self.right.resize(frames, 0.0);
+    }
+}
+
+/// Number of voice channels every poly cable has room for
+pub const MAX_VOICES: usize = 16;
+
+/// ポリフォニックバッファ（ボイスごとのモノラルチャンネル）
+///
+/// Always holds `MAX_VOICES` channels so the voice count can change without
+/// allocating; only the first `voices` of them carry signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyBuffer {
+    pub channels: Vec<Vec<f32>>,
+    pub voices: usize,
+}
+
+impl PolyBuffer {
+    pub fn new(size: usize) -> Self {
+        Self {
+            channels: vec![vec![0.0; size]; MAX_VOICES],
+            voices: 0,
+        }
+    }
+
+    /// フレーム数
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Channels that carry signal
+    pub fn active(&self) -> &[Vec<f32>] {
+        &self.channels[..self.voices]
+    }
+
+    pub fn fill(&mut self, value: f32) {
+        self.channels.iter_mut().for_each(|channel| channel.fill(value));
+    }
+
+    /// フレーム数を変更（増えた分は0.0）
+    pub fn resize(&mut self, frames: usize) {
+        self.channels.iter_mut().for_each(|channel| channel.resize(frames, 0.0));
+    }
+
+    /// ポリ→モノラル: 全ボイスの和
+    pub fn sum_into(&self, output: &mut [f32]) {
+        output.fill(0.0);
+        for channel in self.active() {
+            output.iter_mut().zip(channel.iter()).for_each(|(o, s)| *o += s);
+        }
+    }
+}
+
+/// ポート名で引けるバッファ列
+///
+/// ノードのポート数は少ないので線形探索で十分。追加した位置は変わらないため、
+/// 実行プランはポート名を引かずに `BufferSlot` で直接アクセスできる。
+#[derive(Debug)]
+struct NamedBuffers<T> {
+    entries: Vec<(String, T)>,
+}
+
+impl<T> Default for NamedBuffers<T> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
+impl<T> NamedBuffers<T> {
+    /// 追加（同名があれば置き換え）して位置を返す
+    fn insert(&mut self, name: String, buffer: T) -> usize {
+        match self.index_of(&name) {
+            Some(index) => {
+                self.entries[index].1 = buffer;
+                index
+            }
+            None => {
+                self.entries.push((name, buffer));
+                self.entries.len() - 1
+            }
+        }
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.entries.iter().position(|(entry, _)| entry == name)
+    }
+
+    fn get(&self, name: &str) -> Option<&T> {
+        self.entries.iter().find(|(entry, _)| entry == name).map(|(_, buffer)| buffer)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut T> {
+        self.entries.iter_mut().find(|(entry, _)| entry == name).map(|(_, buffer)| buffer)
+    }
+
+    fn at(&self, index: usize) -> &T {
+        &self.entries[index].1
+    }
+
+    fn at_mut(&mut self, index: usize) -> &mut T {
+        &mut self.entries[index].1
+    }
+
+    /// Move the entry of `name` to the end of `other`.
+    /// Does not allocate while `other` has room for it.
+    fn move_to(&mut self, name: &str, other: &mut Self) -> bool {
+        match self.index_of(name) {
+            Some(index) => {
+                other.entries.push(self.entries.remove(index));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Same names with buffers made by `buffer`
+    fn like(&self, buffer: impl Fn() -> T) -> Self {
+        Self { entries: self.entries.iter().map(|(name, _)| (name.clone(), buffer())).collect() }
+    }
+
+    /// Pair up the buffers with those of a `like` copy
+    fn zip_mut<'a>(&'a mut self, other: &'a NamedBuffers<T>) -> impl Iterator<Item = (&'a mut T, &'a T)> {
+        self.entries.iter_mut().map(|(_, buffer)| buffer).zip(other.entries.iter().map(|(_, buffer)| buffer))
+    }
+}
+
+/// Copy `range` of `source` into `target` (within its capacity)
+fn copy_range(target: &mut Vec<f32>, source: &[f32], range: Range<usize>) {
+    let end = range.end.min(source.len());
+    target.clear();
+    target.extend_from_slice(&source[range.start.min(end)..end]);
+    target.resize(range.len(), 0.0);
+}
+
+/// Copy `source` into `target` starting at `offset`
+fn write_at(target: &mut [f32], source: &[f32], offset: usize) {
+    if let Some(target) = target.get_mut(offset..) {
+        let len = target.len().min(source.len());
+        target[..len].copy_from_slice(&source[..len]);
+    }
+}
+
+/// バッファの位置 - ポート名を引かずにアクセスするため
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BufferSlot {
+    Audio(usize),
+    Cv(usize),
+    Stereo(usize),
+    Poly(usize),
+}
+
+/// 位置で取り出したバッファ（読み取り専用）
+pub(crate) enum SlotRef<'a> {
+    Mono(&'a [f32]),
+    Stereo(&'a StereoBuffer),
+    Poly(&'a PolyBuffer),
+}
+
+/// 位置で取り出したバッファ（可変）
+pub(crate) enum SlotMut<'a> {
+    Mono(&'a mut Vec<f32>),
+    Stereo(&'a mut StereoBuffer),
+    Poly(&'a mut PolyBuffer),
+}
+
+/// 型ごとのバッファ群（入力・出力で共通）
+#[derive(Debug, Default)]
+struct PortBuffers {
+    audio: NamedBuffers<Vec<f32>>,
+    cv: NamedBuffers<Vec<f32>>,
+    stereo: NamedBuffers<StereoBuffer>,
+    poly: NamedBuffers<PolyBuffer>,
+}
+
+impl PortBuffers {
+    fn allocate(&mut self, port_name: String, port_type: PortType, size: usize) -> BufferSlot {
+        match port_type {
+            PortType::AudioMono => BufferSlot::Audio(self.audio.insert(port_name, vec![0.0; size])),
+            PortType::CV => BufferSlot::Cv(self.cv.insert(port_name, vec![0.0; size])),
+            PortType::AudioStereo => BufferSlot::Stereo(self.stereo.insert(port_name, StereoBuffer::new(size))),
+            PortType::Poly => BufferSlot::Poly(self.poly.insert(port_name, PolyBuffer::new(size))),
+        }
+    }
+
+    fn move_port(&mut self, port_name: &str, port_type: PortType, other: &mut PortBuffers) -> bool {
+        match port_type {
+            PortType::AudioMono => self.audio.move_to(port_name, &mut other.audio),
+            PortType::CV => self.cv.move_to(port_name, &mut other.cv),
+            PortType::AudioStereo => self.stereo.move_to(port_name, &mut other.stereo),
+            PortType::Poly => self.poly.move_to(port_name, &mut other.poly),
+        }
+    }
+
+    fn find_slot(&self, port_name: &str, port_type: PortType) -> Option<BufferSlot> {
+        match port_type {
+            PortType::AudioMono => self.audio.index_of(port_name).map(BufferSlot::Audio),
+            PortType::CV => self.cv.index_of(port_name).map(BufferSlot::Cv),
+            PortType::AudioStereo => self.stereo.index_of(port_name).map(BufferSlot::Stereo),
+            PortType::Poly => self.poly.index_of(port_name).map(BufferSlot::Poly),
+        }
+    }
+
+    fn slot(&self, slot: BufferSlot) -> SlotRef<'_> {
+        match slot {
+            BufferSlot::Audio(index) => SlotRef::Mono(self.audio.at(index)),
+            BufferSlot::Cv(index) => SlotRef::Mono(self.cv.at(index)),
+            BufferSlot::Stereo(index) => SlotRef::Stereo(self.stereo.at(index)),
+            BufferSlot::Poly(index) => SlotRef::Poly(self.poly.at(index)),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: BufferSlot) -> SlotMut<'_> {
+        match slot {
+            BufferSlot::Audio(index) => SlotMut::Mono(self.audio.at_mut(index)),
+            BufferSlot::Cv(index) => SlotMut::Mono(self.cv.at_mut(index)),
+            BufferSlot::Stereo(index) => SlotMut::Stereo(self.stereo.at_mut(index)),
+            BufferSlot::Poly(index) => SlotMut::Poly(self.poly.at_mut(index)),
+        }
+    }
+
+    /// Same ports with zeroed buffers of `size`
+    fn like(&self, size: usize) -> Self {
+        Self {
+            audio: self.audio.like(|| vec![0.0; size]),
+            cv: self.cv.like(|| vec![0.0; size]),
+            stereo: self.stereo.like(|| StereoBuffer::new(size)),
+            poly: self.poly.like(|| PolyBuffer::new(size)),
+        }
+    }
+
+    /// Take `range` of every buffer of `source` (a `like` copy of this one)
+    fn copy_range(&mut self, source: &PortBuffers, range: Range<usize>) {
+        for buffers in [self.audio.zip_mut(&source.audio), self.cv.zip_mut(&source.cv)] {
+            buffers.for_each(|(target, source)| copy_range(target, source, range.clone()));
+        }
+        for (target, source) in self.stereo.zip_mut(&source.stereo) {
+            copy_range(&mut target.left, &source.left, range.clone());
+            copy_range(&mut target.right, &source.right, range.clone());
+        }
+        for (target, source) in self.poly.zip_mut(&source.poly) {
+            for (channel, source) in target.channels.iter_mut().zip(&source.channels) {
+                copy_range(channel, source, range.clone());
+            }
+            target.voices = source.voices;
+        }
+    }
+
+    /// Zero every buffer and set its length to `frames`
+    fn reset(&mut self, frames: usize) {
+        for buffer in self.audio.entries.iter_mut().chain(self.cv.entries.iter_mut()).map(|(_, buffer)| buffer) {
+            buffer.resize(frames, 0.0);
+            buffer.fill(0.0);
+        }
+        for (_, buffer) in self.stereo.entries.iter_mut() {
+            buffer.resize(frames);
+            buffer.fill(0.0);
+        }
+        for (_, buffer) in self.poly.entries.iter_mut() {
+            buffer.resize(frames);
+            buffer.fill(0.0);
+            buffer.voices = 0;
+        }
+    }
+
+    /// Write every buffer of `source` (a `like` copy) into this one at `offset`
+    fn write_at(&mut self, source: &PortBuffers, offset: usize) {
+        for buffers in [self.audio.zip_mut(&source.audio), self.cv.zip_mut(&source.cv)] {
+            buffers.for_each(|(target, source)| write_at(target, source, offset));
+        }
+        for (target, source) in self.stereo.zip_mut(&source.stereo) {
+            write_at(&mut target.left, &source.left, offset);
+            write_at(&mut target.right, &source.right, offset);
+        }
+        for (target, source) in self.poly.zip_mut(&source.poly) {
+            for (channel, source) in target.channels.iter_mut().zip(&source.channels) {
+                write_at(channel, source, offset);
+            }
+            // A voice that played in any part of the block counts
+            target.voices = target.voices.max(source.voices);
+        }
+    }
+}
+
 /// 入力バッファの管理
 #[derive(Debug, Default)]
 pub struct InputBuffers {
-    audio_buffers: HashMap<String, Vec<f32>>,
-    cv_buffers: HashMap<String, Vec<f32>>,
+    buffers: PortBuffers,
 }
 
 impl InputBuffers {
@@ -82,22 +578,42 @@ impl InputBuffers {
     
     /// オーディオバッファを追加
     pub fn add_audio(&mut self, port_name: String, buffer: Vec<f32>) {
-        self.audio_buffers.insert(port_name, buffer);
+        self.buffers.audio.insert(port_name, buffer);
     }
     
     /// CVバッファを追加
     pub fn add_cv(&mut self, port_name: String, buffer: Vec<f32>) {
-        self.cv_buffers.insert(port_name, buffer);
+        self.buffers.cv.insert(port_name, buffer);
+    }
+    
+    /// ステレオバッファを追加
+    pub fn add_stereo(&mut self, port_name: String, buffer: StereoBuffer) {
+        self.buffers.stereo.insert(port_name, buffer);
+    }
+
+    /// ポリバッファを追加
+    pub fn add_poly(&mut self, port_name: String, buffer: PolyBuffer) {
+        self.buffers.poly.insert(port_name, buffer);
     }
     
     /// オーディオバッファを取得
     pub fn get_audio(&self, port_name: &str) -> Option<&[f32]> {
-        self.audio_buffers.get(port_name).map(|v| v.as_slice())
+        self.buffers.audio.get(port_name).map(|v| v.as_slice())
     }
     
     /// CVバッファを取得
     pub fn get_cv(&self, port_name: &str) -> Option<&[f32]> {
-        self.cv_buffers.get(port_name).map(|v| v.as_slice())
+        self.buffers.cv.get(port_name).map(|v| v.as_slice())
+    }
+    
+    /// ステレオバッファを取得
+    pub fn get_stereo(&self, port_name: &str) -> Option<&StereoBuffer> {
+        self.buffers.stereo.get(port_name)
+    }
+
+    /// ポリバッファを取得
+    pub fn get_poly(&self, port_name: &str) -> Option<&PolyBuffer> {
+        self.buffers.poly.get(port_name)
     }
     
     /// CVの最初の値を取得（単一値として扱う場合）
@@ -108,6 +624,11 @@ impl InputBuffers {
             .unwrap_or(0.0)
     }
     
+    /// i番目のサンプルのCV値を取得（短いバッファは最後の値を保持）
+    pub fn get_cv_at(&self, port_name: &str, index: usize) -> f32 {
+        cv_sample_at(self.get_cv(port_name), index)
+    }
+    
     /// 空のバッファを作成（デフォルト値で）
     pub fn get_or_default_audio(&self, port_name: &str, size: usize) -> Vec<f32> {
         self.get_audio(port_name)
@@ -120,13 +641,43 @@ impl InputBuffers {
             .map(|buf| buf.to_vec())
             .unwrap_or_else(|| vec![0.0; size])
     }
+
+    /// ポートの型に合わせてバッファを追加し、その位置を返す
+    pub(crate) fn allocate_slot(&mut self, port_name: String, port_type: PortType, size: usize) -> BufferSlot {
+        self.buffers.allocate(port_name, port_type, size)
+    }
+
+    /// Hand the buffer of a port over to `other`, keeping its contents.
+    /// The slots of both sides shift; look them up again with `find_slot`.
+    pub(crate) fn move_port(&mut self, port_name: &str, port_type: PortType, other: &mut InputBuffers) -> bool {
+        self.buffers.move_port(port_name, port_type, &mut other.buffers)
+    }
+
+    pub(crate) fn find_slot(&self, port_name: &str, port_type: PortType) -> Option<BufferSlot> {
+        self.buffers.find_slot(port_name, port_type)
+    }
+
+    pub(crate) fn slot(&self, slot: BufferSlot) -> SlotRef<'_> {
+        self.buffers.slot(slot)
+    }
+
+    pub(crate) fn slot_mut(&mut self, slot: BufferSlot) -> SlotMut<'_> {
+        self.buffers.slot_mut(slot)
+    }
+}
+
+/// CVバッファのi番目の値 - 未接続は0.0、バッファより後ろは最後の値を保持
+pub fn cv_sample_at(buffer: Option<&[f32]>, index: usize) -> f32 {
+    match buffer {
+        Some(buf) => buf.get(index).or(buf.last()).copied().unwrap_or(0.0),
+        None => 0.0,
+    }
 }
 
 /// 出力バッファの管理
 #[derive(Debug, Default)]
 pub struct OutputBuffers {
-    audio_buffers: HashMap<String, Vec<f32>>,
-    cv_buffers: HashMap<String, Vec<f32>>,
+    buffers: PortBuffers,
 }
 
 impl OutputBuffers {
@@ -136,37 +687,76 @@ impl OutputBuffers {
     
     /// オーディオ出力バッファを確保
     pub fn allocate_audio(&mut self, port_name: String, size: usize) {
-        self.audio_buffers.insert(port_name, vec![0.0; size]);
+        self.buffers.allocate(port_name, PortType::AudioMono, size);
     }
     
     /// CV出力バッファを確保
     pub fn allocate_cv(&mut self, port_name: String, size: usize) {
-        self.cv_buffers.insert(port_name, vec![0.0; size]);
+        self.buffers.allocate(port_name, PortType::CV, size);
+    }
+    
+    /// ステレオ出力バッファを確保
+    pub fn allocate_stereo(&mut self, port_name: String, size: usize) {
+        self.buffers.allocate(port_name, PortType::AudioStereo, size);
+    }
+
+    /// ポリ出力バッファを確保
+    pub fn allocate_poly(&mut self, port_name: String, size: usize) {
+        self.buffers.allocate(port_name, PortType::Poly, size);
     }
     
     /// オーディオ出力バッファを取得（可変）
     pub fn get_audio_mut(&mut self, port_name: &str) -> Option<&mut [f32]> {
-        self.audio_buffers.get_mut(port_name).map(|v| v.as_mut_slice())
+        self.buffers.audio.get_mut(port_name).map(|v| v.as_mut_slice())
     }
     
     /// CV出力バッファを取得（可変）
     pub fn get_cv_mut(&mut self, port_name: &str) -> Option<&mut [f32]> {
-        self.cv_buffers.get_mut(port_name).map(|v| v.as_mut_slice())
+        self.buffers.cv.get_mut(port_name).map(|v| v.as_mut_slice())
+    }
+    
+    /// ステレオ出力バッファを取得（可変）
+    pub fn get_stereo_mut(&mut self, port_name: &str) -> Option<&mut StereoBuffer> {
+        self.buffers.stereo.get_mut(port_name)
+    }
+
+    /// ポリ出力バッファを取得（可変）
+    pub fn get_poly_mut(&mut self, port_name: &str) -> Option<&mut PolyBuffer> {
+        self.buffers.poly.get_mut(port_name)
+    }
+
+    /// オーディオ出力とステレオ出力を同時に取得（可変）
+    pub fn get_audio_and_stereo_mut(&mut self, audio_port: &str, stereo_port: &str)
+                                    -> (Option<&mut [f32]>, Option<&mut StereoBuffer>) {
+        (
+            self.buffers.audio.get_mut(audio_port).map(|v| v.as_mut_slice()),
+            self.buffers.stereo.get_mut(stereo_port),
+        )
     }
     
     /// オーディオ出力バッファを取得（読み取り専用）
     pub fn get_audio(&self, port_name: &str) -> Option<&[f32]> {
-        self.audio_buffers.get(port_name).map(|v| v.as_slice())
+        self.buffers.audio.get(port_name).map(|v| v.as_slice())
     }
     
     /// CV出力バッファを取得（読み取り専用）
     pub fn get_cv(&self, port_name: &str) -> Option<&[f32]> {
-        self.cv_buffers.get(port_name).map(|v| v.as_slice())
+        self.buffers.cv.get(port_name).map(|v| v.as_slice())
+    }
+    
+    /// ステレオ出力バッファを取得（読み取り専用）
+    pub fn get_stereo(&self, port_name: &str) -> Option<&StereoBuffer> {
+        self.buffers.stereo.get(port_name)
+    }
+
+    /// ポリ出力バッファを取得（読み取り専用）
+    pub fn get_poly(&self, port_name: &str) -> Option<&PolyBuffer> {
+        self.buffers.poly.get(port_name)
     }
     
     /// CV出力に単一値を設定
     pub fn set_cv_value(&mut self, port_name: &str, value: f32) {
-        if let Some(buffer) = self.cv_buffers.get_mut(port_name) {
+        if let Some(buffer) = self.buffers.cv.get_mut(port_name) {
             for sample in buffer.iter_mut() {
                 *sample = value;
             }
@@ -175,17 +765,37 @@ impl OutputBuffers {
     
     /// オーディオ出力をクリア
     pub fn clear_audio(&mut self, port_name: &str) {
-        if let Some(buffer) = self.audio_buffers.get_mut(port_name) {
+        if let Some(buffer) = self.buffers.audio.get_mut(port_name) {
             buffer.fill(0.0);
         }
     }
     
     /// CV出力をクリア
     pub fn clear_cv(&mut self, port_name: &str) {
-        if let Some(buffer) = self.cv_buffers.get_mut(port_name) {
+        if let Some(buffer) = self.buffers.cv.get_mut(port_name) {
+            buffer.fill(0.0);
+        }
+    }
+    
+    /// ステレオ出力をクリア
+    pub fn clear_stereo(&mut self, port_name: &str) {
+        if let Some(buffer) = self.buffers.stereo.get_mut(port_name) {
             buffer.fill(0.0);
         }
     }
+
+    /// ポートの型に合わせてバッファを確保し、その位置を返す
+    pub(crate) fn allocate_slot(&mut self, port_name: String, port_type: PortType, size: usize) -> BufferSlot {
+        self.buffers.allocate(port_name, port_type, size)
+    }
+
+    pub(crate) fn slot(&self, slot: BufferSlot) -> SlotRef<'_> {
+        self.buffers.slot(slot)
+    }
+
+    pub(crate) fn slot_mut(&mut self, slot: BufferSlot) -> SlotMut<'_> {
+        self.buffers.slot_mut(slot)
+    }
 }
 
 /// ノード情報の詳細版
@@ -220,6 +830,8 @@ pub struct PortInfo {
     pub port_type: PortType,
     pub description: String,
     pub is_optional: bool,
+    /// 複数接続時の合成方法（入力ポートのみ）
+    pub fan_in: FanIn,
 }
 
 impl PortInfo {
@@ -229,6 +841,7 @@ impl PortInfo {
             port_type,
             description: String::new(),
             is_optional: false,
+            fan_in: FanIn::Sum,
         }
     }
     
@@ -241,6 +854,11 @@ impl PortInfo {
         self.is_optional = true;
         self
     }
+
+    pub fn with_fan_in(mut self, fan_in: FanIn) -> Self {
+        self.fan_in = fan_in;
+        self
+    }
 }
 
 /// 改善されたAudioNodeトレイト
@@ -250,9 +868,40 @@ pub trait AudioNode: Send + Sync + Parameterizable {
     
     /// ノード情報を取得
     fn node_info(&self) -> &NodeInfo;
+
+    /// 可変のノード情報 - 保存したIDで復元できるノードだけが返す
+    fn node_info_mut(&mut self) -> Option<&mut NodeInfo> {
+        None
+    }
     
     /// ノードをリセット（内部状態をクリア）
     fn reset(&mut self);
+
+    /// サンプルレートと最大ブロック長の通知 - 処理開始前と、デバイスや書き出し設定が変わるたびに呼ばれる
+    ///
+    /// Never called on the audio thread, so buffers may be reallocated here.
+    /// Keep the node's state (parameters, phase, steps) rather than resetting it.
+    fn prepare(&mut self, _sample_rate: f32, _max_block_size: usize) {}
+
+    /// パラメーターで表せない状態（シーケンスのステップ等）- パッチに保存される
+    fn save_state(&self) -> Option<NodeState> {
+        None
+    }
+
+    /// `save_state` で保存した状態を復元
+    fn load_state(&mut self, _state: &NodeState) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// ブロック内のイベントを受け取る - 次に処理されるサンプルから有効
+    ///
+    /// Parameter events go through `set_parameter` unless overridden;
+    /// gate and note events only mean something to nodes that handle them.
+    fn handle_event(&mut self, event: &EventKind) {
+        if let EventKind::Parameter { name, value } = event {
+            let _ = self.set_parameter(name, *value);
+        }
+    }
     
     /// レイテンシーをサンプル数で返す
     fn latency(&self) -> u32 {
@@ -289,10 +938,12 @@ pub trait AudioNode: Send + Sync + Parameterizable {
             input_ports: info.input_ports.iter().map(|p| Port {
                 name: p.name.clone(),
                 port_type: p.port_type,
+                fan_in: p.fan_in,
             }).collect(),
             output_ports: info.output_ports.iter().map(|p| Port {
                 name: p.name.clone(),
                 port_type: p.port_type,
+                fan_in: p.fan_in,
             }).collect(),
         }
     }
@@ -342,7 +993,7 @@ impl From<ParameterError> for ProcessingError {
 #[macro_export]
 macro_rules! process_audio_samples {
     ($inputs:expr, $outputs:expr, $input_port:expr, $output_port:expr, $process_fn:expr) => {
-        let input_buffer = $inputs.get_or_default_audio($input_port, $outputs.get_audio($output_port).map(|b| b.len()).unwrap_or(512));
+        let input_buffer = $inputs.get_or_default_audio($input_port, $outputs.get_audio($output_port).map_or(0, |b| b.len()));
         
         if let Some(output_buffer) = $outputs.get_audio_mut($output_port) {
             for (i, (input_sample, output_sample)) in input_buffer.iter().zip(output_buffer.iter_mut()).enumerate() {
@@ -367,6 +1018,57 @@ mod tests {
         assert_eq!(inputs.get_cv_value("nonexistent"), 0.0);
     }
 
+    #[test]
+    fn test_cv_at_holds_last_value() {
+        let mut inputs = InputBuffers::new();
+        inputs.add_cv("ramp".to_string(), vec![0.0, 0.25, 0.5]);
+        inputs.add_cv("empty".to_string(), vec![]);
+        
+        assert_eq!(inputs.get_cv_at("ramp", 1), 0.25);
+        assert_eq!(inputs.get_cv_at("ramp", 10), 0.5);
+        assert_eq!(inputs.get_cv_at("empty", 0), 0.0);
+        assert_eq!(inputs.get_cv_at("nonexistent", 3), 0.0);
+    }
+
+    #[test]
+    fn test_stereo_buffer_adaptation() {
+        let stereo = StereoBuffer::from_mono(&[0.5, -1.0]);
+        assert_eq!(stereo.left, stereo.right);
+
+        let stereo = StereoBuffer { left: vec![1.0, 0.0], right: vec![0.0, -1.0] };
+        assert_eq!(stereo.to_mono(), vec![0.5, -0.5]);
+
+        let mut outputs = OutputBuffers::new();
+        outputs.allocate_stereo("out".to_string(), 2);
+        outputs.get_stereo_mut("out").unwrap().left[1] = 0.25;
+        assert_eq!(outputs.get_stereo("out").unwrap().left, vec![0.0, 0.25]);
+        assert_eq!(outputs.get_stereo("out").unwrap().right, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_event_segments() {
+        let mut events = EventList::new();
+        events.push(4, EventKind::GateOff);
+        events.push(0, EventKind::GateOn);
+        events.push(4, EventKind::NoteOff { pitch: 0.0 });
+        events.push(12, EventKind::GateOn);
+
+        // Events at the same offset keep their order; the one past the block is left out
+        let segments: Vec<_> = events.segments(8).collect();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].0, 0..4);
+        assert_eq!(segments[0].1[0].kind, EventKind::GateOn);
+        assert_eq!(segments[1].0, 4..8);
+        assert_eq!(segments[1].1[0].kind, EventKind::GateOff);
+        assert_eq!(segments[1].1.len(), 2);
+
+        // A block starting without an event still covers every sample
+        let mut late = EventList::new();
+        late.push(5, EventKind::GateOn);
+        let ranges: Vec<_> = late.segments(8).map(|(range, events)| (range, events.len())).collect();
+        assert_eq!(ranges, vec![(0..5, 0), (5..8, 1)]);
+    }
+
     #[test]
     fn test_output_buffers() {
         let mut outputs = OutputBuffers::new();
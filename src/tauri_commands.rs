@@ -1,14 +1,18 @@
-use crate::audio::AudioEngine;
+use crate::audio::{AudioConfig, AudioEngine, DeviceInfo, WavFormat};
 use std::sync::{Arc, Mutex};
 use tauri::State;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
-use std::fs;
 
 // Include plugin commands
 pub mod plugin;
 pub use plugin::*;
 
+pub use crate::patch::{PatchConnection, PatchFile, PatchNode, PatchPosition};
+use crate::graph::OutputRoute;
+use crate::processing::EventKind;
+use crate::transport::{MusicalPosition, TransportCommand, TransportInfo};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NodeInfo {
     pub id: String,
@@ -31,39 +35,7 @@ pub struct ConnectionInfo {
     pub source_port: String,
     pub target_node: String,
     pub target_port: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PatchNode {
-    pub id: String,
-    #[serde(rename = "type")]
-    pub node_type: String,
-    pub name: String,
-    pub position: PatchPosition,
-    pub parameters: std::collections::HashMap<String, f32>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct PatchPosition {
-    pub x: f32,
-    pub y: f32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PatchConnection {
-    pub source_node: String,
-    pub source_port: String,
-    pub target_node: String,
-    pub target_port: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PatchFile {
-    pub patch_name: Option<String>,
-    pub description: Option<String>,
-    pub nodes: Vec<PatchNode>,
-    pub connections: Vec<PatchConnection>,
-    pub notes: Option<Vec<String>>,
+    pub feedback: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -152,6 +124,137 @@ pub async fn set_node_parameter(
     engine.set_node_parameter(&node_id, &param, value)
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryState {
+    /// Label of the step `undo` would revert
+    pub undo: Option<String>,
+    /// Label of the step `redo` would make again
+    pub redo: Option<String>,
+}
+
+#[tauri::command]
+pub async fn undo(
+    engine: State<'_, AudioEngineState>,
+) -> Result<Option<String>, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.undo()
+}
+
+#[tauri::command]
+pub async fn redo(
+    engine: State<'_, AudioEngineState>,
+) -> Result<Option<String>, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.redo()
+}
+
+#[tauri::command]
+pub async fn begin_transaction(
+    engine: State<'_, AudioEngineState>,
+    label: String,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.begin_transaction(&label)
+}
+
+#[tauri::command]
+pub async fn end_transaction(
+    engine: State<'_, AudioEngineState>,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.end_transaction()
+}
+
+#[tauri::command]
+pub async fn abort_transaction(
+    engine: State<'_, AudioEngineState>,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.abort_transaction()
+}
+
+#[tauri::command]
+pub async fn get_history_state(
+    engine: State<'_, AudioEngineState>,
+) -> Result<HistoryState, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    let (undo, redo) = engine.history_labels();
+    Ok(HistoryState { undo, redo })
+}
+
+#[tauri::command]
+pub async fn set_node_bypass(
+    engine: State<'_, AudioEngineState>,
+    node_id: String,
+    bypass: bool,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.set_node_bypass(&node_id, bypass)
+}
+
+#[tauri::command]
+pub async fn set_output_route(
+    engine: State<'_, AudioEngineState>,
+    node_id: String,
+    first_channel: usize,
+    channels: usize,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.set_output_route(&node_id, OutputRoute { first_channel, channels })
+}
+
+#[tauri::command]
+pub async fn get_output_route(
+    engine: State<'_, AudioEngineState>,
+    node_id: String,
+) -> Result<OutputRoute, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.get_output_route(&node_id).ok_or_else(|| format!("Node {} is not an output node", node_id))
+}
+
+#[tauri::command]
+pub async fn set_output_channels(
+    engine: State<'_, AudioEngineState>,
+    channels: u16,
+) -> Result<(), String> {
+    let mut engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.set_output_channels(channels)
+}
+
+#[tauri::command]
+pub async fn list_audio_hosts() -> Result<Vec<String>, String> {
+    Ok(crate::audio::list_hosts())
+}
+
+#[tauri::command]
+pub async fn list_audio_devices(
+    host: Option<String>,
+) -> Result<Vec<DeviceInfo>, String> {
+    crate::audio::list_output_devices(host.as_deref())
+}
+
+#[tauri::command]
+pub async fn get_audio_config(
+    engine: State<'_, AudioEngineState>,
+) -> Result<AudioConfig, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    Ok(engine.audio_config().clone())
+}
+
+/// Switch device or settings and remember them for the next start
+#[tauri::command]
+pub async fn set_audio_config(
+    engine: State<'_, AudioEngineState>,
+    config: AudioConfig,
+) -> Result<(), String> {
+    let mut engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.apply_audio_config(config)?;
+    match AudioConfig::default_path() {
+        Some(path) => engine.audio_config().save(path),
+        None => Err("No home directory to save the audio config in".to_string()),
+    }
+}
+
 #[tauri::command]
 pub async fn get_node_parameter(
     engine: State<'_, AudioEngineState>,
@@ -205,11 +308,12 @@ pub async fn get_connections(
     let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
     let graph = engine.graph.lock().map_err(|e| format!("Failed to lock graph: {}", e))?;
     
-    let connections = graph.connections.iter().map(|conn| ConnectionInfo {
+    let connections = graph.connections().iter().map(|conn| ConnectionInfo {
         source_node: conn.source_node.to_string(),
         source_port: conn.source_port.clone(),
         target_node: conn.target_node.to_string(),
         target_port: conn.target_port.clone(),
+        feedback: conn.feedback,
     }).collect();
     
     Ok(connections)
@@ -240,6 +344,60 @@ pub async fn is_audio_running(
     Ok(engine.is_running())
 }
 
+#[tauri::command]
+pub async fn get_patch_latency(
+    engine: State<'_, AudioEngineState>,
+) -> Result<u32, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    Ok(engine.latency_samples())
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransportState {
+    #[serde(flatten)]
+    pub info: TransportInfo,
+    /// Bar, beat and tick of `beat_position`
+    pub position: MusicalPosition,
+}
+
+#[tauri::command]
+pub async fn transport_command(
+    engine: State<'_, AudioEngineState>,
+    command: TransportCommand,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.transport(command)
+}
+
+#[tauri::command]
+pub async fn get_transport_state(
+    engine: State<'_, AudioEngineState>,
+) -> Result<TransportState, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    let info = engine.transport_info();
+    Ok(TransportState { info, position: info.musical_position() })
+}
+
+#[tauri::command]
+pub async fn set_audio_input_file(
+    engine: State<'_, AudioEngineState>,
+    node_id: String,
+    path: String,
+    looping: bool,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.load_audio_input_file(&node_id, &path, looping)
+}
+
+#[tauri::command]
+pub async fn set_audio_input_capture(
+    engine: State<'_, AudioEngineState>,
+    node_id: String,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.use_capture_input(&node_id)
+}
+
 #[tauri::command]
 pub async fn save_project(
     engine: State<'_, AudioEngineState>,
@@ -253,9 +411,27 @@ pub async fn save_project(
 pub async fn load_project(
     engine: State<'_, AudioEngineState>,
     filename: String,
+) -> Result<Vec<String>, String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    engine.load_from_file(&filename).map(|report| report.changes)
+}
+
+#[tauri::command]
+pub async fn render_to_wav(
+    engine: State<'_, AudioEngineState>,
+    filename: String,
+    duration_seconds: f32,
+    bit_depth: Option<u16>,
 ) -> Result<(), String> {
+    let format = match bit_depth.unwrap_or(32) {
+        16 => WavFormat::Pcm16,
+        24 => WavFormat::Pcm24,
+        32 => WavFormat::Float32,
+        other => return Err(format!("Unsupported bit depth: {}", other)),
+    };
+
     let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
-    engine.load_from_file(&filename)
+    engine.render_to_wav(&filename, duration_seconds, format)
 }
 
 #[tauri::command]
@@ -294,7 +470,7 @@ pub async fn get_spectrum_frequencies(
     if let Some(node) = graph.get_node(&node_id.to_string()) {
         if let Some(_spectrum_node) = node.as_any().downcast_ref::<crate::nodes::SpectrumAnalyzerNode>() {
             // Return frequency bins - we need to implement this or use a placeholder
-            let sample_rate = 44100.0; // TODO: get from engine
+            let sample_rate = engine.sample_rate();
             let fft_size = 1024; // TODO: get from spectrum analyzer
             let frequency_bins: Vec<f32> = (0..fft_size/2)
                 .map(|i| i as f32 * sample_rate / fft_size as f32)
@@ -315,162 +491,41 @@ pub async fn save_patch_file(
     node_positions: Option<std::collections::HashMap<String, PatchPosition>>,
 ) -> Result<(), String> {
     let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
-    
-    // Get current nodes
-    let nodes = engine.list_nodes();
-    let mut patch_nodes = Vec::new();
-    
-    for node_id in nodes {
-        if let Some(node_info) = engine.get_node_info(&node_id) {
-            // Get position from provided positions or use default
-            let position = node_positions.as_ref()
-                .and_then(|positions| positions.get(&node_info.name))
-                .cloned()
-                .unwrap_or(PatchPosition { x: 100.0, y: 100.0 });
-                
-            let parameters = engine.get_node_parameters(&node_id).unwrap_or_default();
-            
-            let patch_node = PatchNode {
-                id: node_info.name.clone(),
-                node_type: node_info.node_type.clone(),
-                name: node_info.name.clone(),
-                position,
-                parameters,
-            };
-            patch_nodes.push(patch_node);
-        }
-    }
-    
-    // Get current connections
-    let graph = engine.graph.lock().map_err(|e| format!("Failed to lock graph: {}", e))?;
-    let mut patch_connections = Vec::new();
-    
-    for conn in &graph.connections {
-        // Find node names by ID
-        let source_name = engine.find_node_name_by_id(conn.source_node);
-        let target_name = engine.find_node_name_by_id(conn.target_node);
-        
-        if let (Some(src_name), Some(tgt_name)) = (source_name, target_name) {
-            let patch_conn = PatchConnection {
-                source_node: src_name,
-                source_port: conn.source_port.clone(),
-                target_node: tgt_name,
-                target_port: conn.target_port.clone(),
-            };
-            patch_connections.push(patch_conn);
+
+    // Nodes, connections, routes and bypass as the engine saves them
+    let mut patch = engine.to_patch()?;
+
+    // Add what only the UI knows
+    for node in &mut patch.nodes {
+        if let Some(position) = node_positions.as_ref().and_then(|positions| positions.get(&node.name)) {
+            node.position = position.clone();
         }
     }
-    
-    // Save lengths before moving values
-    let nodes_count = patch_nodes.len();
-    let connections_count = patch_connections.len();
-    
-    // Create patch file structure
-    let patch = PatchFile {
-        patch_name,
-        description,
-        nodes: patch_nodes,
-        connections: patch_connections,
-        notes: Some(vec![
-            "Generated patch file".to_string(),
-            format!("Created with {} nodes and {} connections", 
-                   nodes_count, connections_count)
-        ]),
-    };
-    
-    // Write to file
-    let json_content = serde_json::to_string_pretty(&patch)
-        .map_err(|e| format!("Failed to serialize patch: {}", e))?;
-        
-    fs::write(&file_path, json_content)
-        .map_err(|e| format!("Failed to write file {}: {}", file_path, e))?;
-    
-    Ok(())
+    patch.patch_name = patch_name;
+    patch.description = description;
+    patch.notes = Some(vec![
+        "Generated patch file".to_string(),
+        format!("Created with {} nodes and {} connections",
+               patch.nodes.len(), patch.connections.len())
+    ]);
+
+    patch.save(&file_path)
 }
 
 #[tauri::command]
 pub async fn load_patch_file(
     engine: State<'_, AudioEngineState>,
     file_path: String,
-) -> Result<(), String> {
-    // Read the JSON file
-    let json_content = fs::read_to_string(&file_path)
-        .map_err(|e| format!("Failed to read file {}: {}", file_path, e))?;
-    
-    // Parse the JSON
-    let patch: PatchFile = serde_json::from_str(&json_content)
-        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
-    
+) -> Result<Vec<String>, String> {
     let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
-    
-    // Clear current graph
-    engine.clear_graph().map_err(|e| format!("Failed to clear graph: {}", e))?;
-    
-    // Create nodes from patch
-    for patch_node in &patch.nodes {
-        // Create node using patch ID as name for consistent referencing
-        match engine.create_builtin_node(&patch_node.node_type, patch_node.id.clone()) {
-            Ok(node_id) => {
-                println!("✅ Created node: {} '{}' ({}) with ID: {}", patch_node.id, patch_node.name, patch_node.node_type, node_id);
-                
-                // Set parameters
-                for (param_name, param_value) in &patch_node.parameters {
-                    match engine.set_node_parameter(&node_id, param_name, *param_value) {
-                        Ok(()) => {
-                            println!("  ✅ Set parameter {} = {}", param_name, param_value);
-                        },
-                        Err(e) => {
-                            println!("  ❌ Failed to set parameter {}: {}", param_name, e);
-                        }
-                    }
-                }
-            },
-            Err(e) => {
-                println!("❌ Failed to create node {} '{}': {}", patch_node.id, patch_node.name, e);
-                return Err(format!("Failed to create node {} '{}': {}", patch_node.id, patch_node.name, e));
-            }
-        }
-    }
-    
-    // Create connections
-    for connection in &patch.connections {
-        // Find node IDs by name
-        let source_id = engine.find_node_by_name(&connection.source_node);
-        let target_id = engine.find_node_by_name(&connection.target_node);
-        
-        match (source_id, target_id) {
-            (Some(src_id), Some(tgt_id)) => {
-                println!("🔗 Attempting connection: {} [{}] {} -> {} [{}] {}", 
-                       connection.source_node, src_id, connection.source_port,
-                       connection.target_node, tgt_id, connection.target_port);
-                match engine.connect_nodes(
-                    &src_id.to_string(), 
-                    &connection.source_port, 
-                    &tgt_id.to_string(), 
-                    &connection.target_port
-                ) {
-                    Ok(()) => {
-                        println!("✅ Connected: {} {} -> {} {}", 
-                               connection.source_node, connection.source_port,
-                               connection.target_node, connection.target_port);
-                    },
-                    Err(e) => {
-                        println!("❌ Connection failed: {} {} -> {} {}: {}", 
-                               connection.source_node, connection.source_port,
-                               connection.target_node, connection.target_port, e);
-                    }
-                }
-            },
-            (None, _) => {
-                println!("❌ Source node not found: {}", connection.source_node);
-            },
-            (_, None) => {
-                println!("❌ Target node not found: {}", connection.target_node);
-            }
-        }
+
+    // Replaces the graph and clears the undo history; older formats are upgraded
+    let report = engine.load_from_file(&file_path)?;
+    if !report.is_empty() {
+        println!("🔄 {}", report);
     }
-    
-    Ok(())
+
+    Ok(report.changes)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -485,7 +540,7 @@ pub async fn trigger_gate(
 ) -> Result<(), String> {
     let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
     
-    // ADSRノードのgate_inポートに5Vのゲート信号を送信
+    // GateOn/GateOffイベントでゲートを短時間開く
     match engine.trigger_node_gate(&request.node_id) {
         Ok(()) => {
             println!("🎹 Gate triggered for node: {}", request.node_id);
@@ -495,6 +550,24 @@ pub async fn trigger_gate(
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleEventRequest {
+    pub node_id: String,
+    /// Samples from now; the event lands on the next block if omitted
+    pub delay_samples: Option<u64>,
+    pub event: EventKind,
+}
+
+#[tauri::command]
+pub async fn schedule_node_event(
+    engine: State<'_, AudioEngineState>,
+    request: ScheduleEventRequest,
+) -> Result<(), String> {
+    let engine = engine.inner().lock().map_err(|e| format!("Failed to lock engine: {}", e))?;
+    let time = engine.sample_position() + request.delay_samples.unwrap_or(0);
+    engine.schedule_event(&request.node_id, time, request.event)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetOscilloscopeDataRequest {
     pub node_id: String,
@@ -7,18 +7,22 @@ mod nodes;
 mod cli;
 mod tauri_commands;
 
-use audio::AudioEngine;
+use audio::{AudioConfig, AudioEngine};
 use tauri_commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .setup(|app| {
-            // Initialize audio engine
-            let sample_rate = 44100.0;
-            let buffer_size = 512;
-            
-            let audio_engine = AudioEngine::new(sample_rate, buffer_size)
+            // Initialize audio engine on the device chosen last time
+            let config = AudioConfig::default_path()
+                .map(AudioConfig::load_or_default)
+                .unwrap_or_default();
+            let audio_engine = AudioEngine::from_config(&config)
+                .or_else(|e| {
+                    eprintln!("{}; falling back to the default device", e);
+                    AudioEngine::new().map_err(|e| e.to_string())
+                })
                 .map_err(|e| format!("Failed to create audio engine: {}", e))?;
             
             let engine_state = Arc::new(Mutex::new(audio_engine));
@@ -33,11 +37,31 @@ pub fn run() {
             disconnect_nodes,
             set_node_parameter,
             get_node_parameter,
+            set_node_bypass,
+            set_output_route,
+            get_output_route,
+            set_output_channels,
+            list_audio_hosts,
+            list_audio_devices,
+            get_audio_config,
+            set_audio_config,
+            schedule_node_event,
+            undo,
+            redo,
+            begin_transaction,
+            end_transaction,
+            abort_transaction,
+            get_history_state,
             list_nodes,
             get_connections,
             start_audio,
             stop_audio,
             is_audio_running,
+            get_patch_latency,
+            transport_command,
+            get_transport_state,
+            set_audio_input_file,
+            set_audio_input_capture,
             save_project,
             load_project
         ])
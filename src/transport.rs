@@ -0,0 +1,374 @@
+/*
+ * OrbitalModulator - Professional Modular Synthesizer
+ * Copyright (c) 2025 MACHIKO LAB
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Engine transport: play state, tempo, time signature and song position.
+//!
+//! The `ProcessingGraph` owns one `Transport` and advances it after every
+//! block; nodes see a copy of its state in `ProcessContext::transport`.
+//! Positions are counted in beats (quarter notes) like most hosts do, so a
+//! 6/8 bar is 3 beats long.
+
+use serde::{Deserialize, Serialize};
+
+/// Resolution of `MusicalPosition::tick`
+pub const TICKS_PER_BEAT: u32 = 960;
+
+pub const MIN_TEMPO: f32 = 20.0;
+pub const MAX_TEMPO: f32 = 999.0;
+
+/// 拍子
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSignature {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl TimeSignature {
+    /// `numerator` of 1 - 32 notes of `1 / denominator` (a power of two up to 32)
+    pub fn new(numerator: u32, denominator: u32) -> Result<Self, String> {
+        if !(1..=32).contains(&numerator) {
+            return Err(format!("Invalid time signature numerator: {}", numerator));
+        }
+        if !denominator.is_power_of_two() || denominator > 32 {
+            return Err(format!("Invalid time signature denominator: {}", denominator));
+        }
+        Ok(Self { numerator, denominator })
+    }
+
+    /// Length of a bar in beats
+    pub fn beats_per_bar(&self) -> f64 {
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self { numerator: 4, denominator: 4 }
+    }
+}
+
+/// 小節・拍・ティック（すべて0から数える）
+///
+/// `beat` counts notes of the time signature's denominator, so 6/8 has six.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct MusicalPosition {
+    pub bar: u64,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl std::fmt::Display for MusicalPosition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{:03}", self.bar + 1, self.beat + 1, self.tick)
+    }
+}
+
+/// ループ範囲（拍）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl LoopRange {
+    pub fn new(start: f64, end: f64) -> Result<Self, String> {
+        if !(start >= 0.0 && end > start) {
+            return Err(format!("Invalid loop range: {} - {}", start, end));
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn length(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// ブロック開始時点のトランスポートの状態
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TransportInfo {
+    pub playing: bool,
+    /// Beats per minute
+    pub tempo: f32,
+    pub time_signature: TimeSignature,
+    /// Song position in samples
+    pub sample_position: u64,
+    /// Song position in beats
+    pub beat_position: f64,
+    /// Loop region while looping is on
+    pub loop_range: Option<LoopRange>,
+}
+
+impl TransportInfo {
+    pub fn samples_per_beat(&self, sample_rate: f32) -> f64 {
+        60.0 * sample_rate as f64 / self.tempo as f64
+    }
+
+    /// Beat position `offset` samples into the block. Past the loop end it
+    /// wraps back into the loop, on the exact sample the end is reached.
+    pub fn beat_at(&self, offset: usize, sample_rate: f32) -> f64 {
+        if !self.playing {
+            return self.beat_position;
+        }
+        let beat = self.beat_position + offset as f64 / self.samples_per_beat(sample_rate);
+        match self.loop_range {
+            // Only a block that starts before the loop end wraps
+            Some(range) if self.beat_position < range.end && beat >= range.end => {
+                range.start + (beat - range.end) % range.length()
+            }
+            _ => beat,
+        }
+    }
+
+    /// State `frames` samples later, e.g. for the second half of a split block
+    pub fn advanced(&self, frames: usize, sample_rate: f32) -> Self {
+        if !self.playing {
+            return *self;
+        }
+        let samples_per_beat = self.samples_per_beat(sample_rate);
+        let beat_position = self.beat_at(frames, sample_rate);
+        // After wrapping the sample position follows the beat, as with `Transport::locate`
+        let sample_position = if beat_position < self.beat_position + frames as f64 / samples_per_beat {
+            (beat_position * samples_per_beat) as u64
+        } else {
+            self.sample_position + frames as u64
+        };
+        Self { sample_position, beat_position, ..*self }
+    }
+
+    /// Bar, beat and tick of `beat_position`
+    pub fn musical_position(&self) -> MusicalPosition {
+        let beats_per_bar = self.time_signature.beats_per_bar();
+        let bar = (self.beat_position / beats_per_bar).floor();
+
+        // Beats of the signature's note value within the bar
+        let note_length = 4.0 / self.time_signature.denominator as f64;
+        let in_bar = (self.beat_position - bar * beats_per_bar) / note_length;
+        let beat = in_bar.floor();
+        let tick = ((in_bar - beat) * TICKS_PER_BEAT as f64) as u32;
+
+        MusicalPosition {
+            bar: bar.max(0.0) as u64,
+            beat: (beat as u32).min(self.time_signature.numerator - 1),
+            tick: tick.min(TICKS_PER_BEAT - 1),
+        }
+    }
+}
+
+impl Default for TransportInfo {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            tempo: 120.0,
+            time_signature: TimeSignature::default(),
+            sample_position: 0,
+            beat_position: 0.0,
+            loop_range: None,
+        }
+    }
+}
+
+/// トランスポートの操作 - 再生中はエンジンのコマンドキューで送られる
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransportCommand {
+    Play,
+    Stop,
+    SetTempo { tempo: f32 },
+    SetTimeSignature { numerator: u32, denominator: u32 },
+    /// Set the loop region; `None` removes it and stops looping
+    SetLoop { range: Option<LoopRange> },
+    /// Loop over the region set with `SetLoop` (no effect without one)
+    SetLooping { enabled: bool },
+    /// Jump to a song position in beats
+    Locate { beat: f64 },
+}
+
+/// エンジンのトランスポート
+#[derive(Debug, Clone)]
+pub struct Transport {
+    info: TransportInfo,
+    loop_range: Option<LoopRange>,
+    looping: bool,
+    /// Sample rate of the last block, to convert beats to samples when locating
+    sample_rate: f32,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self {
+            info: TransportInfo::default(),
+            loop_range: None,
+            looping: false,
+            sample_rate: 44100.0,
+        }
+    }
+
+    /// State at the start of the next block
+    pub fn info(&self) -> TransportInfo {
+        TransportInfo {
+            loop_range: self.loop_range.filter(|_| self.looping),
+            ..self.info
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.info.playing
+    }
+
+    pub fn play(&mut self) {
+        self.info.playing = true;
+    }
+
+    /// Stop where the song is; `locate` rewinds
+    pub fn stop(&mut self) {
+        self.info.playing = false;
+    }
+
+    pub fn set_tempo(&mut self, tempo: f32) -> Result<(), String> {
+        if !(MIN_TEMPO..=MAX_TEMPO).contains(&tempo) {
+            return Err(format!("Tempo must be between {} and {} BPM", MIN_TEMPO, MAX_TEMPO));
+        }
+        self.info.tempo = tempo;
+        Ok(())
+    }
+
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.info.time_signature = time_signature;
+    }
+
+    pub fn set_loop(&mut self, range: Option<LoopRange>) {
+        self.loop_range = range;
+        self.looping &= range.is_some();
+    }
+
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping && self.loop_range.is_some();
+    }
+
+    /// Jump to `beat`; the sample position follows at the current tempo
+    pub fn locate(&mut self, beat: f64) {
+        self.info.beat_position = beat.max(0.0);
+        self.info.sample_position = (self.info.beat_position * self.info.samples_per_beat(self.sample_rate)) as u64;
+    }
+
+    /// Take over the state of an outer transport (e.g. inside a subpatch)
+    pub fn follow(&mut self, info: TransportInfo) {
+        self.info = info;
+        self.loop_range = info.loop_range;
+        self.looping = info.loop_range.is_some();
+    }
+
+    /// Apply a command. Only allocates for the error, so a command validated
+    /// on another thread can be applied on the audio thread.
+    pub fn apply(&mut self, command: TransportCommand) -> Result<(), String> {
+        match command {
+            TransportCommand::Play => self.play(),
+            TransportCommand::Stop => self.stop(),
+            TransportCommand::SetTempo { tempo } => self.set_tempo(tempo)?,
+            TransportCommand::SetTimeSignature { numerator, denominator } => {
+                self.set_time_signature(TimeSignature::new(numerator, denominator)?);
+            }
+            TransportCommand::SetLoop { range } => {
+                let range = range.map(|range| LoopRange::new(range.start, range.end)).transpose()?;
+                self.set_loop(range);
+            }
+            TransportCommand::SetLooping { enabled } => self.set_looping(enabled),
+            TransportCommand::Locate { beat } => self.locate(beat),
+        }
+        Ok(())
+    }
+
+    /// Move on by a block of `frames` samples. Crossing the loop end wraps
+    /// back into the loop (see `TransportInfo::beat_at`).
+    pub fn advance(&mut self, frames: usize, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.info = self.info().advanced(frames, sample_rate);
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_runs_and_loops() {
+        let mut transport = Transport::new();
+        transport.set_tempo(120.0).unwrap();
+
+        // Stopped: nothing moves
+        transport.advance(22050, 44100.0);
+        assert_eq!(transport.info().sample_position, 0);
+
+        // 120 BPM at 44.1 kHz is 22050 samples per beat
+        transport.play();
+        transport.advance(22050 * 5, 44100.0);
+        let info = transport.info();
+        assert_eq!(info.sample_position, 110250);
+        assert_eq!(info.beat_position, 5.0);
+        assert_eq!(info.musical_position(), MusicalPosition { bar: 1, beat: 1, tick: 0 });
+        assert_eq!(info.musical_position().to_string(), "2.2.000");
+
+        // Loop beats 4 - 6: half a beat past the end lands half a beat after the start
+        transport.apply(TransportCommand::SetLoop { range: Some(LoopRange { start: 4.0, end: 6.0 }) }).unwrap();
+        transport.apply(TransportCommand::SetLooping { enabled: true }).unwrap();
+        transport.advance(22050 * 3 / 2, 44100.0);
+        assert_eq!(transport.info().beat_position, 4.5);
+        assert_eq!(transport.info().sample_position, 99225);
+
+        assert!(transport.apply(TransportCommand::SetTempo { tempo: 5.0 }).is_err());
+        assert!(transport.apply(TransportCommand::SetTimeSignature { numerator: 7, denominator: 6 }).is_err());
+        assert!(LoopRange::new(2.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_loop_wraps_inside_a_block() {
+        // 4 samples per beat, block starting a quarter beat before the loop end
+        let info = TransportInfo {
+            playing: true,
+            tempo: 60.0,
+            beat_position: 5.75,
+            loop_range: Some(LoopRange { start: 4.0, end: 6.0 }),
+            ..TransportInfo::default()
+        };
+        let beats: Vec<f64> = (0..4).map(|offset| info.beat_at(offset, 4.0)).collect();
+        assert_eq!(beats, vec![5.75, 4.0, 4.25, 4.5]);
+        assert_eq!(info.advanced(3, 4.0).beat_position, 4.5);
+        assert_eq!(info.advanced(3, 4.0).sample_position, 18);
+
+        // Blocks that start past the end (after a locate) run on
+        let outside = TransportInfo { beat_position: 7.0, ..info };
+        assert_eq!(outside.beat_at(2, 4.0), 7.5);
+    }
+
+    #[test]
+    fn test_musical_position_in_compound_time() {
+        let info = TransportInfo {
+            time_signature: TimeSignature::new(6, 8).unwrap(),
+            beat_position: 4.25,
+            ..TransportInfo::default()
+        };
+        // A 6/8 bar is 3 beats; 1.25 beats into bar 2 is the third eighth note, halfway through
+        assert_eq!(info.musical_position(), MusicalPosition { bar: 1, beat: 2, tick: 480 });
+    }
+}